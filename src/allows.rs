@@ -0,0 +1,270 @@
+//! `#[allow]` attributes made redundant by `--strip-allow`, with
+//! `--kinds allow`. `--strip-allow dead_code` has rustc warn about dead code
+//! even where it's allowed, so the code these attributes kept is found and
+//! removed like any other. Once nothing within an item is found, the
+//! attribute allowing only the stripped lints on it suppresses nothing, and
+//! goes as well.
+
+use std::path::Path;
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{
+    punctuated::Punctuated, spanned::Spanned, Attribute, Fields, ImplItem, Item, Token, TraitItem,
+};
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    FileResolutionOptions,
+};
+
+/// The lints that `--strip-allow` takes, which are those that keep rustc from
+/// reporting unused code
+pub const STRIPPABLE: &[&str] = &["dead_code", "unused", "unused_imports", "unused_macros"];
+
+/// The lints of an `#[allow(..)]` attribute, as written, or `None` for
+/// other attributes
+fn allowed_lints(attr: &Attribute) -> Option<Vec<String>> {
+    if !attr.path().is_ident("allow") {
+        return None;
+    }
+    let lints = attr
+        .parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+        .ok()?;
+
+    Some(
+        lints
+            .iter()
+            .map(|lint| lint.to_token_stream().to_string().replace(' ', ""))
+            .collect(),
+    )
+}
+
+/// The attributes of the items, along with the spans of what they are on
+fn collect_attrs<'a>(items: &'a [Item], found: &mut Vec<(&'a Attribute, Span)>) {
+    let fields = |fields: &'a Fields, found: &mut Vec<(&'a Attribute, Span)>| {
+        for field in fields {
+            found.extend(field.attrs.iter().map(|attr| (attr, field.span())));
+        }
+    };
+    for item in items {
+        let attrs = match item {
+            Item::Const(item) => &item.attrs,
+            Item::Enum(item) => {
+                for variant in &item.variants {
+                    found.extend(variant.attrs.iter().map(|attr| (attr, variant.span())));
+                    fields(&variant.fields, found);
+                }
+                &item.attrs
+            }
+            Item::Fn(item) => &item.attrs,
+            Item::Impl(item) => {
+                for impl_item in &item.items {
+                    let attrs = match impl_item {
+                        ImplItem::Const(impl_item) => &impl_item.attrs,
+                        ImplItem::Fn(impl_item) => &impl_item.attrs,
+                        ImplItem::Type(impl_item) => &impl_item.attrs,
+                        _ => continue,
+                    };
+                    found.extend(attrs.iter().map(|attr| (attr, impl_item.span())));
+                }
+                &item.attrs
+            }
+            Item::Macro(item) => &item.attrs,
+            Item::Mod(item) => {
+                if let Some((_, items)) = &item.content {
+                    collect_attrs(items, found);
+                }
+                &item.attrs
+            }
+            Item::Static(item) => &item.attrs,
+            Item::Struct(item) => {
+                fields(&item.fields, found);
+                &item.attrs
+            }
+            Item::Trait(item) => {
+                for trait_item in &item.items {
+                    let attrs = match trait_item {
+                        TraitItem::Const(trait_item) => &trait_item.attrs,
+                        TraitItem::Fn(trait_item) => &trait_item.attrs,
+                        TraitItem::Type(trait_item) => &trait_item.attrs,
+                        _ => continue,
+                    };
+                    found.extend(attrs.iter().map(|attr| (attr, trait_item.span())));
+                }
+                &item.attrs
+            }
+            Item::Type(item) => &item.attrs,
+            Item::Union(item) => {
+                found.extend(
+                    item.fields
+                        .named
+                        .iter()
+                        .flat_map(|field| field.attrs.iter().map(|attr| (attr, field.span()))),
+                );
+                &item.attrs
+            }
+            Item::Use(item) => &item.attrs,
+            _ => continue,
+        };
+        found.extend(attrs.iter().map(|attr| (attr, item.span())));
+    }
+}
+
+/// The `#[allow]` attributes in `files` that allow nothing but the `stripped`
+/// lints, on items (or whole files and modules) within which none of the
+/// `findings` are
+pub fn get_redundant_allows(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+    stripped: &[String],
+    findings: &[UnusedDiagnostic],
+) -> Vec<UnusedDiagnostic> {
+    let mut redundant = Vec::new();
+    for file in files {
+        let file = file.as_ref();
+        let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+        let file_name = file_name.to_string_lossy();
+        if !file_resolution.is_included(&file_name) {
+            continue;
+        }
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+
+        let mut attrs: Vec<_> = parsed
+            .attrs
+            .iter()
+            .map(|attr| (attr, parsed.span()))
+            .collect();
+        collect_attrs(&parsed.items, &mut attrs);
+        for (attr, on) in attrs {
+            let Some(lints) = allowed_lints(attr) else {
+                continue;
+            };
+            if lints.is_empty() || !lints.iter().all(|lint| stripped.contains(lint)) {
+                continue;
+            }
+            let on = synthetic_span(&file_name, &src, on);
+            let suppresses = findings.iter().any(|finding| {
+                finding.span.file_name == file_name
+                    && finding.span.byte_start >= on.byte_start
+                    && finding.span.byte_end <= on.byte_end
+            });
+            if suppresses {
+                continue;
+            }
+            redundant.push(UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::Allow,
+                ident: format!("allow({})", lints.join(", ")),
+                span: synthetic_span(&file_name, &src, attr.span()),
+                suggestion: None,
+                configs: Vec::new(),
+            });
+        }
+    }
+
+    redundant
+}
+
+/// The key to remove the attribute by: its position, as in `3:5`
+pub fn removal_key(diagnostic: &UnusedDiagnostic) -> String {
+    format!(
+        "{}:{}",
+        diagnostic.span.line_start, diagnostic.span.column_start
+    )
+}
+
+/// The span of the attribute starting at the position of the key, found among
+/// the tokens, as in `#[allow(dead_code)]` or `#![allow(dead_code)]`
+fn find_attr(tokens: TokenStream, line: usize, column: usize) -> Option<Span> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                let start = punct.span().start();
+                if start.line != line || start.column + 1 != column {
+                    continue;
+                }
+                let group = tokens[index + 1..]
+                    .iter()
+                    .take(2)
+                    .find_map(|token| match token {
+                        TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket => {
+                            Some(group)
+                        }
+                        _ => None,
+                    })?;
+                return punct.span().join(group.span());
+            }
+            TokenTree::Group(group) => {
+                if let Some(span) = find_attr(group.stream(), line, column) {
+                    return Some(span);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The span of the attribute named by the key of [`removal_key`]
+pub fn allow_spans(parsed: &syn::File, key: &str) -> Vec<Span> {
+    let position = key
+        .split_once(':')
+        .and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)));
+    let Some((line, column)) = position else {
+        return Vec::new();
+    };
+
+    find_attr(parsed.to_token_stream(), line, column)
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cauterize::test::rust_delete, test_support::TempCrate};
+
+    use super::*;
+
+    #[test]
+    fn finds_redundant_allows() {
+        let lib_rs = "#[allow(dead_code)]\nfn kept() {}\n\n#[allow(dead_code)]\nstruct S {\n    \
+                      #[allow(dead_code)]\n    x: u8,\n}\n\n#[allow(dead_code, clippy::all)]\n\
+                      fn other() {}\n";
+        let temp_crate = TempCrate::new(lib_rs).unwrap();
+        let root = temp_crate.root();
+        let lib = root.join("src/lib.rs");
+        let all = FileResolutionOptions::AllBut(Vec::new());
+        // The field is still reported by rustc once its attribute is stripped
+        let parsed = syn::parse_file(lib_rs).unwrap();
+        let Item::Struct(item) = &parsed.items[1] else {
+            unreachable!()
+        };
+        let field = item.fields.iter().next().unwrap();
+        let finding = UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Field,
+            ident: "x".to_string(),
+            span: synthetic_span("src/lib.rs", lib_rs, field.ident.span()),
+            suggestion: None,
+            configs: Vec::new(),
+        };
+
+        let stripped = ["dead_code".to_string()];
+        let found = get_redundant_allows(&[&lib], root, &all, &stripped, &[finding]);
+        let keys: Vec<_> = found.iter().map(removal_key).collect();
+        assert_eq!(keys, ["1:1"]);
+
+        let keys = found.iter().map(|found| (found.kind, removal_key(found)));
+        assert_eq!(
+            String::from_utf8(rust_delete(lib_rs.as_bytes(), keys, true).unwrap()).unwrap(),
+            lib_rs.strip_prefix("#[allow(dead_code)]\n").unwrap()
+        );
+    }
+}
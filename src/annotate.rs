@@ -0,0 +1,70 @@
+//! Findings of the kinds whose policy is `annotate` are kept, with a
+//! `// minify:unused` comment naming their kind and name on the line above,
+//! so that they stand out to whoever reads the code next. Lines already
+//! marked aren't marked again.
+
+use crate::{edit::TextEdit, unused::UnusedDiagnostic};
+
+const MARKER: &str = "// minify:unused";
+
+/// The comment marking the finding, inserted at the start of its line, with
+/// the indentation of that line; `None` if the line above already marks it
+pub fn annotation(src: &[u8], diagnostic: &UnusedDiagnostic) -> Option<TextEdit> {
+    let line = diagnostic.span.line_start.checked_sub(1)?;
+    let mut lines = src.split_inclusive(|&c| c == b'\n');
+    let start: usize = lines.by_ref().take(line).map(<[u8]>::len).sum();
+    let text = String::from_utf8_lossy(lines.next()?);
+    let above = String::from_utf8_lossy(&src[..start]);
+    let above = above.lines().next_back().unwrap_or_default();
+    if above.trim_start().starts_with(MARKER) {
+        return None;
+    }
+
+    let indent = &text[..text.len() - text.trim_start().len()];
+    let comment = format!(
+        "{indent}{MARKER} {} `{}`\n",
+        diagnostic.kind, diagnostic.ident
+    );
+    Some(TextEdit::replace(start..start, comment))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        edit::SourceFileEditor,
+        unused::{synthetic_span, UnusedDiagnosticKind},
+    };
+
+    use super::*;
+
+    #[test]
+    fn annotates_lines() {
+        let src = "mod m {\n    fn helper() {}\n}\n";
+        let file = syn::parse_file(src).unwrap();
+        let syn::Item::Mod(module) = &file.items[0] else {
+            unreachable!()
+        };
+        let syn::Item::Fn(function) = &module.content.as_ref().unwrap().1[0] else {
+            unreachable!()
+        };
+        let diagnostic = UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Function,
+            ident: "helper".to_string(),
+            span: synthetic_span("src/lib.rs", src, function.sig.ident.span()),
+            suggestion: None,
+            configs: Vec::new(),
+        };
+
+        let mut editor = SourceFileEditor::new(src);
+        editor.edit(annotation(src.as_bytes(), &diagnostic).unwrap());
+        let annotated = editor.apply().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&annotated),
+            "mod m {\n    // minify:unused FUNCTION `helper`\n    fn helper() {}\n}\n"
+        );
+        // Lines are only marked once
+        let mut diagnostic = diagnostic;
+        diagnostic.span.line_start += 1;
+        assert!(annotation(&annotated, &diagnostic).is_none());
+    }
+}
@@ -0,0 +1,207 @@
+//! The order in which the changes are applied, with `--verify`. When the
+//! removals touch several packages of a workspace, the packages are applied
+//! and checked one at a time, those that others depend on first, so that
+//! every package is checked against dependencies that already have their
+//! changes. The first package that no longer compiles gets its files back,
+//! and the packages after it are left alone, so the workspace is never left
+//! in a state that doesn't build.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{cauterize, cauterize::Change, toolchain};
+
+/// Orders the packages, given by their names and directories, so that each
+/// one comes after the packages it depends on; packages in a cycle (through
+/// dev-dependencies) keep their order
+pub fn leaves_first(
+    packages: &mut Vec<(String, PathBuf)>,
+    depends_on: impl Fn(&str, &str) -> bool,
+) {
+    let mut left = std::mem::take(packages);
+    while !left.is_empty() {
+        let ready = left.iter().position(|(name, _)| {
+            !left
+                .iter()
+                .any(|(other, _)| other != name && depends_on(name, other))
+        });
+        packages.push(left.remove(ready.unwrap_or(0)));
+    }
+}
+
+/// The changes to the files of each package, in the order of the packages;
+/// the changes to files outside of all of them come last, without a package
+fn by_package(
+    changes: Vec<Change>,
+    packages: &[(String, PathBuf)],
+) -> Vec<(Option<&str>, Vec<Change>)> {
+    let mut groups: Vec<_> = packages
+        .iter()
+        .map(|(name, _)| (Some(name.as_str()), Vec::new()))
+        .collect();
+    let mut outside = Vec::new();
+    for change in changes {
+        let path = change
+            .file_name()
+            .canonicalize()
+            .unwrap_or_else(|_| change.file_name().to_path_buf());
+        // Packages may be nested in the directories of others
+        let package = packages
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, dir))| path.starts_with(dir))
+            .max_by_key(|(_, (_, dir))| dir.components().count());
+        match package {
+            Some((index, _)) => groups[index].1.push(change),
+            None => outside.push(change),
+        }
+    }
+    groups.push((None, outside));
+    groups.retain(|(_, changes)| !changes.is_empty());
+
+    groups
+}
+
+/// Whether the package still compiles, along with its tests and examples
+fn compiles(workspace_root: &Path, package: &str) -> io::Result<bool> {
+    let mut command = Command::new("cargo");
+    if let Some(toolchain) = toolchain::pinned(workspace_root) {
+        toolchain.apply(&mut command);
+    }
+    let status = command
+        .args([
+            "check",
+            "--all-targets",
+            "--quiet",
+            "--package",
+            package,
+            "--manifest-path",
+        ])
+        .arg(workspace_root.join("Cargo.toml"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    Ok(status.success())
+}
+
+/// Applies the changes package by package, in the order of the packages,
+/// checking each package once its changes are written; returns the files
+/// that were not written, or were reverted, along with the reason
+pub fn apply_verified(
+    changes: Vec<Change>,
+    packages: &[(String, PathBuf)],
+    workspace_root: &Path,
+) -> Vec<(PathBuf, io::Error)> {
+    let mut failed = Vec::new();
+    let mut broken = None;
+    for (package, changes) in by_package(changes, packages) {
+        if let Some(broken) = &broken {
+            let reason = format!("not applied, as `{broken}` no longer compiles with its changes");
+            for change in &changes {
+                failed.push((
+                    change.file_name().to_path_buf(),
+                    io::Error::other(reason.clone()),
+                ));
+            }
+            continue;
+        }
+
+        let reverts: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                let file_name = change.file_name().to_path_buf();
+                let (original, proposed) = (change.original_content(), change.proposed_content());
+                match change.is_removal() {
+                    true => Change::restore(file_name, original.to_vec()),
+                    false => Change::new(file_name, proposed.to_vec(), original.to_vec()),
+                }
+            })
+            .collect();
+        failed.extend(
+            cauterize::commit_changes(changes, workspace_root)
+                .err()
+                .unwrap_or_default(),
+        );
+        let Some(package) = package else {
+            continue;
+        };
+        if compiles(workspace_root, package).unwrap_or(false) {
+            continue;
+        }
+
+        let reason = format!("reverted, as `{package}` no longer compiles with the changes");
+        for revert in &reverts {
+            failed.push((
+                revert.file_name().to_path_buf(),
+                io::Error::other(reason.clone()),
+            ));
+        }
+        failed.extend(
+            cauterize::commit_changes(reverts, workspace_root)
+                .err()
+                .unwrap_or_default(),
+        );
+        broken = Some(package.to_string());
+    }
+
+    failed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_support::TempCrate;
+
+    use super::*;
+
+    #[test]
+    fn orders_leaves_first() {
+        let temp_crate = TempCrate::with_files([
+            ("app/src/main.rs", "fn main() {}\n"),
+            ("core/src/lib.rs", "fn a() {}\n"),
+            ("util/src/lib.rs", "fn b() {}\n"),
+        ])
+        .unwrap();
+        let root = temp_crate.root().canonicalize().unwrap();
+        let mut packages: Vec<_> = ["app", "util", "core"]
+            .map(|name| (name.to_string(), root.join(name)))
+            .to_vec();
+        // `app` depends on `util`, which depends on `core`
+        let edges = [("app", "util"), ("util", "core")];
+        leaves_first(&mut packages, |dependent, dependency| {
+            edges.contains(&(dependent, dependency))
+        });
+        let names: Vec<_> = packages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["core", "util", "app"]);
+
+        let change = |file: &str| Change::new(root.join(file), b"fn a() {}\n".to_vec(), Vec::new());
+        let changes = Vec::from(["app/src/main.rs", "src/lib.rs", "core/src/lib.rs"].map(change));
+        let groups: Vec<_> = by_package(changes, &packages)
+            .into_iter()
+            .map(|(package, changes)| (package, changes.len()))
+            .collect();
+        assert_eq!(groups, [(Some("core"), 1), (Some("app"), 1), (None, 1)]);
+    }
+
+    #[test]
+    fn restores_deleted_files() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod dead;\n"),
+            ("src/dead.rs", "fn a() {}\n"),
+        ])
+        .unwrap();
+        let root = temp_crate.root().canonicalize().unwrap();
+        let packages = [("minify-test-crate".to_string(), root.clone())];
+
+        // Without its file, `mod dead;` no longer compiles
+        let dead_rs = root.join("src/dead.rs");
+        let removal = Change::removal(dead_rs.clone(), b"fn a() {}\n".to_vec());
+        let failed = apply_verified(vec![removal], &packages, &root);
+        let failed: Vec<_> = failed.iter().map(|(file, _)| file).collect();
+        assert_eq!(failed, [&dead_rs]);
+        assert_eq!(temp_crate.read("src/dead.rs").unwrap(), "fn a() {}\n");
+    }
+}
@@ -0,0 +1,168 @@
+//! Associated functions, consts and types of inherent impls that are never
+//! used. rustc reports all of an impl's unused items at once, as in
+//! ``associated items `C`, `new`, and `m` are never used``, with a span for
+//! each; the items are found again by their name and line, as several impls
+//! (of different types, or in different modules of a file) may have items of
+//! the same name. Items of trait impls are never removed on their own, as the
+//! trait requires them. Impls left empty are removed afterwards.
+
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticSpan};
+use proc_macro2::Span;
+use syn::{spanned::Spanned, ImplItem, Item};
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The beginnings of the messages about unused associated items, along with
+/// the kind they name, if any
+const MESSAGES: &[(&str, Option<UnusedDiagnosticKind>)] = &[
+    ("associated item", None),
+    (
+        "associated function",
+        Some(UnusedDiagnosticKind::AssociatedFunction),
+    ),
+    ("method", Some(UnusedDiagnosticKind::AssociatedFunction)),
+    (
+        "associated constant",
+        Some(UnusedDiagnosticKind::AssociatedConstant),
+    ),
+    (
+        "associated type",
+        Some(UnusedDiagnosticKind::AssociatedType),
+    ),
+];
+
+/// The kind of the item a span points at the name of, by the keywords
+/// before the name on its line
+fn kind_at(span: &DiagnosticSpan) -> Option<UnusedDiagnosticKind> {
+    let line = span.text.first()?;
+    let before: String = line
+        .text
+        .chars()
+        .take(line.highlight_start.saturating_sub(1))
+        .collect();
+    let keywords: Vec<_> = before.split_whitespace().collect();
+    if keywords.contains(&"fn") {
+        Some(UnusedDiagnosticKind::AssociatedFunction)
+    } else if keywords.contains(&"const") {
+        Some(UnusedDiagnosticKind::AssociatedConstant)
+    } else if keywords.contains(&"type") {
+        Some(UnusedDiagnosticKind::AssociatedType)
+    } else {
+        None
+    }
+}
+
+/// The findings of a diagnostic about unused associated items, one for each
+/// of the items
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    let message = &diagnostic.message;
+    if code.code != "dead_code" || !message.ends_with("never used") {
+        return None;
+    }
+    let (_, named) = MESSAGES
+        .iter()
+        .find(|(start, _)| message.starts_with(start))?;
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .filter_map(|span| {
+            Some(UnusedDiagnostic {
+                kind: kind_at(span).or(*named)?,
+                ident: span_text(span),
+                span: span.clone(),
+                suggestion: None,
+                configs: Vec::new(),
+            })
+        })
+        .collect();
+
+    Some(found)
+}
+
+fn collect_spans(items: &[Item], kind: UnusedDiagnosticKind, line: usize, name: &str) -> Vec<Span> {
+    use UnusedDiagnosticKind::*;
+
+    let mut spans = Vec::new();
+    for item in items {
+        match item {
+            Item::Impl(block) if block.trait_.is_none() => {
+                spans.extend(block.items.iter().filter_map(|item| {
+                    let ident = match item {
+                        ImplItem::Fn(obj) if kind == AssociatedFunction => &obj.sig.ident,
+                        ImplItem::Const(obj) if kind == AssociatedConstant => &obj.ident,
+                        ImplItem::Type(obj) if kind == AssociatedType => &obj.ident,
+                        _ => return None,
+                    };
+                    (ident == name && ident.span().start().line == line).then(|| item.span())
+                }));
+            }
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    spans.extend(collect_spans(items, kind, line, name));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    spans
+}
+
+/// The spans of the unused associated items of the kind, given by their
+/// [`removal_key`](crate::imports::removal_key)s; attributes and doc comments
+/// go along with them
+pub fn assoc_item_spans(file: &syn::File, kind: UnusedDiagnosticKind, key: &str) -> Vec<Span> {
+    let Some((line, name)) = key.split_once(':') else {
+        return Vec::new();
+    };
+    let Ok(line) = line.parse() else {
+        return Vec::new();
+    };
+
+    collect_spans(&file.items, kind, line, name)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::{remove_empty_blocks, test::rust_delete};
+
+    use super::*;
+
+    fn remove(src: &str, keys: &[(UnusedDiagnosticKind, &str)]) -> String {
+        let keys = keys.iter().map(|(kind, key)| (*kind, key.to_string()));
+        let removed = rust_delete(src.as_bytes(), keys, false).unwrap();
+        String::from_utf8(remove_empty_blocks(src.as_bytes(), &removed, false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn removes_items_of_inherent_impls() {
+        use UnusedDiagnosticKind::*;
+
+        let src = "pub struct S;\n\
+                   impl S {\n    /// Doc\n    const C: u8 = 1;\n    pub fn f() {}\n}\n\
+                   mod m {\n    pub struct T;\n    impl T {\n        fn f() {}\n    }\n}\n\
+                   impl Clone for S {\n    fn clone(&self) -> S { S }\n}\n";
+        assert_eq!(
+            remove(
+                src,
+                &[(AssociatedConstant, "4:C"), (AssociatedFunction, "10:f")]
+            ),
+            "pub struct S;\n\
+             impl S {\n    pub fn f() {}\n}\n\
+             mod m {\n    pub struct T;\n}\n\
+             impl Clone for S {\n    fn clone(&self) -> S { S }\n}\n"
+        );
+        // The same name on another line, or of another kind, is left alone
+        assert_eq!(
+            remove(
+                src,
+                &[(AssociatedFunction, "10:C"), (AssociatedConstant, "5:f")]
+            ),
+            src
+        );
+        assert_eq!(remove(src, &[(AssociatedFunction, "14:clone")]), src);
+    }
+}
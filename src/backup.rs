@@ -0,0 +1,84 @@
+//! Keeps a copy of every file touched by `cargo minify apply`, so that
+//! `cargo minify undo` can put them back.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{cauterize::Change, state};
+
+const BACKUP_DIR: &str = "backup";
+const INDEX_FILE: &str = "index";
+
+fn backup_dir(target_dir: &Path) -> io::Result<PathBuf> {
+    Ok(state::tool_dir(target_dir)?.join(BACKUP_DIR))
+}
+
+/// Stores the original contents of the files in `changes`, replacing any
+/// previous backup
+pub fn store(target_dir: &Path, changes: &[Change]) -> io::Result<()> {
+    let dir = backup_dir(target_dir)?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    let mut index = String::new();
+    for (i, change) in changes.iter().enumerate() {
+        let file_name = change.file_name().canonicalize()?;
+        fs::write(dir.join(i.to_string()), change.original_content())?;
+        index.push_str(&file_name.to_string_lossy());
+        index.push('\n');
+    }
+
+    fs::write(dir.join(INDEX_FILE), index)
+}
+
+/// Adds the original contents of the files in `changes` to the backup, except
+/// for the files that are already in it, so that `undo` reverts several
+/// rounds of changes at once
+pub fn extend(target_dir: &Path, changes: &[Change]) -> io::Result<()> {
+    let dir = backup_dir(target_dir)?;
+    let mut index = match fs::read_to_string(dir.join(INDEX_FILE)) {
+        Ok(index) => index,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return store(target_dir, changes),
+        Err(err) => return Err(err),
+    };
+
+    let mut count = index.lines().count();
+    for change in changes {
+        let file_name = change.file_name().canonicalize()?;
+        if index.lines().any(|line| Path::new(line) == file_name) {
+            continue;
+        }
+        fs::write(dir.join(count.to_string()), change.original_content())?;
+        index.push_str(&file_name.to_string_lossy());
+        index.push('\n');
+        count += 1;
+    }
+
+    fs::write(dir.join(INDEX_FILE), index)
+}
+
+/// Restores the files recorded by the last call to [`store`] and removes the
+/// backup, returning the files that were restored
+pub fn restore(target_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = backup_dir(target_dir)?;
+    let index = match fs::read_to_string(dir.join(INDEX_FILE)) {
+        Ok(index) => index,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut restored = Vec::new();
+    for (i, file_name) in index.lines().enumerate() {
+        let file_name = PathBuf::from(file_name);
+        fs::copy(dir.join(i.to_string()), &file_name)?;
+        restored.push(file_name);
+    }
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(restored)
+}
@@ -0,0 +1,275 @@
+//! A syntactic analysis that finds trait bounds on the type parameters of
+//! functions which the function body can't possibly rely on: the parameter
+//! isn't mentioned in the body or the return type, and none of the arguments
+//! of that type are used.
+
+use std::path::Path;
+
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::{
+    spanned::Spanned, FnArg, Generics, ImplItem, Item, Signature, Type, TypeParam, WherePredicate,
+};
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    FileResolutionOptions,
+};
+
+/// A type parameter whose bounds are never required
+struct UnusedBound<'a> {
+    /// `path::to::function::<T>`, used to find the bound again when removing it
+    id: String,
+    param: &'a TypeParam,
+    generics: &'a Generics,
+}
+
+impl UnusedBound<'_> {
+    fn spans(&self) -> Vec<Span> {
+        let name = &self.param.ident;
+        let mut spans = Vec::new();
+
+        if let Some(colon) = &self.param.colon_token {
+            if let Some(span) = colon.span.join(self.param.bounds.span()) {
+                spans.push(span);
+            }
+        }
+
+        if let Some(where_clause) = &self.generics.where_clause {
+            let pairs: Vec<_> = where_clause.predicates.pairs().collect();
+            let is_ours = |predicate: &WherePredicate| bounded_param(predicate) == Some(name);
+
+            if pairs.iter().all(|pair| is_ours(pair.value())) {
+                spans.push(where_clause.span());
+            } else {
+                for (i, pair) in pairs.iter().enumerate() {
+                    if !is_ours(pair.value()) {
+                        continue;
+                    }
+                    let span = match (pair.punct(), i.checked_sub(1)) {
+                        (Some(comma), _) => pair.value().span().join(comma.span),
+                        (None, Some(prev)) => pairs[prev]
+                            .punct()
+                            .and_then(|comma| comma.span.join(pair.value().span())),
+                        (None, None) => Some(pair.value().span()),
+                    };
+                    spans.extend(span);
+                }
+            }
+        }
+
+        spans
+    }
+}
+
+/// The type parameter bounded by a `where` predicate, if it is a plain type
+/// parameter like `T: Display`
+fn bounded_param(predicate: &WherePredicate) -> Option<&syn::Ident> {
+    match predicate {
+        WherePredicate::Type(predicate) => match &predicate.bounded_ty {
+            Type::Path(path) if path.qself.is_none() => path.path.get_ident(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn mentions(tokens: &impl ToTokens, name: &str) -> bool {
+    // A textual match is deliberately crude: it also catches identifiers that
+    // are only used inside string literals of format macros
+    tokens.to_token_stream().to_string().contains(name)
+}
+
+fn collect_items<'a>(items: &'a [Item], prefix: &str, found: &mut Vec<UnusedBound<'a>>) {
+    for item in items {
+        match item {
+            Item::Fn(function) => collect_fn(
+                &function.sig,
+                &function.block,
+                &format!("{prefix}{}", function.sig.ident),
+                found,
+            ),
+            Item::Impl(block) if block.trait_.is_none() => {
+                let self_ty = block.self_ty.to_token_stream().to_string().replace(' ', "");
+                for item in &block.items {
+                    if let ImplItem::Fn(function) = item {
+                        collect_fn(
+                            &function.sig,
+                            &function.block,
+                            &format!("{prefix}{self_ty}::{}", function.sig.ident),
+                            found,
+                        );
+                    }
+                }
+            }
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, &format!("{prefix}{}::", module.ident), found);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_fn<'a>(
+    sig: &'a Signature,
+    body: &'a syn::Block,
+    path: &str,
+    found: &mut Vec<UnusedBound<'a>>,
+) {
+    for param in sig.generics.type_params() {
+        let name = param.ident.to_string();
+        let has_bounds = !param.bounds.is_empty()
+            || sig
+                .generics
+                .where_clause
+                .as_ref()
+                .is_some_and(|where_clause| {
+                    where_clause
+                        .predicates
+                        .iter()
+                        .any(|predicate| bounded_param(predicate) == Some(&param.ident))
+                });
+        if !has_bounds || mentions(body, &name) || mentions(&sig.output, &name) {
+            continue;
+        }
+
+        let used_by_other_bounds = sig.generics.params.iter().any(|other| match other {
+            syn::GenericParam::Type(other) => other.ident != param.ident && mentions(other, &name),
+            syn::GenericParam::Const(other) => mentions(other, &name),
+            syn::GenericParam::Lifetime(_) => false,
+        }) || sig.generics.where_clause.as_ref().is_some_and(
+            |where_clause| {
+                where_clause.predicates.iter().any(|predicate| {
+                    bounded_param(predicate) != Some(&param.ident) && mentions(predicate, &name)
+                })
+            },
+        );
+        if used_by_other_bounds {
+            continue;
+        }
+
+        let argument_used = sig.inputs.iter().any(|input| match input {
+            FnArg::Typed(arg) => {
+                mentions(&arg.ty, &name)
+                    && arg
+                        .pat
+                        .to_token_stream()
+                        .into_iter()
+                        .any(|token| match token {
+                            proc_macro2::TokenTree::Ident(ident) => {
+                                ident != "_"
+                                    && ident != "mut"
+                                    && ident != "ref"
+                                    && mentions(body, &ident.to_string())
+                            }
+                            _ => false,
+                        })
+            }
+            FnArg::Receiver(_) => false,
+        });
+        if argument_used {
+            continue;
+        }
+
+        found.push(UnusedBound {
+            id: format!("{path}::<{name}>"),
+            param,
+            generics: &sig.generics,
+        });
+    }
+}
+
+/// Finds the spans of the unused bounds identified by `id`, as given out in
+/// the diagnostics of [`get_unused_bounds`]
+pub fn bound_spans(file: &syn::File, id: &str) -> Vec<Span> {
+    let mut found = Vec::new();
+    collect_items(&file.items, "", &mut found);
+
+    found
+        .iter()
+        .filter(|bound| bound.id == id)
+        .flat_map(|bound| bound.spans())
+        .collect()
+}
+
+/// Finds unused bounds in the given source files; `workspace_root` is used to
+/// report file names relative to it, just like rustc does
+pub fn get_unused_bounds(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+) -> Vec<UnusedDiagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for file in files {
+        let file = file.as_ref();
+        let file_name = file
+            .strip_prefix(workspace_root)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .into_owned();
+        if !file_resolution.is_included(&file_name) {
+            continue;
+        }
+
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+
+        let mut found = Vec::new();
+        collect_items(&parsed.items, "", &mut found);
+
+        for bound in found {
+            diagnostics.push(UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::Bound,
+                span: synthetic_span(&file_name, &src, bound.param.ident.span()),
+                ident: bound.id,
+                suggestion: None,
+                configs: Vec::new(),
+            });
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unused_bounds(src: &str) -> Vec<String> {
+        let file = syn::parse_file(src).unwrap();
+        let mut found = Vec::new();
+        collect_items(&file.items, "", &mut found);
+        found.into_iter().map(|bound| bound.id).collect()
+    }
+
+    #[test]
+    fn finds_unused_bounds() {
+        assert_eq!(
+            unused_bounds("fn foo<T: Clone, U: Copy>(_t: T, u: U) -> U { u }"),
+            vec!["foo::<T>"]
+        );
+        assert_eq!(
+            unused_bounds("mod m { struct S; impl S { fn f<T>(_: T) where T: Clone {} } }"),
+            vec!["m::S::f::<T>"]
+        );
+    }
+
+    #[test]
+    fn keeps_used_bounds() {
+        assert!(unused_bounds("fn foo<T: Clone>(t: T) { t.clone(); }").is_empty());
+        assert!(unused_bounds("fn foo<T: Default>() -> T { T::default() }").is_empty());
+        assert!(unused_bounds("fn foo<T: Display>(t: T) { println!(\"{t}\") }").is_empty());
+        assert_eq!(
+            unused_bounds("fn foo<T: Clone, U: From<T>>(_: U) {}"),
+            vec!["foo::<U>"]
+        );
+        assert!(unused_bounds("struct S; impl Tr for S { fn f<T: Clone>(_: T) {} }").is_empty());
+    }
+}
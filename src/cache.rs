@@ -0,0 +1,100 @@
+//! Results cached by commit, with `--cache` (or `--cache-dir <DIR>`): CI jobs
+//! running on the same commit reuse the findings and changes of the first one
+//! instead of compiling the workspace again. Results are only cached, and
+//! reused, for clean working trees, as the commit wouldn't cover any other
+//! modifications; they are kept per commit and per set of options. As with
+//! `--resume`, each changed file must still hash to what it was when the
+//! results were cached, or the workspace is analyzed from scratch.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cauterize::Change,
+    state::{self, Kept, ResumeError, Saved},
+    unused::UnusedDiagnostic,
+    vcs::{self, Status, VcsBackend},
+};
+
+/// The directory in the target directory the results are cached in, unless
+/// `--cache-dir` says otherwise
+pub const DEFAULT_DIR: &str = "minify-cache";
+
+/// The directory results are cached in: `target/minify-cache` unless given
+pub fn dir(target_dir: &Path, given: Option<&Path>) -> PathBuf {
+    given.map_or_else(|| target_dir.join(DEFAULT_DIR), Path::to_path_buf)
+}
+
+/// The cached results of the commit checked out in a workspace, for one set
+/// of options
+pub struct Cache {
+    dir: PathBuf,
+    /// The directory is the default one, which is kept out of version control
+    /// along with the target directory; a given one is left to the user
+    default_dir: bool,
+    commit: String,
+    key: u64,
+}
+
+impl Cache {
+    /// The cache for the commit checked out at `workspace_root`, if there is
+    /// one and the working tree is clean, in the given directory or the
+    /// default one; `key` tells the sets of options apart
+    pub fn for_commit(
+        target_dir: &Path,
+        given_dir: Option<&Path>,
+        workspace_root: &Path,
+        backend: VcsBackend,
+        key: u64,
+    ) -> Option<Self> {
+        if !matches!(vcs::status(workspace_root, backend), Status::Clean) {
+            return None;
+        }
+
+        Some(Cache {
+            dir: dir(target_dir, given_dir),
+            default_dir: given_dir.is_none(),
+            commit: vcs::head_commit(workspace_root)?,
+            key,
+        })
+    }
+
+    pub fn commit(&self) -> &str {
+        &self.commit
+    }
+
+    fn file(&self) -> PathBuf {
+        self.dir
+            .join(&self.commit)
+            .join(format!("{:016x}.json", self.key))
+    }
+
+    pub fn load(&self, workspace_root: &Path) -> Result<Saved, ResumeError> {
+        state::load_from(&self.file(), workspace_root, self.key)
+    }
+
+    pub fn save(
+        &self,
+        workspace_root: &Path,
+        diagnostics: &[UnusedDiagnostic],
+        changes: &[Change],
+        kept: &Kept,
+    ) -> io::Result<()> {
+        // The default cache never makes the working tree dirty, even when the
+        // target directory isn't ignored
+        if self.default_dir {
+            state::ignored_dir(&self.dir)?;
+        }
+        std::fs::create_dir_all(self.dir.join(&self.commit))?;
+        state::save_to(
+            &self.file(),
+            workspace_root,
+            self.key,
+            diagnostics,
+            changes,
+            kept,
+        )
+    }
+}
@@ -0,0 +1,428 @@
+//! Finds the code that is only used by the code being removed, so that it can
+//! be removed in the same run rather than in a second one. rustc's own
+//! analysis is already transitive, but the code removed for syntactic
+//! findings (such as unused bounds) may have been the only user of an item.
+//!
+//! The private items of a crate and the names they mention make up a
+//! reference graph, across all the modules of the crate. Once the code is
+//! removed, the items that were mentioned before, but are now only mentioned
+//! by each other, go as well, including the helpers of those helpers. Names
+//! aren't resolved, so an item stays as long as anything else mentions its
+//! name.
+
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Item, Visibility};
+
+use crate::{
+    modules,
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+/// A file of a crate, before and after the removals; its name is relative to
+/// the workspace root, as in rustc's diagnostics
+pub struct CrateFile<'a> {
+    pub file_name: &'a str,
+    pub original: &'a str,
+    pub current: &'a str,
+}
+
+/// The files of a crate, relative to the workspace root
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Crate {
+    pub files: Vec<String>,
+    /// Whether its `pub` items count as private, as nothing outside of a
+    /// binary can use them
+    pub public_items: bool,
+}
+
+/// A private item, and the names it mentions
+struct Node {
+    file: usize,
+    kind: UnusedDiagnosticKind,
+    ident: String,
+    span: Span,
+    mentions: HashSet<String>,
+}
+
+/// The private items of a crate, and the names mentioned by everything else
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    roots: HashSet<String>,
+    restricted: bool,
+    public: bool,
+}
+
+impl Graph {
+    /// The graph of the files; items visible to other modules of the crate,
+    /// such as `pub(crate)` ones, are only nodes if `restricted` is set, and
+    /// `pub` ones only if `public` is
+    fn of(files: &[syn::File], restricted: bool, public: bool) -> Self {
+        let mut graph = Graph {
+            restricted,
+            public,
+            ..Graph::default()
+        };
+        for (file, parsed) in files.iter().enumerate() {
+            graph.add_items(file, &parsed.items);
+        }
+
+        graph
+    }
+
+    fn add_items(&mut self, file: usize, items: &[Item]) {
+        for item in items {
+            if let Item::Mod(module) = item {
+                if let Some((_, items)) = &module.content {
+                    collect_idents(module.ident.to_token_stream(), &mut self.roots);
+                    self.add_items(file, items);
+                    continue;
+                }
+            }
+
+            let tokens = item.to_token_stream();
+            match private_item(item, self.restricted, self.public) {
+                Some((kind, ident)) => {
+                    let mut mentions = HashSet::new();
+                    collect_idents(tokens, &mut mentions);
+                    mentions.remove(&ident.to_string());
+                    self.nodes.push(Node {
+                        file,
+                        kind,
+                        ident: ident.to_string(),
+                        span: ident.span(),
+                        mentions,
+                    });
+                }
+                None => collect_idents(tokens, &mut self.roots),
+            }
+        }
+    }
+
+    /// The names mentioned anywhere but in their own definitions
+    fn mentioned(&self) -> HashSet<&str> {
+        let mentions = self.nodes.iter().flat_map(|node| &node.mentions);
+        self.roots
+            .iter()
+            .chain(mentions)
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// The private items of the crate that were mentioned in the original files,
+/// but are only mentioned by each other in the current ones; with `public`,
+/// the `pub` items count as private
+pub fn crate_candidates(files: &[CrateFile], public: bool) -> Vec<UnusedDiagnostic> {
+    find(files, true, public)
+}
+
+fn find(files: &[CrateFile], restricted: bool, public: bool) -> Vec<UnusedDiagnostic> {
+    let parse = |src: &str| syn::parse_file(src).ok();
+    let Some(original) = files
+        .iter()
+        .map(|file| parse(file.original))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Vec::new();
+    };
+    let Some(current) = files
+        .iter()
+        .map(|file| parse(file.current))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Vec::new();
+    };
+    let graph = |files: &[syn::File]| Graph::of(files, restricted, public);
+    let (original, current) = (graph(&original), graph(&current));
+
+    let mentioned = original.mentioned();
+    let mut dead: Vec<_> = current
+        .nodes
+        .iter()
+        .map(|node| mentioned.contains(node.ident.as_str()))
+        .collect();
+    // Anything mentioned by the items that stay stays as well
+    loop {
+        let mut live = current.roots.clone();
+        for (node, _) in current.nodes.iter().zip(&dead).filter(|(_, dead)| !**dead) {
+            live.extend(node.mentions.iter().cloned());
+        }
+        let mut revived = false;
+        for (node, dead) in current.nodes.iter().zip(&mut dead) {
+            if *dead && live.contains(&node.ident) {
+                *dead = false;
+                revived = true;
+            }
+        }
+        if !revived {
+            break;
+        }
+    }
+
+    current
+        .nodes
+        .iter()
+        .zip(dead)
+        .filter(|(_, dead)| *dead)
+        .filter_map(|(node, _)| {
+            // Point at the item in the original file
+            let original_node = original.nodes.iter().find(|original| {
+                original.file == node.file
+                    && original.kind == node.kind
+                    && original.ident == node.ident
+            })?;
+            let file = &files[node.file];
+
+            Some(UnusedDiagnostic {
+                kind: node.kind,
+                ident: node.ident.clone(),
+                span: synthetic_span(file.file_name, file.original, original_node.span),
+                suggestion: None,
+                configs: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// The private items of the file that were used in `original`, but are no
+/// longer used in `current`, for a file whose crate is unknown. Files with
+/// out-of-line modules are skipped, as these modules could use the items as
+/// well.
+pub fn candidates(file_name: &str, original: &str, current: &str) -> Vec<UnusedDiagnostic> {
+    let Ok(current_file) = syn::parse_file(current) else {
+        return Vec::new();
+    };
+    let has_module_files = current_file
+        .items
+        .iter()
+        .any(|item| matches!(item, Item::Mod(module) if module.content.is_none()));
+    if has_module_files {
+        return Vec::new();
+    }
+
+    let file = CrateFile {
+        file_name,
+        original,
+        current,
+    };
+    find(&[file], false, false)
+}
+
+/// The files of each crate, given by their root files along with whether
+/// their `pub` items count as private
+pub fn crate_files<'a>(
+    roots: impl IntoIterator<Item = (&'a std::path::Path, bool)>,
+    workspace_root: &std::path::Path,
+) -> Vec<Crate> {
+    let workspace_root = workspace_root
+        .canonicalize()
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let mut crates: Vec<Crate> = roots
+        .into_iter()
+        .map(|(root, public_items)| {
+            let mut files: Vec<_> = modules::crate_files(root)
+                .iter()
+                .filter_map(|file| file.strip_prefix(&workspace_root).ok())
+                .map(|file| file.to_string_lossy().replace('\\', "/"))
+                .collect();
+            files.sort();
+            Crate {
+                files,
+                public_items,
+            }
+        })
+        .collect();
+    crates.sort();
+    crates.dedup();
+
+    crates
+}
+
+/// Intersects the candidates found in the crates: files compiled into several
+/// crates, such as modules declared by both `main.rs` and `lib.rs`, only lose
+/// the items that are candidates in each of them
+pub fn intersect(found: Vec<Vec<UnusedDiagnostic>>, crates: &[Crate]) -> Vec<UnusedDiagnostic> {
+    let key = |diagnostic: &UnusedDiagnostic| {
+        (
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+            diagnostic.span.file_name.clone(),
+            diagnostic.span.line_start,
+        )
+    };
+    let mut counts = HashMap::new();
+    for diagnostic in found.iter().flatten() {
+        *counts.entry(key(diagnostic)).or_insert(0) += 1;
+    }
+
+    let mut candidates: Vec<UnusedDiagnostic> = Vec::new();
+    for diagnostic in found.into_iter().flatten() {
+        let file_name = &diagnostic.span.file_name;
+        let compiled_by = crates
+            .iter()
+            .filter(|krate| krate.files.contains(file_name))
+            .count();
+        let unique = !candidates
+            .iter()
+            .any(|known| key(known) == key(&diagnostic));
+        if counts[&key(&diagnostic)] >= compiled_by.max(1) && unique {
+            candidates.push(diagnostic);
+        }
+    }
+
+    candidates
+}
+
+fn private_item(
+    item: &Item,
+    restricted: bool,
+    public: bool,
+) -> Option<(UnusedDiagnosticKind, &syn::Ident)> {
+    use UnusedDiagnosticKind::*;
+
+    let (kind, vis, ident) = match item {
+        Item::Fn(obj) => (Function, &obj.vis, &obj.sig.ident),
+        Item::Const(obj) => (Constant, &obj.vis, &obj.ident),
+        Item::Static(obj) => (Static, &obj.vis, &obj.ident),
+        Item::Struct(obj) => (Struct, &obj.vis, &obj.ident),
+        Item::Enum(obj) => (Enum, &obj.vis, &obj.ident),
+        Item::Union(obj) => (Union, &obj.vis, &obj.ident),
+        Item::Type(obj) => (TypeAlias, &obj.vis, &obj.ident),
+        _ => return None,
+    };
+
+    let private = match vis {
+        Visibility::Inherited => true,
+        Visibility::Restricted(_) => restricted,
+        Visibility::Public(_) => public,
+    };
+    private.then_some((kind, ident))
+}
+
+fn collect_idents(tokens: TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    fn idents(original: &str, current: &str) -> Vec<String> {
+        candidates("lib.rs", original, current)
+            .into_iter()
+            .map(|diagnostic| diagnostic.ident)
+            .collect()
+    }
+
+    #[test]
+    fn finds_items_only_used_by_removed_code() {
+        let original = "struct Only;\nimpl Only { fn new() -> Self { Only } }\nstruct Kept;\n\
+                        fn f(_: Only) {}\nfn main() { let _ = Kept; }";
+        let current = "struct Only;\nimpl Only { fn new() -> Self { Only } }\nstruct Kept;\n\
+                       fn main() { let _ = Kept; }";
+        // `Only` is still mentioned by its impl
+        assert!(idents(original, current).is_empty());
+
+        let original =
+            "struct Arg;\nconst N: usize = 1;\nfn f(_: Arg) -> usize { N }\nfn main() {}";
+        let current = "struct Arg;\nconst N: usize = 1;\nfn main() {}";
+        assert_eq!(idents(original, current), vec!["Arg", "N"]);
+
+        let current = "pub struct Arg;\nconst N: usize = 1;\nmod other;\nfn main() {}";
+        assert!(idents(original, current).is_empty());
+    }
+
+    #[test]
+    fn follows_references_across_modules() {
+        let lib =
+            "mod util;\nfn f() -> u8 { util::public(); util::helper() }\nfn main() { g(); }\n\
+                   fn g() {}";
+        let lib_now = "mod util;\nfn main() { g(); }\nfn g() {}";
+        let util = "pub(crate) fn helper() -> u8 { inner() + LIMIT }\n\
+                    fn inner() -> u8 { 1 }\nconst LIMIT: u8 = 2;\nfn kept() {}\n\
+                    pub fn public() {}";
+        let files = [
+            CrateFile {
+                file_name: "src/main.rs",
+                original: lib,
+                current: lib_now,
+            },
+            CrateFile {
+                file_name: "src/util.rs",
+                original: util,
+                current: util,
+            },
+        ];
+        let found = |public| -> Vec<_> {
+            crate_candidates(&files, public)
+                .into_iter()
+                .map(|diagnostic| (diagnostic.span.file_name, diagnostic.ident))
+                .collect()
+        };
+        // `kept` was never used, so rustc already reports it
+        let util = |ident: &str| ("src/util.rs".to_string(), ident.to_string());
+        assert_eq!(found(false), [util("helper"), util("inner"), util("LIMIT")]);
+        // `public` only goes when `pub` items count as private, as in binaries
+        let public = [util("helper"), util("inner"), util("LIMIT"), util("public")];
+        assert_eq!(found(true), public);
+    }
+
+    #[test]
+    fn removes_pub_items_of_binaries_when_aggressive() {
+        let main_rs = "mod util;\n\n\
+                       trait Shape {\n    fn area(&self) -> u8;\n}\n\n\
+                       struct Square;\n\n\
+                       impl Shape for Square {\n    fn area(&self) -> u8 {\n        1\n    }\n}\n\n\
+                       impl Shape for Box<dyn Shape> {\n    fn area(&self) -> u8 {\n        \
+                       util::scale()\n    }\n}\n\n\
+                       fn main() {\n    let _ = Square.area();\n}\n";
+        let util_rs = "pub fn scale() -> u8 {\n    2\n}\n";
+        let temp_crate =
+            TempCrate::with_files([("src/main.rs", main_rs), ("src/util.rs", util_rs)]).unwrap();
+        let args = ["--kinds", "dyn_shim", "--cascade"];
+
+        // Without the flag, `pub` items stay, even in binaries
+        temp_crate.minify(&args).unwrap();
+        assert!(!temp_crate
+            .read("src/main.rs")
+            .unwrap()
+            .contains("dyn Shape"));
+        assert_eq!(temp_crate.read("src/util.rs").unwrap(), util_rs);
+
+        let aggressive = [&args[..], &["--bin-aggressive"]].concat();
+        let write = |path: &str, contents: &str| {
+            std::fs::write(temp_crate.root().join(path), contents).unwrap();
+        };
+        write("src/main.rs", main_rs);
+        temp_crate.minify(&aggressive).unwrap();
+        assert!(!temp_crate
+            .read("src/main.rs")
+            .unwrap()
+            .contains("dyn Shape"));
+        assert_eq!(temp_crate.read("src/util.rs").unwrap(), "");
+
+        // Nor do they go in packages with a library
+        write("src/main.rs", main_rs);
+        write("src/util.rs", util_rs);
+        write("src/lib.rs", "");
+        temp_crate.minify(&aggressive).unwrap();
+        assert!(!temp_crate
+            .read("src/main.rs")
+            .unwrap()
+            .contains("dyn Shape"));
+        assert_eq!(temp_crate.read("src/util.rs").unwrap(), util_rs);
+    }
+}
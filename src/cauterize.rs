@@ -1,22 +1,108 @@
 use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
+    io,
     ops::Range,
     path::{Path, PathBuf},
+    thread,
+    time::Duration,
 };
 
+use serde::{Deserialize, Serialize};
 use syn::{spanned::Spanned, File};
 
-use crate::unused::{UnusedDiagnostic, UnusedDiagnosticKind};
+use crate::{
+    allows, annotate, assoc_items, bounds, cascade, const_params, dead_modules, dyn_shims,
+    edit::{SourceFileEditor, TextEdit},
+    extern_crates, imports, lifetimes,
+    suggestions::Replacement,
+    trait_impls, unreachable, unreachable_pub,
+    unused::{UnusedDiagnostic, UnusedDiagnosticKind},
+    unused_mut, unused_pub, variables, variants,
+};
 
 const SPACE: u8 = b' ';
+const TAB: u8 = b'\t';
 const NEWLINE: u8 = b'\n';
 
+/// How much reviewing a change needs: mechanical changes only delete whole
+/// items (or names), while semantic ones rewrite signatures and the code
+/// calling them, and are only applied with `--apply-semantic`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeCategory {
+    #[default]
+    Mechanical,
+    Semantic,
+}
+
+impl Display for ChangeCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ChangeCategory::Mechanical => "mechanical",
+            ChangeCategory::Semantic => "semantic",
+        })
+    }
+}
+
 pub struct Change {
     file_name: PathBuf,
     original_content: Vec<u8>,
     proposed_content: Vec<u8>,
+    category: ChangeCategory,
+    /// The file is deleted, rather than emptied
+    removal: bool,
 }
 
 impl Change {
+    pub fn new(file_name: PathBuf, original_content: Vec<u8>, proposed_content: Vec<u8>) -> Self {
+        Change {
+            file_name,
+            original_content,
+            proposed_content,
+            category: ChangeCategory::Mechanical,
+            removal: false,
+        }
+    }
+
+    /// Deletes the file, such as the file of a module whose code is all unused
+    pub fn removal(file_name: PathBuf, original_content: Vec<u8>) -> Self {
+        Change {
+            removal: true,
+            ..Change::new(file_name, original_content, Vec::new())
+        }
+    }
+
+    /// Writes the file back with its content, such as a file that a removal
+    /// deleted
+    pub fn restore(file_name: PathBuf, content: Vec<u8>) -> Self {
+        Change::new(file_name, Vec::new(), content)
+    }
+
+    /// The same change to a file at another path, such as its copy
+    pub fn relocated(&self, file_name: PathBuf) -> Self {
+        Change {
+            file_name,
+            original_content: self.original_content.clone(),
+            proposed_content: self.proposed_content.clone(),
+            category: self.category,
+            removal: self.removal,
+        }
+    }
+
+    pub fn with_category(self, category: ChangeCategory) -> Self {
+        Change { category, ..self }
+    }
+
+    /// A file with any semantic edit is a semantic change as a whole
+    pub fn category(&self) -> ChangeCategory {
+        self.category
+    }
+
+    pub fn is_removal(&self) -> bool {
+        self.removal
+    }
+
     pub fn file_name(&self) -> &Path {
         &self.file_name
     }
@@ -47,15 +133,57 @@ fn find_prefix_whitespace(src: &[u8]) -> usize {
 /// Finds the position of the first whitespace that is not considered belonging
 /// to the previous definition/declaration (this is kind of "heuristic")
 /// Current heuristic:
-/// - if there is a newline, eat all space before it, and the newline
+/// - if there is a newline, eat all spaces and tabs before it, and the newline
 /// - if there is no newline, eat all trailing whitespace until the next token
 fn find_suffix_whitespace(src: &[u8]) -> usize {
     src.iter()
-        .position(|c| *c != SPACE)
+        .position(|&c| !is_blank(c))
         .map(|pos| if src[pos] == NEWLINE { pos + 1 } else { pos })
         .unwrap_or(src.len())
 }
 
+/// Whitespace that doesn't end a line; `\r` is included so that the `\r\n`
+/// line endings are treated like plain newlines
+fn is_blank(c: u8) -> bool {
+    c == SPACE || c == TAB || c == b'\r'
+}
+
+fn is_blank_line(line: &[u8]) -> bool {
+    line.iter().all(|&c| is_blank(c))
+}
+
+/// If the chunk `start..end` consists of whole lines, and removing them would
+/// leave blank lines both above and below it, the blank lines below are
+/// removed as well so that at most one blank line remains
+fn collapse_blank_lines(src: &[u8], start: usize, mut end: usize) -> usize {
+    let at_line_start = |pos: usize| pos == 0 || src[pos - 1] == NEWLINE;
+    if !at_line_start(start) || end == start || !at_line_start(end) {
+        return end;
+    }
+
+    let previous_line = match start.checked_sub(1) {
+        Some(newline) => {
+            &src[src[..newline]
+                .iter()
+                .rposition(|&c| c == NEWLINE)
+                .map_or(0, |pos| pos + 1)..newline]
+        }
+        None => &[],
+    };
+    if !is_blank_line(previous_line) {
+        return end;
+    }
+
+    while let Some(len) = src[end..].iter().position(|&c| c == NEWLINE) {
+        if !is_blank_line(&src[end..end + len]) {
+            break;
+        }
+        end += len + 1;
+    }
+
+    end
+}
+
 /// Turns a list of "locations of identifiers" into a list of "chunks"
 fn diagnostics_to_ranges<'a>(
     src: &'a [u8],
@@ -66,179 +194,667 @@ fn diagnostics_to_ranges<'a>(
 
     let cumulative_lengths = line_offsets(src);
 
+    // The imports of a `use` item are pruned together, re-exports included
+    let (unused_imports, idents): (Vec<_>, Vec<_>) = idents.into_iter().partition(|(kind, _)| {
+        matches!(
+            kind,
+            UnusedDiagnosticKind::Import | UnusedDiagnosticKind::Reexport
+        )
+    });
+    let unused_imports: Vec<_> = unused_imports.into_iter().map(|(_, key)| key).collect();
+    let import_spans = imports::import_spans(&parsed, &unused_imports);
+
     let ranges = idents
         .into_iter()
         .flat_map(move |(kind, ident)| {
-            parsed.items.iter().find_map(|item| {
-                use syn::{ForeignItem, ImplItem, Item};
-                use UnusedDiagnosticKind::*;
-                let item_ident = match item {
-                    Item::Const(obj) if kind == Constant => &obj.ident,
-                    Item::Enum(obj) if kind == Enum => &obj.ident,
-                    Item::Fn(obj) if kind == Function => &obj.sig.ident,
-                    Item::Macro(syn::ItemMacro {
-                        ident: Some(name), ..
-                    }) if kind == MacroDefinition => name,
-                    Item::Static(obj) if kind == Static => &obj.ident,
-                    Item::Struct(obj) if kind == Struct => &obj.ident,
-                    Item::Type(obj) if kind == TypeAlias => &obj.ident,
-                    Item::Union(obj) if kind == Union => &obj.ident,
-                    Item::Mod(block) => return handle_mod_diagnostic(block, &kind, &ident),
-                    Item::ForeignMod(block) => {
-                        return block.items.iter().find_map(|item| {
-                            let item_ident = match item {
-                                ForeignItem::Fn(obj) if kind == Function => &obj.sig.ident,
-                                ForeignItem::Static(obj) if kind == Static => &obj.ident,
-                                ForeignItem::Type(obj) if kind == TypeAlias => &obj.ident,
-                                _ => return None,
-                            };
-
-                            if *item_ident == ident {
-                                Some(item.span())
-                            } else {
-                                None
-                            }
-                        })
-                    }
-                    Item::Impl(block) => {
-                        return block.items.iter().find_map(|item| {
-                            let item_ident = match item {
-                                ImplItem::Const(obj) if kind == Constant => &obj.ident,
-                                ImplItem::Fn(obj) if kind == AssociatedFunction => &obj.sig.ident,
-                                ImplItem::Type(obj) if kind == TypeAlias => &obj.ident,
-                                _ => return None,
-                            };
-
-                            if *item_ident == ident {
-                                Some(item.span())
-                            } else {
-                                None
-                            }
-                        })
-                    }
-                    _ => return None,
-                };
+            if kind == UnusedDiagnosticKind::Bound {
+                return bounds::bound_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::DynShim {
+                return dyn_shims::shim_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::TraitImpl {
+                return trait_impls::impl_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::ConstParam || kind == UnusedDiagnosticKind::TypeParam {
+                return const_params::unused_param_spans(&parsed, kind, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Variant {
+                return variants::variant_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Module {
+                return dead_modules::module_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::ExternCrate {
+                return extern_crates::extern_crate_spans(&parsed, &ident);
+            }
+            if matches!(
+                kind,
+                UnusedDiagnosticKind::AssociatedFunction
+                    | UnusedDiagnosticKind::AssociatedConstant
+                    | UnusedDiagnosticKind::AssociatedType
+            ) {
+                return assoc_items::assoc_item_spans(&parsed, kind, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Lifetime {
+                return lifetimes::lifetime_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Unreachable {
+                return unreachable::unreachable_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Allow {
+                return allows::allow_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Variable {
+                return variables::variable_spans(&parsed, &ident);
+            }
+            if kind == UnusedDiagnosticKind::Mut {
+                return unused_mut::mut_spans(&parsed, &ident);
+            }
 
-                if *item_ident == ident {
-                    Some(item.span())
-                } else {
-                    None
+            // Unused `pub` items may be of any kind of item
+            let kind = match kind {
+                UnusedDiagnosticKind::UnusedPub => {
+                    let found = unused_pub::ITEM_KINDS
+                        .iter()
+                        .find(|&&kind| find_item(&parsed.items, kind, &ident).is_some());
+                    let Some(&kind) = found else {
+                        return Vec::new();
+                    };
+                    kind
                 }
-            })
+                kind => kind,
+            };
+            let Some((span, siblings)) = find_item(&parsed.items, kind, &ident) else {
+                return Vec::new();
+            };
+            let mut spans = vec![span];
+            let is_type = matches!(
+                kind,
+                UnusedDiagnosticKind::Struct
+                    | UnusedDiagnosticKind::Enum
+                    | UnusedDiagnosticKind::Union
+            );
+            if is_type {
+                let name = ident.rsplit(':').next().unwrap_or_default();
+                spans.extend(impl_blocks_of(siblings, name));
+            }
+
+            spans
         })
-        .map(move |span| to_range(&cumulative_lengths, span));
+        .chain(import_spans)
+        .map(move |span| to_range(src, &cumulative_lengths, span));
 
     Ok(ranges)
 }
 
-/// Handles (inline) module content
-fn handle_mod_diagnostic(block: &syn::ItemMod, kind: &UnusedDiagnosticKind, ident: &str) -> Option<proc_macro2::Span> {
-    use syn::Item;
-    use UnusedDiagnosticKind::*;
-
-    block.content.iter().find_map(|(_b, items)| {
-        items.iter().find_map(|item| {
-            let item_ident = match item {
-                Item::Fn(obj) if *kind == Function => &obj.sig.ident,
-                Item::Static(obj) if *kind == Static => &obj.ident,
-                Item::Type(obj) if *kind == TypeAlias => &obj.ident,
-                Item::Mod(obj) => return handle_mod_diagnostic(obj, kind, ident),
-                _ => return None,
-            };
+/// The `impl` and `impl Trait for` blocks of a type among the items, which
+/// can't compile once the type is removed
+fn impl_blocks_of(items: &[syn::Item], ident: &str) -> Vec<proc_macro2::Span> {
+    fn self_type(ty: &syn::Type) -> Option<&syn::Ident> {
+        match ty {
+            syn::Type::Path(path) if path.qself.is_none() => {
+                Some(&path.path.segments.last()?.ident)
+            }
+            syn::Type::Reference(reference) => self_type(&reference.elem),
+            syn::Type::Paren(paren) => self_type(&paren.elem),
+            syn::Type::Group(group) => self_type(&group.elem),
+            _ => None,
+        }
+    }
 
-            if item_ident == ident {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Impl(block) if self_type(&block.self_ty).is_some_and(|ty| ty == ident) => {
                 Some(item.span())
-            } else {
-                None
             }
+            _ => None,
         })
+        .collect()
+}
+
+/// Finds the item of the kind named by `key`, as in `foo`, or `12:foo` for
+/// the one named on line 12, among the items and those of inline modules, such
+/// as `#[cfg(test)] mod tests`; returns its span, along with the items of the
+/// module it's in
+fn find_item<'a>(
+    items: &'a [syn::Item],
+    kind: UnusedDiagnosticKind,
+    key: &str,
+) -> Option<(proc_macro2::Span, &'a [syn::Item])> {
+    use syn::{ForeignItem, Item};
+    use UnusedDiagnosticKind::*;
+
+    let (line, name) = match key.split_once(':') {
+        Some((line, name)) => (Some(line.parse::<usize>().ok()?), name),
+        None => (None, key),
+    };
+    let is_named = |ident: &syn::Ident| {
+        ident == name && line.is_none_or(|line| ident.span().start().line == line)
+    };
+
+    items.iter().find_map(|item| {
+        let item_ident = match item {
+            Item::Const(obj) if kind == Constant => &obj.ident,
+            Item::Enum(obj) if kind == Enum => &obj.ident,
+            Item::Fn(obj) if kind == Function => &obj.sig.ident,
+            Item::Macro(syn::ItemMacro {
+                ident: Some(name), ..
+            }) if kind == MacroDefinition => name,
+            Item::Static(obj) if kind == Static => &obj.ident,
+            Item::Struct(obj) if kind == Struct => &obj.ident,
+            Item::Type(obj) if kind == TypeAlias => &obj.ident,
+            Item::Union(obj) if kind == Union => &obj.ident,
+            Item::Mod(block) => {
+                let (_, items) = block.content.as_ref()?;
+                return find_item(items, kind, key);
+            }
+            Item::ForeignMod(block) => {
+                return block.items.iter().find_map(|foreign| {
+                    let item_ident = match foreign {
+                        ForeignItem::Fn(obj) if kind == Function => &obj.sig.ident,
+                        ForeignItem::Static(obj) if kind == Static => &obj.ident,
+                        ForeignItem::Type(obj) if kind == TypeAlias => &obj.ident,
+                        _ => return None,
+                    };
+                    is_named(item_ident).then(|| (foreign.span(), items))
+                })
+            }
+            _ => return None,
+        };
+
+        is_named(item_ident).then(|| (item.span(), items))
     })
 }
 
+/// Finds the start of the `//` comment lines directly above `start`, which
+/// (by adjacency) are taken to only document the item starting there; doc
+/// comments are left alone, as `///` is already part of the item and `//!`
+/// belongs to the enclosing module
+fn find_orphaned_comments(src: &[u8], mut start: usize) -> usize {
+    while start > 0 && src[start - 1] == NEWLINE {
+        let line_start = src[..start - 1]
+            .iter()
+            .rposition(|&c| c == NEWLINE)
+            .map_or(0, |pos| pos + 1);
+        let line = String::from_utf8_lossy(&src[line_start..start - 1]);
+        let line = line.trim_start();
+        if !line.starts_with("//") || line.starts_with("///") || line.starts_with("//!") {
+            break;
+        }
+        start = line_start;
+    }
+
+    start
+}
+
+/// Squashes the run of blank lines following `end` to a single one
+fn squash_blank_lines(src: &[u8], mut end: usize) -> usize {
+    if end != 0 && src[end - 1] != NEWLINE {
+        return end;
+    }
+
+    let mut blank_lines = Vec::new();
+    let mut pos = end;
+    while let Some(len) = src[pos..].iter().position(|&c| c == NEWLINE) {
+        if !is_blank_line(&src[pos..pos + len]) {
+            break;
+        }
+        pos += len + 1;
+        blank_lines.push(pos);
+    }
+    if blank_lines.len() > 1 {
+        end = blank_lines[blank_lines.len() - 2];
+    }
+
+    end
+}
+
 fn expand_ranges_to_include_whitespace<'a>(
     src: &'a [u8],
     iter: impl Iterator<Item = Range<usize>> + 'a,
+    tidy: bool,
 ) -> impl Iterator<Item = Range<usize>> + 'a {
-    iter.map(|range| {
-        find_prefix_whitespace(&src[..range.start])
-            ..find_suffix_whitespace(&src[range.end..]) + range.end
+    iter.map(move |range| {
+        let mut start = find_prefix_whitespace(&src[..range.start]);
+        let mut end = find_suffix_whitespace(&src[range.end..]) + range.end;
+        // Words on both sides stay apart, as in `impl<'a> S` becoming `impl S`
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+        if start > 0 && end < src.len() && is_word(src[start - 1]) && is_word(src[end]) {
+            end = range.end;
+        }
+        if tidy {
+            start = find_orphaned_comments(src, start);
+        }
+        end = collapse_blank_lines(src, start, end);
+        if tidy {
+            end = squash_blank_lines(src, end);
+        }
+
+        start..end
     })
 }
 
-/// Deletes a list-of-positions-of-identifiers from a bytearray that is valid
-/// rust code BUGS: if the position is in the body of a function, it will try to
-/// delete identifiers there ...  probably?
+/// Deletes the chunks, which may overlap, from a bytearray
 pub fn delete_chunks(src: &[u8], chunks_to_delete: &[Range<usize>]) -> Vec<u8> {
-    src.iter()
-        .enumerate()
-        .filter_map(|(i, &byte)| {
-            if chunks_to_delete.iter().any(|range| range.contains(&i)) {
-                None
-            } else {
-                Some(byte)
-            }
-        })
-        .collect()
+    let mut editor = SourceFileEditor::new(src);
+    for range in merge_chunks(chunks_to_delete) {
+        editor.edit(TextEdit::delete(range));
+    }
+
+    editor.apply().expect("merged chunks don't overlap")
+}
+
+/// Merges the overlapping chunks; they may overlap, as the whitespace around
+/// neighbouring items is claimed by both
+fn merge_chunks(chunks_to_delete: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut chunks = chunks_to_delete.to_vec();
+    chunks.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in chunks {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
 }
 
 /// Deletes a list-of-positions-of-identifiers from a bytearray that is valid
 /// rust code BUGS: if the position is in the body of a function, it will try to
 /// delete identifiers there ...  probably?
-pub fn rust_delete(
+/// The given edits, such as rustc's suggestions, are applied as well; those
+/// overlapping the deleted code, or an earlier edit, are skipped. Deletions
+/// starting at an item take its attributes and doc comments along, which
+/// rustc's spans leave out. With `tidy`, the comments directly above the
+/// deleted items are removed too, and the blank lines left behind are squashed
+pub fn rust_edit(
     src: &[u8],
     diagnostics: impl IntoIterator<Item = (UnusedDiagnosticKind, String)>,
+    edits: impl IntoIterator<Item = TextEdit>,
+    tidy: bool,
 ) -> Result<Vec<u8>, syn::Error> {
-    let chunks_to_delete =
-        expand_ranges_to_include_whitespace(src, diagnostics_to_ranges(src, diagnostics)?);
+    let attributed = attributed_items(&syn::parse_str(&String::from_utf8_lossy(src))?, src);
+    let with_attributes = |mut range: Range<usize>| {
+        if let Some(&start) = attributed.get(&range.start) {
+            range.start = start;
+        }
+        range
+    };
+    let ranges = diagnostics_to_ranges(src, diagnostics)?.map(with_attributes);
+    let chunks_to_delete: Vec<_> = expand_ranges_to_include_whitespace(src, ranges, tidy).collect();
+    let mut taken = merge_chunks(&chunks_to_delete);
+
+    let mut editor = SourceFileEditor::new(src);
+    for range in &taken {
+        editor.edit(TextEdit::delete(range.clone()));
+    }
+    for mut edit in edits {
+        if edit.replacement.is_empty() {
+            edit.range = with_attributes(edit.range);
+        }
+        let range = &edit.range;
+        let overlaps = |other: &Range<usize>| range.start < other.end && other.start < range.end;
+        if range.start > range.end || range.end > src.len() || taken.iter().any(overlaps) {
+            continue;
+        }
+        taken.push(range.clone());
+        editor.edit(edit);
+    }
+
+    Ok(editor.apply().expect("edits don't overlap"))
+}
+
+/// How the code is removed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RemovalOptions {
+    /// Also remove the comments directly above removed items, and squash the
+    /// blank lines left behind
+    pub tidy: bool,
+    /// Also remove the private items that were only used by removed code
+    pub cascade: bool,
+}
+
+/// The key to remove the finding by
+fn removal_key(warn: &UnusedDiagnostic) -> String {
+    match warn.kind {
+        UnusedDiagnosticKind::Import
+        | UnusedDiagnosticKind::Reexport
+        | UnusedDiagnosticKind::AssociatedFunction
+        | UnusedDiagnosticKind::AssociatedConstant
+        | UnusedDiagnosticKind::AssociatedType
+        | UnusedDiagnosticKind::ExternCrate
+        | UnusedDiagnosticKind::Lifetime
+        // Items of the same name may be in several modules of a file, such
+        // as `mod tests`
+        | UnusedDiagnosticKind::Constant
+        | UnusedDiagnosticKind::Static
+        | UnusedDiagnosticKind::Function
+        | UnusedDiagnosticKind::Struct
+        | UnusedDiagnosticKind::Enum
+        | UnusedDiagnosticKind::Union
+        | UnusedDiagnosticKind::TypeAlias
+        | UnusedDiagnosticKind::MacroDefinition
+        | UnusedDiagnosticKind::UnusedPub => imports::removal_key(warn),
+        UnusedDiagnosticKind::Unreachable => unreachable::removal_key(warn),
+        UnusedDiagnosticKind::Allow => allows::removal_key(warn),
+        UnusedDiagnosticKind::Variable => variables::removal_key(warn),
+        UnusedDiagnosticKind::Mut => unused_mut::removal_key(warn),
+        _ => warn.ident.clone(),
+    }
+}
+
+/// A file that was left alone, as it couldn't be parsed before or after the
+/// removals
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnparsedFile {
+    /// The name of the file in the diagnostics
+    pub file_name: String,
+    pub line: usize,
+    pub error: String,
+}
+
+impl UnparsedFile {
+    fn new(file_name: String, error: syn::Error) -> Self {
+        UnparsedFile {
+            file_name,
+            line: error.span().start().line,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// The replacements of rustc's suggestions, by the kind of the findings
+type Suggested = Vec<(UnusedDiagnosticKind, Replacement)>;
+
+/// The removals from a file
+struct FileRemovals {
+    path: PathBuf,
+    /// The name of the file in the diagnostics
+    file_name: String,
+    original_content: Vec<u8>,
+    keys: Vec<(UnusedDiagnosticKind, String)>,
+    /// The findings removed by rustc's suggestions instead
+    suggested: Suggested,
+    /// The comments marking the findings that are kept
+    annotations: Vec<TextEdit>,
+    /// The `pub` tokens of the findings that are restricted to `pub(crate)`
+    /// rather than removed
+    restrictions: Vec<TextEdit>,
+    removed: Vec<u8>,
+    /// Why the file couldn't be edited, in which case it's left alone
+    error: Option<syn::Error>,
+}
+
+impl FileRemovals {
+    fn delete(&mut self, tidy: bool) {
+        let keys = self.keys.iter().cloned();
+        let edits = self
+            .suggested
+            .iter()
+            .map(|(_, replacement)| replacement.to_edit());
+        let edits = edits.chain(self.annotations.iter().cloned());
+        let edits = edits.chain(self.restrictions.iter().cloned());
+        match rust_edit(&self.original_content, keys, edits, tidy) {
+            Ok(removed) => self.removed = removed,
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    fn is_semantic(&self) -> bool {
+        let kinds = self.keys.iter().map(|(kind, _)| kind);
+        kinds
+            .chain(self.suggested.iter().map(|(kind, _)| kind))
+            .any(|kind| kind.is_semantic())
+    }
+}
+
+/// The index of the removals from the file, which are added if there are
+/// none yet
+fn file_entry(files: &mut Vec<FileRemovals>, base: &Path, file_name: &str) -> Option<usize> {
+    if let Some(index) = files.iter().position(|file| file.file_name == file_name) {
+        return Some(index);
+    }
+
+    let path = base.join(file_name);
+    let original_content = std::fs::read(&path).ok()?;
+    files.push(FileRemovals {
+        path,
+        file_name: file_name.to_string(),
+        removed: original_content.clone(),
+        original_content,
+        keys: Vec::new(),
+        suggested: Vec::new(),
+        annotations: Vec::new(),
+        restrictions: Vec::new(),
+        error: None,
+    });
+
+    Some(files.len() - 1)
+}
+
+/// Splits the findings of a file into the keys to remove them by, and the
+/// replacements of rustc's suggestions; a suggestion is only taken when all
+/// the findings it covers are removed
+fn split_suggested(
+    diagnostics: &[&UnusedDiagnostic],
+) -> (Vec<(UnusedDiagnosticKind, String)>, Suggested) {
+    let mut counts = HashMap::new();
+    for suggestion in diagnostics
+        .iter()
+        .filter_map(|warn| warn.suggestion.as_ref())
+    {
+        *counts.entry(suggestion).or_insert(0) += 1;
+    }
+
+    let mut keys = Vec::new();
+    let mut suggested = Vec::new();
+    let mut seen = HashSet::new();
+    for warn in diagnostics {
+        match &warn.suggestion {
+            Some(suggestion) if counts[suggestion] == suggestion.covers => {
+                let new = suggestion.replacements.iter().filter(|r| seen.insert(*r));
+                suggested.extend(new.map(|replacement| (warn.kind, replacement.clone())));
+            }
+            _ => keys.push((warn.kind, removal_key(warn))),
+        }
+    }
+
+    (keys, suggested)
+}
+
+/// The items that were only used by the removed code, in the crates of the
+/// changed files; files that aren't in any of the crates are looked at on
+/// their own
+fn cascade_candidates(
+    files: &[FileRemovals],
+    crates: &[cascade::Crate],
+    base: &Path,
+) -> Vec<UnusedDiagnostic> {
+    let mut found = Vec::new();
+    let mut sources = HashMap::new();
+    for krate in crates {
+        let changed = |name: &String| files.iter().find(|file| file.file_name == *name);
+        if !krate.files.iter().any(|name| changed(name).is_some()) {
+            continue;
+        }
+        let contents: Vec<_> = krate
+            .files
+            .iter()
+            .filter_map(|name| match changed(name) {
+                Some(file) => Some((
+                    name,
+                    String::from_utf8_lossy(&file.original_content).into_owned(),
+                    String::from_utf8_lossy(&file.removed).into_owned(),
+                )),
+                None => {
+                    let src = sources
+                        .entry(name)
+                        .or_insert_with(|| std::fs::read_to_string(base.join(name)).ok())
+                        .clone()?;
+                    Some((name, src.clone(), src))
+                }
+            })
+            .collect();
+        let crate_files: Vec<_> = contents
+            .iter()
+            .map(|(file_name, original, current)| cascade::CrateFile {
+                file_name,
+                original,
+                current,
+            })
+            .collect();
+        found.push(cascade::crate_candidates(&crate_files, krate.public_items));
+    }
+    for file in files {
+        if !crates
+            .iter()
+            .any(|krate| krate.files.contains(&file.file_name))
+        {
+            found.push(cascade::candidates(
+                &file.file_name,
+                &String::from_utf8_lossy(&file.original_content),
+                &String::from_utf8_lossy(&file.removed),
+            ));
+        }
+    }
 
-    Ok(delete_chunks(src, &chunks_to_delete.collect::<Vec<_>>()))
+    cascade::intersect(found, crates)
 }
 
-/// Processes a list of file+list-of-edits into an iterator of
-/// filenames+proposed new contents
-fn process_files<Iter: IntoIterator<Item = UnusedDiagnostic>>(
-    diagnostics: impl IntoIterator<Item = (PathBuf, Iter)>,
-) -> impl Iterator<Item = Change> {
-    diagnostics
+/// Process a list of UnusedDiagnostics into filenames+proposed contents, and
+/// the items that are only used by the removed code (which are removed as
+/// well with [`RemovalOptions::cascade`]); `crates` lists the files of each
+/// crate, named as in the diagnostics. The files that can't be parsed are left
+/// alone, and returned as well.
+pub fn process_diagnostics<'a>(
+    diagnostics: impl IntoIterator<Item = &'a UnusedDiagnostic>,
+    annotated: &[UnusedDiagnostic],
+    manifest_path: Option<&PathBuf>,
+    crates: &[cascade::Crate],
+    options: RemovalOptions,
+) -> (Vec<Change>, Vec<UnusedDiagnostic>, Vec<UnparsedFile>) {
+    let base = manifest_path
+        .and_then(|manifest_path| manifest_path.parent())
+        .unwrap_or(Path::new(""));
+    let by_file: multimap::MultiMap<_, _> = diagnostics
+        .into_iter()
+        .map(|diagnostic| (diagnostic.span.file_name.clone(), diagnostic))
+        .collect();
+
+    let mut files: Vec<_> = by_file
         .into_iter()
-        .filter_map(|(file_name, diagnostic)| {
-            let original_content = std::fs::read(&file_name).ok()?;
-            let removed_unused = rust_delete(
-                &original_content,
-                diagnostic.into_iter().map(|warn| (warn.kind, warn.ident)),
-            )
-            .expect("syntax error");
-            let proposed_content = remove_empty_blocks(&removed_unused).expect("syntax error");
-
-            let change = Change {
+        .filter_map(|(file_name, diagnostics)| {
+            let path = base.join(&file_name);
+            let original_content = std::fs::read(&path).ok()?;
+            // Unreachable `pub` items are kept, with a narrower visibility
+            let (restricted, diagnostics): (Vec<_>, Vec<_>) = diagnostics
+                .into_iter()
+                .partition(|warn| warn.kind == UnusedDiagnosticKind::UnreachablePub);
+            let restrictions = restricted
+                .into_iter()
+                .filter_map(|warn| unreachable_pub::restriction(&original_content, warn))
+                .collect();
+            // Side-effect-free `let` statements of unused variables go altogether
+            let lets = variables::removable_lets(&original_content, &diagnostics);
+            let (lets, diagnostics): (Vec<_>, Vec<_>) = diagnostics.into_iter().partition(|warn| {
+                warn.kind == UnusedDiagnosticKind::Variable
+                    && lets.contains(&variables::removal_key(warn))
+            });
+            let (mut keys, suggested) = split_suggested(&diagnostics);
+            keys.extend(lets.into_iter().map(|warn| (warn.kind, removal_key(warn))));
+            let mut file = FileRemovals {
+                path,
                 file_name,
+                removed: original_content.clone(),
                 original_content,
-                proposed_content,
+                keys,
+                suggested,
+                annotations: Vec::new(),
+                restrictions,
+                error: None,
             };
+            file.delete(options.tidy);
+            Some(file)
+        })
+        .collect();
+
+    // The findings that are kept are marked instead
+    let mut touched = HashSet::new();
+    for diagnostic in annotated {
+        let Some(index) = file_entry(&mut files, base, &diagnostic.span.file_name) else {
+            continue;
+        };
+        let file = &mut files[index];
+        let annotation = annotate::annotation(&file.original_content, diagnostic);
+        let is_new = |edit: &TextEdit| !file.annotations.contains(edit);
+        if let Some(edit) = annotation.filter(is_new) {
+            file.annotations.push(edit);
+            touched.insert(index);
+        }
+    }
+    for index in touched {
+        files[index].delete(options.tidy);
+    }
 
-            Some(change)
+    // The whole graph of references is followed at once, so there's no need
+    // to look for candidates again after removing them
+    let cascaded = cascade_candidates(&files, crates, base);
+    if options.cascade {
+        let mut touched = HashSet::new();
+        for diagnostic in &cascaded {
+            let Some(index) = file_entry(&mut files, base, &diagnostic.span.file_name) else {
+                continue;
+            };
+            files[index]
+                .keys
+                .push((diagnostic.kind, removal_key(diagnostic)));
+            touched.insert(index);
+        }
+        for index in touched {
+            files[index].delete(options.tidy);
+        }
+    }
+
+    let mut changes = Vec::new();
+    let mut unparsed = Vec::new();
+    for mut file in files {
+        let proposed_content = match file.error.take() {
+            Some(err) => Err(err),
+            None => remove_empty_blocks(&file.original_content, &file.removed, options.tidy),
+        };
+        let proposed_content = match proposed_content {
+            Ok(proposed_content) => proposed_content,
+            Err(err) => {
+                unparsed.push(UnparsedFile::new(file.file_name, err));
+                continue;
+            }
+        };
+        let category = if file.is_semantic() {
+            ChangeCategory::Semantic
+        } else {
+            ChangeCategory::Mechanical
+        };
+        changes.push(Change {
+            file_name: file.path,
+            original_content: file.original_content,
+            proposed_content,
+            category,
+            removal: false,
+        });
+    }
+    let cascaded = cascaded
+        .into_iter()
+        .filter(|diagnostic| {
+            !unparsed
+                .iter()
+                .any(|file| file.file_name == diagnostic.span.file_name)
         })
-}
+        .collect();
 
-/// Process a list of UnusedDiagnostics into an iterator of filenames+proposed contents
-pub fn process_diagnostics(
-    diagnostics: impl IntoIterator<Item = UnusedDiagnostic>,
-    manifest_path: Option<&PathBuf>,
-) -> impl Iterator<Item = Change> {
-    process_files(
-        diagnostics
-            .into_iter()
-            .map(|diagnostic| {
-                let file_name = &diagnostic.span.file_name;
-                let path = if let Some(manifest_path) = &manifest_path {
-                    manifest_path.parent().unwrap().join(file_name)
-                } else {
-                    PathBuf::from(file_name)
-                };
-                (path, diagnostic)
-            })
-            .collect::<multimap::MultiMap<_, _>>(),
-    )
+    (changes, cascaded, unparsed)
 }
 
 /// Create a table of byte locations of newline symbols,
@@ -259,46 +875,237 @@ fn line_offsets(bytes: &[u8]) -> Vec<usize> {
     offsets
 }
 
-fn to_range(offsets: &[usize], span: proc_macro2::Span) -> Range<usize> {
-    let byte_offset = |pos: proc_macro2::LineColumn| offsets[pos.line - 1] + pos.column;
+/// The bytes of the source the span covers; proc_macro2 counts columns in
+/// chars, so these are looked up in the line
+fn to_range(src: &[u8], offsets: &[usize], span: proc_macro2::Span) -> Range<usize> {
+    let byte_offset = |pos: proc_macro2::LineColumn| {
+        let start = offsets[pos.line - 1];
+        let end = offsets.get(pos.line).copied().unwrap_or(src.len());
+        let line = String::from_utf8_lossy(&src[start..end]);
+        let column = line.char_indices().nth(pos.column);
+        start + column.map_or(line.len(), |(index, _)| index)
+    };
 
     byte_offset(span.start())..byte_offset(span.end())
 }
 
-fn remove_empty_blocks(bytes: &[u8]) -> Result<Vec<u8>, syn::Error> {
-    let s = String::from_utf8_lossy(bytes).to_string();
-    let ast: File = syn::parse_str(&s)?;
+/// Where each item with outer attributes (doc comments included) starts,
+/// along with them, by where it starts without them; the items of inline
+/// modules, impls and traits included
+fn attributed_items(file: &File, src: &[u8]) -> HashMap<usize, usize> {
+    use proc_macro2::{Delimiter, TokenTree};
+    use quote::ToTokens;
+    use syn::{ImplItem, Item, TraitItem};
 
-    let cumulative_lengths = line_offsets(bytes);
+    let offsets = line_offsets(src);
+    let mut found = HashMap::new();
+    let mut add = |node: &dyn ToTokens| {
+        let tokens: Vec<_> = node.to_token_stream().into_iter().collect();
+        let mut index = 0;
+        while let [TokenTree::Punct(pound), TokenTree::Group(group), ..] = &tokens[index..] {
+            if pound.as_char() != '#' || group.delimiter() != Delimiter::Bracket {
+                break;
+            }
+            index += 2;
+        }
+        if let (true, Some(first), Some(bare)) = (index > 0, tokens.first(), tokens.get(index)) {
+            let start = to_range(src, &offsets, first.span()).start;
+            found.insert(to_range(src, &offsets, bare.span()).start, start);
+        }
+    };
 
-    let spans = ast
-        .items
-        .iter()
-        .filter_map(|item| match item {
-            syn::Item::ForeignMod(block) => {
-                (block.items.is_empty() && block.attrs.is_empty()).then(|| block.span())
+    let mut items: Vec<&Item> = file.items.iter().collect();
+    while let Some(item) = items.pop() {
+        add(item);
+        match item {
+            Item::Mod(module) => {
+                items.extend(module.content.iter().flat_map(|(_, items)| items));
+            }
+            Item::Impl(block) => {
+                for item in &block.items {
+                    if !matches!(item, ImplItem::Verbatim(_)) {
+                        add(item);
+                    }
+                }
             }
+            Item::Trait(block) => {
+                for item in &block.items {
+                    if !matches!(item, TraitItem::Verbatim(_)) {
+                        add(item);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    found
+}
+
+/// The `extern` blocks and inherent impls among the items, and those of the
+/// inline modules, that are left without any items
+fn empty_blocks(items: &[syn::Item]) -> Vec<proc_macro2::Span> {
+    items
+        .iter()
+        .flat_map(|item| match item {
+            syn::Item::ForeignMod(block) => (block.items.is_empty() && block.attrs.is_empty())
+                .then(|| block.span())
+                .into_iter()
+                .collect(),
             syn::Item::Impl(block) => {
                 (block.items.is_empty() && block.attrs.is_empty() && block.trait_.is_none())
                     .then(|| block.span())
+                    .into_iter()
+                    .collect()
             }
-            _ => None,
+            syn::Item::Mod(module) => module
+                .content
+                .as_ref()
+                .map(|(_, items)| empty_blocks(items))
+                .unwrap_or_default(),
+            _ => Vec::new(),
         })
-        .map(|span| to_range(&cumulative_lengths, span));
+        .collect()
+}
+
+/// Removes the `extern` blocks and inherent impls that the removals from
+/// `original` left without any items; those that were empty to begin with,
+/// such as `impl Foo {}` kept on purpose, are left alone
+pub fn remove_empty_blocks(
+    original: &[u8],
+    bytes: &[u8],
+    tidy: bool,
+) -> Result<Vec<u8>, syn::Error> {
+    let s = String::from_utf8_lossy(bytes).to_string();
+    let ast: File = syn::parse_str(&s)?;
+    let original: File = syn::parse_str(&String::from_utf8_lossy(original))?;
+    let text = |span: proc_macro2::Span| span.source_text().unwrap_or_default();
+
+    // Removals don't reorder the code, so the blocks that were already empty
+    // are the first ones with their text
+    let mut already_empty: HashMap<String, usize> = HashMap::new();
+    for span in empty_blocks(&original.items) {
+        *already_empty.entry(text(span)).or_default() += 1;
+    }
+
+    let spans = empty_blocks(&ast.items).into_iter().filter(|span| {
+        match already_empty.get_mut(&text(*span)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        }
+    });
+
+    Ok(remove_spans(bytes, spans, tidy))
+}
+
+/// Deletes the spans of the parsed source, along with the whitespace around
+/// them
+pub fn remove_spans(
+    bytes: &[u8],
+    spans: impl IntoIterator<Item = proc_macro2::Span>,
+    tidy: bool,
+) -> Vec<u8> {
+    let cumulative_lengths = line_offsets(bytes);
+    let ranges = spans
+        .into_iter()
+        .map(|span| to_range(bytes, &cumulative_lengths, span));
 
     let expanded_spans: Vec<Range<usize>> =
-        expand_ranges_to_include_whitespace(bytes, spans).collect();
+        expand_ranges_to_include_whitespace(bytes, ranges, tidy).collect();
 
-    Ok(delete_chunks(bytes, &expanded_spans))
+    delete_chunks(bytes, &expanded_spans)
 }
 
-/// This actually applies a collection of changes to your filesystem (use with care)
+/// Refuses to write a file outside the workspace root, such as the sources
+/// of a registry dependency a diagnostic may point to. Both are
+/// canonicalized, so that neither symlinks nor `..` lead out of the root.
+fn check_within(root: &Path, file: &Path) -> io::Result<()> {
+    let root = root.canonicalize()?;
+    // Files that were deleted, and are about to be restored, are found
+    // through their directory
+    let file = match (file.exists(), file.parent(), file.file_name()) {
+        (false, Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            parent.canonicalize()?.join(name)
+        }
+        _ => file.canonicalize()?,
+    };
+    if file.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "refusing to write outside the workspace root {}",
+                root.display()
+            ),
+        ))
+    }
+}
+
+/// How long to wait before each retry of a write to a locked file
+const LOCK_RETRY_DELAYS: [Duration; 5] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(400),
+    Duration::from_millis(800),
+];
+
+/// Whether the error is due to another program holding the file open, as
+/// editors and IDEs on Windows do: access denied, or a sharing or lock
+/// violation
+fn is_locked(error: &io::Error) -> bool {
+    cfg!(windows) && matches!(error.raw_os_error(), Some(5 | 32 | 33))
+}
+
+/// Runs the operation, retrying it after each of the delays for as long as it
+/// fails with a `transient` error
+fn with_retries<T>(
+    delays: &[Duration],
+    transient: impl Fn(&io::Error) -> bool,
+    mut operation: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    for delay in delays {
+        match operation() {
+            Err(error) if transient(&error) => thread::sleep(*delay),
+            result => return result,
+        }
+    }
+
+    operation().map_err(|error| match transient(&error) {
+        true => io::Error::new(
+            error.kind(),
+            format!("{error}, it may be open in another program"),
+        ),
+        false => error,
+    })
+}
+
+/// This actually applies a collection of changes to your filesystem (use with care),
+/// returning the files that could not be written (or deleted) along with their
+/// errors. Files outside of `root` are never written. Writes to files locked by
+/// other programs are retried for a while, and files that stay locked are
+/// left out, while the others are still written.
 pub fn commit_changes(
     changes: impl IntoIterator<Item = Change>,
-) -> Result<(), Vec<std::io::Error>> {
+    root: &Path,
+) -> Result<(), Vec<(PathBuf, std::io::Error)>> {
     let errors = changes
         .into_iter()
-        .filter_map(|change| std::fs::write(change.file_name, change.proposed_content).err())
+        .filter_map(|change| {
+            let error = check_within(root, &change.file_name)
+                .and_then(|()| {
+                    with_retries(&LOCK_RETRY_DELAYS, is_locked, || match change.removal {
+                        true => std::fs::remove_file(&change.file_name),
+                        false => std::fs::write(&change.file_name, &change.proposed_content),
+                    })
+                })
+                .err()?;
+            Some((change.file_name, error))
+        })
         .collect::<Vec<_>>();
 
     if errors.is_empty() {
@@ -309,8 +1116,18 @@ pub fn commit_changes(
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
+    use crate::test_support::TempCrate;
+
+    /// Like [`rust_edit`], without any edits
+    pub fn rust_delete(
+        src: &[u8],
+        diagnostics: impl IntoIterator<Item = (UnusedDiagnosticKind, String)>,
+        tidy: bool,
+    ) -> Result<Vec<u8>, syn::Error> {
+        rust_edit(src, diagnostics, [], tidy)
+    }
 
     fn fun(name: &str) -> (UnusedDiagnosticKind, String) {
         (UnusedDiagnosticKind::Function, name.to_owned())
@@ -346,32 +1163,173 @@ mod test {
     fn deletion() {
         let src = b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;";
         assert_eq!(
-            rust_delete(src, [fun("foo")]).unwrap(),
+            rust_delete(src, [fun("foo")], false).unwrap(),
             b"fn foa() -> i32 { barf; }const FOO: i32 = 42;"
         );
         assert_eq!(
-            rust_delete(src, [fun("foa")]).unwrap(),
+            rust_delete(src, [fun("foa")], false).unwrap(),
             b"fn foo() { }const FOO: i32 = 42;"
         );
         assert_eq!(
-            rust_delete(src, [constant("FOO")]).unwrap(),
+            rust_delete(src, [constant("FOO")], false).unwrap(),
             b"fn foo() { }fn foa() -> i32 { barf; }"
         );
     }
 
+    #[test]
+    fn edits_after_multi_byte_chars() {
+        let krate = TempCrate::new(
+            "pub fn f() -> usize {\n    let s = \"ü\"; let mut x = 1;\n    s.len() + x\n}\n",
+        )
+        .unwrap();
+        krate.minify(&["--kinds", "mut"]).unwrap();
+        assert_eq!(
+            krate.read("src/lib.rs").unwrap(),
+            "pub fn f() -> usize {\n    let s = \"ü\"; let x = 1;\n    s.len() + x\n}\n"
+        );
+    }
+
+    #[test]
+    fn leaves_unparsable_files_alone() {
+        // Precise capturing compiles, but is newer than the parser
+        let capturing = "pub fn f() -> impl Sized + use<> {}\nfn also_unused() {}\n";
+        let krate = TempCrate::with_files([
+            (
+                "src/lib.rs",
+                "mod capturing;\npub use capturing::f;\nfn unused() {}\n",
+            ),
+            ("src/capturing.rs", capturing),
+        ])
+        .unwrap();
+        let analysis = krate
+            .analyze(&["--allow-no-vcs", "--kinds", "function"])
+            .unwrap();
+        assert_eq!(analysis.unparsed.len(), 1);
+        assert_eq!(analysis.unparsed[0].file_name, "src/capturing.rs");
+        assert!(analysis
+            .diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.span.file_name == "src/lib.rs"));
+
+        crate::apply(analysis).unwrap();
+        assert_eq!(
+            krate.read("src/lib.rs").unwrap(),
+            "mod capturing;\npub use capturing::f;\n"
+        );
+        assert_eq!(krate.read("src/capturing.rs").unwrap(), capturing);
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let delays = [Duration::ZERO; 2];
+        let transient = |error: &io::Error| error.kind() == io::ErrorKind::WouldBlock;
+        let mut attempts = 0;
+        let result = with_retries(&delays, transient, || {
+            attempts += 1;
+            match attempts {
+                3 => Ok(attempts),
+                _ => Err(io::Error::from(io::ErrorKind::WouldBlock)),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        attempts = 0;
+        let error = with_retries(&delays, transient, || {
+            attempts += 1;
+            Err::<(), _>(io::Error::from(io::ErrorKind::WouldBlock))
+        });
+        assert_eq!(attempts, 3);
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .ends_with("it may be open in another program"));
+
+        attempts = 0;
+        let error = with_retries(&delays, transient, || {
+            attempts += 1;
+            Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert_eq!(
+            (attempts, error.unwrap_err().kind()),
+            (1, io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn writes_within_root() {
+        let dir = std::env::temp_dir().join(format!("cargo-minify-sandbox-{}", std::process::id()));
+        let root = dir.join("workspace");
+        std::fs::create_dir_all(&root).unwrap();
+        let (inside, outside) = (root.join("lib.rs"), dir.join("registry.rs"));
+        std::fs::write(&inside, "fn a() {}").unwrap();
+        std::fs::write(&outside, "fn a() {}").unwrap();
+
+        let change =
+            |file: &Path| Change::new(file.to_path_buf(), b"fn a() {}".to_vec(), Vec::new());
+        // Nor through `..`
+        let sneaky = root.join("..").join("registry.rs");
+        let errors = commit_changes([change(&inside), change(&outside), change(&sneaky)], &root)
+            .unwrap_err();
+        let failed: Vec<_> = errors.iter().map(|(file, _)| file.clone()).collect();
+
+        assert_eq!(failed, [outside.clone(), sneaky]);
+        assert!(errors
+            .iter()
+            .all(|(_, e)| e.kind() == io::ErrorKind::PermissionDenied));
+        assert_eq!(std::fs::read(&inside).unwrap(), b"");
+        assert_eq!(std::fs::read(&outside).unwrap(), b"fn a() {}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn impl_cascade() {
+        let src = b"struct Foo<T>(T);\nimpl<T> Foo<T> { fn new() {} }\n\
+                    impl<T> Clone for &Foo<T> {}\nimpl Bar {}\nstruct Bar;\n";
+        let structure = |name: &str| (UnusedDiagnosticKind::Struct, name.to_owned());
+        assert_eq!(
+            rust_delete(src, [structure("Foo")], false).unwrap(),
+            b"impl Bar {}\nstruct Bar;\n"
+        );
+    }
+
+    #[test]
+    fn emptied_impls() {
+        let src = b"struct Foo;\nimpl Foo {\n    fn new() {}\n}\nimpl Foo {}\n\
+                    extern \"C\" {\n    fn abs(x: i32) -> i32;\n}\n";
+        let function = |name: &str| (UnusedDiagnosticKind::Function, name.to_owned());
+        let removed = rust_delete(src, [function("abs")], false).unwrap();
+        let assoc = [(UnusedDiagnosticKind::AssociatedFunction, "3:new".to_owned())];
+        let removed = rust_delete(&removed, assoc, false).unwrap();
+        // The impl that was empty from the start stays
+        assert_eq!(
+            remove_empty_blocks(src, &removed, false).unwrap(),
+            b"struct Foo;\nimpl Foo {}\n"
+        );
+    }
+
+    #[test]
+    fn items_by_line() {
+        let src = b"fn helper() {}\nmod tests {\n    const C: u8 = 1;\n    fn helper() {}\n}\n";
+        assert_eq!(
+            rust_delete(src, [fun("4:helper"), constant("3:C")], false).unwrap(),
+            b"fn helper() {}\nmod tests {\n}\n"
+        );
+        assert_eq!(rust_delete(src, [fun("3:helper")], false).unwrap(), src);
+    }
+
     #[test]
     fn type_check() {
         let src = b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;";
         assert_eq!(
-            rust_delete(src, [constant("foo")]).unwrap(),
+            rust_delete(src, [constant("foo")], false).unwrap(),
             b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
         );
         assert_eq!(
-            rust_delete(src, [constant("foa")]).unwrap(),
+            rust_delete(src, [constant("foa")], false).unwrap(),
             b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
         );
         assert_eq!(
-            rust_delete(src, [fun("FOO")]).unwrap(),
+            rust_delete(src, [fun("FOO")], false).unwrap(),
             b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
         );
     }
@@ -380,28 +1338,28 @@ mod test {
     fn formatting_preserval() {
         let src = b" fn foo(){}  fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  fn bar(){ } ";
         assert_eq!(
-            rust_delete(src, [fun("foo")]).unwrap(),
+            rust_delete(src, [fun("foo")], false).unwrap(),
             b" fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  fn bar(){ } "
         );
         assert_eq!(
-            rust_delete(src, [fun("foa")]).unwrap(),
+            rust_delete(src, [fun("foa")], false).unwrap(),
             b" fn foo(){}  const FOO: i32 = 42;  fn bar(){ } "
         );
         assert_eq!(
-            rust_delete(src, [constant("FOO")]).unwrap(),
+            rust_delete(src, [constant("FOO")], false).unwrap(),
             b" fn foo(){}  fn foa()  -> huk {  barf; }   fn bar(){ } "
         );
         assert_eq!(
-            rust_delete(src, [fun("bar")]).unwrap(),
+            rust_delete(src, [fun("bar")], false).unwrap(),
             b" fn foo(){}  fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  "
         );
 
         assert_eq!(
-            rust_delete(src, [fun("foa"), fun("foo")]).unwrap(),
+            rust_delete(src, [fun("foa"), fun("foo")], false).unwrap(),
             b" const FOO: i32 = 42;  fn bar(){ } "
         );
         assert_eq!(
-            rust_delete(src, [fun("foa"), constant("FOO")]).unwrap(),
+            rust_delete(src, [fun("foa"), constant("FOO")], false).unwrap(),
             b" fn foo(){}  fn bar(){ } "
         );
     }
@@ -411,44 +1369,97 @@ mod test {
     fn whitespace_semi_preserval() {
         let src = b" fn foo() {} fn fixme() {} fn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b" fn foo() {} fn main() {}"
         );
         let src = b" fn foo() {} fn fixme() {}fn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b" fn foo() {} fn main() {}"
         );
         let src = b" fn foo() {}fn fixme() {} fn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b" fn foo() {}fn main() {}"
         );
         let src = b" fn foo() {}\nfn fixme() {}\nfn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b" fn foo() {}\nfn main() {}"
         );
         let src = b" fn foo() {}\n\nfn fixme() {}\nfn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b" fn foo() {}\n\nfn main() {}"
         );
         let src = b" fn foo() {}\nfn fixme() {}\n\nfn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b" fn foo() {}\n\nfn main() {}"
         );
         let src = b" fn foo() {}\n\nfn fixme() {}\n\nfn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {}\n\n\nfn main() {}"
+            rust_delete(src, [fun("fixme")], false).unwrap(),
+            b" fn foo() {}\n\nfn main() {}"
         );
 
         let src = b"fn foo() {}\n          fn fixme() {}\n   fn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
+            rust_delete(src, [fun("fixme")], false).unwrap(),
             b"fn foo() {}\n   fn main() {}"
         );
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn indentation_preserval() {
+        let src = b"mod m {\n\tfn foo() {}\n\n\t#[inline]\n\tfn fixme() {}\t\n\n  \tfn main() {}\n}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")], false).unwrap(),
+            b"mod m {\n\tfn foo() {}\n\n  \tfn main() {}\n}"
+        );
+        let src = b"fn fixme() {}\r\n\r\nfn main() {}\r\n";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")], false).unwrap(),
+            b"fn main() {}\r\n"
+        );
+    }
+
+    #[test]
+    fn attributes_removal() {
+        let src = "/// Docs\n#[inline]\n#[cfg(unix)]\nfn foo() {}\n\
+                   impl S {\n    #[doc(hidden)]\n    fn bar() {}\n}\n\
+                   #[cfg(unix)]\nuse std::fs;\nfn main() {}\n";
+        // A suggestion deleting the `use` item, but not its attribute
+        let start = src.find("use std").unwrap();
+        let edit = TextEdit::delete(start..start + "use std::fs;\n".len());
+        let keys = [
+            fun("foo"),
+            (UnusedDiagnosticKind::AssociatedFunction, "7:bar".into()),
+        ];
+        assert_eq!(
+            String::from_utf8(rust_edit(src.as_bytes(), keys, [edit], false).unwrap()).unwrap(),
+            "impl S {\n}\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn tidy_removal() {
+        let src = b"fn foo() {}\n\n// Helper for foo\n// (see below)\nfn fixme() {}\n\n\nfn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")], false).unwrap(),
+            b"fn foo() {}\n\n// Helper for foo\n// (see below)\n\n\nfn main() {}"
+        );
+        assert_eq!(
+            rust_delete(src, [fun("fixme")], true).unwrap(),
+            b"fn foo() {}\n\nfn main() {}"
+        );
+
+        let src = b"//! Module docs\n// Unrelated\n\n/// Docs\nfn fixme() {}\nfn main() {}\n\n\n";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")], true).unwrap(),
+            b"//! Module docs\n// Unrelated\n\nfn main() {}\n\n\n"
+        );
+    }
 }
@@ -0,0 +1,173 @@
+//! A record of the removals, with `--changelog <FILE>`: every run that applies
+//! changes appends a section with its date, listing the removed items by
+//! package and module, so that a long-running cleanup can be followed without
+//! digging through the git history.
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::report::Analysis;
+
+/// An item that goes with the change to `file`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Removal {
+    /// The file as named by its change
+    pub file: PathBuf,
+    pub package: String,
+    pub module: String,
+    /// The kind and name of the item, as in ``FUNCTION `helper` ``
+    pub item: String,
+}
+
+/// The module a file holds, by its path within the package: `src/a/b.rs` and
+/// `src/a/b/mod.rs` hold `a::b`, and the crate roots hold `crate`
+fn module_of(relative: &Path) -> String {
+    let mut parts: Vec<_> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    if parts.first().is_some_and(|part| part == "src") {
+        parts.remove(0);
+    }
+    if let Some(last) = parts.pop() {
+        let stem = last.strip_suffix(".rs").unwrap_or(&last).to_string();
+        let is_root = parts.is_empty() && (stem == "lib" || stem == "main");
+        if stem != "mod" && !is_root {
+            parts.push(stem);
+        }
+    }
+
+    match parts.is_empty() {
+        true => "crate".to_string(),
+        false => parts.join("::"),
+    }
+}
+
+/// The items the changes of the analysis remove, to be recorded once the
+/// changes are applied
+pub fn removals(analysis: &Analysis) -> Vec<Removal> {
+    let mut removals = Vec::new();
+    for change in &analysis.changes {
+        let path = change.file_name();
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let package = analysis
+            .packages
+            .iter()
+            .filter(|(_, dir)| canonical.starts_with(dir))
+            .max_by_key(|(_, dir)| dir.components().count());
+        let (package, module) = match package {
+            Some((name, dir)) => (
+                name.clone(),
+                module_of(canonical.strip_prefix(dir).unwrap()),
+            ),
+            None => (String::new(), module_of(path)),
+        };
+
+        for diagnostic in &analysis.diagnostics {
+            let item = format!("{} `{}`", diagnostic.kind, diagnostic.ident);
+            let removal = Removal {
+                file: path.to_path_buf(),
+                package: package.clone(),
+                module: module.clone(),
+                item,
+            };
+            // The same finding may come from multiple targets
+            if path.ends_with(&diagnostic.span.file_name) && !removals.contains(&removal) {
+                removals.push(removal);
+            }
+        }
+    }
+
+    removals
+}
+
+/// A section of the changelog, listing the removals by package and module
+pub fn section(date: &str, removals: &[Removal]) -> String {
+    let mut by_package: BTreeMap<_, BTreeMap<_, Vec<_>>> = BTreeMap::new();
+    for removal in removals {
+        by_package
+            .entry(&removal.package)
+            .or_default()
+            .entry(&removal.module)
+            .or_default()
+            .push(&removal.item);
+    }
+
+    let mut section = format!("## {date}\n");
+    for (package, modules) in by_package {
+        if !package.is_empty() {
+            section += &format!("\n### {package}\n");
+        }
+        section += "\n";
+        for (module, items) in modules {
+            section += &format!("- `{module}`\n");
+            for item in items {
+                section += &format!("  - {item}\n");
+            }
+        }
+    }
+
+    section
+}
+
+/// Appends a section with the removals in the applied `files` to the
+/// changelog, returning the number of items recorded
+pub fn append(
+    path: &Path,
+    date: &str,
+    removals: &[Removal],
+    files: &[PathBuf],
+) -> io::Result<usize> {
+    let applied: Vec<_> = removals
+        .iter()
+        .filter(|removal| files.contains(&removal.file))
+        .cloned()
+        .collect();
+    if applied.is_empty() {
+        return Ok(0);
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    // Sections are separated by a blank line
+    let separator = if file.metadata()?.len() > 0 { "\n" } else { "" };
+    write!(file, "{separator}{}", section(date, &applied))?;
+
+    Ok(applied.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        assert_eq!(module_of(Path::new("src/lib.rs")), "crate");
+        assert_eq!(module_of(Path::new("src/a/b/mod.rs")), "a::b");
+        assert_eq!(module_of(Path::new("src/bin/tool.rs")), "bin::tool");
+
+        let removal = |package: &str, module: &str, item: &str| Removal {
+            file: PathBuf::from("src/lib.rs"),
+            package: package.to_string(),
+            module: module.to_string(),
+            item: item.to_string(),
+        };
+        let removals = [
+            removal("b", "crate", "FUNCTION `f`"),
+            removal("a", "x::y", "STRUCT `S`"),
+            removal("a", "crate", "CONST `C`"),
+            removal("a", "x::y", "FUNCTION `g`"),
+        ];
+        assert_eq!(
+            section("2024-05-01", &removals),
+            "## 2024-05-01\n\n### a\n\n- `crate`\n  - CONST `C`\n- `x::y`\n  - STRUCT `S`\n  \
+             - FUNCTION `g`\n\n### b\n\n- `crate`\n  - FUNCTION `f`\n"
+        );
+    }
+}
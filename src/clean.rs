@@ -0,0 +1,225 @@
+//! `cargo minify clean`, which removes the files cargo-minify keeps around:
+//! the backups of `undo` and the pending changes of `--resume` in the target
+//! directory, the results cached by `--cache`, and the temporary copies of
+//! workspaces and crates that interrupted runs left behind.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{cache, state};
+
+/// The temporary directories of runs, which are named after these and the
+/// id of the process
+const TEMP_PREFIXES: &[&str] = &[
+    "cargo-minify-idempotency-",
+    "cargo-minify-diff-",
+    "cargo-minify-crate-",
+];
+
+/// The files and directories of cargo-minify that exist, in the target
+/// directory, the cache directory, and the temporary directory. Of the cache
+/// directory, only the `<commit>/<key>.json` files of `--cache` are listed,
+/// as it may be shared with other files; of the temporary directory, those of
+/// processes that are still running are left out.
+pub fn artifacts(target_dir: &Path, cache_dir: &Path, temp_dir: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<_> = Some(target_dir.join(state::TOOL_DIR))
+        .filter(|path| path.exists())
+        .into_iter()
+        .collect();
+    found.extend(cache_entries(cache_dir));
+    // Only the default directory is given a `.gitignore` of its own
+    let gitignore = cache_dir.join(".gitignore");
+    if cache_dir == target_dir.join(cache::DEFAULT_DIR) && gitignore.is_file() {
+        found.push(gitignore);
+    }
+
+    if let Ok(entries) = fs::read_dir(temp_dir) {
+        let mut temporary: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                TEMP_PREFIXES
+                    .iter()
+                    .filter_map(|prefix| name.strip_prefix(prefix))
+                    .filter(|pid| pid.bytes().all(|byte| byte.is_ascii_digit()))
+                    .filter_map(|pid| pid.parse::<u32>().ok())
+                    .any(|pid| pid != std::process::id() && !is_running(pid))
+            })
+            .map(|entry| entry.path())
+            .collect();
+        temporary.sort();
+        found.extend(temporary);
+    }
+
+    found
+}
+
+/// The results `--cache` wrote, as `<commit>/<key>.json` with a 16 digit
+/// hexadecimal key
+fn cache_entries(cache_dir: &Path) -> Vec<PathBuf> {
+    let is_entry = |path: &Path| {
+        path.is_file()
+            && path.extension().is_some_and(|ext| ext == "json")
+            && path.file_stem().is_some_and(|stem| {
+                let stem = stem.to_string_lossy();
+                stem.len() == 16 && stem.bytes().all(|byte| byte.is_ascii_hexdigit())
+            })
+    };
+
+    let mut entries: Vec<_> = fs::read_dir(cache_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|commit| commit.ok())
+        .filter(|commit| commit.path().is_dir())
+        .flat_map(|commit| fs::read_dir(commit.path()).into_iter().flatten())
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_entry(path))
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Whether a process with this id exists
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Whether a process with this id exists
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/NH", "/FI", &format!("PID eq {pid}")])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+}
+
+/// Removes the files and directories, along with all they contain, and then
+/// the commit directories and the cache directory they were in once these are
+/// empty. A cache directory that holds anything else is left in place, and
+/// reported.
+pub fn remove(paths: &[PathBuf], cache_dir: &Path) -> io::Result<()> {
+    for path in paths {
+        let removed = match path.is_dir() {
+            true => fs::remove_dir_all(path),
+            false => fs::remove_file(path),
+        };
+        match removed {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+
+    let is_empty = |dir: &Path| fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none());
+    for commit in paths.iter().filter_map(|path| path.parent()) {
+        if commit.parent() == Some(cache_dir) && is_empty(commit) {
+            fs::remove_dir(commit)?;
+        }
+    }
+    if !paths.iter().any(|path| path.starts_with(cache_dir)) || !cache_dir.exists() {
+        return Ok(());
+    }
+    if !is_empty(cache_dir) {
+        return Err(io::Error::other(format!(
+            "{} holds files other than cached results, so it was left in place",
+            cache_dir.display()
+        )));
+    }
+
+    fs::remove_dir(cache_dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn finds_artifacts() {
+        let dir = std::env::temp_dir().join(format!("cargo-minify-clean-{}", std::process::id()));
+        let (target, temp) = (dir.join("target"), dir.join("tmp"));
+        // Past the largest process id Linux hands out, so never running
+        let (gone, also_gone) = (4_194_305, 4_194_306);
+        for path in [
+            target.join("debug"),
+            state::tool_dir(&target).unwrap().join("backup"),
+            temp.join(format!("cargo-minify-diff-{gone}")),
+            temp.join(format!("cargo-minify-crate-{also_gone}")),
+            temp.join(format!("cargo-minify-idempotency-{}", std::process::id())),
+            temp.join(format!(
+                "cargo-minify-diff-{}",
+                std::os::unix::process::parent_id()
+            )),
+            temp.join("cargo-minify-test-3-4"),
+            temp.join("cargo-minify-diff-x"),
+        ] {
+            fs::create_dir_all(path).unwrap();
+        }
+
+        let default_cache = cache::dir(&target, None);
+        let found = artifacts(&target, &default_cache, &temp);
+        assert_eq!(
+            found,
+            [
+                target.join("minify"),
+                temp.join(format!("cargo-minify-crate-{also_gone}")),
+                temp.join(format!("cargo-minify-diff-{gone}")),
+            ]
+        );
+
+        let cache = dir.join("cache");
+        let entry = cache
+            .join("0123abcd")
+            .join(format!("{:016x}", 42u64) + ".json");
+        fs::create_dir_all(entry.parent().unwrap()).unwrap();
+        fs::write(&entry, "{}").unwrap();
+        let with_cache = artifacts(&target, &cache, &temp);
+        assert_eq!(with_cache.len(), 4);
+        assert_eq!(with_cache[1], entry);
+
+        remove(&found, &default_cache).unwrap();
+        assert!(target.join("debug").exists());
+        assert!(artifacts(&target, &default_cache, &temp).is_empty());
+        remove(&with_cache, &cache).unwrap();
+        assert!(!cache.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn leaves_other_files_in_the_cache_dir_alone() {
+        let dir = std::env::temp_dir().join(format!("cargo-minify-shared-{}", std::process::id()));
+        let (target, cache, temp) = (dir.join("target"), dir.join("shared"), dir.join("tmp"));
+        let commit = cache.join("0123abcd");
+        let entry = commit.join(format!("{:016x}.json", 7u64));
+        for (path, contents) in [
+            (&entry, "{}"),
+            (&commit.join("notes.json"), "{}"),
+            (&cache.join(".gitignore"), "*\n"),
+            (&cache.join("other").join(format!("{:016x}", 7u64)), ""),
+        ] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        let found = artifacts(&target, &cache, &temp);
+        assert_eq!(found, [entry.as_path()]);
+        let err = remove(&found, &cache).unwrap_err();
+        assert!(err.to_string().contains("left in place"));
+        assert!(!entry.exists());
+        assert!(commit.join("notes.json").exists());
+        assert!(cache.join(".gitignore").exists());
+        assert!(cache.join("other").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+//! `cargo minify compare <OLD> <NEW>`, which tells the findings of two reports
+//! of `--message-format json` apart: the ones that only the new report has
+//! appeared, and the ones that only the old report has were resolved. Lines
+//! shift as code is edited, so findings are matched by their kind, name and
+//! file; when several findings share these, only the difference in their
+//! number counts.
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// A finding of a JSON report
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Finding {
+    pub kind: String,
+    pub ident: String,
+    pub file: String,
+    pub line: usize,
+}
+
+impl Finding {
+    fn key(&self) -> (&str, &str, &str) {
+        (&self.kind, &self.ident, &self.file)
+    }
+}
+
+/// The findings that appeared and were resolved between two reports
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Comparison {
+    pub appeared: Vec<Finding>,
+    pub resolved: Vec<Finding>,
+}
+
+/// The findings of a report, be they removed, only reported, or annotated
+fn findings(report: &Value) -> serde_json::Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for list in ["diagnostics", "reported", "annotated"] {
+        if let Some(list) = report.get(list) {
+            findings.extend(Vec::<Finding>::deserialize(list)?);
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Reads the findings of the report of `--message-format json` at the path
+pub fn load(path: &Path) -> io::Result<Vec<Finding>> {
+    let invalid = |err: serde_json::Error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is not a report of --message-format json: {err}",
+                path.display()
+            ),
+        )
+    };
+    let report: Value = serde_json::from_str(&fs::read_to_string(path)?).map_err(invalid)?;
+
+    findings(&report).map_err(invalid)
+}
+
+/// The findings of `new` that `old` doesn't have, and the other way around
+pub fn compare(old: &[Finding], new: &[Finding]) -> Comparison {
+    let only_in = |these: &[Finding], others: &[Finding]| {
+        let mut counts: HashMap<_, usize> = HashMap::new();
+        for finding in others {
+            *counts.entry(finding.key()).or_default() += 1;
+        }
+        let mut only = Vec::new();
+        for finding in these {
+            match counts.get_mut(&finding.key()) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => only.push(finding.clone()),
+            }
+        }
+        only
+    };
+
+    Comparison {
+        appeared: only_in(new, old),
+        resolved: only_in(old, new),
+    }
+}
+
+/// The comparison as a JSON document, with the findings in the format of the
+/// reports
+pub fn to_json(comparison: &Comparison) -> Value {
+    let to_json = |finding: &Finding| {
+        json!({
+            "kind": finding.kind,
+            "ident": finding.ident,
+            "file": finding.file,
+            "line": finding.line,
+        })
+    };
+
+    json!({
+        "appeared": comparison.appeared.iter().map(to_json).collect::<Vec<_>>(),
+        "resolved": comparison.resolved.iter().map(to_json).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compares_reports() {
+        let finding = |kind: &str, ident: &str, file: &str, line: usize| json!({ "kind": kind, "ident": ident, "file": file, "line": line, "column": 1 });
+        let old = json!({
+            "diagnostics": [
+                finding("FUNCTION", "a", "src/lib.rs", 3),
+                finding("FUNCTION", "b", "src/lib.rs", 7),
+                finding("CONST", "C", "src/lib.rs", 9),
+            ],
+            "reported": [finding("FIELD", "x", "src/lib.rs", 12)],
+        });
+        let new = json!({
+            "diagnostics": [
+                finding("FUNCTION", "a", "src/lib.rs", 5),
+                finding("CONST", "C", "src/lib.rs", 11),
+                finding("CONST", "C", "src/lib.rs", 20),
+            ],
+            "reported": [finding("FIELD", "x", "src/lib.rs", 14)],
+            "annotated": [finding("STRUCT", "S", "src/main.rs", 1)],
+        });
+
+        let comparison = compare(&findings(&old).unwrap(), &findings(&new).unwrap());
+        let names = |findings: &[Finding]| -> Vec<_> {
+            findings
+                .iter()
+                .map(|finding| (finding.ident.clone(), finding.line))
+                .collect()
+        };
+        assert_eq!(
+            names(&comparison.appeared),
+            [("C".to_string(), 20), ("S".to_string(), 1)]
+        );
+        assert_eq!(names(&comparison.resolved), [("b".to_string(), 7)]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Configuration read from the `[workspace.metadata.minify]` table of the
+//! workspace manifest, or from `[package.metadata.minify]` of the root package
+//! if there is no workspace table.
+
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    error::{Error, Result},
+    unused::UnusedDiagnosticKind,
+};
+
+/// Directories holding code that is unreferenced on purpose
+const DEFAULT_IGNORE_CONVENTIONS: &[&str] =
+    &["tests/fixtures/**", "benches/data/**", "examples/wip/**"];
+
+/// What is done with the unused code of a package, depending on its crate
+/// type; ordered from the least to the most restrictive
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrateTypePolicy {
+    Remove,
+    /// Only show the changes as a diff, unless `--include-proc-macros` is given
+    DiffOnly,
+    /// Don't analyze the package at all
+    Skip,
+}
+
+/// What is done with the findings of a kind
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KindPolicy {
+    #[default]
+    Remove,
+    /// Keep the code, marking it with a `// minify:unused` comment
+    Annotate,
+    /// Keep the code, and report it
+    ReportOnly,
+    /// Leave the findings out altogether
+    Skip,
+}
+
+/// Reads the policies by kind, named as for `--kinds`
+fn deserialize_kinds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<UnusedDiagnosticKind, KindPolicy>, D::Error> {
+    HashMap::<String, KindPolicy>::deserialize(deserializer)?
+        .into_iter()
+        .map(
+            |(kind, policy)| match UnusedDiagnosticKind::from_str(&kind) {
+                Ok(kind) => Ok((kind, policy)),
+                Err(_) => Err(serde::de::Error::custom(format!("unknown kind `{kind}`"))),
+            },
+        )
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// File globs that are ignored by convention, matched against paths
+    /// relative to the workspace root as well as to any package root
+    pub ignore_conventions: Vec<String>,
+    /// Policies overriding the defaults per crate type, such as `bin`, `lib`,
+    /// `cdylib` or `proc-macro`
+    pub crate_types: HashMap<String, CrateTypePolicy>,
+    /// The commands regenerating the files marked as `@generated` by a
+    /// generator, by the name of the generator, such as `prost-build`
+    pub generators: HashMap<String, String>,
+    /// Policies overriding `remove` per kind, such as `static = "report-only"`
+    #[serde(deserialize_with = "deserialize_kinds")]
+    pub kinds: HashMap<UnusedDiagnosticKind, KindPolicy>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            ignore_conventions: DEFAULT_IGNORE_CONVENTIONS
+                .iter()
+                .map(|glob| glob.to_string())
+                .collect(),
+            crate_types: HashMap::new(),
+            generators: HashMap::new(),
+            kinds: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_metadata(metadata: &cargo_metadata::Metadata) -> Result<Self> {
+        let table = match metadata.workspace_metadata.get("minify") {
+            Some(table) => Some(("[workspace.metadata.minify]", table)),
+            None => metadata
+                .root_package()
+                .and_then(|package| package.metadata.get("minify"))
+                .map(|table| ("[package.metadata.minify]", table)),
+        };
+
+        match table {
+            Some((name, table)) => Config::deserialize(table).map_err(|source| Error::Config {
+                table: name,
+                source,
+            }),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// What is done with the findings of the kind
+    pub fn kind_policy(&self, kind: UnusedDiagnosticKind) -> KindPolicy {
+        self.kinds.get(&kind).copied().unwrap_or_default()
+    }
+
+    /// The policy for a package with these crate types, the most restrictive
+    /// one if they differ. rustc already reports all code unreachable from
+    /// `main` in binaries, and only private code in libraries, so by default
+    /// only proc-macro crates (whose code is used at compile time in ways the
+    /// analysis can misjudge) are restricted.
+    pub fn crate_type_policy(&self, crate_types: &[String]) -> CrateTypePolicy {
+        crate_types
+            .iter()
+            .map(|crate_type| match self.crate_types.get(crate_type) {
+                Some(policy) => *policy,
+                None if crate_type == "proc-macro" => CrateTypePolicy::DiffOnly,
+                None => CrateTypePolicy::Remove,
+            })
+            .max()
+            .unwrap_or(CrateTypePolicy::Remove)
+    }
+
+    /// Whether the file is ignored by convention
+    pub fn is_conventionally_ignored(&self, file_name: &str) -> bool {
+        self.ignore_conventions.iter().any(|glob| {
+            glob_match::glob_match(glob, file_name)
+                || glob_match::glob_match(&format!("**/{glob}"), file_name)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn conventions() {
+        let config = Config::default();
+        assert!(config.is_conventionally_ignored("tests/fixtures/foo.rs"));
+        assert!(config.is_conventionally_ignored("crates/bar/tests/fixtures/a/foo.rs"));
+        assert!(!config.is_conventionally_ignored("tests/foo.rs"));
+        assert!(!config.is_conventionally_ignored("src/fixtures/foo.rs"));
+
+        let config: Config = serde_json::from_str(r#"{ "ignore-conventions": [] }"#).unwrap();
+        assert!(!config.is_conventionally_ignored("tests/fixtures/foo.rs"));
+    }
+
+    #[test]
+    fn crate_type_policies() {
+        use CrateTypePolicy::*;
+        let policy = |config: &Config, types: &[&str]| {
+            config.crate_type_policy(&types.iter().map(|t| t.to_string()).collect::<Vec<_>>())
+        };
+
+        let config = Config::default();
+        assert_eq!(policy(&config, &["bin"]), Remove);
+        assert_eq!(policy(&config, &["proc-macro"]), DiffOnly);
+
+        let config: Config = serde_json::from_str(
+            r#"{ "crate-types": { "cdylib": "skip", "proc-macro": "remove" } }"#,
+        )
+        .unwrap();
+        assert_eq!(policy(&config, &["proc-macro"]), Remove);
+        assert_eq!(policy(&config, &["cdylib", "rlib"]), Skip);
+    }
+
+    #[test]
+    fn kind_policies() {
+        let config: Config = serde_json::from_str(
+            r#"{ "kinds": { "static": "report-only", "TYPE_ALIAS": "annotate" } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.kind_policy(UnusedDiagnosticKind::Static),
+            KindPolicy::ReportOnly
+        );
+        assert_eq!(
+            config.kind_policy(UnusedDiagnosticKind::TypeAlias),
+            KindPolicy::Annotate
+        );
+        assert_eq!(
+            config.kind_policy(UnusedDiagnosticKind::Function),
+            KindPolicy::Remove
+        );
+        assert!(serde_json::from_str::<Config>(r#"{ "kinds": { "fn": "skip" } }"#).is_err());
+    }
+
+    #[test]
+    fn errors_name_their_table() {
+        let manifest = |table| {
+            format!(
+                "[package]\nname = \"temp\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                 [{table}.metadata.minify]\nkinds = {{ fn = \"skip\" }}\n"
+            )
+        };
+        for table in ["workspace", "package"] {
+            let manifest = manifest(table);
+            let files = [("Cargo.toml", manifest.as_str()), ("src/lib.rs", "")];
+            let temp_crate = TempCrate::with_files(files).unwrap();
+            let err = temp_crate.analyze(&[]).err().unwrap();
+            assert_eq!(err.exit_code(), 1);
+            assert!(err
+                .to_string()
+                .starts_with(&format!("invalid [{table}.metadata.minify] configuration")));
+        }
+    }
+}
@@ -0,0 +1,541 @@
+//! An experimental syntactic analysis that finds const and type generic
+//! parameters of functions which are never used: the parameter isn't mentioned
+//! anywhere in the signature or the body. As such a parameter can't be
+//! inferred, every call passes it explicitly, as in `foo::<4>()`, so these
+//! explicit values are removed along with the parameter. The `where` clause
+//! may still bound an unused type parameter, as in `where T: Clone`; such
+//! predicates go as well.
+
+use std::path::Path;
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{
+    spanned::Spanned, GenericParam, Generics, ImplItem, Item, Signature, Type, WhereClause,
+    WherePredicate,
+};
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    FileResolutionOptions,
+};
+
+/// A const or type parameter that is never used
+struct UnusedParam<'a> {
+    /// `path::to::function::<_, const N>` or `path::to::function::<T, _>`,
+    /// listing the parameters that are passed explicitly, used to find the
+    /// parameter and its call sites again
+    id: String,
+    /// The index of the parameter among all generic parameters
+    index: usize,
+    /// The indices of the `where` predicates that only bound the parameter
+    predicates: Vec<usize>,
+    generics: &'a Generics,
+}
+
+impl UnusedParam<'_> {
+    fn kind(&self) -> UnusedDiagnosticKind {
+        match self.generics.params[self.index] {
+            GenericParam::Type(_) => UnusedDiagnosticKind::TypeParam,
+            _ => UnusedDiagnosticKind::ConstParam,
+        }
+    }
+
+    fn spans(&self) -> Vec<Span> {
+        let mut spans = param_spans(self.generics, self.index);
+        if let Some(clause) = &self.generics.where_clause {
+            spans.extend(predicate_spans(clause, &self.predicates));
+        }
+        spans
+    }
+}
+
+/// The span of a generic parameter along with its comma, or of the whole
+/// angle brackets when it is the only parameter
+pub fn param_spans(generics: &Generics, index: usize) -> Vec<Span> {
+    let params: Vec<_> = generics.params.pairs().collect();
+    if params.len() == 1 {
+        let (Some(lt), Some(gt)) = (&generics.lt_token, &generics.gt_token) else {
+            return Vec::new();
+        };
+        return lt.span.join(gt.span).into_iter().collect();
+    }
+
+    let pair = &params[index];
+    let span = match (pair.punct(), index.checked_sub(1)) {
+        (Some(comma), _) => pair.value().span().join(comma.span),
+        (None, Some(prev)) => params[prev]
+            .punct()
+            .and_then(|comma| comma.span.join(pair.value().span())),
+        (None, None) => Some(pair.value().span()),
+    };
+
+    span.into_iter().collect()
+}
+
+/// The spans of the `where` predicates at the indices along with their commas,
+/// or of the whole clause when none of its predicates are left
+fn predicate_spans(clause: &WhereClause, removed: &[usize]) -> Vec<Span> {
+    let pairs: Vec<_> = clause.predicates.pairs().collect();
+    let (Some(last), false) = (pairs.last(), removed.is_empty()) else {
+        return Vec::new();
+    };
+    let end = last.punct().map_or(last.value().span(), |comma| comma.span);
+    let Some(last_kept) = (0..pairs.len())
+        .rev()
+        .find(|index| !removed.contains(index))
+    else {
+        return clause.where_token.span.join(end).into_iter().collect();
+    };
+
+    // The predicates after the last one that is kept go with its comma
+    let mut spans: Vec<_> = removed
+        .iter()
+        .filter(|index| **index < last_kept)
+        .filter_map(|index| {
+            let pair = &pairs[*index];
+            pair.punct()
+                .and_then(|comma| pair.value().span().join(comma.span))
+        })
+        .collect();
+    if removed.iter().any(|index| *index > last_kept) {
+        spans.extend(
+            pairs[last_kept]
+                .punct()
+                .and_then(|comma| comma.span.join(end)),
+        );
+    }
+
+    spans
+}
+
+/// Whether the `where` predicate bounds the type parameter alone, as in
+/// `T: Clone`
+fn bounds_only(predicate: &WherePredicate, name: &str) -> bool {
+    let WherePredicate::Type(predicate) = predicate else {
+        return false;
+    };
+    let Type::Path(bounded) = &predicate.bounded_ty else {
+        return false;
+    };
+
+    predicate.lifetimes.is_none()
+        && bounded.qself.is_none()
+        && bounded.path.is_ident(name)
+        && !mentions(predicate.bounds.to_token_stream(), name)
+}
+
+/// Whether the tokens mention the identifier, or use it in a format string
+fn mentions(tokens: TokenStream, name: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == name,
+        TokenTree::Group(group) => mentions(group.stream(), name),
+        TokenTree::Literal(literal) => {
+            let literal = literal.to_string();
+            literal.contains(&format!("{{{name}}}")) || literal.contains(&format!("{{{name}:"))
+        }
+        TokenTree::Punct(_) => false,
+    })
+}
+
+fn collect_items<'a>(
+    items: &'a [Item],
+    prefix: &str,
+    kinds: &[UnusedDiagnosticKind],
+    found: &mut Vec<UnusedParam<'a>>,
+) {
+    for item in items {
+        match item {
+            Item::Fn(function) => collect_fn(
+                &function.sig,
+                &function.block,
+                &format!("{prefix}{}", function.sig.ident),
+                kinds,
+                found,
+            ),
+            Item::Impl(block) if block.trait_.is_none() => {
+                let self_ty = block.self_ty.to_token_stream().to_string().replace(' ', "");
+                for item in &block.items {
+                    if let ImplItem::Fn(function) = item {
+                        collect_fn(
+                            &function.sig,
+                            &function.block,
+                            &format!("{prefix}{self_ty}::{}", function.sig.ident),
+                            kinds,
+                            found,
+                        );
+                    }
+                }
+            }
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    let prefix = format!("{prefix}{}::", module.ident);
+                    collect_items(items, &prefix, kinds, found);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finds the first unused parameter of the function among the kinds; any
+/// others are left for the next run, as their explicit values would be
+/// removed together
+fn collect_fn<'a>(
+    sig: &'a Signature,
+    body: &'a syn::Block,
+    path: &str,
+    kinds: &[UnusedDiagnosticKind],
+    found: &mut Vec<UnusedParam<'a>>,
+) {
+    // `impl Trait` arguments forbid passing any parameter explicitly
+    let has_impl_trait = sig.inputs.iter().any(|input| {
+        input
+            .to_token_stream()
+            .into_iter()
+            .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "impl"))
+    });
+    if has_impl_trait {
+        return;
+    }
+
+    for (index, param) in sig.generics.params.iter().enumerate() {
+        let (ident, kind) = match param {
+            GenericParam::Const(param) => (&param.ident, UnusedDiagnosticKind::ConstParam),
+            GenericParam::Type(param) => (&param.ident, UnusedDiagnosticKind::TypeParam),
+            GenericParam::Lifetime(_) => continue,
+        };
+        if !kinds.contains(&kind) {
+            continue;
+        }
+        let name = ident.to_string();
+        let predicates: Vec<_> = sig
+            .generics
+            .where_clause
+            .iter()
+            .flat_map(|clause| clause.predicates.iter().enumerate())
+            .filter(|(_, predicate)| bounds_only(predicate, &name))
+            .map(|(index, _)| index)
+            .collect();
+        let in_where_clause = sig
+            .generics
+            .where_clause
+            .iter()
+            .flat_map(|clause| clause.predicates.iter().enumerate())
+            .filter(|(index, _)| !predicates.contains(index))
+            .any(|(_, predicate)| mentions(predicate.to_token_stream(), &name));
+        let others = sig
+            .generics
+            .params
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .any(|(_, other)| mentions(other.to_token_stream(), &name));
+        if others
+            || mentions(sig.inputs.to_token_stream(), &name)
+            || mentions(sig.output.to_token_stream(), &name)
+            || in_where_clause
+            || mentions(body.to_token_stream(), &name)
+        {
+            continue;
+        }
+
+        // Lifetimes are never passed explicitly along with the others
+        let explicit: Vec<_> = sig
+            .generics
+            .params
+            .iter()
+            .filter_map(|other| match other {
+                GenericParam::Lifetime(_) => None,
+                GenericParam::Const(other) if other.ident == *ident => {
+                    Some(format!("const {name}"))
+                }
+                GenericParam::Type(other) if other.ident == *ident => Some(name.clone()),
+                _ => Some("_".to_string()),
+            })
+            .collect();
+        found.push(UnusedParam {
+            id: format!("{path}::<{}>", explicit.join(", ")),
+            index,
+            predicates,
+            generics: &sig.generics,
+        });
+        return;
+    }
+}
+
+/// The name of the function, the index of the removed parameter among the
+/// explicit ones, and their count, as encoded in the id
+fn parse_id(id: &str) -> Option<(&str, usize, usize)> {
+    let (path, explicit) = id.split_once("::<")?;
+    let name = path.rsplit("::").next()?;
+    let explicit: Vec<_> = explicit.strip_suffix('>')?.split(", ").collect();
+    let index = explicit.iter().position(|param| *param != "_")?;
+
+    Some((name, index, explicit.len()))
+}
+
+/// Finds the spans of the explicit values passed for the parameter in calls
+/// such as `foo::<4>()` or `x.foo::<T, 4>()`
+fn call_spans(tokens: TokenStream, name: &str, index: usize, count: usize, spans: &mut Vec<Span>) {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    let punct =
+        |i: usize, c: char| matches!(tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == c);
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let TokenTree::Group(group) = token {
+            call_spans(group.stream(), name, index, count, spans);
+            continue;
+        }
+        let is_call = matches!(token, TokenTree::Ident(ident) if ident == name)
+            && punct(i + 1, ':')
+            && punct(i + 2, ':')
+            && punct(i + 3, '<');
+        if !is_call {
+            continue;
+        }
+
+        // The explicit arguments, and the commas after them
+        let mut args: Vec<(Vec<&TokenTree>, Option<&TokenTree>)> = vec![(Vec::new(), None)];
+        let mut depth = 1;
+        let mut close = None;
+        for (j, token) in tokens.iter().enumerate().skip(i + 4) {
+            match token {
+                TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+                TokenTree::Punct(p) if p.as_char() == '>' && !punct(j - 1, '-') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(token);
+                        break;
+                    }
+                }
+                TokenTree::Punct(p) if p.as_char() == ',' && depth == 1 => {
+                    args.last_mut().unwrap().1 = Some(token);
+                    args.push((Vec::new(), None));
+                    continue;
+                }
+                _ => {}
+            }
+            args.last_mut().unwrap().0.push(token);
+        }
+        if args.last().is_some_and(|(arg, _)| arg.is_empty()) {
+            args.pop();
+        }
+        let Some(close) = close else {
+            continue;
+        };
+        if args.len() != count {
+            continue;
+        }
+
+        let span = if count == 1 {
+            tokens[i + 1].span().join(close.span())
+        } else {
+            let (arg, comma) = &args[index];
+            let (Some(first), Some(last)) = (arg.first(), arg.last()) else {
+                continue;
+            };
+            match (comma, index.checked_sub(1)) {
+                (Some(comma), _) => first.span().join(comma.span()),
+                (None, Some(prev)) => args[prev]
+                    .1
+                    .and_then(|comma| comma.span().join(last.span())),
+                (None, None) => first.span().join(last.span()),
+            }
+        };
+        spans.extend(span);
+    }
+}
+
+/// Finds the spans of the unused parameter of the kind identified by `id`, as
+/// given out in the diagnostics of [`get_unused_params`], and of the values
+/// passed for it
+pub fn unused_param_spans(file: &syn::File, kind: UnusedDiagnosticKind, id: &str) -> Vec<Span> {
+    let mut found = Vec::new();
+    collect_items(&file.items, "", &[kind], &mut found);
+
+    let mut spans: Vec<_> = found
+        .iter()
+        .filter(|param| param.id == id)
+        .flat_map(|param| param.spans())
+        .collect();
+    if let Some((name, index, count)) = parse_id(id) {
+        call_spans(file.to_token_stream(), name, index, count, &mut spans);
+    }
+
+    spans
+}
+
+/// Finds unused const and type parameters, as far as their kinds are asked
+/// for, in the given source files, along with the files calling the
+/// functions; `workspace_root` is used to report file names relative to it,
+/// just like rustc does
+pub fn get_unused_params(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+    kinds: &[UnusedDiagnosticKind],
+) -> Vec<UnusedDiagnostic> {
+    let parsed: Vec<_> = files
+        .iter()
+        .filter_map(|file| {
+            let file = file.as_ref();
+            let src = std::fs::read_to_string(file).ok()?;
+            let parsed = syn::parse_file(&src).ok()?;
+            let file_name = file
+                .strip_prefix(workspace_root)
+                .unwrap_or(file)
+                .to_string_lossy()
+                .into_owned();
+            Some((file_name, src, parsed))
+        })
+        .collect();
+
+    let mut diagnostics = Vec::new();
+    for (file_name, src, file) in &parsed {
+        if !file_resolution.is_included(file_name) {
+            continue;
+        }
+
+        let mut found = Vec::new();
+        collect_items(&file.items, "", kinds, &mut found);
+        for param in found {
+            let ident = match &param.generics.params[param.index] {
+                GenericParam::Const(const_param) => &const_param.ident,
+                GenericParam::Type(type_param) => &type_param.ident,
+                GenericParam::Lifetime(_) => continue,
+            };
+            diagnostics.push(UnusedDiagnostic {
+                kind: param.kind(),
+                span: synthetic_span(file_name, src, ident.span()),
+                ident: param.id.clone(),
+                suggestion: None,
+                configs: Vec::new(),
+            });
+
+            // The calls in other files have to go as well
+            let Some((name, index, count)) = parse_id(&param.id) else {
+                continue;
+            };
+            for (other_name, other_src, other) in &parsed {
+                if other_name == file_name {
+                    continue;
+                }
+                let mut spans = Vec::new();
+                call_spans(other.to_token_stream(), name, index, count, &mut spans);
+                if let Some(span) = spans.first() {
+                    diagnostics.push(UnusedDiagnostic {
+                        kind: param.kind(),
+                        span: synthetic_span(other_name, other_src, *span),
+                        ident: param.id.clone(),
+                        suggestion: None,
+                        configs: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unused_params(src: &str) -> Vec<String> {
+        let file = syn::parse_file(src).unwrap();
+        let kinds = [
+            UnusedDiagnosticKind::ConstParam,
+            UnusedDiagnosticKind::TypeParam,
+        ];
+        let mut found = Vec::new();
+        collect_items(&file.items, "", &kinds, &mut found);
+        found.into_iter().map(|param| param.id).collect()
+    }
+
+    /// Removes the spans from a single line of source
+    fn remove(src: &str, id: &str) -> String {
+        let file = syn::parse_file(src).unwrap();
+        let kind = match id.contains("const ") {
+            true => UnusedDiagnosticKind::ConstParam,
+            false => UnusedDiagnosticKind::TypeParam,
+        };
+        let mut spans = unused_param_spans(&file, kind, id);
+        spans.sort_by_key(|span| std::cmp::Reverse(span.start().column));
+        let mut src = src.to_string();
+        for span in spans {
+            src.replace_range(span.start().column..span.end().column, "");
+        }
+        src
+    }
+
+    #[test]
+    fn finds_unused_params() {
+        assert_eq!(
+            unused_params("fn foo<T, const N: usize, const M: usize>(t: [T; M]) {}"),
+            vec!["foo::<_, const N, _>"]
+        );
+        assert_eq!(
+            unused_params("mod m { struct S; impl S { fn f<'a, const N: u8>(&'a self) {} } }"),
+            vec!["m::S::f::<const N>"]
+        );
+        assert!(unused_params("fn foo<const N: usize>() { println!(\"{N}\") }").is_empty());
+        assert!(unused_params("fn foo<const N: usize>() -> [u8; N] { todo!() }").is_empty());
+        assert!(unused_params("fn foo<const N: usize>(_: impl Copy) {}").is_empty());
+    }
+
+    #[test]
+    fn removes_params_and_values() {
+        assert_eq!(
+            remove(
+                "fn foo<const N: usize>() {} fn main() { foo::<4>(); }",
+                "foo::<const N>"
+            ),
+            "fn foo() {} fn main() { foo(); }"
+        );
+        assert_eq!(
+            remove(
+                "fn foo<T, const N: usize>() {} fn f() { foo::<Vec<u8>, { 1 + 2 }>(); }",
+                "foo::<_, const N>"
+            ),
+            "fn foo<T>() {} fn f() { foo::<Vec<u8>>(); }"
+        );
+        assert_eq!(
+            remove("fn f() { x.foo::<3, u8>(); }", "S::foo::<const N, _>"),
+            "fn f() { x.foo::< u8>(); }"
+        );
+    }
+
+    #[test]
+    fn removes_type_params() {
+        assert_eq!(
+            unused_params("fn foo<T: Copy, U>(u: U) where T: Clone, U: Send {}"),
+            vec!["foo::<T, _>"]
+        );
+        assert!(unused_params("fn foo<T>() where Vec<T>: Clone {}").is_empty());
+        assert!(unused_params("fn foo<T>() { let _ = std::mem::size_of::<T>(); }").is_empty());
+
+        assert_eq!(
+            remove(
+                "fn foo<T: Copy, U>(u: U) where T: Clone, U: Send {} fn f() { foo::<u8, _>(1); }",
+                "foo::<T, _>"
+            ),
+            "fn foo< U>(u: U) where  U: Send {} fn f() { foo::< _>(1); }"
+        );
+        assert_eq!(
+            remove(
+                "fn foo<T>() where T: Clone, T: Send, {} fn f() { foo::<u8>(); }",
+                "foo::<T>"
+            ),
+            "fn foo()  {} fn f() { foo(); }"
+        );
+        assert_eq!(
+            remove(
+                "fn foo<U, T>(u: U) where U: Send, T: Copy {}",
+                "foo::<_, T>"
+            ),
+            "fn foo<U>(u: U) where U: Send {}"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Modules whose items are all unused. Instead of leaving an empty `mod`
+//! block behind, such a module is removed as a whole, named by its path
+//! within its file, like `outer::inner`. For a `mod foo;` declaration, this
+//! means deleting `foo.rs` (or `foo/mod.rs`), along with the files of its
+//! own modules.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Item, ItemMod};
+
+use crate::{
+    modules,
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+/// The kind and name rustc reports an item by
+fn reported_as(item: &Item) -> Option<(UnusedDiagnosticKind, String)> {
+    use UnusedDiagnosticKind::*;
+    let (kind, ident) = match item {
+        Item::Const(obj) => (Constant, &obj.ident),
+        Item::Enum(obj) => (Enum, &obj.ident),
+        Item::Fn(obj) => (Function, &obj.sig.ident),
+        Item::Macro(syn::ItemMacro {
+            ident: Some(name), ..
+        }) => (MacroDefinition, name),
+        Item::Static(obj) => (Static, &obj.ident),
+        Item::Struct(obj) => (Struct, &obj.ident),
+        Item::Type(obj) => (TypeAlias, &obj.ident),
+        Item::Union(obj) => (Union, &obj.ident),
+        _ => return None,
+    };
+
+    Some((kind, ident.to_string()))
+}
+
+/// The name of the type an impl block is for
+fn self_type(item: &Item) -> Option<String> {
+    let Item::Impl(block) = item else {
+        return None;
+    };
+    let syn::Type::Path(path) = &*block.self_ty else {
+        return None;
+    };
+
+    Some(path.path.segments.last()?.ident.to_string())
+}
+
+/// The unused items of the analyzed files, by their canonical paths
+struct Unused {
+    by_file: HashMap<PathBuf, HashSet<(UnusedDiagnosticKind, String)>>,
+}
+
+impl Unused {
+    /// Whether all the items are unused: `use` items don't count, and impl
+    /// blocks go along with their types. Items without any unused ones are
+    /// left alone. The files of out-of-line modules in `dir` are checked as
+    /// well, and collected in `files`.
+    fn all_dead(&self, items: &[Item], file: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> bool {
+        let no_unused = HashSet::new();
+        let unused = file
+            .canonicalize()
+            .ok()
+            .and_then(|file| self.by_file.get(&file))
+            .unwrap_or(&no_unused);
+        let removed_types: HashSet<_> = items
+            .iter()
+            .filter_map(reported_as)
+            .filter(|found| unused.contains(found))
+            .map(|(_, ident)| ident)
+            .collect();
+
+        let mut has_unused = false;
+        for item in items {
+            let dead = match item {
+                Item::Use(_) => continue,
+                Item::Mod(module) => self.is_dead(module, file, dir, files),
+                Item::Impl(_) => self_type(item).is_some_and(|ty| removed_types.contains(&ty)),
+                _ => reported_as(item).is_some_and(|found| unused.contains(&found)),
+            };
+            if !dead {
+                return false;
+            }
+            has_unused = true;
+        }
+
+        has_unused
+    }
+
+    /// Whether all items of the module are unused, whether they are inline or
+    /// in a file of their own. Modules with a `#[path]` are left alone.
+    fn is_dead(&self, module: &ItemMod, file: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> bool {
+        let name = module.ident.to_string();
+        if let Some((_, items)) = &module.content {
+            return self.all_dead(items, file, &dir.join(name), files);
+        }
+        if module.attrs.iter().any(|attr| attr.path().is_ident("path")) {
+            return false;
+        }
+
+        let Some(module_file) = modules::declared_file(dir, &name) else {
+            return false;
+        };
+        let Some(parsed) = std::fs::read_to_string(&module_file)
+            .ok()
+            .and_then(|src| syn::parse_file(&src).ok())
+        else {
+            return false;
+        };
+        let Some(module_dir) = modules::module_dir(&module_file, false) else {
+            return false;
+        };
+        files.push(module_file.clone());
+
+        self.all_dead(&parsed.items, &module_file, &module_dir, files)
+    }
+
+    /// Collects the paths of the outermost dead modules among the items,
+    /// along with the files of those that are out-of-line
+    fn collect_dead<'a>(
+        &self,
+        items: &'a [Item],
+        prefix: &str,
+        file: &Path,
+        dir: &Path,
+        found: &mut Vec<(String, &'a ItemMod)>,
+        files: &mut Vec<PathBuf>,
+    ) {
+        for item in items {
+            let Item::Mod(module) = item else {
+                continue;
+            };
+            let path = format!("{prefix}{}", module.ident);
+            // The files only go once their module does
+            let mut module_files = Vec::new();
+            if self.is_dead(module, file, dir, &mut module_files) {
+                found.push((path, module));
+                files.extend(module_files);
+            } else if let Some((_, items)) = &module.content {
+                let dir = dir.join(module.ident.to_string());
+                self.collect_dead(items, &format!("{path}::"), file, &dir, found, files);
+            }
+        }
+    }
+}
+
+/// The modules whose items are all among the diagnostics, declared in the
+/// source files: a finding for each of them, and the files of those that
+/// are out-of-line, which are deleted
+pub fn dead_modules(
+    workspace_root: &Path,
+    sources: &[PathBuf],
+    crate_roots: &HashSet<PathBuf>,
+    diagnostics: &[UnusedDiagnostic],
+) -> (Vec<UnusedDiagnostic>, Vec<PathBuf>) {
+    let mut by_file: HashMap<_, HashSet<_>> = HashMap::new();
+    for diagnostic in diagnostics {
+        let Ok(file) = workspace_root
+            .join(&diagnostic.span.file_name)
+            .canonicalize()
+        else {
+            continue;
+        };
+        let found = (diagnostic.kind, diagnostic.ident.clone());
+        by_file.entry(file).or_default().insert(found);
+    }
+    let unused = Unused { by_file };
+
+    let mut modules = Vec::new();
+    let mut files = Vec::new();
+    for source in sources {
+        let Ok(src) = std::fs::read_to_string(source) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+        let is_root = source
+            .canonicalize()
+            .is_ok_and(|path| crate_roots.contains(&path));
+        let Some(dir) = modules::module_dir(source, is_root) else {
+            continue;
+        };
+
+        let mut found = Vec::new();
+        unused.collect_dead(&parsed.items, "", source, &dir, &mut found, &mut files);
+        let file_name = source.strip_prefix(workspace_root).unwrap_or(source);
+        modules.extend(found.into_iter().map(|(path, module)| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Module,
+            ident: path,
+            span: synthetic_span(&file_name.to_string_lossy(), &src, module.ident.span()),
+            suggestion: None,
+            configs: Vec::new(),
+        }));
+    }
+
+    // The modules within the deleted files go along with them
+    files.sort();
+    files.dedup();
+    let deleted: HashSet<_> = files
+        .iter()
+        .filter_map(|file| file.canonicalize().ok())
+        .collect();
+    modules.retain(|module| {
+        workspace_root
+            .join(&module.span.file_name)
+            .canonicalize()
+            .is_ok_and(|file| !deleted.contains(&file))
+    });
+
+    (modules, files)
+}
+
+fn find_module<'a>(items: &'a [Item], prefix: &str, path: &str) -> Option<&'a ItemMod> {
+    items.iter().find_map(|item| {
+        let Item::Mod(module) = item else {
+            return None;
+        };
+        let module_path = format!("{prefix}{}", module.ident);
+        // rustc names modules without their path
+        if module_path == path || module_path.ends_with(&format!("::{path}")) {
+            return Some(module);
+        }
+        find_module(
+            &module.content.as_ref()?.1,
+            &format!("{module_path}::"),
+            path,
+        )
+    })
+}
+
+/// The span of a module (or its declaration), given by its path within the
+/// file
+pub fn module_spans(file: &syn::File, path: &str) -> Vec<Span> {
+    find_module(&file.items, "", path)
+        .map(Spanned::span)
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_support::TempCrate;
+
+    use super::*;
+
+    fn dead(src: &str, unused: &[(UnusedDiagnosticKind, &str)]) -> Vec<String> {
+        let temp_crate = TempCrate::new(src).unwrap();
+        let lib_rs = temp_crate.root().join("src/lib.rs");
+        let unused = unused
+            .iter()
+            .map(|(kind, ident)| (*kind, ident.to_string()))
+            .collect();
+        let unused = Unused {
+            by_file: HashMap::from([(lib_rs.canonicalize().unwrap(), unused)]),
+        };
+
+        let file = syn::parse_file(src).unwrap();
+        let (mut found, mut files) = (Vec::new(), Vec::new());
+        let dir = temp_crate.root().join("src");
+        unused.collect_dead(&file.items, "", &lib_rs, &dir, &mut found, &mut files);
+        found.into_iter().map(|(path, _)| path).collect()
+    }
+
+    #[test]
+    fn dead_inline_modules() {
+        use UnusedDiagnosticKind::*;
+        let src =
+            "mod a { use std::fmt; fn f() {} struct S; impl S {} mod b { const C: u8 = 0; } }\n\
+                   mod c { fn f() {} fn g() {} }\n\
+                   mod d { mod e { fn h() {} } }\n\
+                   mod empty {}\n\
+                   mod file;\n";
+        let unused = [
+            (Function, "f"),
+            (Struct, "S"),
+            (Constant, "C"),
+            (Function, "h"),
+        ];
+        assert_eq!(dead(src, &unused), ["a", "d"]);
+        assert!(dead(src, &unused[..2]).is_empty());
+        assert_eq!(dead(src, &[(Constant, "C")]), ["a::b"]);
+
+        let file = syn::parse_file(src).unwrap();
+        assert_eq!(module_spans(&file, "a::b").len(), 1);
+        assert_eq!(module_spans(&file, "b").len(), 1);
+        assert_eq!(module_spans(&file, "file").len(), 1);
+    }
+
+    #[test]
+    fn dead_file_modules() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod dead;\nmod alive;\npub fn f() {}\n"),
+            ("src/dead.rs", "fn a() {}\nmod nested;\n"),
+            ("src/dead/nested.rs", "fn b() {}\n"),
+            ("src/alive/mod.rs", "fn a() {}\npub fn c() {}\n"),
+        ])
+        .unwrap();
+        let root = temp_crate.root();
+        let function = |file: &str, ident: &str| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Function,
+            ident: ident.to_string(),
+            span: synthetic_span(file, "", Span::call_site()),
+            suggestion: None,
+            configs: Vec::new(),
+        };
+        let diagnostics = [
+            function("src/dead.rs", "a"),
+            function("src/dead/nested.rs", "b"),
+            function("src/alive/mod.rs", "a"),
+        ];
+        let sources = ["lib.rs", "dead.rs", "dead/nested.rs", "alive/mod.rs"]
+            .map(|file| root.join("src").join(file));
+        let crate_roots = HashSet::from([sources[0].canonicalize().unwrap()]);
+
+        let (modules, files) = dead_modules(root, &sources, &crate_roots, &diagnostics);
+        let modules: Vec<_> = modules
+            .iter()
+            .map(|module| (module.span.file_name.as_str(), module.ident.as_str()))
+            .collect();
+        assert_eq!(modules, [("src/lib.rs", "dead")]);
+        assert_eq!(files, [sources[2].clone(), sources[1].clone()]);
+    }
+}
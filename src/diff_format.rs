@@ -1,15 +1,29 @@
-use std::str::FromStr;
+use std::{env, fs, io, process::Command, str::FromStr};
 
 use nu_ansi_term::Color;
 use thiserror::Error;
 
-use crate::cauterize::Change;
+use crate::cauterize::{Change, ChangeCategory};
 
 const BEFORE_CONTEXT: isize = 3;
 const AFTER_CONTEXT: isize = 3;
 
+/// Semantic changes are marked in the headers, as they need a closer review,
+/// and so are the files that are deleted
+fn category_note(change: &Change) -> &'static str {
+    match (change.category(), change.is_removal()) {
+        (_, true) => " (deleted)",
+        (ChangeCategory::Mechanical, false) => "",
+        (ChangeCategory::Semantic, false) => " (semantic, only applied with --apply-semantic)",
+    }
+}
+
 pub fn println(change: &Change, color_mode: ColorMode) {
-    let text = format!("#\n#\tshowing diff for {:?}:\n#", change.file_name());
+    let text = format!(
+        "#\n#\tshowing diff for {:?}{}:\n#",
+        change.file_name(),
+        category_note(change)
+    );
     if color_mode.enabled() {
         println!("{}", Color::DarkGray.paint(text));
     } else {
@@ -64,6 +78,220 @@ pub fn println(change: &Change, color_mode: ColorMode) {
     }
 }
 
+/// Prints only the first line (usually the signature) of each removed item,
+/// followed by the number of removed lines
+pub fn println_compact(change: &Change, color_mode: ColorMode) {
+    let text = format!("#\t{:?}{}:", change.file_name(), category_note(change));
+    if color_mode.enabled() {
+        println!("{}", Color::DarkGray.paint(text));
+    } else {
+        println!("{text}")
+    }
+
+    let left = String::from_utf8_lossy(change.original_content());
+    let right = String::from_utf8_lossy(change.proposed_content());
+
+    let mut removed = Vec::new();
+    let flush = |removed: &mut Vec<&str>| {
+        for (line, count) in compact_blocks(removed) {
+            let format = match count {
+                1 => format!("-\t{line}"),
+                _ => format!("-\t{line} (-{count} lines)"),
+            };
+            if color_mode.enabled() {
+                println!("{}", Color::LightRed.paint(format));
+            } else {
+                println!("{format}");
+            }
+        }
+        removed.clear();
+    };
+
+    for line in diff::lines(&left, &right) {
+        match line {
+            diff::Result::Left(line) => removed.push(line),
+            diff::Result::Right(line) => {
+                flush(&mut removed);
+                let format = format!("+\t{line}");
+                if color_mode.enabled() {
+                    println!("{}", Color::LightGreen.paint(format));
+                } else {
+                    println!("{format}");
+                }
+            }
+            diff::Result::Both(_, _) => flush(&mut removed),
+        }
+    }
+    flush(&mut removed);
+}
+
+/// Splits a run of removed lines into the items it consists of, returning the
+/// first line of each item that is not an attribute or comment, along with
+/// the number of lines of that item. A new item starts at every line that is
+/// indented no further than the first line and is not a closing delimiter.
+fn compact_blocks(lines: &[&str]) -> Vec<(String, usize)> {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let is_preamble = |line: &str| {
+        let line = line.trim_start();
+        line.is_empty() || line.starts_with('#') || line.starts_with("//")
+    };
+
+    let mut blocks: Vec<(Option<String>, usize)> = Vec::new();
+    let Some(base) = lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .map(|l| indent(l))
+    else {
+        return Vec::new();
+    };
+
+    let mut in_preamble = false;
+    for line in lines {
+        let trimmed = line.trim_start();
+        let starts_item =
+            !trimmed.is_empty() && indent(line) <= base && !trimmed.starts_with(['}', ')', ']']);
+
+        match blocks.last_mut() {
+            Some((signature, count)) if !starts_item || in_preamble => {
+                *count += 1;
+                if signature.is_none() && !is_preamble(line) {
+                    *signature = Some(trimmed.to_string());
+                }
+            }
+            _ => blocks.push(((!is_preamble(line)).then(|| trimmed.to_string()), 1)),
+        }
+
+        if starts_item {
+            in_preamble = is_preamble(line);
+        }
+    }
+
+    blocks
+        .into_iter()
+        .filter(|(signature, _)| signature.is_some())
+        .map(|(signature, count)| (signature.unwrap_or_default(), count))
+        .collect()
+}
+
+/// Shows the diff with an external tool (such as delta, difftastic or meld),
+/// which is invoked with a file holding the original content and a file
+/// holding the proposed content as its last two arguments
+pub fn run_external(change: &Change, tool: &str) -> io::Result<()> {
+    let mut words = tool.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty diff tool"))?;
+
+    let dir = env::temp_dir().join(format!("cargo-minify-diff-{}", std::process::id()));
+    let file_name = change
+        .file_name()
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_else(|| "file.rs".into());
+    let before = dir.join("before").join(&file_name);
+    let after = dir.join("after").join(&file_name);
+
+    let result = (|| {
+        fs::create_dir_all(dir.join("before"))?;
+        fs::create_dir_all(dir.join("after"))?;
+        fs::write(&before, change.original_content())?;
+        fs::write(&after, change.proposed_content())?;
+
+        // Diff tools commonly exit with a non-zero status when the files
+        // differ, so the status is not checked
+        Command::new(program)
+            .args(words)
+            .arg(&before)
+            .arg(&after)
+            .status()
+            .map(|_| ())
+    })();
+
+    let _ = fs::remove_dir_all(&dir);
+
+    result
+}
+
+/// The change as a unified diff, as taken by `git apply` or `patch -p1`; the
+/// file is named relative to the workspace root
+pub fn unified(change: &Change, file_name: &str) -> String {
+    let left = String::from_utf8_lossy(change.original_content());
+    let right = String::from_utf8_lossy(change.proposed_content());
+    // Unlike `diff::lines`, carriage returns are kept, and final newlines
+    // don't make for lines of their own
+    let left_lines: Vec<_> = left.split_terminator('\n').collect();
+    let right_lines: Vec<_> = right.split_terminator('\n').collect();
+    let diff = diff::slice(&left_lines, &right_lines);
+
+    let new_name = match change.is_removal() {
+        true => "/dev/null".to_string(),
+        false => format!("b/{file_name}"),
+    };
+    let mut patch = format!("--- a/{file_name}\n+++ {new_name}\n");
+
+    // The lines of both sides that come before each line of the diff
+    let mut positions = Vec::with_capacity(diff.len());
+    let (mut old, mut new) = (0, 0);
+    for line in &diff {
+        positions.push((old, new));
+        match line {
+            diff::Result::Left(_) => old += 1,
+            diff::Result::Right(_) => new += 1,
+            diff::Result::Both(_, _) => (old, new) = (old + 1, new + 1),
+        }
+    }
+    let (old_len, new_len) = (old, new);
+    // The last line of a side without a final newline is marked as such
+    let missing_newline =
+        |side: &str, index: usize, len: usize| !side.ends_with('\n') && index + 1 == len;
+
+    let changed = (0..diff.len()).filter(|&i| !matches!(diff[i], diff::Result::Both(_, _)));
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(BEFORE_CONTEXT as usize);
+        let end = (index + AFTER_CONTEXT as usize + 1).min(diff.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let lines = &diff[start..end];
+        let old_count = lines
+            .iter()
+            .filter(|line| !matches!(line, diff::Result::Right(_)));
+        let new_count = lines
+            .iter()
+            .filter(|line| !matches!(line, diff::Result::Left(_)));
+        let (old_count, new_count) = (old_count.count(), new_count.count());
+        let (old_start, new_start) = positions[start];
+        // Empty ranges are given by the line before them
+        let first = |start: usize, count: usize| if count == 0 { start } else { start + 1 };
+        patch += &format!(
+            "@@ -{},{} +{},{} @@\n",
+            first(old_start, old_count),
+            old_count,
+            first(new_start, new_count),
+            new_count
+        );
+
+        for (line, &(old, new)) in lines.iter().zip(&positions[start..end]) {
+            let (symbol, text, no_newline) = match line {
+                diff::Result::Left(text) => ('-', text, missing_newline(&left, old, old_len)),
+                diff::Result::Right(text) => ('+', text, missing_newline(&right, new, new_len)),
+                diff::Result::Both(text, _) => (' ', text, missing_newline(&left, old, old_len)),
+            };
+            patch += &format!("{symbol}{text}\n");
+            if no_newline {
+                patch += "\\ No newline at end of file\n";
+            }
+        }
+    }
+
+    patch
+}
+
 fn has_changed(diff: &diff::Result<&str>) -> bool {
     match diff {
         diff::Result::Left(_) | diff::Result::Right(_) => true,
@@ -119,3 +347,80 @@ impl FromStr for ColorMode {
 #[derive(Debug, Error)]
 #[error("unsupported color mode, pick any of: auto, always, never")]
 pub struct UnsupportedPrintColor;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn compact() {
+        let lines = [
+            "/// Docs",
+            "#[inline]",
+            "fn foo() {",
+            "    bar();",
+            "}",
+            "",
+            "const FOO: i32 = 42;",
+            "struct Bar {",
+            "    x: i32,",
+            "}",
+        ];
+        assert_eq!(
+            compact_blocks(&lines),
+            vec![
+                ("fn foo() {".to_string(), 6),
+                ("const FOO: i32 = 42;".to_string(), 1),
+                ("struct Bar {".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn unified_patch() {
+        let original = "fn main() {}\n\nfn unused() {}\n";
+        let change = Change::new(
+            "src/lib.rs".into(),
+            original.as_bytes().to_vec(),
+            b"fn main() {}\n\n".to_vec(),
+        );
+        assert_eq!(
+            unified(&change, "src/lib.rs"),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,2 @@\n fn main() {}\n \n\
+             -fn unused() {}\n"
+        );
+
+        let removal = Change::removal("gone.rs".into(), b"fn a() {}".to_vec());
+        assert_eq!(
+            unified(&removal, "src/gone.rs"),
+            "--- a/src/gone.rs\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-fn a() {}\n\
+             \\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn runs_external_tools_on_before_and_after_files() {
+        let lib_rs = "pub fn used() {}\nfn dead() {}\n";
+        // Records the files it's given and their contents
+        let record = "printf '%s\\n%s\\n' \"$2\" \"$3\" > \"$1.paths\"\n\
+                      cat \"$2\" \"$3\" > \"$1\"\n";
+        let temp_crate =
+            TempCrate::with_files([("src/lib.rs", lib_rs), ("record.sh", record)]).unwrap();
+        let root = temp_crate.root();
+        let (script, seen) = (root.join("record.sh"), root.join("seen"));
+        let tool = format!("sh {} {}", script.display(), seen.display());
+
+        crate::execute(&temp_crate.args(&["--diff-tool", &tool])).unwrap();
+        assert_eq!(
+            temp_crate.read("seen").unwrap(),
+            format!("{lib_rs}pub fn used() {{}}\n")
+        );
+        let paths = temp_crate.read("seen.paths").unwrap();
+        let paths: Vec<_> = paths.lines().map(std::path::Path::new).collect();
+        assert!(paths[0].ends_with("before/lib.rs") && paths[1].ends_with("after/lib.rs"));
+        assert!(paths.iter().all(|path| !path.exists()));
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+    }
+}
@@ -0,0 +1,417 @@
+//! Intra-doc links to removed items. Rustdoc warns about links whose target is
+//! gone (and fails with `deny(rustdoc::broken_intra_doc_links)`), so these can
+//! be left broken, rewritten to plain text, or keep their target alive.
+
+use std::{
+    collections::HashSet,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    cauterize::Change,
+    unused::{UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+/// What to do with intra-doc links to removed items
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DocLinks {
+    /// Remove the items anyway, and warn about the links
+    #[default]
+    Break,
+    /// Rewrite the links to plain text
+    Fix,
+    /// Keep the linked items
+    Protect,
+}
+
+impl FromStr for DocLinks {
+    type Err = UnsupportedDocLinks;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "break" => Ok(DocLinks::Break),
+            "fix" => Ok(DocLinks::Fix),
+            "protect" => Ok(DocLinks::Protect),
+            _ => Err(UnsupportedDocLinks),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported doc links mode, pick any of: break, fix, protect")]
+pub struct UnsupportedDocLinks;
+
+/// An intra-doc link to an item
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocLink {
+    pub ident: String,
+    pub file_name: PathBuf,
+    pub line: usize,
+}
+
+/// A link in a line of a doc comment
+struct Link {
+    /// The whole link, including the target
+    range: Range<usize>,
+    /// What rustdoc shows of the link
+    text: Range<usize>,
+    /// The last path segment of the target, if it is an intra-doc link
+    target: Option<String>,
+    /// The label of a `[text][label]` reference link
+    reference: Option<String>,
+    /// The label of a `[label]: target` definition of reference links
+    definition: Option<String>,
+}
+
+impl Link {
+    /// Whether the link points to any of the idents, directly or by the label
+    /// of a definition that does
+    fn points_to(&self, idents: &HashSet<&str>, labels: &HashSet<String>) -> bool {
+        self.target
+            .as_deref()
+            .is_some_and(|target| idents.contains(target))
+            || self
+                .reference
+                .as_ref()
+                .is_some_and(|label| labels.contains(label))
+    }
+}
+
+/// The identifier an intra-doc link target refers to, if it is one; URLs and
+/// other targets are left out
+fn target_ident(target: &str) -> Option<String> {
+    let target = target.trim().trim_matches('`');
+    // Disambiguators, as in `struct@Foo` or `foo()`
+    let target = target.rsplit('@').next().unwrap_or_default();
+    let target = target.trim_end_matches("()").trim_end_matches('!');
+    if target.is_empty()
+        || !target
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+    {
+        return None;
+    }
+
+    target.rsplit("::").next().map(str::to_string)
+}
+
+/// The position of the parenthesis closing the one `s` starts with
+fn closing_paren(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    s.char_indices().find_map(|(i, c)| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        (depth == 0).then_some(i)
+    })
+}
+
+/// The links in the text of a doc comment
+fn parse_links(doc: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut pos = 0;
+
+    while let Some(open) = doc[pos..].find('[').map(|i| pos + i) {
+        let Some(close) = doc[open..].find(']').map(|i| open + i) else {
+            break;
+        };
+        let text = open + 1..close;
+        let rest = &doc[close + 1..];
+
+        let label = || Some(doc[text.clone()].to_string());
+
+        let (end, target, reference, definition) = match rest.chars().next() {
+            Some('(') => match closing_paren(rest) {
+                Some(i) => (close + 2 + i, &rest[1..i], None, None),
+                None => (close + 1, &doc[text.clone()], None, None),
+            },
+            Some('[') => match rest.find(']') {
+                Some(i) => (
+                    close + 2 + i,
+                    &rest[1..i],
+                    Some(rest[1..i].to_string()),
+                    None,
+                ),
+                None => (close + 1, &doc[text.clone()], None, None),
+            },
+            Some(':') if doc[..open].trim().is_empty() => {
+                (doc.len(), rest[1..].trim(), None, label())
+            }
+            // Shortcut `[target]` links, which may be defined elsewhere too
+            _ => (close + 1, &doc[text.clone()], label(), None),
+        };
+
+        links.push(Link {
+            range: open..end,
+            text,
+            target: target_ident(target),
+            reference,
+            definition,
+        });
+        pos = end;
+    }
+
+    links
+}
+
+/// The text of the doc comment on the line, and where it starts
+fn doc_comment(line: &str) -> Option<(usize, &str)> {
+    let indent = line.len() - line.trim_start().len();
+    let comment = &line[indent..];
+    if comment.starts_with("////") {
+        return None;
+    }
+
+    comment
+        .strip_prefix("///")
+        .or_else(|| comment.strip_prefix("//!"))
+        .map(|doc| (indent + 3, doc))
+}
+
+/// Only items can be linked to; bounds and shims are found by other names
+fn is_linkable(kind: UnusedDiagnosticKind) -> bool {
+    !matches!(
+        kind,
+        UnusedDiagnosticKind::Bound
+            | UnusedDiagnosticKind::DynShim
+            | UnusedDiagnosticKind::TraitImpl
+            | UnusedDiagnosticKind::ConstParam
+            | UnusedDiagnosticKind::TypeParam
+            | UnusedDiagnosticKind::Import
+            | UnusedDiagnosticKind::Variant
+            | UnusedDiagnosticKind::Field
+            | UnusedDiagnosticKind::Module
+            | UnusedDiagnosticKind::ExternCrate
+            | UnusedDiagnosticKind::Lifetime
+            | UnusedDiagnosticKind::Unreachable
+            | UnusedDiagnosticKind::UnreachablePub
+            | UnusedDiagnosticKind::Reexport
+            | UnusedDiagnosticKind::Allow
+            | UnusedDiagnosticKind::Variable
+            | UnusedDiagnosticKind::Mut
+    )
+}
+
+fn removed_idents<'a>(removed: impl IntoIterator<Item = &'a UnusedDiagnostic>) -> HashSet<&'a str> {
+    removed
+        .into_iter()
+        .filter(|diagnostic| is_linkable(diagnostic.kind))
+        .map(|diagnostic| diagnostic.ident.as_str())
+        .collect()
+}
+
+/// The labels of the reference links in the source that point to any of the
+/// idents
+fn labels(src: &str, idents: &HashSet<&str>) -> HashSet<String> {
+    src.lines()
+        .filter_map(doc_comment)
+        .flat_map(|(_, doc)| parse_links(doc))
+        .filter(|link| {
+            link.target
+                .as_deref()
+                .is_some_and(|target| idents.contains(target))
+        })
+        .filter_map(|link| link.definition)
+        .collect()
+}
+
+/// The links to any of the idents in the source
+fn find_links(file_name: &Path, src: &str, idents: &HashSet<&str>) -> Vec<DocLink> {
+    src.lines()
+        .enumerate()
+        .filter_map(|(i, line)| Some((i, doc_comment(line)?.1)))
+        .flat_map(|(i, doc)| {
+            parse_links(doc)
+                .into_iter()
+                .filter_map(|link| {
+                    link.target
+                        .filter(|target| idents.contains(target.as_str()))
+                })
+                .map(move |ident| DocLink {
+                    ident,
+                    file_name: file_name.to_path_buf(),
+                    line: i + 1,
+                })
+        })
+        .collect()
+}
+
+/// Rewrites the links to any of the idents to plain text, and removes the
+/// definitions of reference links to them
+fn unlink(src: &str, idents: &HashSet<&str>) -> String {
+    let labels = labels(src, idents);
+    let mut result = String::with_capacity(src.len());
+
+    for line in src.split_inclusive('\n') {
+        let Some((offset, doc)) = doc_comment(line) else {
+            result.push_str(line);
+            continue;
+        };
+        let links: Vec<_> = parse_links(doc)
+            .into_iter()
+            .filter(|link| link.points_to(idents, &labels))
+            .collect();
+        if links.iter().any(|link| link.definition.is_some()) {
+            continue;
+        }
+
+        let mut pos = 0;
+        result.push_str(&line[..offset]);
+        for link in links {
+            result.push_str(&doc[pos..link.range.start]);
+            result.push_str(&doc[link.text]);
+            pos = link.range.end;
+        }
+        result.push_str(&line[offset + pos..]);
+    }
+
+    result
+}
+
+/// The sources of the files, as they are after applying the changes
+fn sources_after<'a>(
+    files: &'a [PathBuf],
+    changes: &'a [Change],
+) -> impl Iterator<Item = (&'a PathBuf, Option<&'a Change>, String)> + 'a {
+    files.iter().filter_map(move |file| {
+        let change = changes.iter().find(|change| {
+            change.file_name() == file
+                || std::fs::canonicalize(change.file_name()).is_ok_and(|path| &path == file)
+        });
+        let src = match change {
+            Some(change) => String::from_utf8_lossy(change.proposed_content()).into_owned(),
+            None => std::fs::read_to_string(file).ok()?,
+        };
+        Some((file, change, src))
+    })
+}
+
+/// The intra-doc links to the removed items that are left after applying the
+/// changes. Links are matched by name, so a link to another item of the same
+/// name is reported as well.
+pub fn broken_links<'a>(
+    files: &[PathBuf],
+    changes: &[Change],
+    removed: impl IntoIterator<Item = &'a UnusedDiagnostic>,
+) -> Vec<DocLink> {
+    let idents = removed_idents(removed);
+    if idents.is_empty() {
+        return Vec::new();
+    }
+
+    sources_after(files, changes)
+        .flat_map(|(file, change, src)| {
+            let mut links = find_links(file, &src, &idents);
+            if let Some(change) = change {
+                let original = String::from_utf8_lossy(change.original_content());
+                for link in &mut links {
+                    link.line = original_line(&original, &src, link.line);
+                }
+            }
+            links
+        })
+        .collect()
+}
+
+/// The line in the original source that ended up as `line` of the changed one
+fn original_line(original: &str, changed: &str, line: usize) -> usize {
+    let (mut original_line, mut changed_line) = (0, 0);
+    for result in diff::lines(original, changed) {
+        match result {
+            diff::Result::Left(_) => original_line += 1,
+            diff::Result::Right(_) => changed_line += 1,
+            diff::Result::Both(_, _) => {
+                original_line += 1;
+                changed_line += 1;
+            }
+        }
+        if changed_line == line {
+            return original_line;
+        }
+    }
+
+    line
+}
+
+/// Rewrites the intra-doc links to the removed items to plain text, as part of
+/// the changes
+pub fn fix_links<'a>(
+    files: &[PathBuf],
+    changes: &mut Vec<Change>,
+    removed: impl IntoIterator<Item = &'a UnusedDiagnostic>,
+) {
+    let idents = removed_idents(removed);
+    if idents.is_empty() {
+        return;
+    }
+
+    let mut fixed = Vec::new();
+    for (file, change, src) in sources_after(files, changes) {
+        let unlinked = unlink(&src, &idents);
+        if unlinked == src {
+            continue;
+        }
+        match change {
+            Some(change) => fixed.push(Change::new(
+                change.file_name().to_path_buf(),
+                change.original_content().to_vec(),
+                unlinked.into_bytes(),
+            )),
+            None => fixed.push(Change::new(
+                file.clone(),
+                src.into_bytes(),
+                unlinked.into_bytes(),
+            )),
+        }
+    }
+
+    for change in fixed {
+        changes.retain(|other| other.file_name() != change.file_name());
+        changes.push(change);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn links() {
+        let idents = HashSet::from(["Foo", "bar"]);
+        let src = "/// See [`Foo`], [the bar](crate::bar()) and [`Baz`]\n\
+                   /// Also [Foo][foo-ref] and [docs](https://docs.rs)\n\
+                   ///\n\
+                   /// [foo-ref]: struct@Foo\n\
+                   // Not a doc comment: [`Foo`]\n\
+                   struct S;\n";
+
+        let lines: Vec<_> = find_links(Path::new("lib.rs"), src, &idents)
+            .into_iter()
+            .map(|link| (link.ident, link.line))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("Foo".to_string(), 1),
+                ("bar".to_string(), 1),
+                ("Foo".to_string(), 4)
+            ]
+        );
+
+        assert_eq!(
+            unlink(src, &idents),
+            "/// See `Foo`, the bar and [`Baz`]\n\
+             /// Also Foo and [docs](https://docs.rs)\n\
+             ///\n\
+             // Not a doc comment: [`Foo`]\n\
+             struct S;\n"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Code that is only compiled for documentation, under `#[cfg(doc)]` or
+//! `#[cfg(docsrs)]`, is invisible to a regular check; so the items it uses look
+//! unused. A `--cfg docsrs` check often fails on stable, as such code tends to
+//! enable nightly features, so the cfg attributes are evaluated syntactically.
+
+use std::{collections::HashSet, path::Path};
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Attribute, Item};
+
+/// Splits a list of cfg predicates at the commas
+fn predicates(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut predicates = vec![Vec::new()];
+    for token in tokens {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => predicates.push(Vec::new()),
+            token => predicates.last_mut().unwrap().push(token),
+        }
+    }
+    predicates.retain(|predicate| !predicate.is_empty());
+
+    predicates
+}
+
+/// Evaluates a cfg predicate with the `options` set to `set`, and any other
+/// option assumed to be set
+fn evaluate(predicate: &[TokenTree], options: &[&str], set: bool) -> bool {
+    match predicate {
+        [TokenTree::Ident(name), TokenTree::Group(group)] => {
+            let mut values = predicates(group.stream())
+                .into_iter()
+                .map(|predicate| evaluate(&predicate, options, set));
+            match name.to_string().as_str() {
+                "all" => values.all(|value| value),
+                "any" => values.any(|value| value),
+                "not" => !values.all(|value| value),
+                _ => true,
+            }
+        }
+        [TokenTree::Ident(name)] => !options.iter().any(|option| name == option) || set,
+        _ => true,
+    }
+}
+
+/// Whether code under the cfg predicate is only compiled with (any of) the
+/// `options` set, such as `test`
+pub fn is_only_compiled_with(predicate: TokenStream, options: &[&str]) -> bool {
+    match &predicates(predicate)[..] {
+        [predicate] => !evaluate(predicate, options, false) && evaluate(predicate, options, true),
+        _ => false,
+    }
+}
+
+/// Whether code under the cfg predicate is only compiled for documentation
+fn is_doc_only(predicate: TokenStream) -> bool {
+    is_only_compiled_with(predicate, &["doc", "docsrs"])
+}
+
+/// Whether the tokens of an item start with a cfg attribute under which it's
+/// only compiled with (any of) the `options` set
+pub fn starts_with_cfg_only(tokens: TokenStream, options: &[&str]) -> bool {
+    let mut tokens = tokens.into_iter();
+    while let (Some(TokenTree::Punct(punct)), Some(TokenTree::Group(attr))) =
+        (tokens.next(), tokens.next())
+    {
+        if punct.as_char() != '#' || attr.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        let mut attr = attr.stream().into_iter();
+        if let (Some(TokenTree::Ident(name)), Some(TokenTree::Group(predicate))) =
+            (attr.next(), attr.next())
+        {
+            if name == "cfg" && is_only_compiled_with(predicate.stream(), options) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Whether the tokens of an item start with a doc-only cfg attribute
+fn has_doc_only_cfg(tokens: TokenStream) -> bool {
+    starts_with_cfg_only(tokens, &["doc", "docsrs"])
+}
+
+fn collect_idents(tokens: TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            TokenTree::Punct(_) | TokenTree::Literal(_) => {}
+        }
+    }
+}
+
+/// Collects the identifiers in the doc-only tokens, returning whether the
+/// tokens are doc-only
+fn collect_if_doc_only(tokens: TokenStream, idents: &mut HashSet<String>) -> bool {
+    let doc_only = has_doc_only_cfg(tokens.clone());
+    if doc_only {
+        collect_idents(tokens, idents);
+    }
+
+    doc_only
+}
+
+fn collect_items(items: &[Item], idents: &mut HashSet<String>) {
+    for item in items {
+        if collect_if_doc_only(item.to_token_stream(), idents) {
+            continue;
+        }
+        match item {
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, idents);
+                }
+            }
+            Item::Impl(block) => {
+                for item in &block.items {
+                    collect_if_doc_only(item.to_token_stream(), idents);
+                }
+            }
+            Item::Trait(block) => {
+                for item in &block.items {
+                    collect_if_doc_only(item.to_token_stream(), idents);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_doc_only_file(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .meta
+                .require_list()
+                .is_ok_and(|list| is_doc_only(list.tokens.clone()))
+    })
+}
+
+/// The identifiers used by code that is only compiled for documentation
+pub fn used_idents(files: &[impl AsRef<Path>]) -> HashSet<String> {
+    let mut idents = HashSet::new();
+
+    for file in files {
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+        if is_doc_only_file(&parsed.attrs) {
+            collect_idents(parsed.into_token_stream(), &mut idents);
+        } else {
+            collect_items(&parsed.items, &mut idents);
+        }
+    }
+
+    idents
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn doc_only(predicate: &str) -> bool {
+        is_doc_only(predicate.parse().unwrap())
+    }
+
+    #[test]
+    fn predicates() {
+        assert!(doc_only("doc"));
+        assert!(doc_only("docsrs"));
+        assert!(doc_only("any(doc, docsrs)"));
+        assert!(doc_only("all(docsrs, feature = \"std\")"));
+        assert!(!doc_only("not(docsrs)"));
+        assert!(!doc_only("any(docsrs, feature = \"std\")"));
+        assert!(!doc_only("test"));
+    }
+
+    #[test]
+    fn idents() {
+        let file = syn::parse_file(
+            "fn helper() {}
+             #[cfg(docsrs)]
+             pub fn example() { helper() }
+             impl S {
+                 #[cfg(doc)]
+                 fn shown() -> Shown { todo!() }
+                 fn hidden() -> Hidden { todo!() }
+             }",
+        )
+        .unwrap();
+        let mut idents = HashSet::new();
+        collect_items(&file.items, &mut idents);
+
+        assert!(idents.contains("helper"));
+        assert!(idents.contains("Shown"));
+        assert!(!idents.contains("Hidden"));
+    }
+}
@@ -0,0 +1,171 @@
+//! A companion analysis for `--duplicates`: finds items that are identical
+//! apart from their names and attributes, across all analyzed sources. These
+//! are not removed, but reported as candidates for consolidation.
+
+use std::{collections::HashMap, path::Path};
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{ImplItem, Item};
+
+use crate::state::content_hash;
+
+/// Items with fewer tokens are too small to be worth consolidating
+const MIN_TOKENS: usize = 30;
+
+/// An item that has duplicates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Duplicate {
+    pub file_name: String,
+    pub line: usize,
+    pub ident: String,
+}
+
+/// The tokens of an item without its outer attributes (such as its docs), and
+/// with its own name replaced, so that only the code itself is compared;
+/// returns the number of tokens
+fn normalize(tokens: TokenStream, ident: &str, normalized: &mut String) -> usize {
+    let mut tokens = tokens.into_iter().peekable();
+
+    // Outer attributes always come first
+    let is_hash = |token: &TokenTree| matches!(token, TokenTree::Punct(p) if p.as_char() == '#');
+    let is_attribute = |token: &TokenTree| matches!(token, TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket);
+    while tokens.next_if(is_hash).is_some() {
+        tokens.next_if(is_attribute);
+    }
+
+    normalize_tokens(tokens, ident, normalized)
+}
+
+fn normalize_tokens(
+    tokens: impl Iterator<Item = TokenTree>,
+    ident: &str,
+    normalized: &mut String,
+) -> usize {
+    let mut count = 0;
+    for token in tokens {
+        count += 1;
+        match token {
+            TokenTree::Ident(name) if name == ident => normalized.push('_'),
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                normalized.push_str(open);
+                count += normalize_tokens(group.stream().into_iter(), ident, normalized);
+                normalized.push_str(close);
+            }
+            token => normalized.push_str(&token.to_string()),
+        }
+        normalized.push(' ');
+    }
+
+    count
+}
+
+/// The items worth comparing: top-level items, items of inline modules and
+/// the functions of impl blocks, along with their names
+fn collect_items(items: &[Item], found: &mut Vec<(String, TokenStream, usize)>) {
+    for item in items {
+        let ident = match item {
+            Item::Const(obj) => &obj.ident,
+            Item::Enum(obj) => &obj.ident,
+            Item::Fn(obj) => &obj.sig.ident,
+            Item::Static(obj) => &obj.ident,
+            Item::Struct(obj) => &obj.ident,
+            Item::Trait(obj) => &obj.ident,
+            Item::Type(obj) => &obj.ident,
+            Item::Union(obj) => &obj.ident,
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, found);
+                }
+                continue;
+            }
+            Item::Impl(block) => {
+                for item in &block.items {
+                    if let ImplItem::Fn(function) = item {
+                        let ident = &function.sig.ident;
+                        let line = ident.span().start().line;
+                        found.push((ident.to_string(), item.to_token_stream(), line));
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        found.push((
+            ident.to_string(),
+            item.to_token_stream(),
+            ident.span().start().line,
+        ));
+    }
+}
+
+/// Groups of duplicate items in the given source files; `workspace_root` is
+/// used to report file names relative to it, just like rustc does
+pub fn find(files: &[impl AsRef<Path>], workspace_root: &Path) -> Vec<Vec<Duplicate>> {
+    let mut groups: HashMap<u64, Vec<Duplicate>> = HashMap::new();
+
+    for file in files {
+        let file = file.as_ref();
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+        let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+
+        let mut items = Vec::new();
+        collect_items(&parsed.items, &mut items);
+        for (ident, tokens, line) in items {
+            let mut normalized = String::new();
+            if normalize(tokens, &ident, &mut normalized) < MIN_TOKENS {
+                continue;
+            }
+            groups
+                .entry(content_hash(normalized.as_bytes()))
+                .or_default()
+                .push(Duplicate {
+                    file_name: file_name.to_string_lossy().into_owned(),
+                    line,
+                    ident,
+                });
+        }
+    }
+
+    let mut groups: Vec<_> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort_by(|a, b| (&a[0].file_name, a[0].line).cmp(&(&b[0].file_name, b[0].line)));
+
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn normalized(src: &str, ident: &str) -> String {
+        let mut normalized = String::new();
+        normalize(src.parse().unwrap(), ident, &mut normalized);
+        normalized
+    }
+
+    #[test]
+    fn normalization() {
+        assert_eq!(
+            normalized("/// Docs\n#[inline] fn foo(x: u32) -> u32 { x + 1 }", "foo"),
+            normalized("fn bar(x: u32) -> u32 { x + 1 }", "bar")
+        );
+        assert_ne!(
+            normalized("fn foo(x: u32) -> u32 { x + 1 }", "foo"),
+            normalized("fn bar(x: u32) -> u32 { x + 2 }", "bar")
+        );
+    }
+}
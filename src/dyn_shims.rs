@@ -0,0 +1,183 @@
+//! An experimental syntactic analysis that finds "dyn-compatibility shims":
+//! impls that forward a trait to a trait object of itself, such as
+//! `impl Foo for Box<dyn Foo>`. rustc never reports trait impls as unused, but
+//! when no `dyn Foo` is mentioned anywhere else in the workspace, nothing can
+//! rely on these impls (unless a downstream crate does, hence experimental).
+
+use std::{collections::HashSet, path::Path};
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{spanned::Spanned, Item, ItemImpl};
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    FileResolutionOptions,
+};
+
+/// An impl of a trait for a trait object of that same trait
+struct Shim<'a> {
+    /// `Trait for SelfTy`, used to find the impl again when removing it
+    id: String,
+    trait_name: String,
+    item: &'a ItemImpl,
+}
+
+/// Whether the tokens contain `dyn <name>`
+fn mentions_dyn(tokens: TokenStream, name: &str) -> bool {
+    let mut after_dyn = false;
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) if after_dyn && ident == name => return true,
+            TokenTree::Ident(ident) => after_dyn = ident == "dyn",
+            TokenTree::Group(group) => {
+                if mentions_dyn(group.stream(), name) {
+                    return true;
+                }
+                after_dyn = false;
+            }
+            TokenTree::Punct(_) | TokenTree::Literal(_) => after_dyn = false,
+        }
+    }
+
+    false
+}
+
+fn as_shim(item: &ItemImpl) -> Option<Shim<'_>> {
+    let (_, path, _) = item.trait_.as_ref()?;
+    let trait_name = path.segments.last()?.ident.to_string();
+    if !mentions_dyn(item.self_ty.to_token_stream(), &trait_name) {
+        return None;
+    }
+
+    Some(Shim {
+        id: format!(
+            "{} for {}",
+            path.to_token_stream().to_string().replace(' ', ""),
+            item.self_ty.to_token_stream().to_string().replace(' ', "")
+        ),
+        trait_name,
+        item,
+    })
+}
+
+/// Collects the shims, and the other items (which may mention `dyn Trait`)
+fn collect_items<'a>(items: &'a [Item], shims: &mut Vec<Shim<'a>>, others: &mut Vec<&'a Item>) {
+    for item in items {
+        match item {
+            Item::Impl(block) => match as_shim(block) {
+                Some(shim) => shims.push(shim),
+                None => others.push(item),
+            },
+            Item::Mod(module) => match &module.content {
+                Some((_, items)) => collect_items(items, shims, others),
+                None => others.push(item),
+            },
+            _ => others.push(item),
+        }
+    }
+}
+
+/// Finds the span of the shim identified by `id`, as given out in the
+/// diagnostics of [`get_unused_shims`]
+pub fn shim_spans(file: &syn::File, id: &str) -> Vec<Span> {
+    let mut shims = Vec::new();
+    collect_items(&file.items, &mut shims, &mut Vec::new());
+
+    shims
+        .iter()
+        .filter(|shim| shim.id == id)
+        .map(|shim| shim.item.span())
+        .collect()
+}
+
+/// Finds the shims of traits that are not used as trait objects anywhere in
+/// the given source files; `workspace_root` is used to report file names
+/// relative to it, just like rustc does
+pub fn get_unused_shims(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+) -> Vec<UnusedDiagnostic> {
+    let parsed: Vec<_> = files
+        .iter()
+        .filter_map(|file| {
+            let file = file.as_ref();
+            let src = std::fs::read_to_string(file).ok()?;
+            let parsed = syn::parse_file(&src).ok()?;
+            let file_name = file
+                .strip_prefix(workspace_root)
+                .unwrap_or(file)
+                .to_string_lossy()
+                .into_owned();
+            Some((file_name, src, parsed))
+        })
+        .collect();
+
+    let mut shims = Vec::new();
+    let mut others = Vec::new();
+    for (file_name, src, parsed) in &parsed {
+        let mut found = Vec::new();
+        collect_items(&parsed.items, &mut found, &mut others);
+        shims.extend(found.into_iter().map(|shim| (file_name, src, shim)));
+    }
+
+    let trait_names: HashSet<_> = shims.iter().map(|(_, _, shim)| &shim.trait_name).collect();
+    let used_as_object: HashSet<_> = trait_names
+        .into_iter()
+        .filter(|name| {
+            others
+                .iter()
+                .any(|item| mentions_dyn(item.to_token_stream(), name))
+        })
+        .collect();
+
+    shims
+        .iter()
+        .filter(|(file_name, _, shim)| {
+            !used_as_object.contains(&shim.trait_name) && file_resolution.is_included(file_name)
+        })
+        .map(|(file_name, src, shim)| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::DynShim,
+            span: synthetic_span(file_name, src, shim.item.impl_token.span),
+            ident: shim.id.clone(),
+            suggestion: None,
+            configs: Vec::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shims(src: &str) -> Vec<String> {
+        let file = syn::parse_file(src).unwrap();
+        let mut shims = Vec::new();
+        collect_items(&file.items, &mut shims, &mut Vec::new());
+        shims.into_iter().map(|shim| shim.id).collect()
+    }
+
+    #[test]
+    fn finds_shims() {
+        assert_eq!(
+            shims("impl<T: Foo + ?Sized> Foo for Box<T> {} impl Foo for Box<dyn Foo> {}"),
+            vec!["Foo for Box<dynFoo>"]
+        );
+        assert_eq!(
+            shims("mod m { impl<'a> Foo for &'a (dyn Foo + Send) {} }"),
+            vec!["Foo for &'a(dynFoo+Send)"]
+        );
+        assert!(shims("impl Foo for Box<dyn Bar> {}").is_empty());
+    }
+
+    #[test]
+    fn dyn_mentions() {
+        let tokens = |src: &str| src.parse::<TokenStream>().unwrap();
+        assert!(mentions_dyn(tokens("fn f(x: Vec<Box<dyn Foo>>) {}"), "Foo"));
+        assert!(!mentions_dyn(
+            tokens("fn f(x: Box<dyn Bar>, y: Foo) {}"),
+            "Foo"
+        ));
+    }
+}
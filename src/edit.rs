@@ -0,0 +1,173 @@
+//! Byte-span edits of source files, as used to remove the unused code. They
+//! are independent of the analysis, so they can be used (and tested) on their
+//! own.
+
+use std::{ops::Range, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::cauterize::Change;
+
+/// Replaces the bytes in `range` with `replacement`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: Vec<u8>,
+}
+
+impl TextEdit {
+    pub fn delete(range: Range<usize>) -> Self {
+        TextEdit {
+            range,
+            replacement: Vec::new(),
+        }
+    }
+
+    pub fn replace(range: Range<usize>, replacement: impl Into<Vec<u8>>) -> Self {
+        TextEdit {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EditError {
+    #[error("edit {range:?} is out of bounds for a source of {len} bytes")]
+    OutOfBounds { range: Range<usize>, len: usize },
+    #[error("edits {0:?} and {1:?} overlap")]
+    Overlapping(Range<usize>, Range<usize>),
+}
+
+/// Collects edits of a source file, and applies them all at once, so that the
+/// ranges of all edits refer to the original source
+pub struct SourceFileEditor {
+    src: Vec<u8>,
+    edits: Vec<TextEdit>,
+}
+
+impl SourceFileEditor {
+    pub fn new(src: impl Into<Vec<u8>>) -> Self {
+        SourceFileEditor {
+            src: src.into(),
+            edits: Vec::new(),
+        }
+    }
+
+    pub fn edit(&mut self, edit: TextEdit) -> &mut Self {
+        self.edits.push(edit);
+        self
+    }
+
+    pub fn original(&self) -> &[u8] {
+        &self.src
+    }
+
+    /// The edits sorted by position, if they are all in bounds and don't
+    /// overlap; empty ranges may touch other edits
+    fn sorted_edits(&self) -> Result<Vec<&TextEdit>, EditError> {
+        let mut edits: Vec<_> = self.edits.iter().collect();
+        edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+
+        for edit in &edits {
+            if edit.range.start > edit.range.end || edit.range.end > self.src.len() {
+                return Err(EditError::OutOfBounds {
+                    range: edit.range.clone(),
+                    len: self.src.len(),
+                });
+            }
+        }
+        for pair in edits.windows(2) {
+            if pair[0].range.end > pair[1].range.start {
+                return Err(EditError::Overlapping(
+                    pair[0].range.clone(),
+                    pair[1].range.clone(),
+                ));
+            }
+        }
+
+        Ok(edits)
+    }
+
+    pub fn validate(&self) -> Result<(), EditError> {
+        self.sorted_edits().map(|_| ())
+    }
+
+    /// The source with all edits applied
+    pub fn apply(&self) -> Result<Vec<u8>, EditError> {
+        let mut result = Vec::with_capacity(self.src.len());
+        let mut pos = 0;
+        for edit in self.sorted_edits()? {
+            result.extend_from_slice(&self.src[pos..edit.range.start]);
+            result.extend_from_slice(&edit.replacement);
+            pos = edit.range.end;
+        }
+        result.extend_from_slice(&self.src[pos..]);
+
+        Ok(result)
+    }
+
+    /// The changed lines, prefixed with `-` or `+`
+    pub fn diff(&self) -> Result<String, EditError> {
+        let proposed = self.apply()?;
+        let left = String::from_utf8_lossy(&self.src);
+        let right = String::from_utf8_lossy(&proposed);
+
+        let mut diff = String::new();
+        for line in diff::lines(&left, &right) {
+            match line {
+                diff::Result::Left(line) => diff += &format!("-{line}\n"),
+                diff::Result::Right(line) => diff += &format!("+{line}\n"),
+                diff::Result::Both(_, _) => {}
+            }
+        }
+
+        Ok(diff)
+    }
+
+    /// The edits as a [`Change`] of `file_name`, as used by the rest of the tool
+    pub fn into_change(self, file_name: PathBuf) -> Result<Change, EditError> {
+        let proposed = self.apply()?;
+
+        Ok(Change::new(file_name, self.src, proposed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn applies_edits_in_any_order() {
+        let mut editor = SourceFileEditor::new("fn foo() {}\nfn bar() {}\n");
+        editor
+            .edit(TextEdit::replace(15..18, "baz"))
+            .edit(TextEdit::delete(0..12))
+            .edit(TextEdit::replace(24..24, "// end\n"));
+
+        assert_eq!(editor.apply().unwrap(), b"fn baz() {}\n// end\n");
+        assert_eq!(
+            editor.diff().unwrap(),
+            "-fn foo() {}\n-fn bar() {}\n+fn baz() {}\n+// end\n"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_edits() {
+        let mut editor = SourceFileEditor::new("fn foo() {}");
+        editor
+            .edit(TextEdit::delete(3..6))
+            .edit(TextEdit::delete(5..8));
+        assert_eq!(editor.validate(), Err(EditError::Overlapping(3..6, 5..8)));
+
+        let mut editor = SourceFileEditor::new("fn foo() {}");
+        editor.edit(TextEdit::delete(3..20));
+        assert_eq!(
+            editor.validate(),
+            Err(EditError::OutOfBounds {
+                range: 3..20,
+                len: 11
+            })
+        );
+    }
+}
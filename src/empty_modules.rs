@@ -0,0 +1,241 @@
+//! Modules emptied by the removals, with `--prune-empty-modules`. Once all the
+//! items of a module are removed, whatever kind they were found as, the
+//! `mod util {}` shell left behind goes as well; for a `mod util;`
+//! declaration, the file is deleted along with the declaration. Removing a
+//! module may empty the module around it, which then goes too. Unlike the
+//! `MODULE` kind, this looks at the result of the removals rather than at the
+//! findings.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Item};
+
+use crate::{
+    cauterize::{self, Change},
+    modules,
+};
+
+/// The paths of the inline modules among the items, with the spans of those
+/// without any items
+fn inline_modules(items: &[Item], prefix: &str, paths: &mut Vec<(String, Option<Span>)>) {
+    for item in items {
+        let Item::Mod(module) = item else {
+            continue;
+        };
+        let Some((_, items)) = &module.content else {
+            continue;
+        };
+        let path = format!("{prefix}{}", module.ident);
+        let empty = items.is_empty().then(|| module.span());
+        inline_modules(items, &format!("{path}::"), paths);
+        paths.push((path, empty));
+    }
+}
+
+/// The spans of the inline modules that had items in `original`, but no
+/// longer have any in `current`
+fn emptied_inline_modules(original: &syn::File, current: &syn::File) -> Vec<Span> {
+    let mut had_items = Vec::new();
+    inline_modules(&original.items, "", &mut had_items);
+    let had_items: HashSet<_> = had_items
+        .into_iter()
+        .filter(|(_, empty)| empty.is_none())
+        .map(|(path, _)| path)
+        .collect();
+
+    let mut modules = Vec::new();
+    inline_modules(&current.items, "", &mut modules);
+    modules
+        .into_iter()
+        .filter(|(path, _)| had_items.contains(path))
+        .filter_map(|(_, empty)| empty)
+        .collect()
+}
+
+/// The `mod` declarations among the items whose files are in `emptied`, by
+/// their canonical paths: their spans, along with the files
+fn declarations(
+    items: &[Item],
+    dir: &Path,
+    emptied: &HashSet<PathBuf>,
+    found: &mut Vec<(Span, PathBuf)>,
+) {
+    for item in items {
+        let Item::Mod(module) = item else {
+            continue;
+        };
+        let name = module.ident.to_string();
+        match &module.content {
+            Some((_, items)) => declarations(items, &dir.join(name), emptied, found),
+            // Modules with a `#[path]` are left alone
+            None if module.attrs.iter().any(|attr| attr.path().is_ident("path")) => {}
+            None => {
+                let file = modules::declared_file(dir, &name)
+                    .and_then(|file| file.canonicalize().ok())
+                    .filter(|file| emptied.contains(file));
+                found.extend(file.map(|file| (module.span(), file)));
+            }
+        }
+    }
+}
+
+fn parse(src: &[u8]) -> Option<syn::File> {
+    syn::parse_file(&String::from_utf8_lossy(src)).ok()
+}
+
+/// Removes the modules the changes leave without any items, deleting the
+/// files of those that are out-of-line; `sources` are the files that may
+/// declare them, and `name` names the changes to the files that weren't
+/// changed yet
+pub fn prune(
+    changes: &mut Vec<Change>,
+    sources: &[PathBuf],
+    crate_roots: &HashSet<PathBuf>,
+    name: impl Fn(&Path) -> PathBuf,
+    tidy: bool,
+) {
+    loop {
+        let mut pruned = false;
+
+        for change in changes.iter_mut().filter(|change| !change.is_removal()) {
+            let (Some(original), Some(current)) = (
+                parse(change.original_content()),
+                parse(change.proposed_content()),
+            ) else {
+                continue;
+            };
+            let spans = emptied_inline_modules(&original, &current);
+            if spans.is_empty() {
+                continue;
+            }
+            let proposed = cauterize::remove_spans(change.proposed_content(), spans, tidy);
+            *change = Change::new(
+                change.file_name().to_path_buf(),
+                change.original_content().to_vec(),
+                proposed,
+            )
+            .with_category(change.category());
+            pruned = true;
+        }
+
+        // Files left without items, though they had some
+        let emptied: HashSet<_> = changes
+            .iter()
+            .filter(|change| !change.is_removal())
+            .filter(|change| {
+                let has_items = |src| parse(src).is_none_or(|file| !file.items.is_empty());
+                has_items(change.original_content()) && !has_items(change.proposed_content())
+            })
+            .filter_map(|change| change.file_name().canonicalize().ok())
+            .collect();
+        if emptied.is_empty() && !pruned {
+            break;
+        }
+
+        let mut declared = HashSet::new();
+        for source in sources {
+            let Ok(path) = source.canonicalize() else {
+                continue;
+            };
+            let index = changes
+                .iter()
+                .position(|change| change.file_name().canonicalize().ok().as_ref() == Some(&path));
+            if index.is_some_and(|index| changes[index].is_removal()) {
+                continue;
+            }
+            let current = match index {
+                Some(index) => changes[index].proposed_content().to_vec(),
+                None => fs::read(source).unwrap_or_default(),
+            };
+            let (Some(parsed), Some(dir)) = (
+                parse(&current),
+                modules::module_dir(source, crate_roots.contains(&path)),
+            ) else {
+                continue;
+            };
+
+            let mut found = Vec::new();
+            declarations(&parsed.items, &dir, &emptied, &mut found);
+            if found.is_empty() {
+                continue;
+            }
+            let (spans, files): (Vec<_>, Vec<_>) = found.into_iter().unzip();
+            declared.extend(files);
+            let proposed = cauterize::remove_spans(&current, spans, tidy);
+            match index {
+                Some(index) => {
+                    let change = &changes[index];
+                    changes[index] = Change::new(
+                        change.file_name().to_path_buf(),
+                        change.original_content().to_vec(),
+                        proposed,
+                    )
+                    .with_category(change.category());
+                }
+                None => changes.push(Change::new(name(source), current, proposed)),
+            }
+        }
+
+        // Only the files whose declarations are gone are deleted
+        let mut deleted = false;
+        for change in changes.iter_mut() {
+            let removed = change
+                .file_name()
+                .canonicalize()
+                .is_ok_and(|file| declared.contains(&file) && !change.is_removal());
+            if removed {
+                let file_name = change.file_name().to_path_buf();
+                *change = Change::removal(file_name, change.original_content().to_vec())
+                    .with_category(change.category());
+                deleted = true;
+            }
+        }
+        if !pruned && !deleted {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_support::TempCrate;
+
+    use super::*;
+
+    #[test]
+    fn prunes_emptied_modules() {
+        let lib_rs = "mod util;\nmod outer {\n    mod inner {\n        fn a() {}\n    }\n}\n\
+                      mod kept {}\npub fn f() {}\n";
+        let temp_crate =
+            TempCrate::with_files([("src/lib.rs", lib_rs), ("src/util.rs", "fn b() {}\n")])
+                .unwrap();
+        let src = temp_crate.root().join("src");
+        let (lib, util) = (src.join("lib.rs"), src.join("util.rs"));
+        let removed =
+            "mod util;\nmod outer {\n    mod inner {\n    }\n}\nmod kept {}\npub fn f() {}\n";
+        let mut changes = vec![
+            Change::new(lib.clone(), lib_rs.into(), removed.into()),
+            Change::new(util.clone(), b"fn b() {}\n".to_vec(), Vec::new()),
+        ];
+
+        let crate_roots = HashSet::from([lib.canonicalize().unwrap()]);
+        prune(
+            &mut changes,
+            &[lib, util],
+            &crate_roots,
+            Path::to_path_buf,
+            false,
+        );
+        // Modules that were empty to begin with are left alone
+        assert_eq!(
+            changes[0].proposed_content(),
+            b"mod kept {}\npub fn f() {}\n"
+        );
+        assert!(changes[1].is_removal());
+    }
+}
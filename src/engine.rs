@@ -0,0 +1,180 @@
+//! How unused code is found. By default, cargo checks the workspace, and the
+//! findings are rustc's. Where compiling is impossible, `--engine syntax` goes
+//! by the names in the sources instead: a private item whose name isn't
+//! mentioned anywhere else in its package is considered unused. Names aren't
+//! resolved, so such findings are approximate, both ways.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use syn::{Item, Visibility};
+use thiserror::Error;
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    variants::Mentions,
+    FileResolutionOptions,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Check the workspace with cargo, and use rustc's findings
+    #[default]
+    Cargo,
+    /// Only parse the sources, without compiling anything
+    Syntax,
+}
+
+impl FromStr for Engine {
+    type Err = UnsupportedEngine;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cargo" => Ok(Engine::Cargo),
+            "syntax" => Ok(Engine::Syntax),
+            _ => Err(UnsupportedEngine),
+        }
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Engine::Cargo => write!(f, "cargo"),
+            Engine::Syntax => write!(f, "syntax"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported engine, pick any of: cargo, syntax")]
+pub struct UnsupportedEngine;
+
+/// The kind and name rustc would report an item by, for the private items
+/// without attributes other than doc comments and derives, as the others
+/// may be used in ways the names don't tell (like `#[no_mangle]` or
+/// `#[test]`)
+fn private_item(item: &Item) -> Option<(UnusedDiagnosticKind, &syn::Ident)> {
+    use UnusedDiagnosticKind::*;
+    let (kind, vis, attrs, ident) = match item {
+        Item::Const(obj) => (Constant, &obj.vis, &obj.attrs, &obj.ident),
+        Item::Enum(obj) => (Enum, &obj.vis, &obj.attrs, &obj.ident),
+        Item::Fn(obj) => (Function, &obj.vis, &obj.attrs, &obj.sig.ident),
+        Item::Static(obj) => (Static, &obj.vis, &obj.attrs, &obj.ident),
+        Item::Struct(obj) => (Struct, &obj.vis, &obj.attrs, &obj.ident),
+        Item::Type(obj) => (TypeAlias, &obj.vis, &obj.attrs, &obj.ident),
+        Item::Union(obj) => (Union, &obj.vis, &obj.attrs, &obj.ident),
+        _ => return None,
+    };
+    let plain = attrs
+        .iter()
+        .all(|attr| attr.path().is_ident("doc") || attr.path().is_ident("derive"));
+
+    (matches!(vis, Visibility::Inherited) && plain && ident != "main").then_some((kind, ident))
+}
+
+fn collect_items<'a>(items: &'a [Item], found: &mut Vec<(UnusedDiagnosticKind, &'a syn::Ident)>) {
+    for item in items {
+        match item {
+            // Modules under a `#[cfg]` may not even be compiled
+            Item::Mod(module) if module.attrs.iter().all(|attr| attr.path().is_ident("doc")) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, found);
+                }
+            }
+            _ => found.extend(private_item(item)),
+        }
+    }
+}
+
+/// The private items in the source files of each package that are mentioned
+/// nowhere in the package but in their definition
+pub fn obviously_unused(
+    packages: &[Vec<PathBuf>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+    kinds: &[UnusedDiagnosticKind],
+) -> Vec<UnusedDiagnostic> {
+    let mut unused = Vec::new();
+    // Packages may share source files
+    let mut seen = HashSet::new();
+    for files in packages {
+        let mentions = Mentions::new(files);
+        for file in files {
+            let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+            let file_name = file_name.to_string_lossy();
+            if !file_resolution.is_included(&file_name) || !seen.insert(file) {
+                continue;
+            }
+            let Ok(src) = std::fs::read_to_string(file) else {
+                continue;
+            };
+            let Ok(parsed) = syn::parse_file(&src) else {
+                continue;
+            };
+
+            let mut found = Vec::new();
+            collect_items(&parsed.items, &mut found);
+            unused.extend(
+                found
+                    .into_iter()
+                    .filter(|(kind, _)| {
+                        (kinds.is_empty() && kind.is_default()) || kinds.contains(kind)
+                    })
+                    .filter(|(_, ident)| mentions.of(&ident.to_string()) <= 1)
+                    .map(|(kind, ident)| UnusedDiagnostic {
+                        kind,
+                        ident: ident.to_string(),
+                        span: synthetic_span(&file_name, &src, ident.span()),
+                        suggestion: None,
+                        configs: Vec::new(),
+                    }),
+            );
+        }
+    }
+
+    unused
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_support::TempCrate;
+
+    use super::*;
+
+    #[test]
+    fn finds_unmentioned_items() {
+        let temp_crate = TempCrate::with_files([
+            (
+                "src/lib.rs",
+                "mod other;\nfn unused() {}\nfn used() {}\npub fn api() { used() }\n\
+                 #[no_mangle]\nfn exported() {}\n#[cfg(test)]\nmod tests { fn helper() {} }\n",
+            ),
+            ("src/other.rs", "const LIMIT: u8 = 1;\nstruct Shared;\n"),
+            ("src/main.rs", "fn main() { let _ = Shared; }\n"),
+            ("other/src/lib.rs", "fn api() {}\n"),
+        ])
+        .unwrap();
+        let root = temp_crate.root();
+        let files = ["lib.rs", "other.rs", "main.rs"].map(|file| root.join("src").join(file));
+        let packages = [files.to_vec(), vec![root.join("other/src/lib.rs")]];
+        let all = FileResolutionOptions::AllBut(Vec::new());
+
+        let found: Vec<_> = obviously_unused(&packages, root, &all, &[])
+            .into_iter()
+            .map(|diagnostic| (diagnostic.ident, diagnostic.span.file_name))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("unused".to_string(), "src/lib.rs".to_string()),
+                ("LIMIT".to_string(), "src/other.rs".to_string()),
+                ("api".to_string(), "other/src/lib.rs".to_string()),
+            ]
+        );
+    }
+}
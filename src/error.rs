@@ -14,4 +14,87 @@ pub enum Error {
 
     #[error("invalid command line arguments: {0}")]
     Args(&'static str),
+
+    #[error("invalid {table} configuration: {source}")]
+    Config {
+        table: &'static str,
+        source: serde_json::Error,
+    },
+
+    #[error("changes are not idempotent: {0}")]
+    NotIdempotent(String),
+
+    #[error("{0}")]
+    InvalidDuration(#[from] crate::unused::InvalidDuration),
+
+    #[error("{0}")]
+    Toolchain(String),
+
+    #[error("only some of the changes were applied")]
+    PartiallyApplied,
+
+    #[error("{0} findings appeared since the old report")]
+    NewFindings(usize),
+
+    #[error("none of the changes were applied")]
+    ApplyRefused,
+}
+
+/// The exit codes of `cargo minify`, along with what they mean
+pub const EXIT_CODES: &[(i32, &str)] = &[
+    (0, "success"),
+    (1, "invalid command line arguments or configuration"),
+    (2, "a file is not valid UTF-8"),
+    (3, "an IO error occurred"),
+    (
+        4,
+        "the changes are not idempotent (with --check-idempotent)",
+    ),
+    (
+        5,
+        "the toolchain pinned by rust-toolchain.toml is not installed",
+    ),
+    (
+        6,
+        "only some of the changes were applied, as others were skipped or failed",
+    ),
+    (7, "findings appeared since the old report (with compare)"),
+    (
+        8,
+        "none of the changes were applied, as the working directory or changes didn't allow it",
+    ),
+];
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => 3,
+            Error::Utf8(_) => 2,
+            Error::CommandLine(_)
+            | Error::Args(_)
+            | Error::Config { .. }
+            | Error::InvalidDuration(_) => 1,
+            Error::NotIdempotent(_) => 4,
+            Error::Toolchain(_) => 5,
+            Error::PartiallyApplied => 6,
+            Error::NewFindings(_) => 7,
+            Error::ApplyRefused => 8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn refusals_exit_with_their_own_code() {
+        let temp_crate = TempCrate::new("pub fn used() {}\nfn unused() {}\n").unwrap();
+        let err = crate::execute(&temp_crate.args(&["--apply", "--quiet"])).unwrap_err();
+        assert!(matches!(err, Error::ApplyRefused));
+        assert_eq!(err.exit_code(), 8);
+        assert!(EXIT_CODES.iter().any(|(code, _)| *code == 8));
+        assert!(temp_crate.read("src/lib.rs").unwrap().contains("fn unused"));
+    }
 }
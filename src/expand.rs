@@ -0,0 +1,267 @@
+//! Items that only seem used because derived code mentions them, with
+//! `--expand`. rustc counts a mention in the impl of a derive as a use, so
+//! an item that only a `#[serde(default = "..")]`, or the `Default` of some
+//! field, keeps around is never reported as dead. `cargo expand` prints each
+//! crate with its macros expanded, in which these impls are marked
+//! `#[automatically_derived]`; private items that nothing but such impls of
+//! other types mentions are reported as ghost usages. Nothing is removed, as
+//! the derives still need them.
+//!
+//! Like `--engine syntax`, this matches names without resolving them, so an
+//! item counts as used whenever anything mentions an item by the same name.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use cargo_metadata::{Metadata, Target};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Item, Type, Visibility};
+
+use crate::{resolver, toolchain};
+
+/// A private item that only derived impls of other types mention
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GhostUsage {
+    pub package: String,
+    pub ident: String,
+    /// The derived impls mentioning it, as in `Default for Config`
+    pub derives: Vec<String>,
+}
+
+/// An item of the expanded crate, or of one of its modules
+#[derive(Default)]
+struct Unit {
+    /// The name of the item, if it's a private definition
+    defines: Option<String>,
+    /// The type an impl is for
+    self_ty: Option<String>,
+    /// The trait and type of a derived impl, as in `Default for Config`
+    derived: Option<String>,
+    mentions: HashSet<String>,
+}
+
+fn collect_idents(tokens: TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+fn last_segment(path: &syn::Path) -> Option<String> {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+}
+
+fn collect_units(items: &[Item], units: &mut Vec<Unit>) {
+    for item in items {
+        let mut unit = Unit::default();
+        let (vis, ident) = match item {
+            Item::Const(item) => (&item.vis, &item.ident),
+            Item::Enum(item) => (&item.vis, &item.ident),
+            Item::Fn(item) => (&item.vis, &item.sig.ident),
+            Item::Static(item) => (&item.vis, &item.ident),
+            Item::Struct(item) => (&item.vis, &item.ident),
+            Item::Type(item) => (&item.vis, &item.ident),
+            Item::Union(item) => (&item.vis, &item.ident),
+            Item::Mod(item) => {
+                if let Some((_, items)) = &item.content {
+                    collect_units(items, units);
+                    continue;
+                }
+                (&item.vis, &item.ident)
+            }
+            Item::Impl(item) => {
+                if let Type::Path(ty) = &*item.self_ty {
+                    unit.self_ty = last_segment(&ty.path);
+                }
+                let derived = item
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("automatically_derived"));
+                if let (true, Some((_, trait_, _))) = (derived, &item.trait_) {
+                    let trait_ = last_segment(trait_).unwrap_or_default();
+                    let self_ty = unit.self_ty.clone().unwrap_or_else(|| "_".to_string());
+                    unit.derived = Some(format!("{trait_} for {self_ty}"));
+                }
+                collect_idents(item.to_token_stream(), &mut unit.mentions);
+                units.push(unit);
+                continue;
+            }
+            _ => {
+                collect_idents(item.to_token_stream(), &mut unit.mentions);
+                units.push(unit);
+                continue;
+            }
+        };
+        if let Visibility::Inherited = vis {
+            unit.defines = Some(ident.to_string());
+        }
+        collect_idents(item.to_token_stream(), &mut unit.mentions);
+        units.push(unit);
+    }
+}
+
+/// The private items of the expanded crate that only derived impls of other
+/// types mention, along with these impls
+fn ghost_usages(expanded: &syn::File) -> BTreeMap<String, Vec<String>> {
+    let mut units = Vec::new();
+    collect_units(&expanded.items, &mut units);
+
+    let mut ghosts = BTreeMap::new();
+    for name in units.iter().filter_map(|unit| unit.defines.as_ref()) {
+        let mut derives = BTreeSet::new();
+        let mut used = false;
+        for unit in &units {
+            let own = unit.defines.as_ref() == Some(name) || unit.self_ty.as_ref() == Some(name);
+            if own || !unit.mentions.contains(name) {
+                continue;
+            }
+            match &unit.derived {
+                Some(derived) => {
+                    derives.insert(derived.clone());
+                }
+                None => used = true,
+            }
+        }
+        if !used && !derives.is_empty() {
+            ghosts.insert(name.clone(), derives.into_iter().collect());
+        }
+    }
+
+    ghosts
+}
+
+/// The source of the target with its macros expanded, by `cargo expand`
+fn expand(
+    workspace_root: &Path,
+    manifest_path: &Path,
+    package: &str,
+    target: &Target,
+) -> io::Result<String> {
+    let mut command = Command::new("cargo");
+    if let Some(toolchain) = toolchain::pinned(workspace_root) {
+        toolchain.apply(&mut command);
+    }
+    command.args([
+        "expand",
+        "--color",
+        "never",
+        "--package",
+        package,
+        "--manifest-path",
+    ]);
+    command.arg(manifest_path);
+    match resolver::is_library(target) {
+        true => command.arg("--lib"),
+        false => command.args(["--bin", &target.name]),
+    };
+
+    let output = command.stderr(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`cargo expand` failed for `{}` of `{package}`, is cargo-expand installed?",
+            target.name
+        )));
+    }
+
+    String::from_utf8(output.stdout).map_err(io::Error::other)
+}
+
+/// The ghost usages in the libraries and binaries among the targets, per
+/// package
+pub fn find<'a>(
+    metadata: &Metadata,
+    targets: impl IntoIterator<Item = &'a Target>,
+) -> io::Result<Vec<GhostUsage>> {
+    let mut found: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+    for target in targets {
+        let is_bin = target.kind.iter().any(|kind| kind == "bin");
+        if !resolver::is_library(target) && !is_bin {
+            continue;
+        }
+        let Some(package) = resolver::package_of(metadata, target) else {
+            continue;
+        };
+
+        let expanded = expand(
+            metadata.workspace_root.as_std_path(),
+            package.manifest_path.as_std_path(),
+            &package.name,
+            target,
+        )?;
+        let Ok(expanded) = syn::parse_file(&expanded) else {
+            continue;
+        };
+        for (ident, derives) in ghost_usages(&expanded) {
+            found
+                .entry((package.name.clone(), ident))
+                .or_default()
+                .extend(derives);
+        }
+    }
+
+    Ok(found
+        .into_iter()
+        .map(|((package, ident), derives)| GhostUsage {
+            package,
+            ident,
+            derives: derives.into_iter().collect(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_ghost_usages() {
+        let expanded = syn::parse_quote! {
+            struct Config { port: u16, name: Name }
+            struct Name(String);
+            #[automatically_derived]
+            impl ::core::default::Default for Config {
+                fn default() -> Config { Config { port: default_port(), name: Name(fallback()) } }
+            }
+            #[automatically_derived]
+            impl ::core::clone::Clone for Name {
+                fn clone(&self) -> Name { Name(helper()) }
+            }
+            fn default_port() -> u16 { default_port() }
+            fn fallback() -> String { String::new() }
+            fn helper() -> String { fallback() }
+            mod inner {
+                fn only_derived() {}
+                #[automatically_derived]
+                impl Clone for super::Name { fn clone(&self) -> Self { only_derived() } }
+            }
+        };
+
+        let ghosts = ghost_usages(&expanded);
+        assert_eq!(
+            ghosts.into_iter().collect::<Vec<_>>(),
+            [
+                (
+                    "default_port".to_string(),
+                    vec!["Default for Config".to_string()]
+                ),
+                ("helper".to_string(), vec!["Clone for Name".to_string()]),
+                (
+                    "only_derived".to_string(),
+                    vec!["Clone for Name".to_string()]
+                ),
+            ]
+        );
+    }
+}
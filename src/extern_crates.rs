@@ -0,0 +1,143 @@
+//! Unused `extern crate` declarations, as reported by the allow-by-default
+//! `unused_extern_crates` lint, which is enabled when they are asked for.
+//! rustc never reports declarations with `#[macro_use]` this way; instead,
+//! `unused_imports` reports the attribute once none of the crate's macros are
+//! used. Only the attribute is removed then, as the crate may still be linked
+//! for its side effects, or used by its path; once it's gone, rustc reports
+//! the declaration itself if it's unused.
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Item, ItemExternCrate};
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The name of an unused `#[macro_use]` attribute
+const MACRO_USE: &str = "#[macro_use]";
+
+/// The findings of an unused `extern crate` or `#[macro_use]` diagnostic; the
+/// name is the declaration as written, like `alloc as a`, or `#[macro_use]`
+/// for the attribute
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    let is_declaration =
+        code.code == "unused_extern_crates" && diagnostic.message == "unused extern crate";
+    let is_macro_use = code.code == "unused_imports"
+        && diagnostic.message == format!("unused `{MACRO_USE}` import");
+    if !is_declaration && !is_macro_use {
+        return None;
+    }
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .filter_map(|span| {
+            let ident = if is_macro_use {
+                MACRO_USE.to_string()
+            } else {
+                declared_name(&span_text(span))?
+            };
+            Some(UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::ExternCrate,
+                ident,
+                span: span.clone(),
+                suggestion: None,
+                configs: Vec::new(),
+            })
+        })
+        .collect();
+
+    Some(found)
+}
+
+/// The crate of a declaration as written, along with its rename
+fn name_of(declaration: &ItemExternCrate) -> String {
+    match &declaration.rename {
+        Some((_, rename)) => format!("{} as {}", declaration.ident, rename),
+        None => declaration.ident.to_string(),
+    }
+}
+
+fn declared_name(declaration: &str) -> Option<String> {
+    syn::parse_str(declaration)
+        .ok()
+        .map(|declaration| name_of(&declaration))
+}
+
+fn collect_spans(items: &[Item], line: usize, ident: &str, spans: &mut Vec<Span>) {
+    for item in items {
+        match item {
+            Item::ExternCrate(declaration) if ident == MACRO_USE => spans.extend(
+                declaration
+                    .attrs
+                    .iter()
+                    .filter(|attr| attr.path().is_ident("macro_use"))
+                    .map(Spanned::span)
+                    .filter(|span| span.start().line == line),
+            ),
+            Item::ExternCrate(declaration)
+                if declaration.extern_token.span.start().line == line
+                    && name_of(declaration) == ident =>
+            {
+                // The attributes go along with the declaration
+                spans.push(declaration.span());
+            }
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_spans(items, line, ident, spans);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The spans to remove for the unused declarations and attributes of a file,
+/// given by their [`removal_key`](crate::imports::removal_key)s
+pub fn extern_crate_spans(file: &syn::File, key: &str) -> Vec<Span> {
+    let Some((line, ident)) = key.split_once(':') else {
+        return Vec::new();
+    };
+    let Ok(line) = line.parse() else {
+        return Vec::new();
+    };
+    let mut spans = Vec::new();
+    collect_spans(&file.items, line, ident, &mut spans);
+
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::test::rust_delete;
+
+    use super::*;
+
+    fn remove(src: &str, keys: &[&str]) -> String {
+        let keys = keys
+            .iter()
+            .map(|key| (UnusedDiagnosticKind::ExternCrate, key.to_string()));
+        String::from_utf8(rust_delete(src.as_bytes(), keys, false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn removes_declarations() {
+        let src = "#[macro_use]\nextern crate log;\n#[cfg(unix)]\nextern crate libc as c;\n\
+                   mod m {\n    extern crate alloc;\n}\npub fn f() {}\n";
+        assert_eq!(
+            remove(src, &["4:libc as c", "6:alloc"]),
+            "#[macro_use]\nextern crate log;\nmod m {\n}\npub fn f() {}\n"
+        );
+        assert_eq!(
+            remove(src, &["1:#[macro_use]"]),
+            "extern crate log;\n#[cfg(unix)]\nextern crate libc as c;\n\
+             mod m {\n    extern crate alloc;\n}\npub fn f() {}\n"
+        );
+        assert_eq!(remove(src, &["4:libc"]), src);
+        assert_eq!(
+            declared_name("extern crate alloc as a;").as_deref(),
+            Some("alloc as a")
+        );
+    }
+}
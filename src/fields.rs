@@ -0,0 +1,176 @@
+//! Struct fields that are never read. Removing a field means editing every
+//! struct expression (or pattern) that names it, and every call of a tuple
+//! struct, so these are only reported, along with the places that would
+//! need editing.
+
+use std::path::Path;
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use serde::{Deserialize, Serialize};
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The findings of a "fields `a` and `b` are never read" diagnostic, one per
+/// field, named like `Struct::a` (or `Struct::0` for tuple structs)
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    let message = &diagnostic.message;
+    let is_fields = message.starts_with("field") && message.ends_with("never read");
+    if code.code != "dead_code" || !is_fields {
+        return None;
+    }
+    // The other span points at the name of the struct
+    let struct_name = span_text(diagnostic.spans.iter().find(|span| !span.is_primary)?);
+
+    let fields: Vec<_> = message.split('`').skip(1).step_by(2).collect();
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .zip(fields)
+        .map(|(span, field)| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Field,
+            ident: format!("{struct_name}::{field}"),
+            span: span.clone(),
+            suggestion: None,
+            configs: Vec::new(),
+        })
+        .collect();
+
+    Some(found)
+}
+
+/// A struct expression or pattern that names a field, or a call of a tuple
+/// struct, which would have to be edited to remove the field
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Initialization {
+    /// The field, as in `Struct::field`
+    pub field: String,
+    pub file_name: String,
+    pub line: usize,
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == c)
+}
+
+/// Whether the braces of a struct expression name the field, either as
+/// `field: value` or as the shorthand `field`
+fn names_field(fields: TokenStream, field: &str) -> bool {
+    let tokens: Vec<_> = fields.into_iter().collect();
+    tokens.iter().enumerate().any(|(i, token)| {
+        let starts_entry = i == 0 || is_punct(tokens.get(i - 1), ',');
+        let ends_name = tokens.get(i + 1).is_none()
+            || is_punct(tokens.get(i + 1), ':')
+            || is_punct(tokens.get(i + 1), ',');
+        matches!(token, TokenTree::Ident(ident) if ident == field) && starts_entry && ends_name
+    })
+}
+
+/// Collects the lines naming the field of the struct; `Self` is the struct
+/// within its impl blocks
+fn scan(tokens: TokenStream, name: &str, field: &str, in_impl: bool, lines: &mut Vec<usize>) {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    let is_tuple = field.parse::<usize>().is_ok();
+
+    let mut impl_of = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenTree::Group(group) = token else {
+            if let TokenTree::Ident(ident) = token {
+                // In trait impls, the type follows `for`
+                if ident == "impl" || (ident == "for" && impl_of.is_some()) {
+                    impl_of = Some(false);
+                } else if ident == name && impl_of.is_some() {
+                    impl_of = Some(true);
+                }
+            }
+            continue;
+        };
+
+        // The body of an impl block
+        if group.delimiter() == Delimiter::Brace {
+            if let Some(is_struct) = impl_of.take() {
+                scan(group.stream(), name, field, is_struct, lines);
+                continue;
+            }
+        }
+
+        let previous = i.checked_sub(1).and_then(|i| tokens.get(i));
+        let before = i.checked_sub(2).and_then(|i| tokens.get(i));
+        let names_struct = match previous {
+            Some(TokenTree::Ident(ident)) => ident == name || (in_impl && ident == "Self"),
+            _ => false,
+        };
+        let is_definition = matches!(before, Some(TokenTree::Ident(ident)) if ident == "struct");
+        let is_initialization = match group.delimiter() {
+            Delimiter::Brace => !is_tuple && names_field(group.stream(), field),
+            Delimiter::Parenthesis => is_tuple,
+            _ => false,
+        };
+        if names_struct && !is_definition && is_initialization {
+            lines.push(group.span_open().start().line);
+        }
+
+        scan(group.stream(), name, field, in_impl, lines);
+    }
+}
+
+/// The places in the files that would have to be edited to remove the field,
+/// given as `Struct::field`
+pub fn initializations(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    field: &str,
+) -> Vec<Initialization> {
+    let Some((name, field_name)) = field.split_once("::") else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    for file in files {
+        let file = file.as_ref();
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(tokens) = src.parse() else {
+            continue;
+        };
+        let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+
+        let mut lines = Vec::new();
+        scan(tokens, name, field_name, false, &mut lines);
+        found.extend(lines.into_iter().map(|line| Initialization {
+            field: field.to_string(),
+            file_name: file_name.to_string_lossy().into_owned(),
+            line,
+        }));
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(src: &str, field: &str) -> Vec<usize> {
+        let (name, field) = field.split_once("::").unwrap();
+        let mut lines = Vec::new();
+        scan(src.parse().unwrap(), name, field, false, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn finds_initializations() {
+        let src = "struct S { a: u8, b: u8 }\n\
+                   impl S { fn new(b: u8) -> Self { Self { a: 1, b } } }\n\
+                   fn f(s: S) { let S { a, .. } = s; let _ = S { a: 1, b: 2 }; }\n\
+                   impl Other { fn new() -> Self { Self { b: 1 } } }\n";
+        assert_eq!(lines(src, "S::b"), [2, 3]);
+        assert_eq!(lines(src, "S::a"), [2, 3, 3]);
+
+        let src = "struct T(u8, u16);\nfn f() -> T { T(1, 2) }\n";
+        assert_eq!(lines(src, "T::1"), [2]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Fixpoint mode, with `--until-stable`: removing an item often leaves the
+//! private helpers it used unused in turn, which rustc only reports on the
+//! next check. The code is checked and the changes applied again and again,
+//! until nothing more is found or `--max-iterations` is reached, and the
+//! changes of all the iterations are shown together at the end, against the
+//! files as they were before the first one.
+
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use crate::cauterize::{Change, ChangeCategory};
+
+/// The number of iterations when `--max-iterations` isn't given
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+/// The contents of the changed files before the first iteration, and whether
+/// any of their changes were semantic
+#[derive(Default)]
+pub struct Originals {
+    files: BTreeMap<PathBuf, (Vec<u8>, ChangeCategory)>,
+}
+
+impl Originals {
+    /// Records the contents of the files of the changes, unless an earlier
+    /// iteration already did
+    pub fn record(&mut self, changes: &[Change]) {
+        for change in changes {
+            let (_, category) = self
+                .files
+                .entry(change.file_name().to_path_buf())
+                .or_insert_with(|| (change.original_content().to_vec(), change.category()));
+            if change.category() == ChangeCategory::Semantic {
+                *category = ChangeCategory::Semantic;
+            }
+        }
+    }
+
+    /// The changes of all the iterations together, from the recorded contents
+    /// to the files as they are now; files that are gone are removals
+    pub fn changes(&self) -> io::Result<Vec<Change>> {
+        let mut changes = Vec::new();
+        for (file_name, (original, category)) in &self.files {
+            let change = match fs::read(file_name) {
+                Ok(current) if current == *original => continue,
+                Ok(current) => Change::new(file_name.clone(), original.clone(), current),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    Change::removal(file_name.clone(), original.clone())
+                }
+                Err(err) => return Err(err),
+            };
+            changes.push(change.with_category(*category));
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn combines_iterations() {
+        let dir = std::env::temp_dir();
+        let dir = dir.join(format!("cargo-minify-fixpoint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (lib, gone, kept) = (dir.join("lib.rs"), dir.join("gone.rs"), dir.join("kept.rs"));
+
+        let mut originals = Originals::default();
+        originals.record(&[
+            Change::new(lib.clone(), b"a\nb\nc\n".to_vec(), b"a\nc\n".to_vec()),
+            Change::removal(gone.clone(), b"d\n".to_vec()),
+            Change::new(kept.clone(), b"e\n".to_vec(), Vec::new())
+                .with_category(ChangeCategory::Semantic),
+        ]);
+        // The second iteration starts from the changes of the first
+        fs::write(&kept, "e\n").unwrap();
+        originals.record(&[Change::new(
+            lib.clone(),
+            b"a\nc\n".to_vec(),
+            b"c\n".to_vec(),
+        )]);
+        fs::write(&lib, "c\n").unwrap();
+
+        let changes = originals.changes().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].is_removal());
+        assert_eq!(changes[0].file_name(), gone);
+        assert_eq!(changes[1].original_content(), b"a\nb\nc\n");
+        assert_eq!(changes[1].proposed_content(), b"c\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+//! Files written by code generators, such as prost-build or bindgen, are marked
+//! with `@generated` in the comments at their top, as in
+//! `// This file is @generated by prost-build.` Removing code from them is
+//! pointless, as the next run of the generator brings it back, so they are
+//! left alone; instead, the command regenerating them is suggested, as mapped
+//! from the name of the generator by `generators` in the configuration.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::cauterize::Change;
+
+/// A generated file that unused code was found in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedFile {
+    pub file_name: PathBuf,
+    /// The generator named by the marker, if any
+    pub generator: Option<String>,
+    /// The command regenerating the file, if its generator is configured
+    pub command: Option<String>,
+}
+
+/// The `@generated` marker in the comments at the top of the source, along
+/// with the generator it names, if any
+pub fn marker(src: &str) -> Option<Option<String>> {
+    let header = src.lines().map(str::trim).take_while(|line| {
+        line.is_empty()
+            || line.starts_with("//")
+            || line.starts_with("/*")
+            || line.starts_with('*')
+            || line.starts_with('#')
+    });
+    for line in header {
+        let Some((_, rest)) = line.split_once("@generated") else {
+            continue;
+        };
+        let generator = rest
+            .trim_start()
+            .strip_prefix("by ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .map(|name| name.trim_end_matches(['.', ',', ';', ':', '*', '/']))
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
+        return Some(generator);
+    }
+
+    None
+}
+
+/// Takes the changes to generated files out of `changes`, returning those
+/// files along with the commands regenerating them
+pub fn take_generated(
+    changes: &mut Vec<Change>,
+    generators: &HashMap<String, String>,
+) -> Vec<GeneratedFile> {
+    let mut generated = Vec::new();
+    changes.retain(|change| {
+        let Some(generator) = marker(&String::from_utf8_lossy(change.original_content())) else {
+            return true;
+        };
+        let command = generator
+            .as_ref()
+            .and_then(|name| generators.get(name))
+            .cloned();
+        generated.push(GeneratedFile {
+            file_name: change.file_name().to_path_buf(),
+            generator,
+            command,
+        });
+        false
+    });
+
+    generated
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn markers() {
+        let prost = "// This file is @generated by prost-build.\npub struct Msg {}\n";
+        assert_eq!(marker(prost), Some(Some("prost-build".to_string())));
+        let bindgen = "#![allow(dead_code)]\n/* automatically @generated by bindgen 0.69 */\n";
+        assert_eq!(marker(bindgen), Some(Some("bindgen".to_string())));
+        assert_eq!(marker("//! @generated\nfn f() {}\n"), Some(None));
+        // Only the header counts
+        assert_eq!(marker("fn f() {}\n// @generated by hand\n"), None);
+        assert_eq!(marker("fn f() {}\n"), None);
+    }
+}
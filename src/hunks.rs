@@ -0,0 +1,168 @@
+//! The removed parts of a change, located in the original file. Changes only
+//! hold the original and proposed content, so the removed byte ranges are
+//! recovered by diffing the lines, and then the characters of changed lines.
+
+use std::ops::Range;
+
+use crate::cauterize::Change;
+
+/// A removed part of the original file. Lines and columns are 1-based, and
+/// columns count characters rather than bytes, like those of rustc; the end
+/// is exclusive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+    pub removed: String,
+}
+
+impl Hunk {
+    pub fn contains(&self, byte: usize) -> bool {
+        (self.byte_start..self.byte_end).contains(&byte)
+    }
+}
+
+pub fn hunks(change: &Change) -> Vec<Hunk> {
+    let original = String::from_utf8_lossy(change.original_content());
+    let proposed = String::from_utf8_lossy(change.proposed_content());
+
+    removed_ranges(&original, &proposed)
+        .into_iter()
+        .map(|range| {
+            let (line_start, column_start) = position(&original, range.start);
+            let (line_end, column_end) = position(&original, range.end);
+            Hunk {
+                line_start,
+                line_end,
+                column_start,
+                column_end,
+                removed: original[range.clone()].to_string(),
+                byte_start: range.start,
+                byte_end: range.end,
+            }
+        })
+        .collect()
+}
+
+fn removed_ranges(original: &str, proposed: &str) -> Vec<Range<usize>> {
+    let left: Vec<_> = original.split_inclusive('\n').collect();
+    let right: Vec<_> = proposed.split_inclusive('\n').collect();
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut push = |range: Range<usize>| match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    };
+
+    let mut offset = 0;
+    let mut removed = String::new();
+    let mut added = String::new();
+    let mut flush = |offset: usize, removed: &mut String, added: &mut String| {
+        let start = offset - removed.len();
+        if added.is_empty() {
+            push(start..offset);
+        } else {
+            // Only part of these lines was removed
+            let mut pos = start;
+            for char in diff::chars(removed, added) {
+                match char {
+                    diff::Result::Left(c) => {
+                        push(pos..pos + c.len_utf8());
+                        pos += c.len_utf8();
+                    }
+                    diff::Result::Both(c, _) => pos += c.len_utf8(),
+                    diff::Result::Right(_) => {}
+                }
+            }
+        }
+        removed.clear();
+        added.clear();
+    };
+
+    for line in diff::slice(&left, &right) {
+        match line {
+            diff::Result::Left(line) => {
+                removed.push_str(line);
+                offset += line.len();
+            }
+            diff::Result::Right(line) => added.push_str(line),
+            diff::Result::Both(line, _) => {
+                if !removed.is_empty() {
+                    flush(offset, &mut removed, &mut added);
+                }
+                added.clear();
+                offset += line.len();
+            }
+        }
+    }
+    if !removed.is_empty() {
+        flush(offset, &mut removed, &mut added);
+    }
+
+    ranges.retain(|range| !range.is_empty());
+    ranges
+}
+
+/// The 1-based line and (character) column of the byte offset
+fn position(src: &str, byte: usize) -> (usize, usize) {
+    let before = &src[..byte];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hunks_of(original: &str, proposed: &str) -> Vec<Hunk> {
+        hunks(&Change::new(
+            "lib.rs".into(),
+            original.as_bytes().to_vec(),
+            proposed.as_bytes().to_vec(),
+        ))
+    }
+
+    #[test]
+    fn whole_lines() {
+        let hunks = hunks_of(
+            "fn a() {}\nfn b() {}\nfn c() {}\n",
+            "fn a() {}\nfn c() {}\n",
+        );
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                byte_start: 10,
+                byte_end: 20,
+                line_start: 2,
+                line_end: 3,
+                column_start: 1,
+                column_end: 1,
+                removed: "fn b() {}\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn multi_byte_content() {
+        let original = "const Ä: &str = \"ö\";\nfn ü() {} fn ß() {}\n";
+        let hunks = hunks_of(original, "const Ä: &str = \"ö\";\nfn ü() {} \n");
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!(hunk.removed, "fn ß() {}");
+        assert_eq!(&original[hunk.byte_start..hunk.byte_end], "fn ß() {}");
+        assert_eq!((hunk.line_start, hunk.column_start), (2, 11));
+        assert_eq!((hunk.line_end, hunk.column_end), (2, 20));
+
+        let hunks = hunks_of("// ✓✓\nfn ✓() {}\n", "// ✓✓\n");
+        assert_eq!((hunks[0].line_start, hunks[0].column_start), (2, 1));
+        assert_eq!(hunks[0].byte_start, "// ✓✓\n".len());
+    }
+}
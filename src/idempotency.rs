@@ -0,0 +1,180 @@
+//! Checks that the proposed changes are idempotent: once they are applied to a
+//! copy of the workspace, the copy should still compile, and none of the code
+//! that was removed should be reported as unused again. Code that only became
+//! unused because of the removals is fine, and merely reported.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    cauterize::{self, Change},
+    toolchain,
+    unused::{UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+pub struct IdempotencyReport {
+    /// Whether the minified copy still compiles
+    pub compiles: bool,
+    /// Diagnostics that were reported again, although their code should
+    /// have been removed
+    pub repeated: Vec<UnusedDiagnostic>,
+    /// Code that only became unused because of the removals
+    pub transitive: Vec<UnusedDiagnostic>,
+}
+
+/// A copy of a workspace in a temporary directory, removed again on drop
+pub struct WorkspaceCopy {
+    root: PathBuf,
+    original_root: PathBuf,
+}
+
+impl WorkspaceCopy {
+    /// Copies the workspace, except for its target directory and `.git`
+    pub fn new(workspace_root: &Path, target_directory: &Path) -> io::Result<Self> {
+        let root =
+            std::env::temp_dir().join(format!("cargo-minify-idempotency-{}", std::process::id()));
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+
+        let copy = WorkspaceCopy {
+            root,
+            original_root: workspace_root.canonicalize()?,
+        };
+        let skipped = [
+            copy.original_root.join(".git"),
+            target_directory
+                .canonicalize()
+                .unwrap_or_else(|_| target_directory.to_path_buf()),
+        ];
+        copy_dir(&copy.original_root, &copy.root, &skipped)?;
+
+        Ok(copy)
+    }
+
+    /// The path in the copy corresponding to `path` in the original workspace
+    pub fn path_of(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = path.canonicalize()?;
+        let relative = path.strip_prefix(&self.original_root).map_err(|_| {
+            io::Error::other(format!("{} is outside the workspace", path.display()))
+        })?;
+
+        Ok(self.root.join(relative))
+    }
+
+    /// Applies the changes to the corresponding files in the copy
+    pub fn apply(&self, changes: &[Change]) -> io::Result<()> {
+        let changes = changes
+            .iter()
+            .map(|change| Ok(change.relocated(self.path_of(change.file_name())?)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        cauterize::commit_changes(changes, &self.root).map_err(|errors| {
+            errors
+                .into_iter()
+                .next()
+                .map_or_else(|| io::Error::other(""), |(_, error)| error)
+        })
+    }
+
+    /// Whether the copy compiles
+    pub fn compiles(&self, manifest_path: &Path) -> io::Result<bool> {
+        let mut command = Command::new("cargo");
+        if let Some(toolchain) = toolchain::pinned(&self.root) {
+            toolchain.apply(&mut command);
+        }
+        let status = command
+            .args(["check", "--all-targets", "--quiet", "--manifest-path"])
+            .arg(manifest_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+
+        Ok(status.success())
+    }
+}
+
+impl Drop for WorkspaceCopy {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Copies a directory recursively, except for the `skipped` paths within it,
+/// which are given as found under `from`
+pub fn copy_dir(from: &Path, to: &Path, skipped: &[PathBuf]) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skipped.contains(&entry.path()) {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(name), skipped)?;
+        } else {
+            fs::copy(entry.path(), to.join(name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares the diagnostics before and after applying the changes
+pub fn compare(
+    compiles: bool,
+    before: &[UnusedDiagnostic],
+    after: Vec<UnusedDiagnostic>,
+) -> IdempotencyReport {
+    let key = |diagnostic: &UnusedDiagnostic| -> (UnusedDiagnosticKind, String, String) {
+        (
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+            diagnostic.span.file_name.clone(),
+        )
+    };
+    let before: HashSet<_> = before.iter().map(key).collect();
+
+    let mut seen = HashSet::new();
+    let (repeated, transitive) = after
+        .into_iter()
+        .filter(|diagnostic| seen.insert(key(diagnostic)))
+        .partition(|diagnostic| before.contains(&key(diagnostic)));
+
+    IdempotencyReport {
+        compiles,
+        repeated,
+        transitive,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn copies_nested_target_and_git_dirs() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod target;\n"),
+            ("src/target/mod.rs", "pub fn f() {}\n"),
+            ("vendor/dep/.git/HEAD", "ref: refs/heads/main\n"),
+            (".git/HEAD", "ref: refs/heads/main\n"),
+            ("target/debug/lib.rlib", ""),
+        ])
+        .unwrap();
+        let root = temp_crate.root();
+
+        let copy = WorkspaceCopy::new(root, &root.join("target")).unwrap();
+        assert!(copy.root.join("src/target/mod.rs").exists());
+        assert!(copy.root.join("vendor/dep/.git/HEAD").exists());
+        assert!(!copy.root.join(".git").exists());
+        assert!(!copy.root.join("target").exists());
+    }
+}
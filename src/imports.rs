@@ -0,0 +1,235 @@
+//! Unused imports, as reported by the `unused_imports` lint. rustc reports
+//! all unused names of a `use` item at once, each with a span of its own, so
+//! every name becomes a finding. When removing them, the names of a `use`
+//! item are pruned together: groups left without names go as well, and so
+//! does the whole item once none of its names are left.
+
+use std::collections::HashSet;
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::{spanned::Spanned, Item, Stmt, UseGroup, UseTree};
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The findings of an `unused_imports` diagnostic, one per unused name; the
+/// name is the import as written in its group, like `rc::Rc` in
+/// `use std::{rc::Rc, sync::Arc};`
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    if code.code != "unused_imports" || !diagnostic.message.starts_with("unused import") {
+        return None;
+    }
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .map(|span| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Import,
+            ident: span_text(span),
+            span: span.clone(),
+            suggestion: None,
+            configs: Vec::new(),
+        })
+        .collect();
+
+    Some(found)
+}
+
+/// Imports are compared without whitespace, as the tokens are printed with
+/// spaces in between
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// The ident to remove an import by: its name along with its line, as the
+/// same name may be imported by several modules of a file
+pub fn removal_key(diagnostic: &UnusedDiagnostic) -> String {
+    format!("{}:{}", diagnostic.span.line_start, diagnostic.ident)
+}
+
+/// The line and the normalized name of a [`removal_key`]
+type Key = (usize, String);
+
+fn key_of(tree: &UseTree) -> Key {
+    let text = tree.to_token_stream().to_string();
+    (tree.span().start().line, normalize(&text))
+}
+
+/// The group a tree ends in, as in `a::b::{c, d}`
+fn group_of(tree: &UseTree) -> Option<&UseGroup> {
+    match tree {
+        UseTree::Path(path) => group_of(&path.tree),
+        UseTree::Group(group) => Some(group),
+        _ => None,
+    }
+}
+
+/// Whether the tree is unused, either itself or by all the names in its group
+fn is_unused(tree: &UseTree, unused: &HashSet<Key>) -> bool {
+    unused.contains(&key_of(tree))
+        || group_of(tree)
+            .is_some_and(|group| group.items.iter().all(|tree| is_unused(tree, unused)))
+}
+
+/// The spans of the unused names in the group of a tree that is still used
+fn prune(tree: &UseTree, unused: &HashSet<Key>, spans: &mut Vec<Span>) {
+    let Some(group) = group_of(tree) else {
+        return;
+    };
+    let pairs: Vec<_> = group
+        .items
+        .pairs()
+        .map(|pair| {
+            (
+                *pair.value(),
+                pair.punct().copied(),
+                is_unused(pair.value(), unused),
+            )
+        })
+        .collect();
+    let kept: Vec<_> = (0..pairs.len()).filter(|&i| !pairs[i].2).collect();
+
+    // A single name left on a single line doesn't need its braces anymore
+    let (open, close) = (
+        group.brace_token.span.open(),
+        group.brace_token.span.close(),
+    );
+    if let [index] = kept[..] {
+        let (tree, punct, _) = pairs[index];
+        let is_self = matches!(tree, UseTree::Name(name) if name.ident == "self");
+        let single_line = open.start().line == close.end().line;
+        if pairs.len() > 1 && single_line && !is_self {
+            let before = index.checked_sub(1).and_then(|i| pairs[i].1);
+            spans.push(
+                before
+                    .and_then(|comma| open.join(comma.span))
+                    .unwrap_or(open),
+            );
+            spans.push(
+                punct
+                    .and_then(|comma| comma.span.join(close))
+                    .unwrap_or(close),
+            );
+            prune(tree, unused, spans);
+            return;
+        }
+    }
+
+    let mut index = 0;
+    while index < pairs.len() {
+        if !pairs[index].2 {
+            prune(pairs[index].0, unused, spans);
+            index += 1;
+            continue;
+        }
+        let first = index;
+        while index < pairs.len() && pairs[index].2 {
+            index += 1;
+        }
+        let last = index - 1;
+
+        // The commas go along with the names, the one before the names only
+        // when they end the group without a trailing comma
+        let span = match (pairs[last].1, first.checked_sub(1).and_then(|i| pairs[i].1)) {
+            (Some(comma), _) => pairs[first].0.span().join(comma.span),
+            (None, Some(comma)) => comma.span.join(pairs[last].0.span()),
+            (None, None) => None,
+        };
+        spans.extend(span);
+    }
+}
+
+fn collect_spans(item: &Item, unused: &HashSet<Key>, spans: &mut Vec<Span>) {
+    match item {
+        Item::Use(item_use) if is_unused(&item_use.tree, unused) => spans.push(item.span()),
+        Item::Use(item_use) => prune(&item_use.tree, unused, spans),
+        Item::Mod(module) => {
+            for item in module.content.iter().flat_map(|(_, items)| items) {
+                collect_spans(item, unused, spans);
+            }
+        }
+        Item::Fn(function) => {
+            for stmt in &function.block.stmts {
+                if let Stmt::Item(item) = stmt {
+                    collect_spans(item, unused, spans);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The spans to remove for the unused imports of a file, given by their
+/// [`removal_key`]s
+pub fn import_spans(file: &syn::File, keys: &[String]) -> Vec<Span> {
+    let unused: HashSet<_> = keys
+        .iter()
+        .filter_map(|key| key.split_once(':'))
+        .filter_map(|(line, ident)| Some((line.parse().ok()?, normalize(ident))))
+        .collect();
+    let mut spans = Vec::new();
+    for item in &file.items {
+        collect_spans(item, &unused, &mut spans);
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::test::rust_delete;
+
+    use super::*;
+
+    fn prune(src: &str, keys: &[&str]) -> String {
+        let keys = keys
+            .iter()
+            .map(|key| (UnusedDiagnosticKind::Import, key.to_string()));
+        String::from_utf8(rust_delete(src.as_bytes(), keys, false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn pruning() {
+        let src = "use std::fmt;\nuse std::path::Path as P;\nfn main() {}\n";
+        assert_eq!(
+            prune(src, &["1:std::fmt"]),
+            "use std::path::Path as P;\nfn main() {}\n"
+        );
+        assert_eq!(
+            prune(src, &["2:std::path::Path as P"]),
+            "use std::fmt;\nfn main() {}\n"
+        );
+
+        let src = "use std::collections::{HashMap, HashSet, BTreeMap};\n";
+        assert_eq!(
+            prune(src, &["1:HashMap", "1:BTreeMap"]),
+            "use std::collections::HashSet;\n"
+        );
+        assert_eq!(
+            prune(src, &["1:HashSet"]),
+            "use std::collections::{HashMap, BTreeMap};\n"
+        );
+        assert_eq!(prune(src, &["1:HashMap", "1:HashSet", "1:BTreeMap"]), "");
+
+        let src = "use std::{rc::Rc, sync::{Arc, Mutex}};\n";
+        assert_eq!(
+            prune(src, &["1:rc::Rc", "1:Mutex"]),
+            "use std::sync::Arc;\n"
+        );
+        assert_eq!(prune(src, &["1:Arc", "1:Mutex"]), "use std::rc::Rc;\n");
+        assert_eq!(prune(src, &["1:Mutex"]), "use std::{rc::Rc, sync::Arc};\n");
+
+        let src = "use std::io::{self, Read};\n";
+        assert_eq!(prune(src, &["1:Read"]), "use std::io::{self};\n");
+
+        // The same import in another module is left alone
+        let src = "use a::{\n    B,\n    C,\n    D,\n};\nmod m {\n    use a::B;\n}\n";
+        assert_eq!(
+            prune(src, &["2:B", "4:D"]),
+            "use a::{\n    C,\n};\nmod m {\n    use a::B;\n}\n"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Findings can be kept on purpose with a justification comment above the
+//! item, as in `// minify:allow(reason = "used by the plugin ABI", until =
+//! "2025-12-31")`. Once the `until` date has passed, the justification no
+//! longer keeps the item, and is warned about, so that kept dead code is paid
+//! back eventually.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::unused::{UnusedDiagnostic, UnusedDiagnosticKind};
+
+const MARKER: &str = "minify:allow(";
+
+/// A `minify:allow` comment above a finding
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Justification {
+    pub file_name: String,
+    /// The line of the comment
+    pub line: usize,
+    pub ident: String,
+    pub reason: Option<String>,
+    /// The last day the justification holds, as `YYYY-MM-DD`
+    pub until: Option<String>,
+}
+
+impl Justification {
+    /// Whether the justification no longer holds on `today`; dates that are
+    /// not `YYYY-MM-DD` never hold
+    pub fn is_expired(&self, today: &str) -> bool {
+        self.until
+            .as_deref()
+            .is_some_and(|until| !is_date(until) || until < today)
+    }
+}
+
+fn is_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            4 | 7 => *byte == b'-',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+/// Today's date in UTC, as `YYYY-MM-DD`
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86400) as i64;
+
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// The `key = "value"` pairs of a `minify:allow(...)` comment
+fn parse(comment: &str) -> Option<(Option<String>, Option<String>)> {
+    let (_, rest) = comment.split_once(MARKER)?;
+    let (mut reason, mut until) = (None, None);
+
+    let mut rest = rest;
+    while let Some((key, value)) = rest.split_once('=') {
+        let value = value.trim_start().strip_prefix('"')?;
+        let (value, after) = value.split_once('"')?;
+        match key.trim_matches([' ', ',']) {
+            "reason" => reason = Some(value.to_string()),
+            "until" => until = Some(value.to_string()),
+            _ => {}
+        }
+        rest = after;
+    }
+
+    Some((reason, until))
+}
+
+/// The justification in the comments and attributes right above the line
+fn above(lines: &[&str], line: usize) -> Option<(usize, Option<String>, Option<String>)> {
+    // rustc points at the item's name, which may follow its attributes and docs
+    let start = line.checked_sub(1)?;
+    for index in (0..start).rev() {
+        let text = lines.get(index)?.trim();
+        if text.starts_with("//") {
+            if let Some((reason, until)) = parse(text) {
+                return Some((index + 1, reason, until));
+            }
+        } else if !text.starts_with("#[") {
+            return None;
+        }
+    }
+
+    None
+}
+
+/// Removes the justified findings, returning the justifications that kept
+/// them, and the expired ones, whose findings are kept. Findings of the same
+/// kind and ident go together, as syntactic kinds may span several files.
+pub fn apply(
+    workspace_root: &Path,
+    diagnostics: &mut Vec<UnusedDiagnostic>,
+    today: &str,
+) -> (Vec<Justification>, Vec<Justification>) {
+    let mut sources = HashMap::new();
+    let mut justified = Vec::new();
+    let mut expired = Vec::new();
+    let mut keys = HashSet::<(UnusedDiagnosticKind, String)>::new();
+
+    for diagnostic in diagnostics.iter() {
+        let file_name = &diagnostic.span.file_name;
+        let src = sources.entry(file_name.clone()).or_insert_with(|| {
+            std::fs::read_to_string(workspace_root.join(file_name)).unwrap_or_default()
+        });
+        let lines: Vec<_> = src.lines().collect();
+        let Some((line, reason, until)) = above(&lines, diagnostic.span.line_start) else {
+            continue;
+        };
+
+        let justification = Justification {
+            file_name: file_name.clone(),
+            line,
+            ident: diagnostic.ident.clone(),
+            reason,
+            until,
+        };
+        if justification.is_expired(today) {
+            expired.push(justification);
+        } else {
+            keys.insert((diagnostic.kind, diagnostic.ident.clone()));
+            justified.push(justification);
+        }
+    }
+
+    diagnostics.retain(|diagnostic| !keys.contains(&(diagnostic.kind, diagnostic.ident.clone())));
+    // The same finding may come from multiple targets
+    justified.dedup();
+    expired.dedup();
+
+    (justified, expired)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            parse("// minify:allow(reason = \"kept, for now\", until = \"2025-12-31\")"),
+            Some((
+                Some("kept, for now".to_string()),
+                Some("2025-12-31".to_string())
+            ))
+        );
+        assert_eq!(parse("// minify:allow()"), Some((None, None)));
+        assert_eq!(parse("// allow(dead_code)"), None);
+
+        let lines = [
+            "// minify:allow(reason = \"ABI\")",
+            "/// Docs",
+            "#[inline]",
+            "fn foo() {}",
+            "",
+            "fn bar() {}",
+        ];
+        assert_eq!(above(&lines, 4), Some((1, Some("ABI".to_string()), None)));
+        assert_eq!(above(&lines, 6), None);
+    }
+
+    #[test]
+    fn expiry() {
+        let justification = |until: &str| Justification {
+            file_name: "src/lib.rs".to_string(),
+            line: 1,
+            ident: "foo".to_string(),
+            reason: None,
+            until: Some(until.to_string()),
+        };
+        assert!(!justification("2025-12-31").is_expired("2025-12-31"));
+        assert!(justification("2025-12-31").is_expired("2026-01-01"));
+        assert!(justification("next year").is_expired("2025-01-01"));
+        assert!(is_date(&today()));
+    }
+}
@@ -1,24 +1,127 @@
-use std::{env, io, io::Write, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, io,
+    io::Write,
+    mem,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
+use cargo_metadata::{Metadata, Target};
 use gumdrop::Options;
+use thiserror::Error;
 
 use crate::{
+    cache::Cache,
+    cauterize::RemovalOptions,
+    config::{Config, CrateTypePolicy, KindPolicy},
     diff_format::ColorMode,
-    error::{Error, Result},
-    unused::UnusedDiagnosticKind,
+    doc_links::DocLinks,
+    duplicates::Duplicate,
+    engine::Engine,
+    error::{Error, Result, EXIT_CODES},
+    idempotency::{IdempotencyReport, WorkspaceCopy},
+    manifest_lints::HiddenByLints,
+    message_format::MessageFormat,
+    preset::Preset,
+    published::PublishedCrate,
+    report::ApplyPolicy,
+    sinks::Sinks,
+    state::{Kept, ResumeError},
+    unused_deps::UnusedDependency,
+    unused_features::UnusedFeature,
+    vcs::VcsBackend,
+};
+pub use crate::{
+    cauterize::{Change, ChangeCategory},
+    doc_links::DocLink,
+    edit::{EditError, SourceFileEditor, TextEdit},
+    modules::LayoutProblem,
+    pipeline::{Findings, Hook, Pipeline, Stage},
+    report::{Analysis, ApplyReport},
+    unused::{BenchReference, UnusedDiagnostic, UnusedDiagnosticKind},
 };
 
+mod allows;
+mod annotate;
+mod apply_order;
+mod assoc_items;
+mod backup;
+mod bounds;
+mod cache;
+mod cascade;
 mod cauterize;
+mod changelog;
+mod clean;
+mod compare;
+mod config;
+mod const_params;
+mod dead_modules;
 mod diff_format;
+mod doc_links;
+mod doc_only;
+mod duplicates;
+mod dyn_shims;
+mod edit;
+mod empty_modules;
+mod engine;
 mod error;
+mod expand;
+mod extern_crates;
+mod fields;
+mod fixpoint;
+mod generated;
+mod hunks;
+mod idempotency;
+mod imports;
+mod justifications;
+mod lifetimes;
+mod manifest_lints;
+mod message_format;
+mod metrics;
+mod modules;
+mod offline;
+mod pipeline;
+mod preset;
+mod published;
+mod reexports;
+mod report;
 mod resolver;
+mod selection;
+mod serve;
+mod sinks;
+mod state;
+mod suggestions;
+mod suppressions;
+mod test_only;
+pub mod test_support;
+mod toolchain;
+mod trait_impls;
+mod unreachable;
+mod unreachable_pub;
 mod unused;
+mod unused_deps;
+mod unused_features;
+mod unused_mut;
+mod unused_pub;
+mod variables;
+mod variants;
 mod vcs;
 
 const SUBCOMMAND_NAME: &str = "minify";
+const COMMIT_MESSAGE: &str = "Remove unused code with cargo minify";
 
-#[derive(Debug, Options)]
+#[derive(Debug, Clone, Options)]
 struct MinifyOptions {
+    #[options(
+        free,
+        help = "check (default), apply, stats, undo, clean, compare or formats"
+    )]
+    command: Option<Subcommand>,
+    #[options(free, help = "With compare, the old and the new report")]
+    reports: Vec<String>,
+
     #[options(help = "No output printed to stdout")]
     quiet: bool,
 
@@ -35,21 +138,317 @@ struct MinifyOptions {
     ignore: Vec<String>,
 
     #[options(
-        help = "specify which kinds of diagnostics to apply (all by default)",
-        meta = "< FUNCTION | CONST | STATIC | STRUCT | ENUM | UNION | TYPE_ALIAS | \
-                ASSOCIATED_FUNCTION | MACRO_DEFINITION >"
+        help = "specify which kinds of diagnostics to apply (the default kinds if none); \
+                `cargo minify formats` lists them all",
+        meta = "KIND"
     )]
     kinds: Vec<UnusedDiagnosticKind>,
 
+    #[options(
+        no_short,
+        help = "Report duplicate items as candidates for consolidation, instead of unused code"
+    )]
+    duplicates: bool,
+
+    #[options(no_short, help = "Deprecated, same as --report variant")]
+    report_only: bool,
+
+    #[options(
+        no_short,
+        help = "Only report the findings of the kind, without removing them",
+        meta = "KIND"
+    )]
+    report: Vec<UnusedDiagnosticKind>,
+
+    #[options(no_short, help = "Don't let usage by benchmarks keep code alive")]
+    ignore_bench_usage: bool,
+
+    #[options(
+        no_short,
+        help = "Also report the code only tests use, by checking once more without tests"
+    )]
+    test_only_usage: bool,
+
+    #[options(no_short, help = "Also remove unused code from build scripts")]
+    build_scripts: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove code only used under cfg(doc) or cfg(docsrs)"
+    )]
+    include_doc_only: bool,
+
+    #[options(no_short, help = "Also report the dependencies that no target uses")]
+    unused_deps: bool,
+
+    #[options(no_short, help = "Also remove the features that gate no code")]
+    unused_features: bool,
+
+    #[options(
+        no_short,
+        help = "Also report the items only derived impls use, by running cargo expand"
+    )]
+    expand: bool,
+
+    #[options(
+        no_short,
+        help = "Find the unused code even where a lint like dead_code is allowed",
+        meta = "LINT"
+    )]
+    strip_allow: Vec<String>,
+
+    #[options(
+        no_short,
+        help = "Adjust the defaults to a kind of project: embedded",
+        meta = "NAME"
+    )]
+    preset: Option<Preset>,
+
+    #[options(no_short, help = "Check for the given target triple", meta = "TRIPLE")]
+    target: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Check for the host, instead of the configured target"
+    )]
+    host: bool,
+
     #[options(no_short, help = "Apply changes instead of outputting a diff")]
     apply: bool,
 
+    #[options(
+        no_short,
+        help = "Fail if applying the changes to a copy leaves code that should have been removed"
+    )]
+    check_idempotent: bool,
+
+    #[options(
+        no_short,
+        help = "Apply the packages one at a time, dependencies first, reverting the first that \
+                no longer compiles"
+    )]
+    verify: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove comments directly above removed items, and squash blank lines"
+    )]
+    tidy: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove private items only used by the removed code"
+    )]
+    cascade: bool,
+
+    #[options(
+        no_short,
+        help = "With --cascade, also remove pub items of packages without a library"
+    )]
+    bin_aggressive: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove the modules left without any items, and their files"
+    )]
+    prune_empty_modules: bool,
+
+    #[options(
+        no_short,
+        help = "Intra-doc links to removed items: break (default), fix, protect",
+        meta = "MODE"
+    )]
+    doc_links: DocLinks,
+
+    #[options(
+        no_short,
+        help = "Resume the pending changes of an interrupted run instead of analyzing again"
+    )]
+    resume: bool,
+
+    #[options(
+        no_short,
+        help = "Reuse the results of an earlier run on the same commit, if the tree is clean"
+    )]
+    cache: bool,
+
+    #[options(
+        no_short,
+        help = "Cache the results in this directory instead of target/minify-cache",
+        meta = "DIR"
+    )]
+    cache_dir: Option<String>,
+
+    #[options(
+        no_short,
+        help = "With clean, only list the files that would be removed"
+    )]
+    dry_run: bool,
+
+    #[options(
+        no_short,
+        help = "Stop checking after a while, e.g. 90s, 15m or 1h, keeping the findings so far",
+        meta = "DURATION",
+        parse(try_from_str = "unused::parse_duration")
+    )]
+    timeout: Option<Duration>,
+
+    #[options(
+        no_short,
+        help = "Run cargo without accessing the network; without a Cargo.lock, only report \
+                obviously unused code"
+    )]
+    offline: bool,
+
+    #[options(
+        no_short,
+        help = "How to find unused code: cargo (default), or syntax, which compiles nothing and \
+                is approximate",
+        meta = "ENGINE"
+    )]
+    engine: Engine,
+
+    #[options(
+        no_short,
+        help = "Also apply the approximate changes of --engine syntax"
+    )]
+    force: bool,
+
+    #[options(
+        no_short,
+        help = "Review the changes in a web page on localhost, and apply the selected ones",
+        meta = "PORT"
+    )]
+    serve: Option<u16>,
+
+    #[options(
+        no_short,
+        help = "Save the ids of the changes (or those selected with --serve) to a file",
+        meta = "FILE"
+    )]
+    save_selection: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Only keep the changes saved with --save-selection",
+        meta = "FILE"
+    )]
+    selection: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Apply changes to the git index instead of the working tree"
+    )]
+    apply_staged: bool,
+
+    #[options(
+        no_short,
+        help = "Create and switch to a new branch before applying",
+        meta = "NAME"
+    )]
+    vcs_branch: Option<String>,
+
+    #[options(no_short, help = "Reset the --vcs-branch branch if it already exists")]
+    force_branch: bool,
+
+    #[options(no_short, help = "Commit the changes after applying")]
+    vcs_commit: bool,
+
+    #[options(
+        no_short,
+        help = "Apply, check again and apply again until no more unused code is found (implies \
+                --apply)"
+    )]
+    until_stable: bool,
+
+    #[options(
+        no_short,
+        help = "Stop --until-stable after this many iterations (10 by default)",
+        meta = "N"
+    )]
+    max_iterations: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Also apply changes to proc-macro and other diff-only crates"
+    )]
+    include_proc_macros: bool,
+
+    #[options(
+        no_short,
+        help = "Also apply semantic changes, which rewrite signatures and calls (implies --apply)"
+    )]
+    apply_semantic: bool,
+
     #[options(help = "Print help message")]
     help: bool,
 
     #[options(no_short, help = "Coloring: auto, always, never", meta = "WHEN")]
     color: ColorMode,
 
+    #[options(
+        no_short,
+        help = "Output format: human, json, sarif; machine readable formats ignore --quiet",
+        meta = "FMT"
+    )]
+    message_format: MessageFormat,
+
+    #[options(
+        no_short,
+        help = "Only show the first line of every removed item in the diff"
+    )]
+    compact: bool,
+
+    #[options(
+        no_short,
+        help = "Show diffs with an external tool, e.g. delta or difft",
+        meta = "CMD"
+    )]
+    diff_tool: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Also write the changes and findings to an HTML page",
+        meta = "FILE"
+    )]
+    report_html: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Also write the changes to a patch file",
+        meta = "FILE"
+    )]
+    output_patch: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Also write the findings to a Vim quickfix file",
+        meta = "FILE"
+    )]
+    output_quickfix: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Also write counts of the findings in the OpenMetrics text format",
+        meta = "FILE"
+    )]
+    metrics: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Append the removed items to a changelog file",
+        meta = "FILE"
+    )]
+    changelog: Option<String>,
+
+    #[options(
+        no_short,
+        long = "crate",
+        help = "Show the unused code of a published crate, given as name@version",
+        meta = "SPEC"
+    )]
+    krate: Option<String>,
+
     #[options(no_short, help = "Path to Cargo.toml", meta = "PATH")]
     manifest_path: Option<String>,
 
@@ -61,6 +460,13 @@ struct MinifyOptions {
 
     #[options(no_short, help = "Also operate if no version control system was found")]
     allow_no_vcs: bool,
+
+    #[options(
+        no_short,
+        help = "How to check the working directory: git-cli, libgit2 (default), none",
+        meta = "BACKEND"
+    )]
+    vcs_backend: VcsBackend,
 }
 
 pub fn run() {
@@ -79,25 +485,24 @@ pub fn run() {
     };
 
     let status_code = match execute(&args.collect::<Vec<_>>()) {
-        Err(Error::Io(err)) => {
-            eprintln!("IO error: {}", err);
-            3
-        }
-        Err(Error::Utf8(err)) => {
-            eprintln!("Encoding error: {}", err);
-            2
-        }
-        Err(Error::Args(err)) => {
-            eprintln!("error: {}", err);
-            mini_help();
-            1
-        }
-        Err(Error::CommandLine(err)) => {
-            eprintln!("error: {}", err);
-            mini_help();
-            1
+        Ok(()) => 0,
+        Err(err) => {
+            match &err {
+                Error::Io(err) => eprintln!("IO error: {}", err),
+                Error::Utf8(err) => eprintln!("Encoding error: {}", err),
+                Error::Args(_) | Error::CommandLine(_) | Error::InvalidDuration(_) => {
+                    eprintln!("error: {}", err);
+                    mini_help();
+                }
+                Error::Config { .. }
+                | Error::NotIdempotent(_)
+                | Error::Toolchain(_)
+                | Error::PartiallyApplied
+                | Error::NewFindings(_)
+                | Error::ApplyRefused => eprintln!("error: {}", err),
+            }
+            err.exit_code()
         }
-        _ => 0,
     };
 
     io::stdout().flush().unwrap();
@@ -105,120 +510,1884 @@ pub fn run() {
     std::process::exit(status_code);
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+enum Subcommand {
+    #[default]
+    Check,
+    Apply,
+    Stats,
+    Undo,
+    Clean,
+    Compare,
+    Formats,
+}
+
+impl Subcommand {
+    const HELP: &'static [(&'static str, &'static str)] = &[
+        ("check", "Show the changes that would be made (default)"),
+        ("apply", "Apply the changes, same as --apply"),
+        (
+            "stats",
+            "Summarize the unused code per kind instead of showing a diff",
+        ),
+        ("undo", "Restore the files changed by the last apply"),
+        (
+            "clean",
+            "Remove the backups, caches and temporary copies of cargo-minify",
+        ),
+        (
+            "compare",
+            "Show the findings that appeared and were resolved between two JSON reports",
+        ),
+        ("formats", "List the message formats and exit codes as JSON"),
+    ];
+}
+
+impl FromStr for Subcommand {
+    type Err = UnsupportedCommand;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "check" => Ok(Subcommand::Check),
+            "apply" => Ok(Subcommand::Apply),
+            "stats" => Ok(Subcommand::Stats),
+            "undo" => Ok(Subcommand::Undo),
+            "clean" => Ok(Subcommand::Clean),
+            "compare" => Ok(Subcommand::Compare),
+            "formats" => Ok(Subcommand::Formats),
+            _ => Err(UnsupportedCommand),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported command, pick any of: check, apply, stats, undo, clean, compare, formats")]
+pub struct UnsupportedCommand;
+
+fn print_help() {
+    println!("Usage: cargo minify [COMMAND] [OPTIONS]");
+    println!();
+    println!("Commands:");
+    for (name, help) in Subcommand::HELP {
+        println!("  {name:<8}{help}");
+    }
+    println!();
+    println!("{}", MinifyOptions::usage());
+}
+
 pub fn execute(args: &[String]) -> Result<()> {
-    let opts = MinifyOptions::parse_args_default(args)?;
-    let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
-    let crate_resolution = CrateResolutionOptions::from_options(&opts)?;
-    let file_resolution = FileResolutionOptions::from_options(&opts)?;
+    execute_with(args, &mut Pipeline::default())
+}
 
-    if opts.help {
-        println!("{}", MinifyOptions::usage());
+/// Parses the command line arguments, turning the kinds that are only reported
+/// into kinds to look for
+fn parse_options(args: &[String]) -> Result<MinifyOptions> {
+    let mut opts = MinifyOptions::parse_args_default(args)?;
+    if opts.report_only {
+        if !opts.quiet {
+            eprintln!("warning: --report-only is deprecated, use --report variant instead");
+        }
+        opts.report_only = false;
+        opts.report.push(UnusedDiagnosticKind::Variant);
+    }
+    // The kinds that are only reported have to be looked for as well
+    if !opts.report.is_empty() {
+        if opts.kinds.is_empty() {
+            opts.kinds = UnusedDiagnosticKind::ALL
+                .iter()
+                .copied()
+                .filter(|kind| kind.is_default())
+                .collect();
+        }
+        for kind in opts.report.clone() {
+            if !opts.kinds.contains(&kind) {
+                opts.kinds.push(kind);
+            }
+        }
+    }
+
+    Ok(opts)
+}
+
+/// Runs `cargo minify` like [`execute`], with the hooks of the pipeline
+/// around its stages
+pub fn execute_with(args: &[String], pipeline: &mut Pipeline) -> Result<()> {
+    let started = Instant::now();
+    let mut opts = parse_options(args)?;
+    let command = opts.command.unwrap_or_default();
+
+    // The copy of a published crate is kept until the end of the run
+    let published = if let Some(spec) = &opts.krate {
+        let applies = opts.apply
+            || opts.apply_staged
+            || opts.apply_semantic
+            || command == Subcommand::Apply
+            || opts.until_stable
+            || opts.serve.is_some();
+        if applies || opts.manifest_path.is_some() {
+            return Err(Error::Args(
+                "--crate only shows the changes, and can't be combined with applying them or \
+                 with --manifest-path",
+            ));
+        }
+        Some(PublishedCrate::fetch(spec)?)
     } else {
-        let unused = unused::get_unused(
-            manifest_path.as_deref(),
-            &crate_resolution,
-            &file_resolution,
-            &opts.kinds,
-        )?;
-        let changes: Vec<_> = cauterize::process_diagnostics(unused, manifest_path.as_ref()).collect();
+        None
+    };
+    if let Some(published) = &published {
+        opts.manifest_path = Some(published.manifest_path().to_string_lossy().into_owned());
+    }
+
+    let single_run = opts.apply_staged
+        || opts.serve.is_some()
+        || opts.save_selection.is_some()
+        || opts.selection.is_some()
+        || opts.check_idempotent;
+    if opts.until_stable && single_run {
+        return Err(Error::Args(
+            "--until-stable applies the changes of every iteration, and can't be combined with \
+             --apply-staged, --serve, --save-selection, --selection or --check-idempotent",
+        ));
+    }
+
+    if opts.help {
+        print_help();
+    } else if command == Subcommand::Formats {
+        println!("{}", message_format::formats_to_json(EXIT_CODES));
+    } else if command == Subcommand::Undo {
+        let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
+        let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+        let restored = backup::restore(metadata.target_directory.as_std_path())?;
 
         if !opts.quiet {
-            if changes.is_empty() {
-                eprintln!("no unused code that can be minified")
+            if restored.is_empty() {
+                eprintln!("nothing to undo")
             } else {
-                for change in &changes {
-                    diff_format::println(change, opts.color);
+                for file in restored {
+                    eprintln!("restored {}", file.display())
                 }
             }
         }
+    } else if command == Subcommand::Compare {
+        let [old, new] = &opts.reports[..] else {
+            return Err(Error::Args("compare takes the old and the new JSON report"));
+        };
+        let comparison = compare::compare(
+            &compare::load(Path::new(old))?,
+            &compare::load(Path::new(new))?,
+        );
 
-        let cargo_root = resolver::get_cargo_metadata(manifest_path.as_deref())?.workspace_root;
+        if opts.message_format.is_machine_readable() {
+            println!("{}", compare::to_json(&comparison));
+        } else if !opts.quiet {
+            for (sign, findings) in [("+", &comparison.appeared), ("-", &comparison.resolved)] {
+                for finding in findings {
+                    println!(
+                        "{sign} {} `{}` in {}:{}",
+                        finding.kind, finding.ident, finding.file, finding.line
+                    );
+                }
+            }
+            eprintln!(
+                "{} appeared, {} resolved",
+                comparison.appeared.len(),
+                comparison.resolved.len()
+            );
+        }
+        if !comparison.appeared.is_empty() {
+            return Err(Error::NewFindings(comparison.appeared.len()));
+        }
+    } else if command == Subcommand::Clean {
+        let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
+        let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+        let target_dir = metadata.target_directory.as_std_path();
+        let cache_dir = cache::dir(target_dir, opts.cache_dir.as_ref().map(Path::new));
+        let artifacts = clean::artifacts(target_dir, &cache_dir, &env::temp_dir());
+        if !opts.dry_run {
+            clean::remove(&artifacts, &cache_dir)?;
+        }
 
-        if opts.apply {
-            use vcs::Status;
-            match vcs::status(&cargo_root) {
-                Status::Error(e) => {
-                    eprintln!("git problem: {}", e)
+        if !opts.quiet {
+            if artifacts.is_empty() {
+                eprintln!("nothing to clean")
+            } else {
+                let verb = if opts.dry_run {
+                    "would remove"
+                } else {
+                    "removed"
+                };
+                for path in artifacts {
+                    eprintln!("{verb} {}", path.display())
                 }
-                Status::NoVCS if !opts.allow_no_vcs => {
+            }
+        }
+    } else if opts.duplicates {
+        let groups = find_duplicates(&opts)?;
+        if opts.message_format.is_machine_readable() {
+            println!(
+                "{}",
+                message_format::duplicates_to_json(opts.message_format, &groups)
+            );
+        } else if !opts.quiet {
+            if groups.is_empty() {
+                eprintln!("no duplicate items found");
+            }
+            for group in &groups {
+                eprintln!("note: {} items are duplicates of each other:", group.len());
+                for duplicate in group {
                     eprintln!(
-                        "no VCS found for this package and `cargo minify` can potentially perform \
-                         destructive changes; if you'd like to suppress this error pass \
-                         `--allow-no-vcs`"
+                        "\t{}:{} `{}`",
+                        duplicate.file_name, duplicate.line, duplicate.ident
                     );
                 }
-                Status::Unclean { dirty, staged }
-                    if !(dirty.is_empty() || opts.allow_dirty)
-                        || !(staged.is_empty() || opts.allow_staged) =>
-                {
-                    eprintln!("working directory contains dirty/staged files:");
-                    for file in dirty {
-                        eprintln!("\t{} (dirty)", file)
-                    }
-                    for file in staged {
-                        eprintln!("\t{} (staged)", file)
-                    }
+            }
+        }
+    } else {
+        // With --until-stable, the changes are already applied, and the
+        // analysis holds those of all the iterations
+        let mut applied = None;
+        let mut analysis = if opts.until_stable {
+            let (analysis, report) = apply_until_stable(&opts, pipeline)?;
+            applied = Some(report);
+            analysis
+        } else {
+            analyze_options(&opts, pipeline)?
+        };
+        if let Some(path) = &opts.selection {
+            let ids = selection::load(Path::new(path))?;
+            let missing = selection::retain(&analysis.workspace_root, &mut analysis.changes, &ids);
+            if !opts.quiet && !missing.is_empty() {
+                eprintln!(
+                    "warning: {} selected change(s) no longer match the code, and were left out",
+                    missing.len()
+                );
+            }
+        }
+
+        if !opts.quiet {
+            for reference in &analysis.bench_references {
+                eprintln!(
+                    "warning: benchmark `{}` refers to `{}`, which will be removed",
+                    reference.bench, reference.ident
+                );
+            }
+
+            for diagnostic in &analysis.cascade_candidates {
+                eprintln!(
+                    "note: {} `{}` in {} is only used by the removed code, pass --cascade to \
+                     remove it as well",
+                    diagnostic.kind, diagnostic.ident, diagnostic.span.file_name
+                );
+            }
+
+            for link in &analysis.doc_links {
+                let file_name = link.file_name.strip_prefix(analysis.workspace_root());
+                let file_name = file_name.unwrap_or(&link.file_name).display();
+                if analysis.diagnostics.iter().any(|d| d.ident == link.ident) {
+                    eprintln!(
+                        "warning: removing `{}` breaks the intra-doc link in {}:{}, see \
+                         --doc-links",
+                        link.ident, file_name, link.line
+                    );
+                } else {
                     eprintln!(
-                        "please fix this or ignore this warning with --allow-dirty and/or \
-                         --allow-staged"
+                        "note: `{}` is kept, as it is linked to in {}:{}",
+                        link.ident, file_name, link.line
                     );
                 }
-                _ => {
-                    // TODO: Remove unwrap
-                    cauterize::commit_changes(changes).unwrap();
+            }
+
+            if !analysis.suppressions.is_empty() {
+                let mut counts = BTreeMap::new();
+                for lint in analysis.suppressions.iter().flat_map(|s| &s.lints) {
+                    *counts.entry(lint).or_insert(0) += 1;
+                }
+                let counts: Vec<_> = counts
+                    .iter()
+                    .map(|(lint, count)| format!("{lint}: {count}"))
+                    .collect();
+                eprintln!(
+                    "note: {} suppression(s) found near dead code, which may hide more unused code \
+                     ({})",
+                    analysis.suppressions.len(),
+                    counts.join(", ")
+                );
+                for suppression in &analysis.suppressions {
+                    eprintln!(
+                        "\t{}:{} allows {}{}",
+                        suppression.file_name,
+                        suppression.line,
+                        suppression.lints.join(", "),
+                        if suppression.module_level {
+                            " in the whole module"
+                        } else {
+                            ""
+                        }
+                    );
                 }
             }
-        } else if !changes.is_empty() {
-            println!("run with --apply to apply these changes")
-        }
-    }
 
-    Ok(())
-}
+            if analysis.approximate {
+                eprintln!(
+                    "warning: the syntax engine doesn't compile the code, so the findings are \
+                     approximate: names are matched without resolving them"
+                );
+            }
 
-pub enum CrateResolutionOptions<'a> {
-    Root,
-    Workspace { exclude: &'a [String] },
-    Package { packages: &'a [String] },
-}
+            for file in &analysis.unparsed {
+                eprintln!(
+                    "warning: {} was left alone, as it can't be parsed (line {}): {}",
+                    file.file_name, file.line, file.error
+                );
+            }
 
-impl<'a> CrateResolutionOptions<'a> {
-    fn from_options(opts: &'a MinifyOptions) -> Result<Self> {
-        match (
-            opts.workspace,
-            !opts.package.is_empty(),
-            !opts.exclude.is_empty(),
-        ) {
-            (true, false, true) | (true, false, false) => Ok(CrateResolutionOptions::Workspace {
-                exclude: &opts.exclude,
-            }),
-            (false, true, false) => Ok(CrateResolutionOptions::Package {
-                packages: &opts.package,
-            }),
-            (false, false, false) => Ok(CrateResolutionOptions::Root),
-            (true, true, false) | (false, true, true) | (true, true, true) => Err(Error::Args(
-                "either specify --workspace and optionally --exclude specific targets, or specify \
-                 specific targets with --package",
-            )),
-            (false, false, true) => Err(Error::Args(
-                "--exclude can only be used in conjunction with --workspace",
-            )),
-        }
-    }
-}
+            if analysis.timed_out {
+                eprintln!(
+                    "warning: the check was stopped by --timeout, so only the findings in the \
+                     crates checked until then are shown"
+                );
+            }
 
-pub enum FileResolutionOptions<'a> {
-    Only(&'a [String]),
-    AllBut(&'a [String]),
-}
+            for hidden in &analysis.hidden_by_lints {
+                let kinds: Vec<_> = hidden.kinds.iter().map(ToString::to_string).collect();
+                let lints: Vec<_> = hidden
+                    .lints
+                    .iter()
+                    .map(|lint| format!("`{lint}`"))
+                    .collect();
+                eprintln!(
+                    "warning: unused {} can't be found in `{}`, as its [lints] allow {}",
+                    kinds.join(", "),
+                    hidden.package,
+                    lints.join(", ")
+                );
+            }
 
-impl<'a> FileResolutionOptions<'a> {
-    fn from_options(opts: &'a MinifyOptions) -> Result<Self> {
-        match (!opts.file.is_empty(), !opts.ignore.is_empty()) {
-            (false, false) | (false, true) => Ok(FileResolutionOptions::AllBut(&opts.ignore)),
-            (true, false) => Ok(FileResolutionOptions::Only(&opts.file)),
-            (true, true) => Err(Error::Args(
-                "either specify --ignore to minify all files except",
+            for diagnostic in &analysis.reported {
+                // Other kinds are only reported as configured or with --report, or offline
+                let reason = match diagnostic.kind {
+                    UnusedDiagnosticKind::Variant | UnusedDiagnosticKind::Field => {
+                        ", as removing it could break the code"
+                    }
+                    _ => "",
+                };
+                eprintln!(
+                    "note: {} `{}` in {}:{} is unused, but is only reported{reason}",
+                    diagnostic.kind,
+                    diagnostic.ident,
+                    diagnostic.span.file_name,
+                    diagnostic.span.line_start
+                );
+                for site in &analysis.field_initializations {
+                    if site.field == diagnostic.ident {
+                        eprintln!("      set in {}:{}", site.file_name, site.line);
+                    }
+                }
+            }
+
+            for dependency in &analysis.unused_dependencies {
+                eprintln!(
+                    "warning: `{}` in the [{}] of `{}` is never used, it can be removed from {}",
+                    dependency.name,
+                    if dependency.dev {
+                        "dev-dependencies"
+                    } else {
+                        "dependencies"
+                    },
+                    dependency.package,
+                    dependency.manifest_path.display()
+                );
+            }
+
+            for feature in &analysis.unused_features {
+                eprintln!(
+                    "warning: the feature `{}` of `{}` gates no code and nothing enables it, it \
+                     can be removed from {}",
+                    feature.name,
+                    feature.package,
+                    feature.manifest_path.display()
+                );
+            }
+
+            for diagnostic in &analysis.test_only_usage {
+                eprintln!(
+                    "note: {} `{}` in {}:{} is only used by tests, consider moving it under \
+                     #[cfg(test)]",
+                    diagnostic.kind,
+                    diagnostic.ident,
+                    diagnostic.span.file_name,
+                    diagnostic.span.line_start
+                );
+            }
+
+            for ghost in &analysis.ghost_usages {
+                eprintln!(
+                    "warning: `{}` of `{}` is only used by derived code ({}), review whether it's \
+                     needed",
+                    ghost.ident,
+                    ghost.package,
+                    ghost.derives.join(", ")
+                );
+            }
+
+            for justification in &analysis.expired_justifications {
+                eprintln!(
+                    "warning: the justification for keeping `{}` in {}:{} expired on {}{}",
+                    justification.ident,
+                    justification.file_name,
+                    justification.line,
+                    justification.until.as_deref().unwrap_or_default(),
+                    justification
+                        .reason
+                        .as_ref()
+                        .map(|reason| format!(" ({reason})"))
+                        .unwrap_or_default()
+                );
+            }
+            if !analysis.justified.is_empty() {
+                eprintln!(
+                    "note: {} finding(s) were kept with a `minify:allow` justification",
+                    analysis.justified.len()
+                );
+            }
+
+            if analysis.suppressed_doc_only > 0 {
+                eprintln!(
+                    "note: {} finding(s) used by code only compiled for documentation were \
+                     ignored, pass --include-doc-only to remove them anyway",
+                    analysis.suppressed_doc_only
+                );
+            }
+
+            if analysis.suppressed_by_convention > 0 {
+                eprintln!(
+                    "note: {} finding(s) in fixture directories were ignored by convention, see \
+                     `ignore-conventions` in [package.metadata.minify]",
+                    analysis.suppressed_by_convention
+                );
+            }
+
+            for file in &analysis.generated {
+                let file_name = file.file_name.strip_prefix(analysis.workspace_root());
+                let file_name = file_name.unwrap_or(&file.file_name).display();
+                let generator = match &file.generator {
+                    Some(generator) => format!(" by {generator}"),
+                    None => String::new(),
+                };
+                match &file.command {
+                    Some(command) => eprintln!(
+                        "note: {file_name} is generated{generator}, so its unused code is left \
+                         alone; regenerate with `{command}`"
+                    ),
+                    None => eprintln!(
+                        "note: {file_name} is generated{generator}, so its unused code is left \
+                         alone; regenerate it instead, or add its command to `generators` in \
+                         [package.metadata.minify]"
+                    ),
+                }
+            }
+
+            if analysis.changes.is_empty() {
+                eprintln!("no unused code that can be minified")
+            } else if command == Subcommand::Stats && !opts.message_format.is_machine_readable() {
+                print_stats(&analysis);
+            }
+        }
+
+        let mut sinks = Sinks::default();
+        if opts.message_format == MessageFormat::Short {
+            sinks.register(sinks::Short);
+        } else if opts.message_format.is_machine_readable() {
+            sinks.register(sinks::MachineReadable::new(opts.message_format));
+        } else if !opts.quiet && command != Subcommand::Stats {
+            sinks.register(sinks::Diffs {
+                color: opts.color,
+                compact: opts.compact,
+                tool: opts.diff_tool.clone(),
+            });
+        }
+        if let Some(path) = &opts.report_html {
+            sinks.register(sinks::Html { path: path.into() });
+        }
+        if let Some(path) = &opts.output_patch {
+            sinks.register(sinks::Patch { path: path.into() });
+        }
+        if let Some(path) = &opts.output_quickfix {
+            sinks.register(sinks::Quickfix { path: path.into() });
+        }
+        if let Some(path) = &opts.metrics {
+            sinks.register(sinks::Metrics {
+                path: path.into(),
+                started,
+            });
+        }
+        let Analysis {
+            diagnostics,
+            changes,
+            ..
+        } = &mut analysis;
+        pipeline.before(Stage::Render, diagnostics, changes)?;
+        sinks.analysis(&analysis)?;
+        let Analysis {
+            diagnostics,
+            changes,
+            ..
+        } = &mut analysis;
+        pipeline.after(Stage::Render, diagnostics, changes)?;
+
+        if opts.check_idempotent {
+            let report = check_idempotent(&opts, &analysis)?;
+            if !opts.quiet {
+                for diagnostic in &report.transitive {
+                    eprintln!(
+                        "note: {} `{}` in {} becomes unused after applying",
+                        diagnostic.kind, diagnostic.ident, diagnostic.span.file_name
+                    );
+                }
+            }
+            if !report.compiles {
+                return Err(Error::NotIdempotent(
+                    "the minified code no longer compiles".to_string(),
+                ));
+            }
+            if let Some(diagnostic) = report.repeated.first() {
+                return Err(Error::NotIdempotent(format!(
+                    "{} `{}` in {} is still reported after it should have been removed",
+                    diagnostic.kind, diagnostic.ident, diagnostic.span.file_name
+                )));
+            }
+        }
+
+        // The findings go along with the analysis when applying
+        let removals = opts
+            .changelog
+            .as_ref()
+            .map(|_| changelog::removals(&analysis));
+        let save_selection = opts.save_selection.as_deref().map(Path::new);
+        let report = if let Some(report) = applied {
+            report
+        } else if let Some(port) = opts.serve {
+            serve::serve(analysis, port, save_selection)?
+        } else if let Some(path) = save_selection {
+            let ids = analysis.changes.iter();
+            let ids = ids.map(|change| selection::change_id(&analysis.workspace_root, change));
+            selection::save(path, ids.collect())?;
+            if !opts.quiet {
+                eprintln!("saved the selection to {}", path.display());
+            }
+            None
+        } else if opts.apply
+            || opts.apply_staged
+            || opts.apply_semantic
+            || command == Subcommand::Apply
+        {
+            Some(apply_with(&mut analysis, pipeline)?)
+        } else {
+            if !analysis.changes.is_empty() && !opts.quiet {
+                eprintln!("run with --apply to apply these changes")
+            }
+            None
+        };
+        let (mut partial, mut refused) = (false, false);
+        if let Some(report) = &report {
+            if !opts.quiet {
+                print_apply_report(report);
+                if let Some(summary) = report.summary() {
+                    eprintln!("{summary}");
+                }
+            }
+            partial = report.is_partial();
+            refused = !report.is_applied();
+            if let (Some(path), Some(removals), ApplyReport::Applied { files, .. }) =
+                (&opts.changelog, &removals, report)
+            {
+                let recorded =
+                    changelog::append(Path::new(path), &justifications::today(), removals, files)?;
+                if recorded > 0 && !opts.quiet {
+                    eprintln!("recorded {recorded} removed items in {path}");
+                }
+            }
+        }
+
+        sinks.finish(report.as_ref())?;
+        if partial {
+            return Err(Error::PartiallyApplied);
+        }
+        if refused {
+            return Err(Error::ApplyRefused);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks and applies the changes over and over, until no more changes are
+/// found or `--max-iterations` is reached; the analysis returned holds the
+/// findings and changes of all the iterations, and the report those applied
+fn apply_until_stable(
+    opts: &MinifyOptions,
+    pipeline: &mut Pipeline,
+) -> Result<(Analysis, Option<ApplyReport>)> {
+    let max_iterations = opts
+        .max_iterations
+        .unwrap_or(fixpoint::DEFAULT_MAX_ITERATIONS);
+    let mut opts = opts.clone();
+    let mut originals = fixpoint::Originals::default();
+    let mut diagnostics = Vec::new();
+    let mut commit = None;
+    let mut iterations = 0;
+
+    // What was left out of the last iteration applied
+    let mut left_out = (Vec::new(), Vec::new(), Vec::new(), None);
+
+    let (mut analysis, refused) = loop {
+        let mut analysis = analyze_options(&opts, pipeline)?;
+        if analysis.changes.is_empty() {
+            break (analysis, None);
+        }
+        if iterations == max_iterations {
+            if !opts.quiet {
+                eprintln!(
+                    "warning: unused code is still found after {iterations} iteration(s), pass a \
+                     higher --max-iterations to remove it as well"
+                );
+            }
+            break (analysis, None);
+        }
+
+        iterations += 1;
+        if iterations > 1 {
+            // The working directory holds the changes of the earlier
+            // iterations, which `undo` reverts along with these
+            analysis.policy.allow_dirty = true;
+            analysis.policy.branch = None;
+            analysis.policy.extend_backup = true;
+            opts.resume = false;
+        }
+        originals.record(&analysis.changes);
+        diagnostics.extend(analysis.diagnostics.iter().cloned());
+
+        let report = apply_with(&mut analysis, pipeline)?;
+        let ApplyReport::Applied {
+            files,
+            skipped,
+            held_back,
+            failed,
+            commit: committed,
+            commit_error,
+            ..
+        } = report
+        else {
+            break (analysis, Some(report));
+        };
+        if !opts.quiet {
+            eprintln!(
+                "iteration {iterations}: applied changes to {} file(s)",
+                files.len()
+            );
+        }
+        let stuck = files.is_empty() || !failed.is_empty() || commit_error.is_some();
+        commit = committed.or(commit);
+        left_out = (skipped, held_back, failed, commit_error);
+        if stuck {
+            break (analysis, None);
+        }
+    };
+    if iterations == 0 {
+        return Ok((analysis, refused));
+    }
+
+    let changes = originals.changes()?;
+    let (skipped, held_back, failed, commit_error) = left_out;
+    let report = refused.unwrap_or(ApplyReport::Applied {
+        files: changes
+            .iter()
+            .map(|change| change.file_name().to_path_buf())
+            .collect(),
+        skipped,
+        held_back,
+        failed,
+        staged: false,
+        branch: opts.vcs_branch.clone(),
+        commit,
+        commit_error,
+    });
+    if !changes.is_empty() {
+        analysis.changes = changes;
+        analysis.diagnostics = diagnostics;
+    }
+
+    Ok((analysis, Some(report)))
+}
+
+/// Applies the changes of the analysis to a copy of the workspace and analyzes
+/// the copy again
+fn check_idempotent(opts: &MinifyOptions, analysis: &Analysis) -> Result<IdempotencyReport> {
+    let copy = WorkspaceCopy::new(&analysis.workspace_root, &analysis.target_directory)?;
+    let manifest_path = match &opts.manifest_path {
+        Some(manifest_path) => PathBuf::from(manifest_path),
+        None => env::current_dir()?.join("Cargo.toml"),
+    };
+    let copy_manifest_path = copy.path_of(&manifest_path)?;
+
+    copy.apply(&analysis.changes)?;
+    let compiles = copy.compiles(&copy_manifest_path)?;
+
+    let mut copy_opts = opts.clone();
+    copy_opts.manifest_path = Some(copy_manifest_path.to_string_lossy().into_owned());
+    copy_opts.resume = false;
+    copy_opts.cache = false;
+    copy_opts.cache_dir = None;
+    let after = analyze_options_with(&copy_opts, false, &mut Pipeline::default())?;
+
+    Ok(idempotency::compare(
+        compiles,
+        &analysis.diagnostics,
+        after.diagnostics,
+    ))
+}
+
+/// Finds the duplicate items in the sources of the selected packages
+fn find_duplicates(opts: &MinifyOptions) -> Result<Vec<Vec<Duplicate>>> {
+    let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
+    let crate_resolution = CrateResolutionOptions::from_options(opts)?;
+
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let file_resolution = FileResolutionOptions::from_options(opts, &metadata)?;
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let targets = resolver::get_targets(manifest_path.as_deref(), &crate_resolution)?;
+    let files: Vec<_> = resolver::source_files(&targets, &resolver::excluded_dirs(&metadata))
+        .into_iter()
+        .filter(|file| {
+            let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+            file_resolution.is_included(&file_name.to_string_lossy())
+        })
+        .collect();
+
+    Ok(duplicates::find(&files, workspace_root))
+}
+
+/// Finds the unused code and the changes that would remove it, given the same
+/// command line arguments as `cargo minify`
+pub fn analyze(args: &[String]) -> Result<Analysis> {
+    analyze_with(args, &mut Pipeline::default())
+}
+
+/// Analyzes like [`analyze`], with the hooks of the pipeline around collecting,
+/// filtering and planning
+pub fn analyze_with(args: &[String], pipeline: &mut Pipeline) -> Result<Analysis> {
+    analyze_options(&parse_options(args)?, pipeline)
+}
+
+fn analyze_options(opts: &MinifyOptions, pipeline: &mut Pipeline) -> Result<Analysis> {
+    analyze_options_with(opts, true, pipeline)
+}
+
+/// The options that the results of an analysis depend on, along with the
+/// version of cargo-minify, hashed to tell the cached results apart
+fn cache_key(opts: &MinifyOptions) -> u64 {
+    let options = [
+        format!("{:?}", env!("CARGO_PKG_VERSION")),
+        format!("{:?}", opts.manifest_path),
+        format!("{:?} {:?} {:?}", opts.package, opts.workspace, opts.exclude),
+        format!("{:?} {:?} {:?}", opts.file, opts.ignore, opts.kinds),
+        format!("{:?}", opts.ignore_bench_usage),
+        format!("{:?} {:?}", opts.build_scripts, opts.include_doc_only),
+        format!("{:?} {:?} {:?}", opts.preset, opts.target, opts.host),
+        format!("{:?} {:?} {:?}", opts.tidy, opts.cascade, opts.doc_links),
+        format!("{:?} {:?}", opts.prune_empty_modules, opts.bin_aggressive),
+        format!(
+            "{:?} {:?} {:?}",
+            opts.offline, opts.engine, opts.strip_allow
+        ),
+        format!("{:?} {:?}", opts.report, opts.test_only_usage),
+        format!(
+            "{:?} {:?} {:?}",
+            opts.unused_deps, opts.unused_features, opts.expand
+        ),
+    ];
+
+    state::content_hash(options.join("\n").as_bytes())
+}
+
+/// Only analyses with `persist` save their pending changes for `--resume`
+fn analyze_options_with(
+    opts: &MinifyOptions,
+    persist: bool,
+    pipeline: &mut Pipeline,
+) -> Result<Analysis> {
+    let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
+    let crate_resolution = CrateResolutionOptions::from_options(opts)?;
+    if !opts
+        .strip_allow
+        .iter()
+        .all(|lint| allows::STRIPPABLE.contains(&lint.as_str()))
+    {
+        return Err(Error::Args(
+            "--strip-allow takes dead_code, unused, unused_imports or unused_macros",
+        ));
+    }
+    // Reused results skip collecting, filtering and planning, hooks and all
+    if !pipeline.is_empty() && (opts.resume || opts.cache || opts.cache_dir.is_some()) {
+        return Err(Error::Args(
+            "--resume and --cache can't be used with pipeline hooks",
+        ));
+    }
+
+    // rustup only finds the pinned toolchain from within the workspace, so it
+    // is looked up from the manifest instead of the current directory
+    let manifest_dir = match manifest_path.as_deref().and_then(Path::parent) {
+        Some(dir) => dir.to_path_buf(),
+        None => env::current_dir()?,
+    };
+    let toolchain = toolchain::pinned(&manifest_dir);
+    // Nothing is compiled by the syntax engine
+    if let (Engine::Cargo, Some(toolchain)) = (opts.engine, &toolchain) {
+        let (installed, missing) = toolchain.installed()?;
+        if !installed {
+            return Err(Error::Toolchain(format!(
+                "the toolchain `{}` pinned by {} is not installed, install it with `rustup \
+                 toolchain install {}`",
+                toolchain.channel,
+                toolchain.file.display(),
+                toolchain.channel
+            )));
+        }
+        if !opts.quiet {
+            for missing in missing {
+                eprintln!(
+                    "warning: the {} pinned by {} is not installed",
+                    missing,
+                    toolchain.file.display()
+                );
+            }
+        }
+    }
+
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let file_resolution = FileResolutionOptions::from_options(opts, &metadata)?;
+    let mut config = Config::from_metadata(&metadata)?;
+    for kind in &opts.report {
+        config.kinds.insert(*kind, KindPolicy::ReportOnly);
+    }
+    let target_directory = metadata.target_directory.clone().into_std_path_buf();
+    let policy_of = |package: &_| config.crate_type_policy(&resolver::crate_types(package));
+    let diff_only_roots = resolver::package_roots(&metadata, |package| {
+        policy_of(package) == CrateTypePolicy::DiffOnly
+    });
+    let excluded_dirs = resolver::excluded_dirs(&metadata);
+    let mut targets = resolver::get_targets(manifest_path.as_deref(), &crate_resolution)?;
+    if !opts.build_scripts {
+        targets.retain(|target| !resolver::is_build_script(target));
+    }
+    targets.retain(|target| {
+        resolver::package_of(&metadata, target)
+            .is_none_or(|package| policy_of(package) != CrateTypePolicy::Skip)
+    });
+
+    let check_options = unused::CheckOptions {
+        ignore_benches: opts.ignore_bench_usage,
+        lib_and_bins_only: opts.preset.is_some_and(|preset| preset.lib_and_bins_only()),
+        target: match (&opts.target, opts.host) {
+            (Some(target), _) => Some(target.clone()),
+            (None, true) => Some(resolver::host_target(toolchain.as_ref())?),
+            (None, false) => resolver::default_target(&metadata, &targets),
+        },
+        toolchain,
+        deadline: opts
+            .timeout
+            .map(|timeout| {
+                let seconds = format!("{}s", timeout.as_secs());
+                Instant::now()
+                    .checked_add(timeout)
+                    .ok_or(unused::InvalidDuration(seconds))
+            })
+            .transpose()?,
+        timed_out: Default::default(),
+        unused_deps: opts.unused_deps,
+        lints: opts
+            .kinds
+            .iter()
+            .filter_map(|kind| kind.allowed_lint())
+            .collect(),
+        force_warn: opts.strip_allow.clone(),
+        offline: opts.offline,
+        crate_usage: Default::default(),
+    };
+
+    let cache = if persist && (opts.cache || opts.cache_dir.is_some()) {
+        Cache::for_commit(
+            &target_directory,
+            opts.cache_dir.as_ref().map(Path::new),
+            metadata.workspace_root.as_std_path(),
+            opts.vcs_backend,
+            cache_key(opts),
+        )
+    } else {
+        None
+    };
+    let resumed = if opts.resume {
+        let workspace_root = metadata.workspace_root.as_std_path();
+        match state::load(&target_directory, workspace_root, cache_key(opts)) {
+            Ok(pending) => Some(pending),
+            Err(err) => {
+                eprintln!("cannot resume: {}; analyzing from scratch", err);
+                None
+            }
+        }
+    } else if let Some(cache) = &cache {
+        match cache.load(metadata.workspace_root.as_std_path()) {
+            Ok(cached) => {
+                if !opts.quiet {
+                    eprintln!("reusing the results cached for commit {}", cache.commit());
+                }
+                Some(cached)
+            }
+            Err(ResumeError::NotFound) => None,
+            Err(err) => {
+                eprintln!(
+                    "cannot use the cached results: {}; analyzing from scratch",
+                    err
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let run = Run {
+        opts,
+        manifest_path,
+        crate_resolution,
+        metadata,
+        file_resolution,
+        config,
+        excluded_dirs,
+        targets,
+        check_options,
+    };
+    let (mut diagnostics, mut changes, kept) = match resumed {
+        Some(saved) => saved,
+        None => {
+            let (mut diagnostics, mut changes, mut kept) = Default::default();
+            pipeline.before(Stage::Collect, &mut diagnostics, &mut changes)?;
+            collect(&run, &mut diagnostics, &mut kept)?;
+            pipeline.after(Stage::Collect, &mut diagnostics, &mut changes)?;
+
+            pipeline.before(Stage::Filter, &mut diagnostics, &mut changes)?;
+            filter(&run, &mut diagnostics, &mut kept)?;
+            pipeline.after(Stage::Filter, &mut diagnostics, &mut changes)?;
+
+            pipeline.before(Stage::Plan, &mut diagnostics, &mut changes)?;
+            plan(&run, &mut diagnostics, &mut changes, &mut kept)?;
+            pipeline.after(Stage::Plan, &mut diagnostics, &mut changes)?;
+            // Resumed and cached results don't run the check that fills it
+            if opts.unused_deps && !run.check_options.timed_out.get() {
+                kept.crate_usage = run.check_options.crate_usage.borrow().clone();
+            }
+
+            let workspace_root = run.metadata.workspace_root.as_std_path();
+            if persist {
+                let key = cache_key(opts);
+                state::save(
+                    &target_directory,
+                    workspace_root,
+                    key,
+                    &diagnostics,
+                    &changes,
+                    &kept,
+                )?;
+            }
+            // Partial results would be reused as if they were complete
+            if let Some(cache) = cache
+                .as_ref()
+                .filter(|_| !run.check_options.timed_out.get())
+            {
+                cache.save(workspace_root, &diagnostics, &changes, &kept)?;
+            }
+
+            (diagnostics, changes, kept)
+        }
+    };
+    let Run {
+        manifest_path,
+        metadata,
+        config,
+        excluded_dirs,
+        targets,
+        check_options,
+        ..
+    } = run;
+    let Kept {
+        reported,
+        annotated,
+        field_initializations,
+        justified,
+        expired_justifications,
+        cascade_candidates,
+        doc_links,
+        test_only_usage,
+        suppressed_by_convention,
+        suppressed_doc_only,
+        unparsed,
+        crate_usage,
+    } = kept;
+
+    // Generated files are left alone, as they would be regenerated anyway
+    let generated = generated::take_generated(&mut changes, &config.generators);
+    if !generated.is_empty() {
+        let files: HashSet<_> = generated
+            .iter()
+            .filter_map(|file| file.file_name.canonicalize().ok())
+            .collect();
+        diagnostics.retain(|diagnostic| {
+            let path = metadata.workspace_root.join(&diagnostic.span.file_name);
+            !path.canonicalize().is_ok_and(|path| files.contains(&path))
+        });
+    }
+
+    let bench_references = unused::bench_references(&targets, &diagnostics);
+    let suppressions = suppressions::near_dead_code(
+        &resolver::source_files(&targets, &excluded_dirs),
+        metadata.workspace_root.as_std_path(),
+        &diagnostics,
+    );
+    let crate_roots = targets
+        .iter()
+        .filter_map(|target| target.src_path.canonicalize().ok())
+        .collect();
+    let mut packages: Vec<(String, PathBuf)> = Vec::new();
+    for package in targets
+        .iter()
+        .filter_map(|target| resolver::package_of(&metadata, target))
+    {
+        let dir = package
+            .manifest_path
+            .parent()
+            .and_then(|dir| dir.canonicalize().ok());
+        if let Some(dir) = dir.filter(|dir| !packages.iter().any(|(_, known)| known == dir)) {
+            packages.push((package.name.clone(), dir));
+        }
+    }
+    apply_order::leaves_first(&mut packages, |dependent, dependency| {
+        resolver::depends_on(&metadata, dependent, dependency)
+    });
+    // Lints allowed in the manifests keep rustc from reporting anything
+    let workspace_manifest =
+        std::fs::read_to_string(metadata.workspace_root.join("Cargo.toml")).unwrap_or_default();
+    let mut hidden_by_lints: Vec<HiddenByLints> = Vec::new();
+    for package in targets
+        .iter()
+        .filter_map(|target| resolver::package_of(&metadata, target))
+    {
+        if hidden_by_lints
+            .iter()
+            .any(|hidden| hidden.package == package.name)
+        {
+            continue;
+        }
+        let manifest = std::fs::read_to_string(&package.manifest_path)?;
+        hidden_by_lints.extend(manifest_lints::hidden_kinds(
+            &package.name,
+            &manifest,
+            &workspace_manifest,
+            &opts.kinds,
+        ));
+    }
+
+    // rustc's reports are only complete when all crates were checked
+    let unused_dependencies =
+        if opts.unused_deps && opts.engine == Engine::Cargo && !check_options.timed_out.get() {
+            unused_deps::find(&metadata, &targets, &crate_usage)
+        } else {
+            Vec::new()
+        };
+    let unused_features = match opts.unused_features {
+        true => unused_features::find(&metadata, &targets, &excluded_dirs),
+        false => Vec::new(),
+    };
+    let ghost_usages = match opts.expand {
+        true => expand::find(&metadata, &targets)?,
+        false => Vec::new(),
+    };
+    let manifest_entries: Vec<_> = unused_dependencies
+        .iter()
+        .map(UnusedDependency::entry)
+        .chain(unused_features.iter().map(UnusedFeature::entry))
+        .collect();
+    changes.extend(unused_deps::removals(&manifest_entries, |file| {
+        let file_name = file.strip_prefix(&metadata.workspace_root).unwrap_or(file);
+        match &manifest_path {
+            Some(manifest_path) => manifest_path.parent().unwrap().join(file_name),
+            None => file_name.to_path_buf(),
+        }
+    }));
+
+    Ok(Analysis {
+        diagnostics,
+        changes,
+        bench_references,
+        suppressed_by_convention,
+        suppressed_doc_only,
+        cascade_candidates,
+        doc_links,
+        suppressions,
+        hidden_by_lints,
+        unused_dependencies,
+        unused_features,
+        ghost_usages,
+        test_only_usage,
+        reported,
+        annotated,
+        field_initializations,
+        justified,
+        expired_justifications,
+        generated,
+        unparsed,
+        timed_out: check_options.timed_out.get(),
+        approximate: opts.engine == Engine::Syntax,
+        workspace_root: metadata.workspace_root.into_std_path_buf(),
+        target_directory,
+        crate_roots,
+        packages,
+        diff_only_roots,
+        policy: ApplyPolicy {
+            allow_dirty: opts.allow_dirty,
+            allow_staged: opts.allow_staged,
+            allow_no_vcs: opts.allow_no_vcs,
+            vcs_backend: opts.vcs_backend,
+            include_proc_macros: opts.include_proc_macros,
+            apply_semantic: opts.apply_semantic,
+            force: opts.force,
+            stage: opts.apply_staged,
+            branch: opts.vcs_branch.clone(),
+            force_branch: opts.force_branch,
+            commit: opts.vcs_commit,
+            extend_backup: false,
+            verify: opts.verify,
+        },
+    })
+}
+
+/// What the stages of an analysis work with: the options, and the workspace
+/// and targets they select
+struct Run<'a> {
+    opts: &'a MinifyOptions,
+    manifest_path: Option<PathBuf>,
+    crate_resolution: CrateResolutionOptions<'a>,
+    metadata: Metadata,
+    file_resolution: FileResolutionOptions,
+    config: Config,
+    excluded_dirs: Vec<PathBuf>,
+    targets: HashSet<Target>,
+    check_options: unused::CheckOptions,
+}
+
+/// Collects the findings of the kinds asked for, by checking the targets
+fn collect(run: &Run, diagnostics: &mut Vec<UnusedDiagnostic>, kept: &mut Kept) -> Result<()> {
+    let Run {
+        opts,
+        manifest_path,
+        crate_resolution,
+        metadata,
+        file_resolution,
+        excluded_dirs,
+        targets,
+        check_options,
+        ..
+    } = run;
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let resolvable = !opts.offline
+        || opts.engine == Engine::Syntax
+        || offline::can_resolve(metadata, check_options.toolchain.as_ref());
+    let found: Vec<_> = if opts.engine == Engine::Syntax {
+        engine::obviously_unused(
+            &resolver::source_files_by_package(metadata, targets, excluded_dirs),
+            workspace_root,
+            file_resolution,
+            &opts.kinds,
+        )
+    } else if resolvable {
+        let diagnostics: Vec<_> = unused::get_unused(
+            manifest_path.as_deref(),
+            crate_resolution,
+            targets.clone(),
+            file_resolution,
+            &opts.kinds,
+            check_options,
+        )?
+        .collect();
+        // What the libraries and binaries leave unused on their own,
+        // but the full check doesn't, is only used by tests
+        if opts.test_only_usage {
+            let without_tests = unused::CheckOptions {
+                lib_and_bins_only: true,
+                timed_out: Default::default(),
+                crate_usage: Default::default(),
+                ..check_options.clone()
+            };
+            kept.test_only_usage = test_only::used_by_tests_only(
+                unused::get_unused(
+                    manifest_path.as_deref(),
+                    crate_resolution,
+                    targets.clone(),
+                    file_resolution,
+                    &opts.kinds,
+                    &without_tests,
+                )?
+                .collect(),
+                &diagnostics,
+            );
+        }
+        diagnostics
+    } else {
+        // Without type information, the findings are only reported
+        if !opts.quiet {
+            eprintln!(
+                "warning: the dependencies can't be resolved offline without a \
+                 Cargo.lock, only reporting the code that is obviously unused by its name"
+            );
+        }
+        kept.reported.extend(engine::obviously_unused(
+            &resolver::source_files_by_package(metadata, targets, excluded_dirs),
+            workspace_root,
+            file_resolution,
+            &opts.kinds,
+        ));
+        Vec::new()
+    };
+    diagnostics.extend(found);
+    // Unused bounds are found syntactically, and only when asked for
+    if opts.kinds.contains(&UnusedDiagnosticKind::Bound) {
+        diagnostics.extend(bounds::get_unused_bounds(
+            &resolver::source_files(targets, excluded_dirs),
+            metadata.workspace_root.as_std_path(),
+            file_resolution,
+        ));
+    }
+    // So are dyn-compatibility shims, which need all sources at once
+    if opts.kinds.contains(&UnusedDiagnosticKind::DynShim) {
+        diagnostics.extend(dyn_shims::get_unused_shims(
+            &resolver::source_files(targets, excluded_dirs),
+            metadata.workspace_root.as_std_path(),
+            file_resolution,
+        ));
+    }
+    // And const and type parameters that are never used, along with their values
+    if opts.kinds.contains(&UnusedDiagnosticKind::ConstParam)
+        || opts.kinds.contains(&UnusedDiagnosticKind::TypeParam)
+    {
+        diagnostics.extend(const_params::get_unused_params(
+            &resolver::source_files(targets, excluded_dirs),
+            metadata.workspace_root.as_std_path(),
+            file_resolution,
+            &opts.kinds,
+        ));
+    }
+    // And trait impls whose methods are never used
+    if opts.kinds.contains(&UnusedDiagnosticKind::TraitImpl) {
+        diagnostics.extend(trait_impls::get_unused_trait_impls(
+            &resolver::source_files(targets, excluded_dirs),
+            metadata.workspace_root.as_std_path(),
+            file_resolution,
+        ));
+    }
+    // And re-exports and `pub` items that nothing mentions, in the
+    // libraries whose dependents in the workspace are all analyzed
+    let with_reexports = opts.kinds.contains(&UnusedDiagnosticKind::Reexport);
+    if with_reexports || opts.kinds.contains(&UnusedDiagnosticKind::UnusedPub) {
+        let analyzed: HashSet<_> = targets
+            .iter()
+            .filter_map(|target| resolver::package_of(metadata, target))
+            .map(|package| package.name.as_str())
+            .collect();
+        let exports = |package: &cargo_metadata::Package| {
+            let depends = |other: &cargo_metadata::Package| {
+                resolver::depends_on(metadata, &other.name, &package.name)
+            };
+            resolver::has_library(package)
+                && metadata
+                    .packages
+                    .iter()
+                    .all(|other| analyzed.contains(other.name.as_str()) || !depends(other))
+        };
+        let exporting: HashSet<_> = targets
+            .iter()
+            .filter(|target| resolver::package_of(metadata, target).is_some_and(exports))
+            .cloned()
+            .collect();
+        let (files, sources) = (
+            resolver::source_files(&exporting, excluded_dirs),
+            resolver::source_files(targets, excluded_dirs),
+        );
+        let workspace_root = metadata.workspace_root.as_std_path();
+        if with_reexports {
+            diagnostics.extend(reexports::get_unused_reexports(
+                &files,
+                &sources,
+                workspace_root,
+                file_resolution,
+            ));
+        }
+        if opts.kinds.contains(&UnusedDiagnosticKind::UnusedPub) {
+            diagnostics.extend(unused_pub::get_unused_pub_items(
+                &files,
+                &sources,
+                workspace_root,
+                file_resolution,
+                with_reexports,
+            ));
+        }
+    }
+    // And the attributes of the stripped lints that no longer allow anything
+    if opts.kinds.contains(&UnusedDiagnosticKind::Allow) && !opts.strip_allow.is_empty() {
+        let findings = diagnostics.clone();
+        diagnostics.extend(allows::get_redundant_allows(
+            &resolver::source_files(targets, excluded_dirs),
+            metadata.workspace_root.as_std_path(),
+            file_resolution,
+            &opts.strip_allow,
+            &findings,
+        ));
+    }
+    // Excluded workspace directories are off limits, even for --file
+    diagnostics.retain(|diagnostic| {
+        let path = metadata.workspace_root.join(&diagnostic.span.file_name);
+        !excluded_dirs.iter().any(|dir| path.starts_with(dir))
+    });
+    // Variants can only be removed when nothing refers to them
+    if opts.kinds.contains(&UnusedDiagnosticKind::Variant) {
+        let mentions = variants::Mentions::new(&resolver::source_files(targets, excluded_dirs));
+        let workspace_root = metadata.workspace_root.as_std_path();
+        let variants: Vec<_>;
+        (variants, *diagnostics) = mem::take(diagnostics).into_iter().partition(|diagnostic| {
+            diagnostic.kind == UnusedDiagnosticKind::Variant
+                && !mentions.is_removable(workspace_root, diagnostic)
+        });
+        kept.reported.extend(variants);
+    }
+    // Fields are only reported, along with the code that sets them
+    if opts.kinds.contains(&UnusedDiagnosticKind::Field) {
+        let mut fields: Vec<_>;
+        (fields, *diagnostics) = mem::take(diagnostics)
+            .into_iter()
+            .partition(|diagnostic| diagnostic.kind == UnusedDiagnosticKind::Field);
+        // Every compilation of the struct reports its fields
+        fields.sort_by_key(|field| (field.span.file_name.clone(), field.ident.clone()));
+        fields.dedup_by(|a, b| a.span.file_name == b.span.file_name && a.ident == b.ident);
+        let files = resolver::source_files(targets, excluded_dirs);
+        for field in &fields {
+            kept.field_initializations.extend(fields::initializations(
+                &files,
+                metadata.workspace_root.as_std_path(),
+                &field.ident,
+            ));
+        }
+        kept.reported.extend(fields);
+    }
+
+    Ok(())
+}
+
+/// Filters the findings by justifications, presets, conventions and the
+/// policies of their kinds, keeping those that aren't removed aside
+fn filter(run: &Run, diagnostics: &mut Vec<UnusedDiagnostic>, kept: &mut Kept) -> Result<()> {
+    let Run {
+        opts,
+        metadata,
+        file_resolution,
+        config,
+        excluded_dirs,
+        targets,
+        ..
+    } = run;
+    // Findings may be kept on purpose, with a `minify:allow` comment
+    (kept.justified, kept.expired_justifications) = justifications::apply(
+        metadata.workspace_root.as_std_path(),
+        diagnostics,
+        &justifications::today(),
+    );
+    // Code used under cfg(doc) or cfg(docsrs) only looks unused
+    if !opts.include_doc_only {
+        let used = doc_only::used_idents(&resolver::source_files(targets, excluded_dirs));
+        let count = diagnostics.len();
+        diagnostics.retain(|diagnostic| !used.contains(&diagnostic.ident));
+        kept.suppressed_doc_only = count - diagnostics.len();
+    }
+    // Presets may protect code that is used from outside of Rust
+    if let Some(preset) = opts.preset {
+        let mut sources = HashMap::new();
+        diagnostics.retain(|diagnostic| {
+            let path = metadata.workspace_root.join(&diagnostic.span.file_name);
+            let src = sources
+                .entry(path.clone())
+                .or_insert_with(|| std::fs::read_to_string(&path).unwrap_or_default());
+            !preset::is_protected(preset, src, diagnostic)
+        });
+    }
+    // Explicitly requested files are never ignored by convention
+    if let FileResolutionOptions::AllBut(_) = file_resolution {
+        let count = diagnostics.len();
+        diagnostics
+            .retain(|diagnostic| !config.is_conventionally_ignored(&diagnostic.span.file_name));
+        kept.suppressed_by_convention = count - diagnostics.len();
+    }
+    // Each kind is removed, annotated, only reported or skipped, as configured
+    let others: Vec<_>;
+    (*diagnostics, others) = mem::take(diagnostics)
+        .into_iter()
+        .partition(|diagnostic| config.kind_policy(diagnostic.kind) == KindPolicy::Remove);
+    // Every compilation of a target reports its findings
+    let mut seen = HashSet::new();
+    for diagnostic in others {
+        let span = &diagnostic.span;
+        let key = (
+            span.file_name.clone(),
+            span.byte_start,
+            diagnostic.ident.clone(),
+        );
+        if !seen.insert(key) {
+            continue;
+        }
+        match config.kind_policy(diagnostic.kind) {
+            KindPolicy::Annotate => kept.annotated.push(diagnostic),
+            KindPolicy::ReportOnly => kept.reported.push(diagnostic),
+            KindPolicy::Remove | KindPolicy::Skip => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Plans the changes that remove the findings
+fn plan(
+    run: &Run,
+    diagnostics: &mut Vec<UnusedDiagnostic>,
+    changes: &mut Vec<Change>,
+    kept: &mut Kept,
+) -> Result<()> {
+    let Run {
+        opts,
+        manifest_path,
+        metadata,
+        excluded_dirs,
+        targets,
+        ..
+    } = run;
+    // Modules whose items all go can go as a whole, along with their files
+    let mut deleted_files = Vec::new();
+    if opts.kinds.contains(&UnusedDiagnosticKind::Module) {
+        let crate_roots = targets
+            .iter()
+            .filter_map(|target| target.src_path.canonicalize().ok())
+            .collect();
+        let modules;
+        (modules, deleted_files) = dead_modules::dead_modules(
+            metadata.workspace_root.as_std_path(),
+            &resolver::source_files(targets, excluded_dirs),
+            &crate_roots,
+            diagnostics,
+        );
+        diagnostics.extend(modules);
+    }
+    let removal = RemovalOptions {
+        tidy: opts.tidy,
+        cascade: opts.cascade,
+    };
+    // Nothing outside of a binary can use its `pub` items
+    let public_items = |target: &cargo_metadata::Target| {
+        opts.bin_aggressive
+            && resolver::package_of(metadata, target)
+                .is_some_and(|package| !resolver::has_library(package))
+    };
+    let crates = cascade::crate_files(
+        targets
+            .iter()
+            .map(|target| (target.src_path.as_std_path(), public_items(target))),
+        metadata.workspace_root.as_std_path(),
+    );
+    let (mut planned, mut cascaded, mut unparsed) = cauterize::process_diagnostics(
+        diagnostics.iter(),
+        &kept.annotated,
+        manifest_path.as_ref(),
+        &crates,
+        removal,
+    );
+    let sources = resolver::source_files(targets, excluded_dirs);
+    // Protecting linked items may bring back links to other items
+    let mut protecting = opts.doc_links == DocLinks::Protect;
+    while protecting {
+        let removed = if opts.cascade { &cascaded[..] } else { &[] };
+        let links = doc_links::broken_links(&sources, &planned, diagnostics.iter().chain(removed));
+        let count = diagnostics.len();
+        diagnostics.retain(|diagnostic| !links.iter().any(|link| link.ident == diagnostic.ident));
+        protecting = diagnostics.len() < count;
+        if protecting {
+            kept.doc_links.extend(links.into_iter().filter(|link| {
+                !diagnostics
+                    .iter()
+                    .any(|diagnostic| diagnostic.ident == link.ident)
+            }));
+            (planned, cascaded, unparsed) = cauterize::process_diagnostics(
+                diagnostics.iter(),
+                &kept.annotated,
+                manifest_path.as_ref(),
+                &crates,
+                removal,
+            );
+        }
+    }
+    // The findings in files that can't be parsed are left alone with them
+    diagnostics.retain(|diagnostic| {
+        !unparsed
+            .iter()
+            .any(|file| file.file_name == diagnostic.span.file_name)
+    });
+    kept.unparsed = unparsed;
+    if opts.cascade {
+        diagnostics.extend(cascaded);
+    } else {
+        kept.cascade_candidates = cascaded;
+    }
+    if opts.doc_links == DocLinks::Fix {
+        doc_links::fix_links(&sources, &mut planned, diagnostics.iter());
+    } else {
+        kept.doc_links.extend(doc_links::broken_links(
+            &sources,
+            &planned,
+            diagnostics.iter(),
+        ));
+    }
+    let deleted: HashSet<_> = deleted_files
+        .iter()
+        .filter_map(|file| file.canonicalize().ok())
+        .collect();
+    planned.retain(|change| {
+        !change
+            .file_name()
+            .canonicalize()
+            .is_ok_and(|file| deleted.contains(&file))
+    });
+    // Named like the other planned
+    let change_name = |file: &Path| {
+        let file_name = file.strip_prefix(&metadata.workspace_root).unwrap_or(file);
+        match &manifest_path {
+            Some(manifest_path) => manifest_path.parent().unwrap().join(file_name),
+            None => file_name.to_path_buf(),
+        }
+    };
+    for file in deleted_files {
+        let original_content = std::fs::read(&file)?;
+        planned.push(Change::removal(change_name(&file), original_content));
+    }
+    if opts.prune_empty_modules {
+        let crate_roots = targets
+            .iter()
+            .filter_map(|target| target.src_path.canonicalize().ok())
+            .collect();
+        empty_modules::prune(&mut planned, &sources, &crate_roots, change_name, opts.tidy);
+    }
+    changes.extend(planned);
+
+    Ok(())
+}
+
+/// Writes the changes of an [`Analysis`] to disk, if the state of the version
+/// control system allows it
+pub fn apply(mut analysis: Analysis) -> Result<ApplyReport> {
+    apply_in_place(&mut analysis)
+}
+
+/// Applies the changes like [`apply`], with the hooks of the pipeline around
+/// writing them; the analysis is left with the changes that were applied
+pub fn apply_with(analysis: &mut Analysis, pipeline: &mut Pipeline) -> Result<ApplyReport> {
+    pipeline.before(
+        Stage::Apply,
+        &mut analysis.diagnostics,
+        &mut analysis.changes,
+    )?;
+    let report = apply_in_place(analysis)?;
+    pipeline.after(
+        Stage::Apply,
+        &mut analysis.diagnostics,
+        &mut analysis.changes,
+    )?;
+
+    Ok(report)
+}
+
+/// Applies the changes like [`apply`], keeping the rest of the analysis
+fn apply_in_place(analysis: &mut Analysis) -> Result<ApplyReport> {
+    use vcs::Status;
+
+    let policy = analysis.policy.clone();
+    if analysis.approximate && !policy.force {
+        return Ok(ApplyReport::Approximate);
+    }
+    let report = match vcs::status(&analysis.workspace_root, policy.vcs_backend) {
+        Status::Error(e) => ApplyReport::VcsError(e),
+        Status::NoVCS if policy.stage || policy.branch.is_some() || policy.commit => {
+            ApplyReport::VcsError(git2::Error::from_str(
+                "--apply-staged, --vcs-branch and --vcs-commit require a git repository",
+            ))
+        }
+        Status::NoVCS if !policy.allow_no_vcs => ApplyReport::NoVcs,
+        Status::Unclean { dirty, staged }
+            if !(dirty.is_empty() || policy.allow_dirty)
+                || !(staged.is_empty() || policy.allow_staged) =>
+        {
+            ApplyReport::Unclean { dirty, staged }
+        }
+        _ => {
+            let (skipped, changes): (Vec<_>, Vec<_>) = std::mem::take(&mut analysis.changes)
+                .into_iter()
+                .partition(|change| !policy.include_proc_macros && analysis.is_diff_only(change));
+            // Semantic changes need a closer review
+            let (held_back, changes): (Vec<_>, Vec<_>) = changes.into_iter().partition(|change| {
+                !policy.apply_semantic && change.category() == ChangeCategory::Semantic
+            });
+            analysis.changes = changes;
+
+            let problems = modules::check_layout(&analysis.changes, &analysis.crate_roots);
+            if !problems.is_empty() {
+                return Ok(ApplyReport::ModuleLayout(problems));
+            }
+
+            let skipped = skipped
+                .iter()
+                .map(|change| change.file_name().to_path_buf())
+                .collect();
+            let held_back = held_back
+                .iter()
+                .map(|change| change.file_name().to_path_buf())
+                .collect();
+
+            if let Some(branch) = &policy.branch {
+                if let Err(e) =
+                    vcs::switch_to_new_branch(&analysis.workspace_root, branch, policy.force_branch)
+                {
+                    return Ok(ApplyReport::VcsError(e));
+                }
+            }
+
+            let mut failed = Vec::new();
+            let mut removed: Vec<PathBuf> = Vec::new();
+            let files = if policy.stage {
+                match vcs::stage(&analysis.workspace_root, &analysis.changes) {
+                    Ok(files) => files,
+                    Err(e) => return Ok(ApplyReport::VcsError(e)),
+                }
+            } else {
+                if policy.extend_backup {
+                    backup::extend(&analysis.target_directory, &analysis.changes)?;
+                } else {
+                    backup::store(&analysis.target_directory, &analysis.changes)?;
+                }
+                let files: Vec<_> = analysis.files().map(PathBuf::from).collect();
+                removed = analysis
+                    .changes
+                    .iter()
+                    .filter(|change| change.is_removal())
+                    .map(|change| change.file_name().to_path_buf())
+                    .collect();
+                let changes = std::mem::take(&mut analysis.changes);
+                let root = &analysis.workspace_root;
+                failed = if policy.verify {
+                    apply_order::apply_verified(changes, &analysis.packages, root)
+                } else {
+                    cauterize::commit_changes(changes, root)
+                        .err()
+                        .unwrap_or_default()
+                };
+                files
+                    .into_iter()
+                    .filter(|file| !failed.iter().any(|(failed, _)| failed == file))
+                    .collect()
+            };
+            state::clear(&analysis.target_directory)?;
+
+            let (commit, commit_error) = if policy.commit {
+                // Staged changes are already in the index, and deleted files
+                // are removed from it rather than added
+                removed.retain(|file| files.contains(file));
+                let to_add: Vec<_> = match policy.stage {
+                    true => Vec::new(),
+                    false => files
+                        .iter()
+                        .filter(|file| !removed.contains(file))
+                        .cloned()
+                        .collect(),
+                };
+                // The files are written either way, so the report lists them
+                match vcs::commit(&analysis.workspace_root, &to_add, &removed, COMMIT_MESSAGE) {
+                    Ok(oid) => (oid.map(|oid| oid.to_string()), None),
+                    Err(e) => (None, Some(e)),
+                }
+            } else {
+                (None, None)
+            };
+
+            ApplyReport::Applied {
+                files,
+                skipped,
+                held_back,
+                failed,
+                staged: policy.stage,
+                branch: policy.branch,
+                commit,
+                commit_error,
+            }
+        }
+    };
+
+    Ok(report)
+}
+
+fn print_apply_report(report: &ApplyReport) {
+    match report {
+        ApplyReport::Applied {
+            files,
+            skipped,
+            held_back,
+            failed,
+            branch,
+            commit,
+            commit_error,
+            ..
+        } => {
+            if let Some(branch) = branch {
+                eprintln!("switched to new branch `{}`", branch);
+            }
+            if let Some(commit) = commit {
+                eprintln!("committed the changes as {}", commit);
+            }
+            if let Some(e) = commit_error {
+                eprintln!("the changes were written to these files, but not committed:");
+                for file in files {
+                    eprintln!("\t{}", file.display())
+                }
+                eprintln!("git problem: {}", e);
+            }
+            if !skipped.is_empty() {
+                eprintln!("skipped changes to proc-macro (or other diff-only) crates:");
+                for file in skipped {
+                    eprintln!("\t{}", file.display())
+                }
+                eprintln!("pass --include-proc-macros to apply these as well");
+            }
+            if !held_back.is_empty() {
+                eprintln!("held back semantic changes, which rewrite signatures or calls, to:");
+                for file in held_back {
+                    eprintln!("\t{}", file.display())
+                }
+                eprintln!("review them and pass --apply-semantic to apply these as well");
+            }
+            if !failed.is_empty() {
+                eprintln!("failed to write the changes to:");
+                for (file, e) in failed {
+                    eprintln!("\t{}: {}", file.display(), e)
+                }
+            }
+        }
+        ApplyReport::VcsError(e) => {
+            eprintln!("git problem: {}", e)
+        }
+        ApplyReport::NoVcs => {
+            eprintln!(
+                "no VCS found for this package and `cargo minify` can potentially perform \
+                 destructive changes; if you'd like to suppress this error pass \
+                 `--allow-no-vcs`"
+            );
+        }
+        ApplyReport::Approximate => {
+            eprintln!(
+                "refusing to apply the approximate changes of --engine syntax; review them, and \
+                 pass --force to apply them anyway"
+            );
+        }
+        ApplyReport::ModuleLayout(problems) => {
+            eprintln!("refusing to apply, as the module files would no longer resolve:");
+            for problem in problems {
+                eprintln!("\t{}", problem)
+            }
+        }
+        ApplyReport::Unclean { dirty, staged } => {
+            eprintln!("working directory contains dirty/staged files:");
+            for file in dirty {
+                eprintln!("\t{} (dirty)", file)
+            }
+            for file in staged {
+                eprintln!("\t{} (staged)", file)
+            }
+            eprintln!(
+                "please fix this or ignore this warning with --allow-dirty and/or \
+                 --allow-staged"
+            );
+        }
+    }
+}
+
+fn print_stats(analysis: &Analysis) {
+    for (kind, count) in analysis.counts() {
+        println!("{kind:<20}{count}");
+    }
+
+    println!(
+        "{} file(s) changed, {} line(s) removed",
+        analysis.changes.len(),
+        analysis.removed_lines()
+    );
+}
+
+pub enum CrateResolutionOptions<'a> {
+    Root,
+    Workspace { exclude: &'a [String] },
+    Package { packages: &'a [String] },
+}
+
+impl<'a> CrateResolutionOptions<'a> {
+    fn from_options(opts: &'a MinifyOptions) -> Result<Self> {
+        match (
+            opts.workspace,
+            !opts.package.is_empty(),
+            !opts.exclude.is_empty(),
+        ) {
+            (true, false, true) | (true, false, false) => Ok(CrateResolutionOptions::Workspace {
+                exclude: &opts.exclude,
+            }),
+            (false, true, false) => Ok(CrateResolutionOptions::Package {
+                packages: &opts.package,
+            }),
+            (false, false, false) => Ok(CrateResolutionOptions::Root),
+            (true, true, false) | (false, true, true) | (true, true, true) => Err(Error::Args(
+                "either specify --workspace and optionally --exclude specific targets, or specify \
+                 specific targets with --package",
+            )),
+            (false, false, true) => Err(Error::Args(
+                "--exclude can only be used in conjunction with --workspace",
+            )),
+        }
+    }
+}
+
+/// The files to minify, as globs relative to the workspace root
+pub enum FileResolutionOptions {
+    Only(Vec<String>),
+    AllBut(Vec<String>),
+}
+
+impl FileResolutionOptions {
+    fn from_options(opts: &MinifyOptions, metadata: &cargo_metadata::Metadata) -> Result<Self> {
+        match (!opts.file.is_empty(), !opts.ignore.is_empty()) {
+            (false, false) | (false, true) => Ok(FileResolutionOptions::AllBut(
+                resolver::package_globs(metadata, &opts.ignore)?,
+            )),
+            (true, false) => Ok(FileResolutionOptions::Only(resolver::package_globs(
+                metadata, &opts.file,
+            )?)),
+            (true, true) => Err(Error::Args(
+                "either specify --ignore to minify all files except",
             )),
         }
     }
@@ -234,3 +2403,290 @@ impl<'a> FileResolutionOptions<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn undoes_what_apply_changed() {
+        let lib_rs = "pub fn used() {}\nfn dead() {}\n";
+        let temp_crate = test_support::TempCrate::new(lib_rs).unwrap();
+        let run = |args: &[&str]| execute(&temp_crate.args(&[args, &["--quiet"]].concat()));
+
+        run(&["stats"]).unwrap();
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+        run(&["apply", "--allow-no-vcs"]).unwrap();
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), "pub fn used() {}\n");
+        run(&["undo"]).unwrap();
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+
+        let err = run(&["shrink"]).unwrap_err();
+        assert!(matches!(err, Error::CommandLine(_)));
+        assert_eq!(err.exit_code(), 1);
+    }
+
+    #[test]
+    fn only_shows_changes_to_proc_macro_crates() {
+        let manifest = "[package]\nname = \"macros\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                        [lib]\nproc-macro = true\n";
+        let lib_rs = "fn dead() {}\n";
+        let temp_crate =
+            test_support::TempCrate::with_files([("Cargo.toml", manifest), ("src/lib.rs", lib_rs)])
+                .unwrap();
+
+        let report = temp_crate.minify(&[]).unwrap();
+        assert!(
+            matches!(&report, ApplyReport::Applied { files, skipped, .. }
+            if files.is_empty() && skipped.len() == 1)
+        );
+        assert!(report.is_partial());
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+        let err = execute(&temp_crate.args(&["apply", "--allow-no-vcs", "--quiet"])).unwrap_err();
+        assert_eq!(err.exit_code(), 6);
+
+        let report = temp_crate.minify(&["--include-proc-macros"]).unwrap();
+        assert!(report.is_applied() && !report.is_partial());
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), "");
+    }
+
+    #[test]
+    fn leaves_build_scripts_alone_unless_asked() {
+        let build_rs = "fn main() {}\n\nfn unused_in_build() {}\n";
+        let temp_crate =
+            test_support::TempCrate::with_files([("build.rs", build_rs), ("src/lib.rs", "")])
+                .unwrap();
+
+        let analysis = temp_crate.analyze(&[]).unwrap();
+        assert!(analysis.diagnostics.is_empty());
+        assert!(temp_crate.minify(&[]).unwrap().is_applied());
+        assert_eq!(temp_crate.read("build.rs").unwrap(), build_rs);
+
+        temp_crate.minify(&["--build-scripts"]).unwrap();
+        assert_eq!(temp_crate.read("build.rs").unwrap(), "fn main() {}\n\n");
+    }
+
+    #[test]
+    fn holds_back_semantic_changes() {
+        let generic_rs = "pub fn id<'a>(x: u8) -> u8 {\n    x\n}\n";
+        let temp_crate = test_support::TempCrate::with_files([
+            ("src/lib.rs", "pub mod generic;\n\nfn dead() {}\n"),
+            ("src/generic.rs", generic_rs),
+        ])
+        .unwrap();
+        let args = ["--kinds", "function", "--kinds", "lifetime"];
+
+        let analysis = temp_crate.analyze(&args).unwrap();
+        let mut categories: Vec<_> = analysis
+            .changes
+            .iter()
+            .map(|change| {
+                (
+                    change.file_name().ends_with("generic.rs"),
+                    change.category(),
+                )
+            })
+            .collect();
+        categories.sort_by_key(|(generic, _)| *generic);
+        assert_eq!(
+            categories,
+            [
+                (false, ChangeCategory::Mechanical),
+                (true, ChangeCategory::Semantic)
+            ]
+        );
+
+        let report = temp_crate.minify(&args).unwrap();
+        assert!(
+            matches!(&report, ApplyReport::Applied { files, held_back, .. }
+            if files.len() == 1 && held_back.len() == 1)
+        );
+        assert_eq!(
+            temp_crate.read("src/lib.rs").unwrap(),
+            "pub mod generic;\n\n"
+        );
+        assert_eq!(temp_crate.read("src/generic.rs").unwrap(), generic_rs);
+
+        let report = temp_crate
+            .minify(&[&args[..], &["--apply-semantic"]].concat())
+            .unwrap();
+        assert!(report.is_applied() && !report.is_partial());
+        let generic_rs = temp_crate.read("src/generic.rs").unwrap();
+        assert_eq!(generic_rs, "pub fn id(x: u8) -> u8 {\n    x\n}\n");
+    }
+
+    #[test]
+    fn writes_quickfix_files() {
+        let lib_rs = "pub fn used() {}\nfn dead() {}\n";
+        let temp_crate = test_support::TempCrate::new(lib_rs).unwrap();
+        let root = temp_crate.root().canonicalize().unwrap();
+        let quickfix = root.join("minify.qf");
+
+        let args = ["--output-quickfix", quickfix.to_str().unwrap(), "--quiet"];
+        execute(&temp_crate.args(&args)).unwrap();
+        let lib_rs_path = root.join("src/lib.rs");
+        assert_eq!(
+            std::fs::read_to_string(&quickfix).unwrap(),
+            format!(
+                "{}:2:4: unused function `dead` (function)\n",
+                lib_rs_path.display()
+            )
+        );
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+    }
+
+    #[test]
+    fn cache_keys_tell_options_apart() {
+        let key = |args: &[&str]| {
+            let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+            cache_key(&parse_options(&args).unwrap())
+        };
+        let removing = key(&["--kinds", "function"]);
+        assert_eq!(removing, key(&["--kinds", "function"]));
+        assert_ne!(
+            removing,
+            key(&["--kinds", "function", "--report", "function"])
+        );
+        assert_ne!(removing, key(&["--kinds", "function", "--test-only-usage"]));
+        for flag in ["--unused-deps", "--unused-features", "--expand"] {
+            assert_ne!(removing, key(&["--kinds", "function", flag]));
+        }
+        assert_eq!(
+            key(&["--report-only", "--quiet"]),
+            key(&["--report", "variant", "--quiet"])
+        );
+    }
+
+    #[test]
+    fn refuses_timeouts_past_the_end_of_time() {
+        let temp_crate = test_support::TempCrate::new("fn dead() {}\n").unwrap();
+        let timeout = |timeout: &str| analyze(&temp_crate.args(&["--timeout", timeout]));
+        let err = timeout("99999999999999999h").err().unwrap();
+        assert!(matches!(err, Error::CommandLine(_)));
+        let err = timeout(&u64::MAX.to_string()).err().unwrap();
+        assert!(matches!(err, Error::InvalidDuration(_)));
+        assert_eq!(err.exit_code(), 1);
+
+        assert!(!timeout("99999999999h").unwrap().timed_out);
+    }
+
+    /// Records the number of findings and changes after each stage, and adds
+    /// a change of its own once the findings are filtered
+    struct Recorder(std::rc::Rc<std::cell::RefCell<Vec<(Stage, usize, usize)>>>);
+
+    impl Hook for Recorder {
+        fn after(&mut self, stage: Stage, findings: Findings<'_>) -> Result<()> {
+            if stage == Stage::Filter {
+                findings
+                    .changes
+                    .push(Change::new("notes.txt".into(), Vec::new(), b"x".to_vec()));
+            }
+            let counts = (stage, findings.diagnostics.len(), findings.changes.len());
+            self.0.borrow_mut().push(counts);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hooks_are_given_the_findings_of_each_stage() {
+        let temp_crate = test_support::TempCrate::new("fn dead() {}\n").unwrap();
+        let log = Default::default();
+        let mut pipeline = Pipeline::default();
+        pipeline.register(Recorder(std::rc::Rc::clone(&log)));
+
+        let analysis = analyze_with(&temp_crate.args(&[]), &mut pipeline).unwrap();
+        assert_eq!(
+            *log.borrow(),
+            [
+                (Stage::Collect, 1, 0),
+                (Stage::Filter, 1, 1),
+                (Stage::Plan, 1, 2)
+            ]
+        );
+        assert_eq!(analysis.changes.len(), 2);
+
+        // Reused results would skip the hooks
+        let resumed = analyze_with(&temp_crate.args(&["--resume"]), &mut pipeline);
+        assert!(matches!(resumed, Err(Error::Args(_))));
+    }
+
+    #[test]
+    fn resumes_kept_findings_with_the_same_options() {
+        let temp_crate = test_support::TempCrate::with_files([
+            (
+                "src/lib.rs",
+                "mod kept;\nfn dead() {}\nconst REPORTED: u8 = 0;\n",
+            ),
+            (
+                "src/kept.rs",
+                "// minify:allow(reason = \"kept\")\nfn justified() {}\n",
+            ),
+        ])
+        .unwrap();
+        let args = [
+            "--kinds", "function", "--kinds", "const", "--report", "const",
+        ];
+        let idents = |diagnostics: &[UnusedDiagnostic]| {
+            let mut idents: Vec<_> = diagnostics.iter().map(|d| d.ident.clone()).collect();
+            idents.sort();
+            idents
+        };
+
+        let analysis = temp_crate.analyze(&args).unwrap();
+        assert_eq!(idents(&analysis.diagnostics), ["dead"]);
+        assert_eq!(idents(&analysis.reported), ["REPORTED"]);
+        assert_eq!(analysis.justified.len(), 1);
+
+        // Only the files with changes are checked when resuming, so the
+        // justification is still restored once it's gone
+        std::fs::write(temp_crate.root().join("src/kept.rs"), "fn justified() {}\n").unwrap();
+        let resumed = temp_crate
+            .analyze(&[&args[..], &["--resume"]].concat())
+            .unwrap();
+        assert_eq!(idents(&resumed.diagnostics), ["dead"]);
+        assert_eq!(idents(&resumed.reported), ["REPORTED"]);
+        assert_eq!(resumed.justified, analysis.justified);
+
+        // Other options analyze from scratch
+        let args = ["--kinds", "function", "--resume"];
+        let fresh = temp_crate.analyze(&args).unwrap();
+        assert_eq!(idents(&fresh.diagnostics), ["dead", "justified"]);
+        assert!(fresh.reported.is_empty() && fresh.justified.is_empty());
+    }
+
+    #[test]
+    fn reuses_unused_dependencies() {
+        let manifest = "[package]\nname = \"minify-test-crate\"\nversion = \"0.1.0\"\n\
+                        edition = \"2021\"\n\n[dependencies]\nhelper = { path = \"helper\" }\n";
+        let temp_crate = test_support::TempCrate::with_files([
+            ("Cargo.toml", manifest),
+            ("src/lib.rs", "fn dead() {}\n"),
+            (
+                "helper/Cargo.toml",
+                "[package]\nname = \"helper\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            ),
+            ("helper/src/lib.rs", ""),
+        ])
+        .unwrap();
+        temp_crate.init_git().unwrap();
+        let cache_dir = env::temp_dir().join(format!("cargo-minify-cache-{}", std::process::id()));
+        let cache_dir = cache_dir.to_string_lossy().into_owned();
+        let dependencies = |extra: &[&str]| {
+            let args = [&["--kinds", "function", "--unused-deps"], extra].concat();
+            let analysis = temp_crate.analyze(&args).unwrap();
+            let names = analysis.unused_dependencies.iter();
+            names
+                .map(|dependency| dependency.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(dependencies(&["--cache-dir", &cache_dir]), ["helper"]);
+        assert!(Path::new(&cache_dir).read_dir().unwrap().next().is_some());
+        // Only the default directory is ignored on its own
+        assert!(!Path::new(&cache_dir).join(".gitignore").exists());
+        assert_eq!(dependencies(&["--cache-dir", &cache_dir]), ["helper"]);
+        assert_eq!(dependencies(&["--resume"]), ["helper"]);
+
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! Lifetime parameters that are never used, as reported by the
+//! allow-by-default `unused_lifetimes` lint, which is enabled when they are
+//! asked for. Types can't have any (rustc rejects them), so these are the
+//! parameters of functions and impl blocks. Those of trait methods, and of
+//! the methods implementing them, are left alone, as the trait and its impls
+//! have to agree on them.
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::Span;
+use syn::{GenericParam, Generics, ImplItem, Item};
+
+use crate::{
+    const_params,
+    unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+/// The findings of an `unused_lifetimes` diagnostic, named by the lifetime
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    if code.code != "unused_lifetimes" || !diagnostic.message.starts_with("lifetime parameter") {
+        return None;
+    }
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .map(|span| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Lifetime,
+            ident: span_text(span),
+            span: span.clone(),
+            suggestion: None,
+            configs: Vec::new(),
+        })
+        .collect();
+
+    Some(found)
+}
+
+/// The span of the lifetime parameter on the line among the generics
+fn param_spans(generics: &Generics, line: usize, name: &str) -> Vec<Span> {
+    let index = generics.params.iter().position(|param| {
+        let GenericParam::Lifetime(param) = param else {
+            return false;
+        };
+        param.lifetime.apostrophe.start().line == line && param.lifetime.to_string() == name
+    });
+
+    index
+        .map(|index| const_params::param_spans(generics, index))
+        .unwrap_or_default()
+}
+
+fn collect_spans(items: &[Item], line: usize, name: &str, spans: &mut Vec<Span>) {
+    for item in items {
+        match item {
+            Item::Fn(function) => spans.extend(param_spans(&function.sig.generics, line, name)),
+            Item::Impl(block) => {
+                spans.extend(param_spans(&block.generics, line, name));
+                if block.trait_.is_some() {
+                    continue;
+                }
+                for item in &block.items {
+                    if let ImplItem::Fn(function) = item {
+                        spans.extend(param_spans(&function.sig.generics, line, name));
+                    }
+                }
+            }
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_spans(items, line, name, spans);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The spans to remove for the unused lifetimes of a file, given by their
+/// [`removal_key`](crate::imports::removal_key)s
+pub fn lifetime_spans(file: &syn::File, key: &str) -> Vec<Span> {
+    let Some((line, name)) = key.split_once(':') else {
+        return Vec::new();
+    };
+    let Ok(line) = line.parse() else {
+        return Vec::new();
+    };
+    let mut spans = Vec::new();
+    collect_spans(&file.items, line, name, &mut spans);
+
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::test::rust_delete;
+
+    use super::*;
+
+    fn remove(src: &str, keys: &[&str]) -> String {
+        let keys = keys
+            .iter()
+            .map(|key| (UnusedDiagnosticKind::Lifetime, key.to_string()));
+        String::from_utf8(rust_delete(src.as_bytes(), keys, false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn removes_lifetimes() {
+        let src = "fn f<'a>() {}\n\
+                   fn g<'a, 'b, T>(x: &'b T) -> &'b T { x }\n\
+                   struct S;\n\
+                   impl<'a> S {\n    fn h<'b>(&self) {}\n}\n\
+                   trait Tr { fn m<'a>(&self); }\n\
+                   impl Tr for S { fn m<'a>(&self) {} }\n";
+        assert_eq!(
+            remove(src, &["1:'a", "2:'a", "4:'a", "5:'b"]),
+            "fn f() {}\n\
+             fn g<'b, T>(x: &'b T) -> &'b T { x }\n\
+             struct S;\n\
+             impl S {\n    fn h(&self) {}\n}\n\
+             trait Tr { fn m<'a>(&self); }\n\
+             impl Tr for S { fn m<'a>(&self) {} }\n"
+        );
+        assert_eq!(remove(src, &["7:'a", "8:'a"]), src);
+    }
+}
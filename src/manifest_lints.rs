@@ -0,0 +1,191 @@
+//! The `[lints]` tables of the manifests (or `[workspace.lints]`, for packages
+//! that inherit them). rustc reports nothing for lints at the `allow` level,
+//! so in packages that allow `dead_code`, `unused_macros`, `unused_imports`, or
+//! a group holding them, the corresponding kinds can't be found at all.
+
+use std::collections::HashSet;
+
+use crate::{resolver::toml_value, unused::UnusedDiagnosticKind};
+
+/// The lints that rustc reports unused code with, along with the groups
+/// holding them
+const LINTS: &[(&str, &[&str])] = &[
+    ("dead_code", &["unused", "warnings"]),
+    ("unused_macros", &["unused", "warnings"]),
+    ("unused_imports", &["unused", "warnings"]),
+    ("unused_extern_crates", &["unused", "warnings"]),
+    ("unused_lifetimes", &["warnings"]),
+    ("unreachable_code", &["unused", "warnings"]),
+    ("unreachable_pub", &["warnings"]),
+];
+
+/// The kinds found by rustc, along with the lint they are reported by
+const KINDS: &[(UnusedDiagnosticKind, &str)] = &[
+    (UnusedDiagnosticKind::Constant, "dead_code"),
+    (UnusedDiagnosticKind::Static, "dead_code"),
+    (UnusedDiagnosticKind::Function, "dead_code"),
+    (UnusedDiagnosticKind::Struct, "dead_code"),
+    (UnusedDiagnosticKind::Enum, "dead_code"),
+    (UnusedDiagnosticKind::Union, "dead_code"),
+    (UnusedDiagnosticKind::TypeAlias, "dead_code"),
+    (UnusedDiagnosticKind::AssociatedFunction, "dead_code"),
+    (UnusedDiagnosticKind::AssociatedConstant, "dead_code"),
+    (UnusedDiagnosticKind::AssociatedType, "dead_code"),
+    (UnusedDiagnosticKind::Variant, "dead_code"),
+    (UnusedDiagnosticKind::Field, "dead_code"),
+    (UnusedDiagnosticKind::MacroDefinition, "unused_macros"),
+    (UnusedDiagnosticKind::Import, "unused_imports"),
+    (UnusedDiagnosticKind::ExternCrate, "unused_extern_crates"),
+    (UnusedDiagnosticKind::Lifetime, "unused_lifetimes"),
+    (UnusedDiagnosticKind::Unreachable, "unreachable_code"),
+    (UnusedDiagnosticKind::UnreachablePub, "unreachable_pub"),
+];
+
+/// A package whose `[lints]` allow some of the lints unused code is reported
+/// by, so that the `kinds` can't be found in it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HiddenByLints {
+    pub package: String,
+    /// The allowed lints (or groups) in the manifest
+    pub lints: Vec<String>,
+    pub kinds: Vec<UnusedDiagnosticKind>,
+}
+
+/// The level and priority of a lint, given as either `"allow"` or
+/// `{ level = "allow", priority = -1 }`
+fn parse_setting(value: &str) -> Option<(String, i64)> {
+    let value = value.trim();
+    let Some(table) = value.strip_prefix('{') else {
+        return Some((value.trim_matches(['"', '\'']).to_string(), 0));
+    };
+
+    let mut level = None;
+    let mut priority = 0;
+    for entry in table.trim_end_matches('}').split(',') {
+        let Some((key, value)) = entry.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(['"', '\'']);
+        match key.trim() {
+            "level" => level = Some(value.to_string()),
+            "priority" => priority = value.parse().ok()?,
+            _ => {}
+        }
+    }
+
+    Some((level?, priority))
+}
+
+/// The table holding the rustc lints of a manifest, which is the one of the
+/// workspace when they are inherited
+fn lints_table<'a>(manifest: &'a str, workspace_manifest: &'a str) -> (&'a str, &'static str) {
+    let inherits = toml_value(manifest, "lints", "workspace").is_some_and(|value| value == "true");
+    if inherits {
+        (workspace_manifest, "workspace.lints.rust")
+    } else {
+        (manifest, "lints.rust")
+    }
+}
+
+/// Whether the lint is allowed in the lints table, directly or by a group;
+/// returns the allowing lint or group. As with cargo, higher priorities win,
+/// and the lint itself wins over its groups at the same priority.
+fn allowed_by(src: &str, table: &str, lint: &str, groups: &[&str]) -> Option<String> {
+    let setting = |name: &str| {
+        let (level, priority) = parse_setting(&toml_value(src, table, name)?)?;
+        Some((priority, name == lint, level, name.to_string()))
+    };
+    let (_, _, level, name) = std::iter::once(lint)
+        .chain(groups.iter().copied())
+        .filter_map(setting)
+        .max_by_key(|(priority, is_lint, ..)| (*priority, *is_lint))?;
+
+    (level == "allow").then_some(name)
+}
+
+/// The rustc kinds that can't be found in a package, as its manifest allows
+/// the lints they are reported by; `kinds` limits the kinds of interest, all
+/// kinds if empty
+pub fn hidden_kinds(
+    package: &str,
+    manifest: &str,
+    workspace_manifest: &str,
+    kinds: &[UnusedDiagnosticKind],
+) -> Option<HiddenByLints> {
+    let (src, table) = lints_table(manifest, workspace_manifest);
+
+    let mut lints = Vec::new();
+    let mut allowed = HashSet::new();
+    for (lint, groups) in LINTS {
+        if let Some(name) = allowed_by(src, table, lint, groups) {
+            allowed.insert(*lint);
+            if !lints.contains(&name) {
+                lints.push(name);
+            }
+        }
+    }
+    let hidden: Vec<_> = KINDS
+        .iter()
+        .filter(|(kind, lint)| {
+            let wanted = (kinds.is_empty() && kind.is_default()) || kinds.contains(kind);
+            allowed.contains(lint) && wanted
+        })
+        .map(|(kind, _)| *kind)
+        .collect();
+
+    (!hidden.is_empty()).then(|| HiddenByLints {
+        package: package.to_string(),
+        lints,
+        kinds: hidden,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings() {
+        assert_eq!(parse_setting("\"allow\""), Some(("allow".to_string(), 0)));
+        assert_eq!(
+            parse_setting("{ level = \"warn\", priority = -1 }"),
+            Some(("warn".to_string(), -1))
+        );
+        assert_eq!(parse_setting("{ priority = 1 }"), None);
+    }
+
+    #[test]
+    fn hidden() {
+        let hidden = hidden_kinds("a", "[lints.rust]\ndead_code = \"allow\"\n", "", &[]).unwrap();
+        assert_eq!(hidden.lints, ["dead_code"]);
+        assert!(!hidden
+            .kinds
+            .contains(&UnusedDiagnosticKind::MacroDefinition));
+        assert!(hidden.kinds.contains(&UnusedDiagnosticKind::Function));
+
+        // The lint itself wins over its group, unless the group has priority
+        let manifest = "[lints.rust]\nunused = \"allow\"\ndead_code = \"warn\"\n";
+        let hidden = hidden_kinds("a", manifest, "", &[]).unwrap();
+        assert_eq!(hidden.lints, ["unused"]);
+        assert_eq!(hidden.kinds, [UnusedDiagnosticKind::MacroDefinition]);
+        let manifest =
+            "[lints.rust]\nunused = { level = \"allow\", priority = 1 }\ndead_code = \"warn\"\n";
+        assert_eq!(
+            hidden_kinds("a", manifest, "", &[]).unwrap().kinds.len(),
+            KINDS.iter().filter(|(kind, _)| kind.is_default()).count()
+        );
+        let imports = hidden_kinds("a", manifest, "", &[UnusedDiagnosticKind::Import]);
+        assert_eq!(imports.unwrap().kinds, [UnusedDiagnosticKind::Import]);
+
+        let workspace = "[workspace.lints.rust]\nunused_macros = \"allow\"\n";
+        assert!(hidden_kinds("a", "[lints]\nworkspace = true\n", workspace, &[]).is_some());
+        assert!(hidden_kinds("a", "", workspace, &[]).is_none());
+        let macros = hidden_kinds(
+            "a",
+            "[lints]\nworkspace = true\n",
+            workspace,
+            &[UnusedDiagnosticKind::Function],
+        );
+        assert!(macros.is_none());
+    }
+}
@@ -0,0 +1,572 @@
+//! Machine readable output. Unlike the human readable output, which is meant
+//! to be skimmed and is silenced by `--quiet`, these formats always go to
+//! stdout, so that scripts can rely on them.
+
+use std::{collections::HashSet, fmt::Write, path::Path, str::FromStr};
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{
+    cauterize::Change,
+    duplicates::Duplicate,
+    hunks::{self, Hunk},
+    report::{Analysis, ApplyReport},
+    unused::{UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+/// The SARIF version of the output of `--message-format sarif`
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+    Sarif,
+    Short,
+}
+
+impl MessageFormat {
+    const HELP: &'static [(&'static str, &'static str)] = &[
+        ("human", "Diffs and notes meant for people (default)"),
+        (
+            "json",
+            "The findings and the removed hunks as a single JSON document",
+        ),
+        (
+            "sarif",
+            "The findings as a SARIF 2.1.0 log, with the removals as fixes",
+        ),
+        (
+            "short",
+            "One line per finding, like rustc's short error format, for quickfix lists",
+        ),
+    ];
+
+    /// Whether the format is a single JSON document
+    pub fn is_machine_readable(&self) -> bool {
+        matches!(self, MessageFormat::Json | MessageFormat::Sarif)
+    }
+}
+
+impl FromStr for MessageFormat {
+    type Err = UnsupportedMessageFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            "sarif" => Ok(MessageFormat::Sarif),
+            "short" => Ok(MessageFormat::Short),
+            _ => Err(UnsupportedMessageFormat),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported message format, pick any of: human, json, sarif, short")]
+pub struct UnsupportedMessageFormat;
+
+/// The analysis as a JSON document; the outcome of applying it can be added
+/// with [`add_apply_report`]
+pub fn to_json(analysis: &Analysis) -> Value {
+    let to_json = |diagnostic: &UnusedDiagnostic| {
+        json!({
+            "kind": diagnostic.kind.to_string(),
+            "ident": diagnostic.ident,
+            "file": diagnostic.span.file_name,
+            "line": diagnostic.span.line_start,
+            "column": diagnostic.span.column_start,
+            "line_end": diagnostic.span.line_end,
+            "column_end": diagnostic.span.column_end,
+            "configs": diagnostic.configs,
+        })
+    };
+    let diagnostics: Vec<_> = analysis.diagnostics.iter().map(to_json).collect();
+    let reported: Vec<_> = analysis.reported.iter().map(to_json).collect();
+    let annotated: Vec<_> = analysis.annotated.iter().map(to_json).collect();
+    let test_only_usage: Vec<_> = analysis.test_only_usage.iter().map(to_json).collect();
+    let changes: Vec<_> = analysis
+        .changes
+        .iter()
+        .map(|change| {
+            let hunks: Vec<_> = hunks::hunks(change).iter().map(hunk_to_json).collect();
+            json!({
+                "file": change.file_name(),
+                "category": change.category().to_string(),
+                "deleted": change.is_removal(),
+                "hunks": hunks,
+            })
+        })
+        .collect();
+
+    let unused_features: Vec<_> = analysis
+        .unused_features
+        .iter()
+        .map(|feature| {
+            json!({
+                "package": feature.package,
+                "manifest_path": feature.manifest_path,
+                "name": feature.name,
+            })
+        })
+        .collect();
+
+    let ghost_usages: Vec<_> = analysis
+        .ghost_usages
+        .iter()
+        .map(|ghost| {
+            json!({
+                "package": ghost.package,
+                "ident": ghost.ident,
+                "derives": ghost.derives,
+            })
+        })
+        .collect();
+
+    let unused_dependencies: Vec<_> = analysis
+        .unused_dependencies
+        .iter()
+        .map(|dependency| {
+            json!({
+                "package": dependency.package,
+                "manifest_path": dependency.manifest_path,
+                "name": dependency.name,
+                "kind": if dependency.dev { "dev" } else { "normal" },
+            })
+        })
+        .collect();
+
+    let field_initializations: Vec<_> = analysis
+        .field_initializations
+        .iter()
+        .map(|site| json!({ "field": site.field, "file": site.file_name, "line": site.line }))
+        .collect();
+
+    let generated: Vec<_> = analysis
+        .generated
+        .iter()
+        .map(|file| {
+            json!({
+                "file": file.file_name,
+                "generator": file.generator,
+                "command": file.command,
+            })
+        })
+        .collect();
+
+    let unparsed: Vec<_> = analysis
+        .unparsed
+        .iter()
+        .map(|file| json!({ "file": file.file_name, "line": file.line, "error": file.error }))
+        .collect();
+
+    json!({
+        "diagnostics": diagnostics,
+        "changes": changes,
+        "generated": generated,
+        "unparsed": unparsed,
+        "reported": reported,
+        "annotated": annotated,
+        "field_initializations": field_initializations,
+        "unused_dependencies": unused_dependencies,
+        "unused_features": unused_features,
+        "ghost_usages": ghost_usages,
+        "test_only_usage": test_only_usage,
+        "removed_lines": analysis.removed_lines(),
+        "timed_out": analysis.timed_out,
+        "approximate": analysis.approximate,
+    })
+}
+
+/// The findings one per line, as in `src/foo.rs:42: unused function `bar`
+/// (function)`, sorted by their location; those that are only reported say
+/// so. The kind in parentheses is the name `--kinds` takes.
+pub fn to_short(analysis: &Analysis) -> String {
+    short(&analysis.diagnostics, &analysis.reported)
+}
+
+/// The findings as lines of a Vim quickfix file, for `--output-quickfix`:
+/// like those of `--message-format short`, with the column of every finding
+/// as well, and the file relative to `root` rather than to the workspace
+/// root, so that `:cfile` finds it from anywhere
+pub fn to_quickfix(analysis: &Analysis, root: &Path) -> String {
+    quickfix(&analysis.diagnostics, &analysis.reported, root)
+}
+
+/// The removed and reported findings, without the duplicates, sorted by their
+/// location; the flag tells the reported ones
+fn findings<'a>(
+    removed: &'a [UnusedDiagnostic],
+    reported: &'a [UnusedDiagnostic],
+) -> Vec<(&'a UnusedDiagnostic, bool)> {
+    let removed = removed.iter().map(|diagnostic| (diagnostic, false));
+    let reported = reported.iter().map(|diagnostic| (diagnostic, true));
+    // Every compilation of a target reports its findings
+    let mut seen = HashSet::new();
+    let mut findings: Vec<_> = removed
+        .chain(reported)
+        .filter(|(diagnostic, _)| {
+            let span = &diagnostic.span;
+            seen.insert((
+                diagnostic.kind,
+                &span.file_name,
+                span.byte_start,
+                &diagnostic.ident,
+            ))
+        })
+        .collect();
+    findings.sort_by_key(|(diagnostic, _)| {
+        let span = &diagnostic.span;
+        (&span.file_name, span.line_start, span.column_start)
+    });
+
+    findings
+}
+
+fn describe(diagnostic: &UnusedDiagnostic, reported: bool) -> String {
+    let kind = diagnostic.kind.to_string().to_lowercase();
+    format!(
+        "unused {} `{}` ({kind}{})",
+        kind.replace('_', " "),
+        diagnostic.ident,
+        if reported { ", only reported" } else { "" },
+    )
+}
+
+fn short(removed: &[UnusedDiagnostic], reported: &[UnusedDiagnostic]) -> String {
+    let mut out = String::new();
+    for (diagnostic, reported) in findings(removed, reported) {
+        let span = &diagnostic.span;
+        let message = describe(diagnostic, reported);
+        let _ = writeln!(out, "{}:{}: {message}", span.file_name, span.line_start);
+    }
+
+    out
+}
+
+fn quickfix(removed: &[UnusedDiagnostic], reported: &[UnusedDiagnostic], root: &Path) -> String {
+    let mut out = String::new();
+    for (diagnostic, reported) in findings(removed, reported) {
+        let span = &diagnostic.span;
+        let file = root.join(&span.file_name);
+        let _ = writeln!(
+            out,
+            "{}:{}:{}: {}",
+            file.display(),
+            span.line_start,
+            span.column_start,
+            describe(diagnostic, reported)
+        );
+    }
+
+    out
+}
+
+fn hunk_to_json(hunk: &Hunk) -> Value {
+    json!({
+        "byte_start": hunk.byte_start,
+        "byte_end": hunk.byte_end,
+        "line_start": hunk.line_start,
+        "line_end": hunk.line_end,
+        "column_start": hunk.column_start,
+        "column_end": hunk.column_end,
+        "removed": hunk.removed,
+    })
+}
+
+/// The analysis as a SARIF log, with a result per diagnostic, and the removal
+/// of its code as the fix
+pub fn to_sarif(analysis: &Analysis) -> Value {
+    let hunks: Vec<(&Change, Vec<Hunk>)> = analysis
+        .changes
+        .iter()
+        .map(|change| (change, hunks::hunks(change)))
+        .collect();
+
+    let results: Vec<_> = analysis
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let fix = hunks
+                .iter()
+                .find(|(change, _)| change.file_name().ends_with(&diagnostic.span.file_name))
+                .and_then(|(change, hunks)| {
+                    hunks
+                        .iter()
+                        .find(|hunk| hunk.contains(diagnostic.span.byte_start as usize))
+                        .map(|hunk| (*change, hunk))
+                });
+
+            let mut result = json!({
+                "ruleId": diagnostic.kind.to_string(),
+                "level": "warning",
+                "message": { "text": message(diagnostic) },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": diagnostic.span.file_name },
+                        "region": {
+                            "startLine": diagnostic.span.line_start,
+                            "startColumn": diagnostic.span.column_start,
+                            "endLine": diagnostic.span.line_end,
+                            "endColumn": diagnostic.span.column_end,
+                        },
+                    },
+                }],
+            });
+            if let Some((change, hunk)) = fix {
+                result["properties"] = json!({ "category": change.category().to_string() });
+                result["fixes"] = json!([{
+                    "description": { "text": format!("remove `{}`", diagnostic.ident) },
+                    "artifactChanges": [{
+                        "artifactLocation": { "uri": diagnostic.span.file_name },
+                        "replacements": [{
+                            "deletedRegion": {
+                                "startLine": hunk.line_start,
+                                "startColumn": hunk.column_start,
+                                "endLine": hunk.line_end,
+                                "endColumn": hunk.column_end,
+                                "snippet": { "text": hunk.removed },
+                            },
+                        }],
+                    }],
+                }]);
+            }
+
+            result
+        })
+        .collect();
+
+    json!({
+        "version": SARIF_VERSION,
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "cargo-minify",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// The duplicate items found with `--duplicates`; in SARIF logs, every group
+/// is a result at its first item, with the other items as related locations
+pub fn duplicates_to_json(format: MessageFormat, groups: &[Vec<Duplicate>]) -> Value {
+    let location = |duplicate: &Duplicate| {
+        json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": duplicate.file_name },
+                "region": { "startLine": duplicate.line },
+            },
+        })
+    };
+
+    if format != MessageFormat::Sarif {
+        let groups: Vec<Vec<_>> = groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|duplicate| {
+                        json!({
+                            "ident": duplicate.ident,
+                            "file": duplicate.file_name,
+                            "line": duplicate.line,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        return json!({ "duplicates": groups });
+    }
+
+    let results: Vec<_> = groups
+        .iter()
+        .map(|group| {
+            let names: Vec<_> = group.iter().map(|d| format!("`{}`", d.ident)).collect();
+            json!({
+                "ruleId": "DUPLICATE",
+                "level": "note",
+                "message": { "text": format!("{} are duplicates", names.join(", ")) },
+                "locations": [location(&group[0])],
+                "relatedLocations": group[1..].iter().map(location).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    json!({
+        "version": SARIF_VERSION,
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "cargo-minify",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn message(diagnostic: &UnusedDiagnostic) -> String {
+    format!("{} `{}` is never used", diagnostic.kind, diagnostic.ident)
+}
+
+/// Adds the outcome of applying the analysis to its machine readable output;
+/// SARIF logs keep it in the properties of the run
+pub fn add_apply_report(format: MessageFormat, output: &mut Value, report: &ApplyReport) {
+    match format {
+        MessageFormat::Human | MessageFormat::Short => {}
+        MessageFormat::Json => output["applied"] = apply_report_to_json(report),
+        MessageFormat::Sarif => {
+            output["runs"][0]["properties"] = json!({ "applied": apply_report_to_json(report) })
+        }
+    }
+}
+
+/// The supported message formats, kinds and exit codes, for wrapper scripts
+pub fn formats_to_json(exit_codes: &[(i32, &str)]) -> Value {
+    let formats: Vec<_> = MessageFormat::HELP
+        .iter()
+        .map(|(name, description)| json!({ "name": name, "description": description }))
+        .collect();
+    let kinds: Vec<_> = UnusedDiagnosticKind::ALL
+        .iter()
+        .map(|kind| json!({ "name": kind.to_string(), "default": kind.is_default() }))
+        .collect();
+    let exit_codes: Vec<_> = exit_codes
+        .iter()
+        .map(|(code, meaning)| json!({ "code": code, "meaning": meaning }))
+        .collect();
+
+    json!({
+        "message_formats": formats,
+        "kinds": kinds,
+        "exit_codes": exit_codes,
+    })
+}
+
+fn apply_report_to_json(report: &ApplyReport) -> Value {
+    match report {
+        ApplyReport::Applied {
+            files,
+            skipped,
+            held_back,
+            failed,
+            staged,
+            branch,
+            commit,
+            commit_error,
+        } => json!({
+            "files": files,
+            "skipped": skipped,
+            "held_back": held_back,
+            "failed": failed
+                .iter()
+                .map(|(file, e)| json!({ "file": file, "error": e.to_string() }))
+                .collect::<Vec<_>>(),
+            "staged": staged,
+            "branch": branch,
+            "commit": commit,
+            "commit_error": commit_error.as_ref().map(ToString::to_string),
+        }),
+        ApplyReport::NoVcs => json!({ "refused": "no-vcs" }),
+        ApplyReport::Unclean { .. } => json!({ "refused": "unclean" }),
+        ApplyReport::Approximate => json!({ "refused": "approximate" }),
+        ApplyReport::ModuleLayout(_) => json!({ "refused": "module-layout" }),
+        ApplyReport::VcsError(_) => json!({ "refused": "vcs-error" }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::spanned::Spanned;
+
+    use super::*;
+    use crate::unused::synthetic_span;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "json".parse::<MessageFormat>().ok(),
+            Some(MessageFormat::Json)
+        );
+        assert_eq!(
+            "short".parse::<MessageFormat>().ok(),
+            Some(MessageFormat::Short)
+        );
+        assert!("xml".parse::<MessageFormat>().is_err());
+    }
+
+    #[test]
+    fn lists_formats_and_exit_codes() {
+        let listed = formats_to_json(crate::error::EXIT_CODES);
+        let formats = listed["message_formats"].as_array().unwrap();
+        let names: Vec<_> = formats
+            .iter()
+            .map(|format| format["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["human", "json", "sarif", "short"]);
+        assert!(names
+            .iter()
+            .all(|name| name.parse::<MessageFormat>().is_ok()));
+
+        let exit_codes = listed["exit_codes"].as_array().unwrap();
+        let codes: Vec<_> = exit_codes
+            .iter()
+            .map(|code| code["code"].as_i64().unwrap())
+            .collect();
+        assert_eq!(codes, (0..=8).collect::<Vec<_>>());
+        assert_eq!(
+            exit_codes[1]["meaning"],
+            "invalid command line arguments or configuration"
+        );
+    }
+
+    #[test]
+    fn lists_every_kind() {
+        let kinds = formats_to_json(&[])["kinds"].as_array().unwrap().clone();
+        assert_eq!(kinds.len(), UnusedDiagnosticKind::ALL.len());
+        for kind in kinds {
+            let name = kind["name"].as_str().unwrap();
+            let parsed: UnusedDiagnosticKind = name.parse().unwrap();
+            assert_eq!(kind["default"], parsed.is_default(), "{name}");
+        }
+    }
+
+    #[test]
+    fn short_lines() {
+        let src = "struct S {\n    x: u8,\n}\nfn bar() {}\nfn helper() {}\n";
+        let file = syn::parse_file(src).unwrap();
+        let finding = |kind, index: usize, ident: &str| UnusedDiagnostic {
+            kind,
+            ident: ident.to_string(),
+            span: synthetic_span("src/foo.rs", src, file.items[index].span()),
+            suggestion: None,
+            configs: Vec::new(),
+        };
+        let removed = [
+            finding(UnusedDiagnosticKind::Function, 2, "helper"),
+            finding(UnusedDiagnosticKind::Function, 1, "bar"),
+            finding(UnusedDiagnosticKind::Function, 1, "bar"),
+        ];
+        let reported = [finding(UnusedDiagnosticKind::Field, 0, "x")];
+        assert_eq!(
+            short(&removed, &reported),
+            "src/foo.rs:1: unused field `x` (field, only reported)\n\
+             src/foo.rs:4: unused function `bar` (function)\n\
+             src/foo.rs:5: unused function `helper` (function)\n"
+        );
+        assert_eq!(
+            quickfix(&removed, &[], Path::new("/ws")),
+            "/ws/src/foo.rs:4:1: unused function `bar` (function)\n\
+             /ws/src/foo.rs:5:1: unused function `helper` (function)\n"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Metrics of a run in the OpenMetrics text format, with `--metrics`, as read
+//! by the textfile collector of Prometheus' node exporter, so that nightly
+//! jobs can feed a dashboard of the unused code without parsing the JSON.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+    time::Duration,
+};
+
+use crate::report::Analysis;
+
+/// The metrics of the analysis, and of the run that took `runtime`
+pub fn openmetrics(analysis: &Analysis, runtime: Duration) -> String {
+    // Every compilation of a target reports its findings
+    let mut findings = BTreeMap::new();
+    let mut seen = HashSet::new();
+    for diagnostic in &analysis.diagnostics {
+        let span = &diagnostic.span;
+        if seen.insert((
+            diagnostic.kind,
+            &span.file_name,
+            span.byte_start,
+            &diagnostic.ident,
+        )) {
+            *findings.entry(diagnostic.kind.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    let gauges = [
+        (
+            "removable_lines",
+            "Lines the changes remove.",
+            analysis.removed_lines().to_string(),
+        ),
+        (
+            "changed_files",
+            "Files the changes touch.",
+            analysis.changes.len().to_string(),
+        ),
+        (
+            "reported",
+            "Unused code that is only reported.",
+            analysis.reported.len().to_string(),
+        ),
+        (
+            "runtime_seconds",
+            "Duration of the run.",
+            format!("{:.3}", runtime.as_secs_f64()),
+        ),
+    ];
+
+    render(&findings, &gauges)
+}
+
+/// The findings by kind, and the gauges by their name, help and value, as
+/// OpenMetrics
+fn render(findings: &BTreeMap<String, usize>, gauges: &[(&str, &str, String)]) -> String {
+    let mut out = String::new();
+    out.push_str("# TYPE cargo_minify_findings gauge\n");
+    out.push_str("# HELP cargo_minify_findings Unused code that is removed, by kind.\n");
+    for (kind, count) in findings {
+        let _ = writeln!(out, "cargo_minify_findings{{kind=\"{kind}\"}} {count}");
+    }
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# TYPE cargo_minify_{name} gauge");
+        let _ = writeln!(out, "# HELP cargo_minify_{name} {help}");
+        let _ = writeln!(out, "cargo_minify_{name} {value}");
+    }
+    out.push_str("# EOF\n");
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_openmetrics() {
+        let findings = BTreeMap::from([("FUNCTION".to_string(), 3), ("IMPORT".to_string(), 1)]);
+        let gauges = [(
+            "removable_lines",
+            "Lines the changes remove.",
+            "42".to_string(),
+        )];
+        assert_eq!(
+            render(&findings, &gauges),
+            "# TYPE cargo_minify_findings gauge\n\
+             # HELP cargo_minify_findings Unused code that is removed, by kind.\n\
+             cargo_minify_findings{kind=\"FUNCTION\"} 3\n\
+             cargo_minify_findings{kind=\"IMPORT\"} 1\n\
+             # TYPE cargo_minify_removable_lines gauge\n\
+             # HELP cargo_minify_removable_lines Lines the changes remove.\n\
+             cargo_minify_removable_lines 42\n\
+             # EOF\n"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Checks that the module files of a crate still resolve unambiguously once a
+//! set of changes has been applied.
+
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use crate::cauterize::Change;
+
+/// A `mod foo;` declaration that doesn't resolve to exactly one file
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutProblem {
+    /// Both `foo.rs` and `foo/mod.rs` exist
+    Ambiguous {
+        declared_in: PathBuf,
+        candidates: [PathBuf; 2],
+    },
+    /// Neither `foo.rs` nor `foo/mod.rs` exists
+    Missing {
+        declared_in: PathBuf,
+        candidates: [PathBuf; 2],
+    },
+}
+
+impl Display for LayoutProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutProblem::Ambiguous {
+                declared_in,
+                candidates: [file, mod_file],
+            } => write!(
+                f,
+                "module declared in {} would be ambiguous: both {} and {} exist",
+                declared_in.display(),
+                file.display(),
+                mod_file.display()
+            ),
+            LayoutProblem::Missing {
+                declared_in,
+                candidates: [file, mod_file],
+            } => write!(
+                f,
+                "module declared in {} would be missing: neither {} nor {} exists",
+                declared_in.display(),
+                file.display(),
+                mod_file.display()
+            ),
+        }
+    }
+}
+
+/// Checks every out-of-line `mod` declaration in the proposed contents of
+/// `changes`. Files in `crate_roots` resolve their modules next to themselves,
+/// just like `mod.rs` files do.
+pub fn check_layout(changes: &[Change], crate_roots: &HashSet<PathBuf>) -> Vec<LayoutProblem> {
+    let mut problems = Vec::new();
+
+    for change in changes {
+        let src = String::from_utf8_lossy(change.proposed_content());
+        let Ok(file) = syn::parse_file(&src) else {
+            continue;
+        };
+
+        let declared_in = change.file_name();
+        let is_root = declared_in.file_name().is_some_and(|name| name == "mod.rs")
+            || declared_in
+                .canonicalize()
+                .is_ok_and(|path| crate_roots.contains(&path));
+        let dir = match (declared_in.parent(), declared_in.file_stem()) {
+            (Some(parent), _) if is_root => parent.to_path_buf(),
+            (Some(parent), Some(stem)) => parent.join(stem),
+            _ => continue,
+        };
+
+        check_items(&file.items, &dir, declared_in, changes, &mut problems);
+    }
+
+    problems
+}
+
+fn check_items(
+    items: &[syn::Item],
+    dir: &Path,
+    declared_in: &Path,
+    changes: &[Change],
+    problems: &mut Vec<LayoutProblem>,
+) {
+    for item in items {
+        let syn::Item::Mod(module) = item else {
+            continue;
+        };
+        // Modules with an explicit `#[path]` don't follow the naming convention
+        if module.attrs.iter().any(|attr| attr.path().is_ident("path")) {
+            continue;
+        }
+
+        let name = module.ident.to_string();
+        match &module.content {
+            Some((_, items)) => {
+                check_items(items, &dir.join(&name), declared_in, changes, problems)
+            }
+            None => {
+                let candidates = [
+                    dir.join(format!("{name}.rs")),
+                    dir.join(&name).join("mod.rs"),
+                ];
+                let declared_in = declared_in.to_path_buf();
+                match candidates
+                    .each_ref()
+                    .map(|path| exists_after(path, changes))
+                {
+                    [true, true] => problems.push(LayoutProblem::Ambiguous {
+                        declared_in,
+                        candidates,
+                    }),
+                    [false, false] => problems.push(LayoutProblem::Missing {
+                        declared_in,
+                        candidates,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// The files of the crate with the given root file, found by following its
+/// out-of-line `mod` declarations, including those with a `#[path]`
+pub fn crate_files(root: &Path) -> HashSet<PathBuf> {
+    let mut files = HashSet::new();
+    collect_files(root, true, &mut files);
+    files
+}
+
+fn collect_files(file: &Path, is_root: bool, files: &mut HashSet<PathBuf>) {
+    let Ok(path) = file.canonicalize() else {
+        return;
+    };
+    if !files.insert(path) {
+        return;
+    }
+    let Ok(src) = std::fs::read_to_string(file) else {
+        return;
+    };
+    let Ok(parsed) = syn::parse_file(&src) else {
+        return;
+    };
+
+    let (Some(parent), Some(dir)) = (file.parent(), module_dir(file, is_root)) else {
+        return;
+    };
+    collect_declared(&parsed.items, &dir, parent, files);
+}
+
+/// The directory the `mod` declarations of a file refer to: next to it for
+/// crate roots and `mod.rs` files, else in the directory named after it
+pub fn module_dir(file: &Path, is_root: bool) -> Option<PathBuf> {
+    let parent = file.parent()?;
+    match file.file_stem() {
+        Some(stem) if !is_root && file.file_name().is_some_and(|name| name != "mod.rs") => {
+            Some(parent.join(stem))
+        }
+        _ => Some(parent.to_path_buf()),
+    }
+}
+
+/// The file of a `mod name;` declaration in `dir`, if exactly one of
+/// `name.rs` and `name/mod.rs` exists
+pub fn declared_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    match [
+        dir.join(format!("{name}.rs")),
+        dir.join(name).join("mod.rs"),
+    ] {
+        [file, mod_file] if file.is_file() && !mod_file.is_file() => Some(file),
+        [file, mod_file] if !file.is_file() && mod_file.is_file() => Some(mod_file),
+        _ => None,
+    }
+}
+
+/// Follows the `mod` declarations in `items`; `dir` is where their files are
+/// by convention, and `path_dir` what a `#[path]` is relative to
+fn collect_declared(
+    items: &[syn::Item],
+    dir: &Path,
+    path_dir: &Path,
+    files: &mut HashSet<PathBuf>,
+) {
+    for item in items {
+        let syn::Item::Mod(module) = item else {
+            continue;
+        };
+        let path = module.attrs.iter().find_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(path),
+                    ..
+                }) => Some(path_dir.join(path.value())),
+                _ => None,
+            },
+            _ => None,
+        });
+
+        let name = module.ident.to_string();
+        match (&module.content, path) {
+            (Some((_, items)), _) => {
+                let dir = dir.join(&name);
+                collect_declared(items, &dir, &dir, files)
+            }
+            (None, Some(path)) => collect_files(&path, true, files),
+            (None, None) => {
+                let candidates = [
+                    dir.join(format!("{name}.rs")),
+                    dir.join(&name).join("mod.rs"),
+                ];
+                if let Some(file) = candidates.iter().find(|path| path.is_file()) {
+                    collect_files(file, false, files);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the file will exist once the changes are applied
+fn exists_after(path: &Path, changes: &[Change]) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    let removed = changes.iter().any(|change| {
+        change.is_removal()
+            && change
+                .file_name()
+                .canonicalize()
+                .is_ok_and(|file| file == path)
+    });
+    !removed && path.is_file()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    fn change(file_name: PathBuf, content: &str) -> Change {
+        Change::new(file_name, Vec::new(), content.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn ambiguous_module() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod foo;"),
+            ("src/foo.rs", ""),
+            ("src/foo/mod.rs", ""),
+        ])
+        .unwrap();
+        let lib_rs = temp_crate.root().join("src/lib.rs");
+        let roots = HashSet::from([lib_rs.canonicalize().unwrap()]);
+
+        let problems = check_layout(&[change(lib_rs, "mod foo;")], &roots);
+        assert!(matches!(problems[..], [LayoutProblem::Ambiguous { .. }]));
+    }
+
+    #[test]
+    fn files_of_crates() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod foo; #[path = \"other.rs\"] mod bar;"),
+            ("src/main.rs", "mod foo;"),
+            ("src/foo.rs", "mod baz { mod qux; }"),
+            ("src/foo/baz/qux.rs", ""),
+            ("src/other.rs", ""),
+            ("src/unused.rs", ""),
+        ])
+        .unwrap();
+        let file = |name: &str| temp_crate.root().join(name).canonicalize().unwrap();
+
+        assert_eq!(
+            crate_files(&temp_crate.root().join("src/lib.rs")),
+            HashSet::from([
+                file("src/lib.rs"),
+                file("src/foo.rs"),
+                file("src/foo/baz/qux.rs"),
+                file("src/other.rs"),
+            ])
+        );
+        assert_eq!(crate_files(&temp_crate.root().join("src/main.rs")).len(), 3);
+    }
+
+    #[test]
+    fn nested_modules() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod foo;"),
+            ("src/foo.rs", "mod bar; mod baz { mod qux; }"),
+            ("src/foo/bar.rs", ""),
+        ])
+        .unwrap();
+        let lib_rs = temp_crate.root().join("src/lib.rs");
+        let foo_rs = temp_crate.root().join("src/foo.rs");
+        let roots = HashSet::from([lib_rs.canonicalize().unwrap()]);
+
+        let problems = check_layout(
+            &[change(foo_rs.clone(), "mod bar; mod baz { mod qux; }")],
+            &roots,
+        );
+        assert_eq!(
+            problems,
+            vec![LayoutProblem::Missing {
+                declared_in: foo_rs,
+                candidates: [
+                    temp_crate.root().join("src/foo/baz/qux.rs"),
+                    temp_crate.root().join("src/foo/baz/qux/mod.rs"),
+                ],
+            }]
+        );
+    }
+}
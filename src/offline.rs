@@ -0,0 +1,104 @@
+//! Running without network access, with `--offline`. Without a `Cargo.lock`,
+//! cargo can only check a workspace if its dependencies resolve from what was
+//! downloaded before. When they don't, unused code is found by name instead,
+//! as with `--engine syntax`; unless that was asked for, such findings are
+//! only reported.
+
+use std::{
+    fs, io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use cargo_metadata::Metadata;
+
+use crate::{state, toolchain::Toolchain};
+
+/// Whether the dependencies of the workspace can be resolved offline; without
+/// a `Cargo.lock`, one is generated from the downloaded packages, just like a
+/// check would. That happens in a copy of the manifests, so the workspace is
+/// left as it is.
+pub fn can_resolve(metadata: &Metadata, toolchain: Option<&Toolchain>) -> bool {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    if workspace_root.join("Cargo.lock").is_file() {
+        return true;
+    }
+
+    // Within the target directory, cargo still finds the configuration of the
+    // workspace, such as vendored sources
+    let Ok(tool_dir) = state::tool_dir(metadata.target_directory.as_std_path()) else {
+        return false;
+    };
+    let copy = tool_dir.join(format!("resolve-{}", std::process::id()));
+    let resolved = copy_manifests(metadata, &copy).is_ok() && {
+        let mut command = Command::new("cargo");
+        if let Some(toolchain) = toolchain {
+            toolchain.apply(&mut command);
+        }
+        command
+            .args(["generate-lockfile", "--offline", "--quiet"])
+            .arg("--manifest-path")
+            .arg(copy.join("Cargo.toml"))
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    let _ = fs::remove_dir_all(&copy);
+
+    resolved
+}
+
+/// Copies the manifests of the workspace to `dir`, where they are in the same
+/// places, along with empty sources for their targets
+fn copy_manifests(metadata: &Metadata, dir: &Path) -> io::Result<()> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let place = |path: &Path| {
+        let relative = path
+            .strip_prefix(workspace_root)
+            .map_err(io::Error::other)?;
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::Result::Ok(path)
+    };
+
+    let root_manifest = workspace_root.join("Cargo.toml");
+    fs::copy(&root_manifest, place(&root_manifest)?)?;
+    for package in &metadata.packages {
+        let manifest_path = package.manifest_path.as_std_path();
+        fs::copy(manifest_path, place(manifest_path)?)?;
+        for target in &package.targets {
+            fs::write(place(target.src_path.as_std_path())?, "")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{resolver, test_support::TempCrate};
+
+    #[test]
+    fn resolves_without_writing_a_lockfile() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "mod a;\n"),
+            ("src/a.rs", "fn unused() {}\n"),
+            ("src/bin/tool.rs", "fn main() {}\n"),
+        ])
+        .unwrap();
+        let metadata = resolver::get_cargo_metadata(Some(&temp_crate.manifest_path())).unwrap();
+        assert!(can_resolve(&metadata, None));
+        assert!(!temp_crate.root().join("Cargo.lock").exists());
+        assert_eq!(temp_crate.read("src/a.rs").unwrap(), "fn unused() {}\n");
+
+        let manifest = "[package]\nname = \"minify-test-crate\"\nversion = \"0.1.0\"\n\
+                        edition = \"2021\"\n\n[dependencies]\nnever-downloaded-crate = \"9\"\n";
+        std::fs::write(temp_crate.manifest_path(), manifest).unwrap();
+        let metadata = resolver::get_cargo_metadata(Some(&temp_crate.manifest_path())).unwrap();
+        assert!(!can_resolve(&metadata, None));
+        assert!(!temp_crate.root().join("Cargo.lock").exists());
+    }
+}
@@ -0,0 +1,186 @@
+//! The stages of a run, in order: collecting the findings, filtering them by
+//! justifications, presets, conventions and the policies of their kinds,
+//! planning the changes that remove them, rendering the results to the sinks,
+//! and applying the changes. Embedders register hooks that run before and
+//! after each stage, and may edit the findings and changes that the later
+//! stages are given, as a baseline dropping known findings after
+//! [`Stage::Filter`] would.
+//!
+//! Results resumed with `--resume` or reused from `--cache` skip collecting,
+//! filtering and planning, so these options are refused when hooks are
+//! registered. The apply hooks only run when the changes are written by this
+//! process, so not with `--serve`.
+
+use std::fmt;
+
+use crate::{cauterize::Change, error::Result, unused::UnusedDiagnostic};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Collect,
+    Filter,
+    Plan,
+    Render,
+    Apply,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Collect => "collect",
+            Stage::Filter => "filter",
+            Stage::Plan => "plan",
+            Stage::Render => "render",
+            Stage::Apply => "apply",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// What the stages have produced so far; there are no changes until
+/// [`Stage::Plan`] is done
+pub struct Findings<'a> {
+    pub diagnostics: &'a mut Vec<UnusedDiagnostic>,
+    pub changes: &'a mut Vec<Change>,
+}
+
+pub trait Hook {
+    fn before(&mut self, _stage: Stage, _findings: Findings<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    fn after(&mut self, _stage: Stage, _findings: Findings<'_>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The hooks of a run, which run in the order they were registered
+#[derive(Default)]
+pub struct Pipeline {
+    hooks: Vec<Box<dyn Hook>>,
+}
+
+impl Pipeline {
+    pub fn register(&mut self, hook: impl Hook + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub(crate) fn before(
+        &mut self,
+        stage: Stage,
+        diagnostics: &mut Vec<UnusedDiagnostic>,
+        changes: &mut Vec<Change>,
+    ) -> Result<()> {
+        self.hooks.iter_mut().try_for_each(|hook| {
+            let (diagnostics, changes) = (&mut *diagnostics, &mut *changes);
+            hook.before(
+                stage,
+                Findings {
+                    diagnostics,
+                    changes,
+                },
+            )
+        })
+    }
+
+    pub(crate) fn after(
+        &mut self,
+        stage: Stage,
+        diagnostics: &mut Vec<UnusedDiagnostic>,
+        changes: &mut Vec<Change>,
+    ) -> Result<()> {
+        self.hooks.iter_mut().try_for_each(|hook| {
+            let (diagnostics, changes) = (&mut *diagnostics, &mut *changes);
+            hook.after(
+                stage,
+                Findings {
+                    diagnostics,
+                    changes,
+                },
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::unused::{synthetic_span, UnusedDiagnosticKind};
+
+    /// Records the stages it's run around, along with the findings it's given
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl Hook for Recorder {
+        fn before(&mut self, stage: Stage, _findings: Findings<'_>) -> Result<()> {
+            self.0.borrow_mut().push(format!("before {stage}"));
+            Ok(())
+        }
+
+        fn after(&mut self, stage: Stage, findings: Findings<'_>) -> Result<()> {
+            let count = findings.diagnostics.len();
+            self.0.borrow_mut().push(format!("after {stage}: {count}"));
+            Ok(())
+        }
+    }
+
+    /// Drops the findings of a kind once they are filtered, like a baseline
+    struct Baseline(UnusedDiagnosticKind);
+
+    impl Hook for Baseline {
+        fn after(&mut self, stage: Stage, findings: Findings<'_>) -> Result<()> {
+            if stage == Stage::Filter {
+                findings
+                    .diagnostics
+                    .retain(|diagnostic| diagnostic.kind != self.0);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn runs_hooks_in_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut pipeline = Pipeline::default();
+        pipeline.register(Baseline(UnusedDiagnosticKind::Constant));
+        pipeline.register(Recorder(log.clone()));
+
+        let diagnostic = |kind| UnusedDiagnostic {
+            kind,
+            ident: "x".to_string(),
+            span: synthetic_span("src/lib.rs", "x", proc_macro2::Span::call_site()),
+            suggestion: None,
+            configs: Vec::new(),
+        };
+        let mut diagnostics = vec![
+            diagnostic(UnusedDiagnosticKind::Constant),
+            diagnostic(UnusedDiagnosticKind::Function),
+        ];
+        let mut changes = Vec::new();
+        for stage in [Stage::Filter, Stage::Plan] {
+            pipeline
+                .before(stage, &mut diagnostics, &mut changes)
+                .unwrap();
+            pipeline
+                .after(stage, &mut diagnostics, &mut changes)
+                .unwrap();
+        }
+
+        assert_eq!(
+            *log.borrow(),
+            [
+                "before filter",
+                "after filter: 1",
+                "before plan",
+                "after plan: 1"
+            ]
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, UnusedDiagnosticKind::Function);
+    }
+}
@@ -0,0 +1,125 @@
+//! Presets adjust the defaults of the tool to a kind of project.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::unused::UnusedDiagnostic;
+
+/// Attributes of items that are used from outside of Rust (by the linker, or
+/// the hardware) on embedded targets, and must be kept
+const EMBEDDED_PROTECTED_ATTRIBUTES: &[&str] = &[
+    "no_mangle",
+    "export_name",
+    "link_section",
+    "used",
+    "interrupt",
+    "exception",
+    "entry",
+    "pre_init",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// For firmware and other `no_std` projects
+    Embedded,
+}
+
+impl Preset {
+    /// Whether unused code with this attribute must be kept anyway; `attr` is
+    /// the text between `#[` and `]`
+    pub fn protects(&self, attr: &str) -> bool {
+        // Edition 2024 wraps some of these in `unsafe(...)`
+        let attr = attr.strip_prefix("unsafe(").unwrap_or(attr);
+        let name = attr
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .next()
+            .unwrap_or_default();
+        let name = name.rsplit("::").next().unwrap_or_default();
+
+        match self {
+            Preset::Embedded => EMBEDDED_PROTECTED_ATTRIBUTES.contains(&name),
+        }
+    }
+
+    /// Whether only libraries and binaries are checked, as tests, examples
+    /// and benchmarks usually need std
+    pub fn lib_and_bins_only(&self) -> bool {
+        match self {
+            Preset::Embedded => true,
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = UnsupportedPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "embedded" => Ok(Preset::Embedded),
+            _ => Err(UnsupportedPreset),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported preset, pick any of: embedded")]
+pub struct UnsupportedPreset;
+
+/// Whether the item of the diagnostic carries an attribute the preset protects.
+/// The attributes are found textually, on the lines above the item, as syn
+/// can't parse the `unsafe(...)` attributes of edition 2024.
+pub fn is_protected(preset: Preset, src: &str, diagnostic: &UnusedDiagnostic) -> bool {
+    let lines: Vec<_> = src.lines().collect();
+    let item_line = diagnostic
+        .span
+        .line_start
+        .saturating_sub(1)
+        .min(lines.len());
+
+    // The item's own line may start with attributes as well
+    let own_line = lines.get(item_line).copied().unwrap_or_default();
+    lines[..item_line]
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .take_while(|line| line.starts_with("#[") || line.starts_with("//"))
+        .chain([own_line.trim()])
+        .flat_map(|line| line.split("#[").skip(1))
+        .any(|attr| preset.protects(attr))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn protected(src: &str, line: usize) -> bool {
+        let mut diagnostic = UnusedDiagnostic {
+            kind: crate::UnusedDiagnosticKind::Function,
+            ident: String::new(),
+            span: crate::unused::synthetic_span("lib.rs", src, proc_macro2::Span::call_site()),
+            suggestion: None,
+            configs: Vec::new(),
+        };
+        diagnostic.span.line_start = line;
+        is_protected(Preset::Embedded, src, &diagnostic)
+    }
+
+    #[test]
+    fn embedded_protects_linker_items() {
+        assert!(protected("#[no_mangle]\nfn handler() {}", 2));
+        assert!(protected(
+            "#[unsafe(no_mangle)]\n/// Docs\nfn handler() {}",
+            3
+        ));
+        assert!(protected(
+            "mod vectors {\n    #[unsafe(link_section = \".vector_table\")]\n    static V: u32 = 0;\n}",
+            3
+        ));
+        assert!(protected("#[cortex_m_rt::exception] fn SysTick() {}", 1));
+        assert!(!protected(
+            "#[no_mangle]\nfn other() {}\n\n#[inline]\nfn helper() {}",
+            5
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+//! Published crates, with `--crate <name@version>`: the crate is downloaded by
+//! cargo, through a throwaway package depending on exactly that version, and
+//! its sources are copied to a temporary directory to be analyzed there. This
+//! shows how much dead weight a dependency carries before vendoring or forking
+//! it; nothing is ever applied, as the copy is removed afterwards.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::semver::VersionReq;
+
+use crate::idempotency;
+
+/// A copy of the sources of a published crate, removed when dropped
+pub struct PublishedCrate {
+    root: PathBuf,
+    manifest_path: PathBuf,
+}
+
+/// The name and version requirement of `name@version`, or of just `name` for
+/// the latest version; the version must be one cargo would accept, as it's
+/// written into a manifest
+fn parse_spec(spec: &str) -> Option<(&str, String)> {
+    let (name, version) = match spec.split_once('@') {
+        Some((name, version)) => (name, format!("={version}")),
+        None => (spec, "*".to_string()),
+    };
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if name.is_empty() || !name.chars().all(is_name) || VersionReq::parse(&version).is_err() {
+        return None;
+    }
+
+    Some((name, version))
+}
+
+impl PublishedCrate {
+    /// Downloads the crate, as given by `name@version`, and copies its sources
+    pub fn fetch(spec: &str) -> io::Result<Self> {
+        let Some((name, version)) = parse_spec(spec) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{spec}` is not a crate, give it as <name>@<version>"),
+            ));
+        };
+        let root = std::env::temp_dir().join(format!("cargo-minify-crate-{}", std::process::id()));
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+
+        // The copy is removed along with the package fetching it, also when
+        // anything goes wrong in between
+        let mut published = PublishedCrate {
+            manifest_path: PathBuf::new(),
+            root,
+        };
+        let fetch = published.root.join("fetch");
+        fs::create_dir_all(fetch.join("src"))?;
+        fs::write(fetch.join("src/lib.rs"), "")?;
+        fs::write(
+            fetch.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"cargo-minify-fetch\"\nversion = \"0.0.0\"\n\
+                 edition = \"2021\"\n\n[dependencies]\n{name} = \"{version}\"\n\n[workspace]\n"
+            ),
+        )?;
+
+        // Resolving the dependencies downloads and unpacks them
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path(fetch.join("Cargo.toml"))
+            .exec()
+            .map_err(|error| io::Error::other(format!("could not fetch `{spec}`: {error}")))?;
+        let package = metadata
+            .packages
+            .iter()
+            .find(|package| package.name == name && package.source.is_some())
+            .ok_or_else(|| io::Error::other(format!("could not fetch `{spec}`")))?;
+        let sources = package.manifest_path.parent().unwrap().as_std_path();
+
+        let copy = published
+            .root
+            .join(format!("{}-{}", package.name, package.version));
+        idempotency::copy_dir(
+            sources,
+            &copy,
+            &[sources.join("target"), sources.join(".git")],
+        )?;
+        // Keeps the copy out of any workspace the temporary directory is in
+        let manifest_path = copy.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path)?;
+        if !manifest.lines().any(|line| line.trim() == "[workspace]") {
+            fs::write(&manifest_path, manifest + "\n[workspace]\n")?;
+        }
+        published.manifest_path = manifest_path;
+
+        Ok(published)
+    }
+
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
+}
+
+impl Drop for PublishedCrate {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_specs() {
+        assert_eq!(
+            parse_spec("serde@1.0.193"),
+            Some(("serde", "=1.0.193".to_string()))
+        );
+        assert_eq!(
+            parse_spec("cargo_metadata"),
+            Some(("cargo_metadata", "*".to_string()))
+        );
+        assert_eq!(parse_spec("serde@"), None);
+        assert_eq!(parse_spec("../serde@1"), None);
+        assert_eq!(parse_spec("serde@1.0\"\nevil = \"1"), None);
+        assert_eq!(parse_spec("serde@latest"), None);
+        assert_eq!(parse_spec("serde@1.0"), Some(("serde", "=1.0".to_string())));
+    }
+}
@@ -0,0 +1,270 @@
+//! Re-exports that nothing refers to, with `--kinds reexport`. rustc never
+//! reports a `pub use` as unused, as other crates may import it; but when
+//! the name it exports is mentioned nowhere in the analyzed packages, not even
+//! by the workspace members depending on the library, the re-export only
+//! clutters the module. Names aren't resolved, so the name of a re-export
+//! counts as used when anything else mentions it, besides the definitions of
+//! items by that name and other re-exports. Re-exports are aliases of the
+//! names they re-export, so a re-export is also used when a re-export of its
+//! name is, and [`Aliases`] tells the same of the items behind them, for
+//! `--kinds unused_pub`. Glob re-exports are left alone.
+//!
+//! The re-exports are removed like unused imports, name by name.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use quote::ToTokens;
+use syn::{spanned::Spanned, Item, UseTree, Visibility};
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    variants::{count_idents, Mentions},
+    FileResolutionOptions,
+};
+
+/// A name exported by a `pub use` item, along with the tree exporting it,
+/// as written in its group
+struct Reexport<'a> {
+    name: String,
+    tree: &'a UseTree,
+}
+
+/// The name the tree ends in, as it's exported
+fn exported_name(tree: &UseTree) -> Option<String> {
+    match tree {
+        UseTree::Path(path) => exported_name(&path.tree),
+        UseTree::Name(name) if name.ident != "self" => Some(name.ident.to_string()),
+        UseTree::Rename(rename) => Some(rename.rename.to_string()),
+        _ => None,
+    }
+}
+
+/// The name the tree re-exports, which it ends in before any renaming
+fn original_name(tree: &UseTree) -> Option<String> {
+    match tree {
+        UseTree::Path(path) => original_name(&path.tree),
+        UseTree::Name(name) if name.ident != "self" => Some(name.ident.to_string()),
+        UseTree::Rename(rename) if rename.ident != "self" => Some(rename.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// The re-exported names of the tree: the tree itself, or the names of its
+/// groups, as written within them
+fn collect_trees<'a>(tree: &'a UseTree, found: &mut Vec<Reexport<'a>>) {
+    match tree {
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                collect_trees(tree, found);
+            }
+        }
+        UseTree::Path(path) if contains_group(&path.tree) => collect_trees(&path.tree, found),
+        _ => found.extend(exported_name(tree).map(|name| Reexport { name, tree })),
+    }
+}
+
+fn contains_group(tree: &UseTree) -> bool {
+    match tree {
+        UseTree::Path(path) => contains_group(&path.tree),
+        UseTree::Group(_) => true,
+        _ => false,
+    }
+}
+
+fn collect_items<'a>(items: &'a [Item], found: &mut Vec<Reexport<'a>>) {
+    for item in items {
+        match item {
+            Item::Use(item_use) if matches!(item_use.vis, Visibility::Public(_)) => {
+                collect_trees(&item_use.tree, found);
+            }
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, found);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Counts the names of the items defined among the items
+fn count_definitions(items: &[Item], counts: &mut HashMap<String, usize>) {
+    for item in items {
+        let ident = match item {
+            Item::Const(item) => &item.ident,
+            Item::Enum(item) => &item.ident,
+            Item::Fn(item) => &item.sig.ident,
+            Item::Macro(item) => match &item.ident {
+                Some(ident) => ident,
+                None => continue,
+            },
+            Item::Mod(item) => {
+                if let Some((_, items)) = &item.content {
+                    count_definitions(items, counts);
+                }
+                &item.ident
+            }
+            Item::Static(item) => &item.ident,
+            Item::Struct(item) => &item.ident,
+            Item::Trait(item) => &item.ident,
+            Item::Type(item) => &item.ident,
+            Item::Union(item) => &item.ident,
+            _ => continue,
+        };
+        *counts.entry(ident.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// The alias graph of the `pub use` re-exports of the sources, in which a
+/// name is used when it's mentioned anywhere but in definitions and
+/// re-exports, or when a name re-exporting it is used
+pub struct Aliases {
+    mentions: Mentions,
+    definitions: HashMap<String, usize>,
+    in_reexports: HashMap<String, usize>,
+    /// The exported names, along with the names they re-export
+    edges: Vec<(String, String)>,
+    /// The names kept by the re-exports of them that are used
+    reexported: HashSet<String>,
+}
+
+impl Aliases {
+    pub fn new(sources: &[impl AsRef<Path>]) -> Self {
+        let mut aliases = Aliases {
+            mentions: Mentions::new(sources),
+            definitions: HashMap::new(),
+            in_reexports: HashMap::new(),
+            edges: Vec::new(),
+            reexported: HashSet::new(),
+        };
+        for source in sources {
+            let parsed = std::fs::read_to_string(source)
+                .ok()
+                .and_then(|src| syn::parse_file(&src).ok());
+            let Some(parsed) = parsed else {
+                continue;
+            };
+            count_definitions(&parsed.items, &mut aliases.definitions);
+            let mut found = Vec::new();
+            collect_items(&parsed.items, &mut found);
+            for reexport in found {
+                count_idents(reexport.tree.to_token_stream(), &mut aliases.in_reexports);
+                let original = original_name(reexport.tree);
+                aliases
+                    .edges
+                    .extend(original.map(|original| (reexport.name, original)));
+            }
+        }
+
+        // A used re-export keeps what it re-exports, through any number of them
+        while let Some(original) = aliases
+            .edges
+            .iter()
+            .find(|(name, original)| aliases.is_used(name) && !aliases.is_used(original))
+            .map(|(_, original)| original.clone())
+        {
+            aliases.reexported.insert(original);
+        }
+
+        aliases
+    }
+
+    /// Whether anything but definitions and re-exports mentions the name
+    fn is_mentioned(&self, name: &str) -> bool {
+        let count = |counts: &HashMap<String, usize>| counts.get(name).copied().unwrap_or_default();
+        self.mentions.of(name) > count(&self.definitions) + count(&self.in_reexports)
+    }
+
+    /// Whether the name is mentioned, or re-exported by a name that's used
+    pub fn is_used(&self, name: &str) -> bool {
+        self.is_mentioned(name) || self.reexported.contains(name)
+    }
+
+    /// Whether any `pub use` re-exports the name, possibly under another one
+    pub fn is_reexported(&self, name: &str) -> bool {
+        self.edges.iter().any(|(_, original)| original == name)
+    }
+}
+
+/// The re-exports in `files` whose names are mentioned nowhere in `sources`
+/// (which include `files`), but in re-exports and definitions, and which no
+/// used re-export re-exports
+pub fn get_unused_reexports(
+    files: &[impl AsRef<Path>],
+    sources: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+) -> Vec<UnusedDiagnostic> {
+    let aliases = Aliases::new(sources);
+
+    let mut unused = Vec::new();
+    for file in files {
+        let file = file.as_ref();
+        let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+        let file_name = file_name.to_string_lossy();
+        if !file_resolution.is_included(&file_name) {
+            continue;
+        }
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+
+        let mut found = Vec::new();
+        collect_items(&parsed.items, &mut found);
+        for reexport in found {
+            if aliases.is_used(&reexport.name) {
+                continue;
+            }
+            let text = reexport.tree.to_token_stream().to_string();
+            unused.push(UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::Reexport,
+                ident: text.replace(" :: ", "::"),
+                span: synthetic_span(&file_name, &src, reexport.tree.span()),
+                suggestion: None,
+                configs: Vec::new(),
+            });
+        }
+    }
+
+    unused
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cauterize::test::rust_delete, imports, test_support::TempCrate};
+
+    use super::*;
+
+    #[test]
+    fn finds_unused_reexports() {
+        let lib_rs = "mod inner {\n    pub fn f() {}\n    pub fn g() {}\n    pub struct S;\n}\n\
+                      pub use inner::f;\npub use inner::{g as h, S};\npub use inner::*;\n";
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", lib_rs),
+            ("app/src/main.rs", "fn main() { let _ = lib::S; }\n"),
+        ])
+        .unwrap();
+        let root = temp_crate.root();
+        let (lib, main) = (root.join("src/lib.rs"), root.join("app/src/main.rs"));
+        let all = FileResolutionOptions::AllBut(Vec::new());
+
+        let found = get_unused_reexports(&[&lib], &[&lib, &main], root, &all);
+        let idents: Vec<_> = found.iter().map(|found| found.ident.as_str()).collect();
+        assert_eq!(idents, ["inner::f", "g as h"]);
+
+        let keys = found
+            .iter()
+            .map(|found| (found.kind, imports::removal_key(found)));
+        assert_eq!(
+            String::from_utf8(rust_delete(lib_rs.as_bytes(), keys, false).unwrap()).unwrap(),
+            "mod inner {\n    pub fn f() {}\n    pub fn g() {}\n    pub struct S;\n}\n\
+             pub use inner::S;\npub use inner::*;\n"
+        );
+    }
+}
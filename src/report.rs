@@ -0,0 +1,351 @@
+//! Structured results of a minify run, so that callers can inspect what was
+//! found and what was changed without having to parse any printed output.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cauterize::{Change, UnparsedFile},
+    doc_links::DocLink,
+    expand::GhostUsage,
+    fields::Initialization,
+    generated::GeneratedFile,
+    justifications::Justification,
+    manifest_lints::HiddenByLints,
+    modules::LayoutProblem,
+    suppressions::Suppression,
+    unused::{BenchReference, UnusedDiagnostic, UnusedDiagnosticKind},
+    unused_deps::UnusedDependency,
+    unused_features::UnusedFeature,
+    vcs::VcsBackend,
+};
+
+/// The outcome of analyzing a crate: the unused code that was found and the
+/// changes that would remove it
+pub struct Analysis {
+    pub diagnostics: Vec<UnusedDiagnostic>,
+    pub changes: Vec<Change>,
+    /// Benchmarks that refer to code that is about to be removed
+    pub bench_references: Vec<BenchReference>,
+    /// Number of diagnostics left out because their file is ignored by
+    /// convention, such as test fixtures
+    pub suppressed_by_convention: usize,
+    /// Number of diagnostics left out because their code is used by code that
+    /// is only compiled for documentation
+    pub suppressed_doc_only: usize,
+    /// Private items only used by the code being removed; these are removed
+    /// as well with `--cascade`
+    pub cascade_candidates: Vec<UnusedDiagnostic>,
+    /// Intra-doc links to the removed items, or with `--doc-links protect`,
+    /// to the items that are kept because of them
+    pub doc_links: Vec<DocLink>,
+    /// `#[allow(dead_code)]` and similar attributes near the findings, or
+    /// covering whole modules, which may hide more unused code
+    pub suppressions: Vec<Suppression>,
+    /// Packages whose `[lints]` allow the lints some kinds are reported by,
+    /// so that these can't be found in them
+    pub hidden_by_lints: Vec<HiddenByLints>,
+    /// Findings that are only reported, as removing them could break the
+    /// code, with `--report-only`, or as configured for their kind
+    pub reported: Vec<UnusedDiagnostic>,
+    /// Findings that are kept, marked with a comment, as configured for
+    /// their kind
+    pub annotated: Vec<UnusedDiagnostic>,
+    /// The struct expressions, patterns and tuple struct calls that would
+    /// need editing to remove the reported fields
+    pub field_initializations: Vec<Initialization>,
+    /// Dependencies that no target of their package uses, with `--unused-deps`
+    pub unused_dependencies: Vec<UnusedDependency>,
+    /// Features that gate no code and that nothing enables, with
+    /// `--unused-features`
+    pub unused_features: Vec<UnusedFeature>,
+    /// Private items that only derived impls mention, with `--expand`
+    pub ghost_usages: Vec<GhostUsage>,
+    /// Code that only tests use, with `--test-only-usage`
+    pub test_only_usage: Vec<UnusedDiagnostic>,
+    /// `minify:allow` comments that kept findings
+    pub justified: Vec<Justification>,
+    /// `minify:allow` comments past their `until` date, which no longer keep
+    /// their findings
+    pub expired_justifications: Vec<Justification>,
+    /// Generated files with unused code, which are left alone, as they would
+    /// be regenerated anyway
+    pub generated: Vec<GeneratedFile>,
+    /// Files with unused code that are left alone, as they can't be parsed
+    pub unparsed: Vec<UnparsedFile>,
+    /// The check was stopped by `--timeout`, so the findings are partial
+    pub timed_out: bool,
+    /// The findings are by `--engine syntax`, which matches names without
+    /// resolving them, so the changes are only applied with `--force`
+    pub approximate: bool,
+    pub(crate) workspace_root: PathBuf,
+    pub(crate) target_directory: PathBuf,
+    pub(crate) crate_roots: HashSet<PathBuf>,
+    /// The names and canonical directories of the analyzed packages, those
+    /// that others depend on first
+    pub(crate) packages: Vec<(String, PathBuf)>,
+    pub(crate) diff_only_roots: Vec<PathBuf>,
+    pub(crate) policy: ApplyPolicy,
+}
+
+/// What `apply` is allowed to do when the working directory is not clean
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ApplyPolicy {
+    pub allow_dirty: bool,
+    pub allow_staged: bool,
+    pub allow_no_vcs: bool,
+    pub vcs_backend: VcsBackend,
+    pub include_proc_macros: bool,
+    pub apply_semantic: bool,
+    pub force: bool,
+    pub stage: bool,
+    pub branch: Option<String>,
+    pub force_branch: bool,
+    pub commit: bool,
+    /// Adds to the backup of an earlier apply instead of replacing it, with
+    /// `--until-stable`
+    pub extend_backup: bool,
+    /// Applies the packages one at a time, those others depend on first,
+    /// checking that each still compiles
+    pub verify: bool,
+}
+
+impl Analysis {
+    /// Number of diagnostics found per kind
+    pub fn counts(&self) -> BTreeMap<UnusedDiagnosticKind, usize> {
+        let mut counts = BTreeMap::new();
+        for diagnostic in &self.diagnostics {
+            *counts.entry(diagnostic.kind).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// The files that would be changed
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.changes.iter().map(|change| change.file_name())
+    }
+
+    /// Total number of lines that would be removed
+    pub fn removed_lines(&self) -> usize {
+        let lines = |bytes: &[u8]| bytes.iter().filter(|&&b| b == b'\n').count();
+
+        self.changes
+            .iter()
+            .map(|change| {
+                lines(change.original_content()).saturating_sub(lines(change.proposed_content()))
+            })
+            .sum()
+    }
+
+    /// A `git diff --stat`-style summary of the changes, as in `12 files
+    /// changed, 0 insertions(+), 1,204 deletions(-)`
+    pub fn diff_stat(&self) -> String {
+        let (mut insertions, mut deletions) = (0, 0);
+        for change in &self.changes {
+            let left = String::from_utf8_lossy(change.original_content());
+            let right = String::from_utf8_lossy(change.proposed_content());
+            for line in diff::lines(&left, &right) {
+                match line {
+                    diff::Result::Left(_) => deletions += 1,
+                    diff::Result::Right(_) => insertions += 1,
+                    diff::Result::Both(_, _) => {}
+                }
+            }
+        }
+
+        format_diff_stat(self.changes.len(), insertions, deletions)
+    }
+
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
+    }
+
+    /// Whether the change is in a diff-only crate, such as a proc-macro crate;
+    /// these are only shown as a diff unless `--include-proc-macros` is given
+    pub fn is_diff_only(&self, change: &Change) -> bool {
+        change.file_name().canonicalize().is_ok_and(|path| {
+            self.diff_only_roots
+                .iter()
+                .any(|root| path.starts_with(root))
+        })
+    }
+}
+
+/// The outcome of applying an [`Analysis`]
+#[derive(Debug)]
+pub enum ApplyReport {
+    /// The changes were written to `files`; the changes to `skipped` were
+    /// left alone as they are in diff-only crates, such as proc-macro crates,
+    /// the semantic ones to `held_back` as `--apply-semantic` wasn't given,
+    /// and the ones to `failed` could not be written
+    Applied {
+        files: Vec<PathBuf>,
+        skipped: Vec<PathBuf>,
+        held_back: Vec<PathBuf>,
+        failed: Vec<(PathBuf, std::io::Error)>,
+        /// The changes went to the git index instead of the working tree
+        staged: bool,
+        /// The new branch the changes were applied on
+        branch: Option<String>,
+        /// The id of the commit holding the changes
+        commit: Option<String>,
+        /// Why the changes that were written could not be committed, with
+        /// `--vcs-commit`
+        commit_error: Option<git2::Error>,
+    },
+    /// No version control system was found, and `--allow-no-vcs` was not given
+    NoVcs,
+    /// The working directory has dirty or staged files that were not allowed
+    Unclean {
+        dirty: Vec<String>,
+        staged: Vec<String>,
+    },
+    /// The changes are approximate, as found by `--engine syntax`, and
+    /// `--force` was not given
+    Approximate,
+    /// Applying would leave `mod` declarations that don't resolve to exactly
+    /// one file
+    ModuleLayout(Vec<LayoutProblem>),
+    /// The version control system could not be queried
+    VcsError(git2::Error),
+}
+
+impl ApplyReport {
+    pub fn is_applied(&self) -> bool {
+        matches!(self, ApplyReport::Applied { .. })
+    }
+
+    /// Whether only some of the changes were applied, as others were skipped
+    /// or failed, or the changes could not be committed
+    pub fn is_partial(&self) -> bool {
+        matches!(self, ApplyReport::Applied { skipped, held_back, failed, commit_error, .. }
+            if !skipped.is_empty() || !held_back.is_empty() || !failed.is_empty()
+                || commit_error.is_some())
+    }
+
+    /// A machine-parsable summary line, as in `applied=12 skipped=3 failed=1`
+    pub fn summary(&self) -> Option<String> {
+        match self {
+            ApplyReport::Applied {
+                files,
+                skipped,
+                held_back,
+                failed,
+                ..
+            } => Some(format!(
+                "applied={} skipped={} failed={}",
+                files.len(),
+                skipped.len() + held_back.len(),
+                failed.len()
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn format_diff_stat(files: usize, insertions: usize, deletions: usize) -> String {
+    // Digits are grouped by thousands
+    let count = |n: usize| {
+        let digits = n.to_string();
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        grouped
+    };
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+
+    format!(
+        "{} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+        count(files),
+        plural(files),
+        count(insertions),
+        plural(insertions),
+        count(deletions),
+        plural(deletions)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn apply_summary() {
+        let report = ApplyReport::Applied {
+            files: vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")],
+            skipped: vec![PathBuf::from("c.rs")],
+            held_back: Vec::new(),
+            failed: Vec::new(),
+            staged: false,
+            branch: None,
+            commit: None,
+            commit_error: None,
+        };
+        assert_eq!(
+            report.summary().as_deref(),
+            Some("applied=2 skipped=1 failed=0")
+        );
+        assert!(report.is_partial());
+        assert_eq!(ApplyReport::NoVcs.summary(), None);
+        assert!(!ApplyReport::NoVcs.is_partial());
+    }
+
+    #[test]
+    fn diff_stat() {
+        assert_eq!(
+            format_diff_stat(12, 0, 1204),
+            "12 files changed, 0 insertions(+), 1,204 deletions(-)"
+        );
+        assert_eq!(
+            format_diff_stat(1, 1, 1234567),
+            "1 file changed, 1 insertion(+), 1,234,567 deletions(-)"
+        );
+    }
+
+    #[test]
+    fn analyses_are_applied_as_reported() {
+        let temp_crate = TempCrate::new("pub fn used() {}\nfn dead() {}\n").unwrap();
+        let analysis = temp_crate.analyze(&[]).unwrap();
+        assert_eq!(
+            analysis.counts(),
+            BTreeMap::from([(UnusedDiagnosticKind::Function, 1)])
+        );
+        let lib_rs = temp_crate.root().join("src/lib.rs");
+        assert!(analysis.files().all(|file| file.ends_with("src/lib.rs")));
+        assert_eq!(analysis.removed_lines(), 1);
+        assert_eq!(
+            analysis.diff_stat(),
+            "1 file changed, 0 insertions(+), 1 deletion(-)"
+        );
+
+        // Nothing is written outside of version control unless allowed
+        assert!(matches!(
+            crate::apply(analysis).unwrap(),
+            ApplyReport::NoVcs
+        ));
+        assert!(temp_crate.read("src/lib.rs").unwrap().contains("fn dead"));
+
+        let report = temp_crate.minify(&[]).unwrap();
+        assert_eq!(
+            report.summary().as_deref(),
+            Some("applied=1 skipped=0 failed=0")
+        );
+        let ApplyReport::Applied { files, .. } = report else {
+            unreachable!()
+        };
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].canonicalize().unwrap(),
+            lib_rs.canonicalize().unwrap()
+        );
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), "pub fn used() {}\n");
+    }
+}
@@ -12,7 +12,11 @@ use std::{
 
 use cargo_metadata::Target;
 
-use crate::{error::Result, CrateResolutionOptions};
+use crate::{
+    error::{Error, Result},
+    toolchain::Toolchain,
+    CrateResolutionOptions,
+};
 
 pub fn get_targets(
     manifest_path: Option<&Path>,
@@ -84,9 +88,10 @@ fn workspace_targets(
 ) -> Result<()> {
     let metadata = get_cargo_metadata(manifest_path)?;
     for package in &metadata.packages {
+        let version = package.version.to_string();
         if !exclude
             .iter()
-            .any(|name| glob_match::glob_match(name, &package.name))
+            .any(|spec| matches_spec(spec, &package.name, &version))
         {
             for target in &package.targets {
                 targets.insert(target.clone());
@@ -121,26 +126,468 @@ fn package_targets(
     targets: &mut HashSet<Target>,
 ) -> Result<()> {
     let metadata = get_cargo_metadata(manifest_path)?;
-    let mut workspace_hitlist: BTreeSet<&String> = BTreeSet::from_iter(packages);
 
-    for package in metadata.packages {
-        if workspace_hitlist.remove(&package.name) {
-            for target in package.targets {
-                targets.insert(target);
+    for spec in expand_package_specs(&metadata, packages)? {
+        let matches: Vec<_> = metadata
+            .packages
+            .iter()
+            .filter(|package| matches_spec(&spec, &package.name, &package.version.to_string()))
+            .collect();
+        let message = match matches[..] {
+            [package] => {
+                targets.extend(package.targets.iter().cloned());
+                continue;
             }
+            [] => format!("package `{}` is not a member of the workspace", spec),
+            _ => {
+                let ids: Vec<_> = matches
+                    .iter()
+                    .map(|package| format!("`{}@{}`", package.name, package.version))
+                    .collect();
+                format!(
+                    "package `{}` is ambiguous, it matches {}",
+                    spec,
+                    ids.join(", ")
+                )
+            }
+        };
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    Ok(())
+}
+
+/// Whether a package spec matches the package of the name and version. Specs
+/// are names, which may contain wildcards, optionally followed by a version
+/// like cargo's package ids, as in `helpers@0.3`; a partial version matches
+/// all versions that start with it.
+fn matches_spec(spec: &str, name: &str, version: &str) -> bool {
+    let (spec_name, spec_version) = match spec.split_once('@') {
+        Some((spec_name, spec_version)) => (spec_name, Some(spec_version)),
+        None => (spec, None),
+    };
+    let matches_version = spec_version.is_none_or(|spec_version| {
+        version == spec_version.trim_start_matches('v')
+            || version.starts_with(&format!("{}.", spec_version.trim_start_matches('v')))
+    });
+
+    glob_match::glob_match(spec_name, name) && matches_version
+}
+
+/// Expands package specs containing wildcards (such as `internal-*`) into the
+/// names of the matching workspace packages, and those with a version (such as
+/// `helpers@0.3`) into the full versions of the matching packages, as cargo
+/// takes `-p` only with these; other specs are kept as they are
+pub fn expand_package_specs(
+    metadata: &cargo_metadata::Metadata,
+    specs: &[String],
+) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+
+    for spec in specs {
+        let versioned = spec.contains('@');
+        if !versioned && !spec.contains(['*', '?', '[', '{']) {
+            names.push(spec.clone());
+            continue;
         }
+
+        let matches: Vec<_> = metadata
+            .packages
+            .iter()
+            .filter(|package| matches_spec(spec, &package.name, &package.version.to_string()))
+            .map(|package| match versioned {
+                true => format!("{}@{}", package.name, package.version),
+                false => package.name.clone(),
+            })
+            .collect();
+        if matches.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no package in the workspace matches `{}`", spec),
+            )
+            .into());
+        }
+        names.extend(matches);
     }
 
-    if workspace_hitlist.is_empty() {
-        Ok(())
-    } else {
-        let package = workspace_hitlist.iter().next().unwrap();
-        Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("package `{}` is not a member of the workspace", package),
+    names.sort();
+    names.dedup();
+
+    Ok(names)
+}
+
+/// All Rust source files in the directories of the given targets' root files,
+/// except for build scripts
+pub fn source_files(targets: &HashSet<Target>, excluded: &[PathBuf]) -> Vec<PathBuf> {
+    fn walk(dir: &Path, excluded: &[PathBuf], files: &mut BTreeSet<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                // Nested crates are never part of the targets' sources
+                if !excluded.iter().any(|dir| path.starts_with(dir))
+                    && !path.join("Cargo.toml").exists()
+                {
+                    walk(&path, excluded, files);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                files.insert(path);
+            }
+        }
+    }
+
+    let mut files = BTreeSet::new();
+    for target in targets {
+        if is_build_script(target) {
+            continue;
+        }
+        if let Some(dir) = target.src_path.parent() {
+            walk(dir.as_std_path(), excluded, &mut files);
+        }
+    }
+
+    files.into_iter().collect()
+}
+
+/// Whether the target is a library of any kind (as opposed to a binary,
+/// example, test, bench or build script)
+pub fn is_library(target: &Target) -> bool {
+    target.kind.iter().any(|kind| {
+        matches!(
+            kind.as_str(),
+            "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro"
         )
-        .into())
+    })
+}
+
+pub fn is_build_script(target: &Target) -> bool {
+    target.kind.iter().any(|kind| kind == "custom-build")
+}
+
+/// The target triple to check the packages of `targets` for, when none is
+/// given: the `forced-target` that all these packages share, or else the
+/// `build.target` of the `.cargo/config.toml` of the workspace or any of the
+/// directories above it
+pub fn default_target(
+    metadata: &cargo_metadata::Metadata,
+    targets: &HashSet<Target>,
+) -> Option<String> {
+    let forced: Vec<_> = metadata
+        .packages
+        .iter()
+        .filter(|package| {
+            package
+                .targets
+                .iter()
+                .any(|target| targets.contains(target))
+        })
+        .map(|package| {
+            let manifest = std::fs::read_to_string(&package.manifest_path).ok()?;
+            let value = toml_value(&manifest, "package", "forced-target")?;
+            toml_strings(&value).into_iter().next()
+        })
+        .collect();
+    if let Some(Some(first)) = forced.first() {
+        if forced.iter().all(|target| target.as_ref() == Some(first)) {
+            return Some(first.clone());
+        }
+    }
+
+    metadata.workspace_root.ancestors().find_map(|dir| {
+        ["config.toml", "config"].iter().find_map(|name| {
+            let config = std::fs::read_to_string(dir.join(".cargo").join(name)).ok()?;
+            let value = toml_value(&config, "build", "target")?;
+            toml_strings(&value).into_iter().next()
+        })
+    })
+}
+
+/// The target triple of the host, as reported by rustc
+pub fn host_target(toolchain: Option<&Toolchain>) -> Result<String> {
+    let mut command = std::process::Command::new("rustc");
+    if let Some(toolchain) = toolchain {
+        toolchain.apply(&mut command);
+    }
+    let output = command.arg("-vV").output()?;
+    let output = String::from_utf8(output.stdout)?;
+
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .ok_or_else(|| io::Error::other("rustc did not report its host target").into())
+}
+
+/// The package that contains the target
+pub fn package_of<'a>(
+    metadata: &'a cargo_metadata::Metadata,
+    target: &Target,
+) -> Option<&'a cargo_metadata::Package> {
+    metadata
+        .packages
+        .iter()
+        .find(|package| package.targets.contains(target))
+}
+
+/// The source files of the targets, by package, as private items can only be
+/// used within their own
+pub fn source_files_by_package(
+    metadata: &cargo_metadata::Metadata,
+    targets: &HashSet<Target>,
+    excluded: &[PathBuf],
+) -> Vec<Vec<PathBuf>> {
+    let mut by_package: Vec<(&cargo_metadata::PackageId, HashSet<Target>)> = Vec::new();
+    for target in targets {
+        let Some(package) = package_of(metadata, target) else {
+            continue;
+        };
+        match by_package.iter_mut().find(|(id, _)| **id == package.id) {
+            Some((_, targets)) => {
+                targets.insert(target.clone());
+            }
+            None => by_package.push((&package.id, HashSet::from([target.clone()]))),
+        }
+    }
+    by_package.sort_by_key(|(id, _)| *id);
+
+    by_package
+        .into_iter()
+        .map(|(_, targets)| source_files(&targets, excluded))
+        .collect()
+}
+
+/// The features that workspace members enable on the libraries of the
+/// targets' packages through their dev-dependencies, such as `test-util`; the
+/// code behind these features may only be used by the members' tests
+pub fn dev_dependency_features<'a>(
+    metadata: &'a cargo_metadata::Metadata,
+    targets: &HashSet<Target>,
+) -> Vec<(&'a cargo_metadata::Package, Vec<String>)> {
+    let members = metadata.workspace_packages();
+    let mut found = Vec::new();
+
+    for package in &members {
+        let is_checked = |target: &Target| is_library(target) && targets.contains(target);
+        if !package.targets.iter().any(is_checked) {
+            continue;
+        }
+        let mut features: Vec<_> = members
+            .iter()
+            .flat_map(|member| &member.dependencies)
+            .filter(|dependency| {
+                dependency.kind == cargo_metadata::DependencyKind::Development
+                    && dependency.name == package.name
+                    && dependency.path.is_some()
+            })
+            .flat_map(|dependency| dependency.features.iter().cloned())
+            .collect();
+        features.sort();
+        features.dedup();
+        if !features.is_empty() {
+            found.push((*package, features));
+        }
+    }
+
+    found
+}
+
+/// Whether the package named `dependent` depends on the workspace member
+/// named `dependency`, through a path dependency of any kind
+pub fn depends_on(metadata: &cargo_metadata::Metadata, dependent: &str, dependency: &str) -> bool {
+    metadata
+        .packages
+        .iter()
+        .filter(|package| package.name == dependent)
+        .flat_map(|package| &package.dependencies)
+        .any(|dep| dep.name == dependency && dep.path.is_some())
+}
+
+/// Whether the package has a library target, rather than only binaries
+pub fn has_library(package: &cargo_metadata::Package) -> bool {
+    package.targets.iter().any(is_library)
+}
+
+/// The crate types a package is known by: those of its library, or `bin` if
+/// it only has binaries
+pub fn crate_types(package: &cargo_metadata::Package) -> Vec<String> {
+    match package.targets.iter().find(|target| is_library(target)) {
+        Some(library) => library.crate_types.clone(),
+        None => vec!["bin".to_string()],
+    }
+}
+
+/// The root directories of the packages matching the predicate
+pub fn package_roots(
+    metadata: &cargo_metadata::Metadata,
+    predicate: impl Fn(&cargo_metadata::Package) -> bool,
+) -> Vec<PathBuf> {
+    metadata
+        .packages
+        .iter()
+        .filter(|package| predicate(package))
+        .filter_map(|package| package.manifest_path.parent())
+        .filter_map(|root| root.canonicalize().ok())
+        .collect()
+}
+
+/// Resolves a `--file` or `--ignore` pattern of the form `<package>:<glob>`
+/// to a glob relative to the workspace root, given the workspace members by
+/// their names and their directories relative to the root; other patterns are
+/// relative to the root already. `None` if there is no such member.
+fn package_glob(pattern: &str, members: &[(&str, PathBuf)]) -> Option<String> {
+    let Some((package, glob)) = pattern.split_once(':') else {
+        return Some(pattern.to_string());
+    };
+    let (_, dir) = members.iter().find(|(name, _)| *name == package)?;
+    let dir = dir.to_string_lossy().replace('\\', "/");
+    let glob = glob.trim_start_matches("./");
+
+    Some(match dir.is_empty() {
+        true => glob.to_string(),
+        false => format!("{dir}/{glob}"),
+    })
+}
+
+/// The `--file` or `--ignore` patterns as globs relative to the workspace
+/// root, which is what the names of the files are matched against
+pub fn package_globs(
+    metadata: &cargo_metadata::Metadata,
+    patterns: &[String],
+) -> Result<Vec<String>> {
+    let members: Vec<_> = metadata
+        .workspace_packages()
+        .into_iter()
+        .map(|package| {
+            let dir = package
+                .manifest_path
+                .parent()
+                .unwrap_or(&metadata.workspace_root);
+            let dir = dir.strip_prefix(&metadata.workspace_root).unwrap_or(dir);
+            (package.name.as_str(), dir.as_std_path().to_path_buf())
+        })
+        .collect();
+
+    patterns
+        .iter()
+        .map(|pattern| {
+            package_glob(pattern, &members).ok_or(Error::Args(
+                "the package before the colon of a --file or --ignore pattern is not a member of \
+                 the workspace",
+            ))
+        })
+        .collect()
+}
+
+/// The directories listed in `workspace.exclude` of the workspace manifest;
+/// any crates in there are never analyzed or edited
+pub fn excluded_dirs(metadata: &cargo_metadata::Metadata) -> Vec<PathBuf> {
+    let manifest = metadata.workspace_root.join("Cargo.toml");
+    let Ok(manifest) = std::fs::read_to_string(manifest) else {
+        return Vec::new();
+    };
+
+    workspace_excludes(&manifest)
+        .into_iter()
+        .map(|dir| metadata.workspace_root.join(dir).into_std_path_buf())
+        .collect()
+}
+
+/// Reads the `exclude` array of the `[workspace]` table, which cargo metadata
+/// doesn't report
+fn workspace_excludes(manifest: &str) -> Vec<String> {
+    toml_value(manifest, "workspace", "exclude")
+        .map(|value| toml_strings(&value))
+        .unwrap_or_default()
+}
+
+/// The characters of a line of TOML that aren't within a string, along with
+/// their byte offsets. Strings spanning several lines aren't supported.
+fn unquoted(line: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    line.char_indices().filter(move |&(_, c)| match quote {
+        Some(_) if escaped => {
+            escaped = false;
+            false
+        }
+        Some(q) => {
+            if c == '\\' && q == '"' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            false
+        }
+        None if c == '"' || c == '\'' => {
+            quote = Some(c);
+            false
+        }
+        None => true,
+    })
+}
+
+/// The line without its comment, if any
+fn strip_comment(line: &str) -> &str {
+    match unquoted(line).find(|&(_, c)| c == '#') {
+        Some((end, _)) => &line[..end],
+        None => line,
+    }
+}
+
+/// Whether the line closes an array, outside of its strings
+fn closes_array(line: &str) -> bool {
+    unquoted(line).any(|(_, c)| c == ']')
+}
+
+/// Reads the raw value of `key` in `[table]` of a TOML file, such as a
+/// manifest or `.cargo/config.toml`. This is a minimal reader that only knows
+/// about strings and arrays of strings, which covers the keys the tool needs.
+pub fn toml_value(src: &str, table: &str, key: &str) -> Option<String> {
+    let header = format!("[{table}]");
+    let mut in_table = false;
+    let mut value = None::<String>;
+
+    for line in src.lines() {
+        let line = strip_comment(line).trim();
+        if let Some(value) = &mut value {
+            value.push_str(line);
+            if closes_array(line) {
+                break;
+            }
+        } else if line.starts_with('[') {
+            in_table = line == header;
+        } else if in_table {
+            if let Some((name, rest)) = line.split_once('=') {
+                if name.trim() == key {
+                    let rest = rest.trim();
+                    if !rest.starts_with('[') || closes_array(&rest[1..]) {
+                        return Some(rest.to_string());
+                    }
+                    value = Some(rest.to_string());
+                }
+            }
+        }
+    }
+
+    value
+}
+
+/// The strings in a raw TOML value, as read by [`toml_value`]
+pub fn toml_strings(value: &str) -> Vec<String> {
+    let separators = unquoted(value).filter(|&(_, c)| matches!(c, '[' | ']' | ','));
+    let mut entries = Vec::new();
+    let mut start = 0;
+    for (end, _) in separators.chain([(value.len(), ',')]) {
+        entries.push(&value[start..end]);
+        start = end + 1;
     }
+    entries
+        .into_iter()
+        .map(|entry| entry.trim().trim_matches(['"', '\'']))
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 pub fn get_cargo_metadata(manifest_path: Option<&Path>) -> Result<cargo_metadata::Metadata> {
@@ -157,8 +604,172 @@ pub fn get_cargo_metadata(manifest_path: Option<&Path>) -> Result<cargo_metadata
             cmd.other_options(vec![]);
             match cmd.exec() {
                 Ok(metadata) => Ok(metadata),
-                Err(error) => Err(io::Error::new(io::ErrorKind::Other, error.to_string()).into()),
+                Err(error) => Err(io::Error::other(error.to_string()).into()),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn reads_workspace_excludes() {
+        let manifest = r#"
+[package]
+exclude = ["not-this"]
+
+[workspace]
+members = ["a"]
+exclude = [
+    "vendor/foo", # vendored
+    "fixtures",
+]
+
+[dependencies]
+"#;
+        assert_eq!(workspace_excludes(manifest), vec!["vendor/foo", "fixtures"]);
+        assert!(workspace_excludes("[workspace]\nmembers = []").is_empty());
+        assert_eq!(
+            toml_value(
+                "[build]\ntarget = \"thumbv7em-none-eabihf\"\n",
+                "build",
+                "target"
+            ),
+            Some("\"thumbv7em-none-eabihf\"".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_strings_with_comment_and_bracket_characters() {
+        let manifest = r#"
+[workspace]
+exclude = [
+    "fixtures/#1", # the first
+    "fixtures/[2]",
+    'fixtures/a,b',
+] # done
+members = ["a"]
+"#;
+        assert_eq!(
+            workspace_excludes(manifest),
+            vec!["fixtures/#1", "fixtures/[2]", "fixtures/a,b"]
+        );
+        assert_eq!(
+            toml_value(
+                "[lints]\nworkspace = true # inherited\n",
+                "lints",
+                "workspace"
+            ),
+            Some("true".to_string())
+        );
+        assert_eq!(
+            toml_strings(&toml_value("[t]\nk = [\"]\", \"\\\"#\"] # c\n", "t", "k").unwrap()),
+            vec!["]", "\\\"#"]
+        );
+    }
+
+    #[test]
+    fn matches_package_specs() {
+        assert!(matches_spec("helpers", "helpers", "0.3.1"));
+        assert!(matches_spec("help*", "helpers", "0.3.1"));
+        assert!(matches_spec("helpers@0.3", "helpers", "0.3.1"));
+        assert!(matches_spec("helpers@0", "helpers", "0.3.1"));
+        assert!(matches_spec("helpers@0.3.1", "helpers", "0.3.1"));
+        assert!(matches_spec("help*@0.3", "helpers", "0.3.1"));
+        assert!(!matches_spec("helpers@0.4", "helpers", "0.3.1"));
+        assert!(!matches_spec("helpers@0.3.10", "helpers", "0.3.1"));
+        assert!(!matches_spec("helpers@0.31", "helpers", "0.3.1"));
+        assert!(!matches_spec("other@0.3", "helpers", "0.3.1"));
+    }
+
+    #[test]
+    fn resolves_package_globs() {
+        let members = [
+            ("root", PathBuf::new()),
+            ("core", PathBuf::from("crates/core")),
+        ];
+        let glob = |pattern| package_glob(pattern, &members);
+        assert_eq!(glob("src/lib.rs").as_deref(), Some("src/lib.rs"));
+        assert_eq!(
+            glob("core:src/lib.rs").as_deref(),
+            Some("crates/core/src/lib.rs")
+        );
+        assert_eq!(glob("core:./src/**").as_deref(), Some("crates/core/src/**"));
+        assert_eq!(glob("root:src/*.rs").as_deref(), Some("src/*.rs"));
+        assert_eq!(glob("app:src/lib.rs"), None);
+    }
+
+    #[test]
+    fn excludes_packages_by_glob() {
+        let manifest = |name: &str| {
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n")
+        };
+        let members = ["app", "internal-a", "internal-b"];
+        let manifests: Vec<_> = members.iter().map(|name| manifest(name)).collect();
+        let mut files = vec![(
+            "Cargo.toml".to_string(),
+            "[workspace]\nmembers = [\"app\", \"internal-a\", \"internal-b\"]\n".to_string(),
+        )];
+        for (name, manifest) in members.iter().zip(&manifests) {
+            files.push((format!("{name}/Cargo.toml"), manifest.clone()));
+            files.push((format!("{name}/src/lib.rs"), "fn dead() {}\n".to_string()));
+        }
+        let files = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()));
+        let temp_crate = TempCrate::with_files(files).unwrap();
+        let minified = |name: &str| temp_crate.read(&format!("{name}/src/lib.rs")).unwrap() == "";
+
+        let err = temp_crate
+            .minify(&["--workspace", "--exclude", "external-*"])
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("no package in the workspace matches `external-*`"));
+        assert!(!members.iter().any(|name| minified(name)));
+
+        let report = temp_crate
+            .minify(&["--workspace", "--exclude", "internal-*"])
+            .unwrap();
+        assert!(report.is_applied());
+        assert_eq!(members.map(minified), [true, false, false]);
+
+        temp_crate.minify(&["--package", "internal-?"]).unwrap();
+        assert_eq!(members.map(minified), [true, true, true]);
+    }
+
+    #[test]
+    fn defaults_to_the_configured_target() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "fn dead() {}\n"),
+            (
+                ".cargo/config.toml",
+                "[build]\ntarget = \"thumbv7em-none-eabihf\" # the board\n",
+            ),
+        ])
+        .unwrap();
+        let metadata = get_cargo_metadata(Some(&temp_crate.manifest_path())).unwrap();
+        let targets = get_targets(
+            Some(&temp_crate.manifest_path()),
+            &CrateResolutionOptions::Root,
+        )
+        .unwrap();
+        assert_eq!(
+            default_target(&metadata, &targets).as_deref(),
+            Some("thumbv7em-none-eabihf")
+        );
+
+        // The host can be checked for regardless
+        let analysis = temp_crate.analyze(&["--host"]).unwrap();
+        let idents: Vec<_> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| d.ident.as_str())
+            .collect();
+        assert_eq!(idents, ["dead"]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Selections of changes, saved after a review with `--save-selection` and
+//! applied later, possibly on another machine, with `--selection`. Changes are
+//! identified by their file and content, so a selection only matches the
+//! exact same changes.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{cauterize::Change, state::content_hash};
+
+const SELECTION_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Selection {
+    version: u32,
+    changes: Vec<String>,
+}
+
+/// The id of the change: its file relative to the workspace, and hashes of
+/// the original and the proposed contents
+pub fn change_id(workspace_root: &Path, change: &Change) -> String {
+    let file_name = change.file_name();
+    let file_name = file_name.strip_prefix(workspace_root).unwrap_or(file_name);
+    let file_name: Vec<_> = file_name
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+
+    format!(
+        "{}:{:016x}:{:016x}",
+        file_name.join("/"),
+        content_hash(change.original_content()),
+        content_hash(change.proposed_content())
+    )
+}
+
+pub fn save(path: &Path, ids: Vec<String>) -> io::Result<()> {
+    let selection = Selection {
+        version: SELECTION_VERSION,
+        changes: ids,
+    };
+
+    fs::write(path, serde_json::to_string_pretty(&selection)?)
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<String>> {
+    let selection: Selection = serde_json::from_slice(&fs::read(path)?)?;
+    if selection.version != SELECTION_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} was written by an incompatible version", path.display()),
+        ));
+    }
+
+    Ok(selection.changes)
+}
+
+/// Keeps only the selected changes, returning the ids of the selected changes
+/// that were not found, as their code changed since
+pub fn retain(workspace_root: &Path, changes: &mut Vec<Change>, ids: &[String]) -> Vec<String> {
+    let found: Vec<_> = changes
+        .iter()
+        .map(|change| change_id(workspace_root, change))
+        .collect();
+    let missing = ids
+        .iter()
+        .filter(|id| !found.contains(id))
+        .cloned()
+        .collect();
+
+    let mut found = found.into_iter();
+    changes.retain(|_| found.next().is_some_and(|id| ids.contains(&id)));
+
+    missing
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn selections() {
+        let root = PathBuf::from("/ws");
+        let change = |name: &str, proposed: &str| {
+            Change::new(root.join(name), b"fn a() {}\n".to_vec(), proposed.into())
+        };
+        let mut changes = vec![change("src/a.rs", ""), change("src/b.rs", "")];
+        let ids = vec![
+            change_id(&root, &changes[1]),
+            change_id(&root, &change("src/a.rs", "fn b() {}\n")),
+        ];
+        assert!(ids[0].starts_with("src/b.rs:"));
+
+        let missing = retain(&root, &mut changes, &ids);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].file_name(), root.join("src/b.rs"));
+        assert_eq!(missing, &ids[1..]);
+    }
+}
@@ -0,0 +1,442 @@
+//! A tiny web page on localhost to review the changes, for example together
+//! with teammates, and apply the selected ones (or save them as a selection,
+//! with `--save-selection`). Applying goes through the same path as `--apply`,
+//! after which the server stops.
+//!
+//! Any web page the browser has open can send requests to localhost, so the
+//! form carries a token made up for the run, which other pages can't know,
+//! and requests naming another host or origin are refused.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{
+    cauterize::ChangeCategory,
+    error::Result,
+    report::{Analysis, ApplyReport},
+    selection,
+};
+
+/// The largest request body that is read, far more than any selection needs
+const MAX_BODY: usize = 1024 * 1024;
+
+/// How long a client may keep the server waiting for the next part of its
+/// request; connections are served one at a time
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A request, as far as the server cares
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_length: usize,
+    /// Empty when the body is larger than [`MAX_BODY`]
+    body: String,
+}
+
+fn read_request(stream: &mut TcpStream, timeout: Duration) -> io::Result<Request> {
+    stream.set_read_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let (mut host, mut origin, mut content_length) = (None, None, 0);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_string());
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    if content_length <= MAX_BODY {
+        body.resize(content_length, 0);
+        reader.read_exact(&mut body)?;
+    }
+
+    Ok(Request {
+        method,
+        path,
+        host,
+        origin,
+        content_length,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// The status to answer a request that couldn't be read with, if the client
+/// is still there to read it: one that took too long is timed out
+fn read_failure(err: &io::Error) -> Option<&'static str> {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Some("408 Request Timeout"),
+        _ => None,
+    }
+}
+
+/// A token that only the pages served in this run know, from the randomly
+/// seeded hashers of the standard library
+fn new_token() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+
+    format!("{:016x}{:016x}", random(), random())
+}
+
+/// Why the request can't be served, if it comes from elsewhere than the pages
+/// of this server: its `Host` or `Origin` names another host (as a page using
+/// DNS rebinding would), or a form is posted without the token of the run
+fn refusal(request: &Request, port: u16, token: &str) -> Option<&'static str> {
+    let local = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+    if !request
+        .host
+        .as_ref()
+        .is_some_and(|host| local.contains(host))
+    {
+        return Some("403 Forbidden");
+    }
+    if let Some(origin) = &request.origin {
+        let origin = origin.strip_prefix("http://").unwrap_or_default();
+        if !local.iter().any(|local| local == origin) {
+            return Some("403 Forbidden");
+        }
+    }
+    if request.method == "POST" {
+        if request.content_length > MAX_BODY {
+            return Some("413 Payload Too Large");
+        }
+        if form_values(&request.body, "token")
+            .first()
+            .map(String::as_str)
+            != Some(token)
+        {
+            return Some("403 Forbidden");
+        }
+    }
+
+    None
+}
+
+fn respond(stream: &mut TcpStream, status: &str, html: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{html}",
+        html.len()
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The values of a field in an `application/x-www-form-urlencoded` form body
+fn form_values(body: &str, name: &str) -> Vec<String> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(field, _)| *field == name)
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+/// The indices of the changes selected in a form body, as in
+/// `change=0&change=2`
+fn selected_changes(body: &str) -> Vec<usize> {
+    form_values(body, "change")
+        .iter()
+        .filter_map(|index| index.parse().ok())
+        .collect()
+}
+
+/// The changed lines of a file, prefixed with `-` or `+`
+fn diff_lines(original: &[u8], proposed: &[u8]) -> String {
+    let (original, proposed) = (
+        String::from_utf8_lossy(original),
+        String::from_utf8_lossy(proposed),
+    );
+    let mut lines = String::new();
+    for line in diff::lines(&original, &proposed) {
+        match line {
+            diff::Result::Left(line) => lines += &format!("-{line}\n"),
+            diff::Result::Right(line) => lines += &format!("+{line}\n"),
+            diff::Result::Both(_, _) => {}
+        }
+    }
+
+    lines
+}
+
+/// The submit button of the review page, and the token of the run that the
+/// form sends along
+pub struct Form<'a> {
+    pub button: &'a str,
+    pub token: &'a str,
+}
+
+/// The changes along with their findings; with a `form`, the changes can be
+/// selected and submitted, otherwise the page is a static report, as written
+/// by `--report-html`
+pub fn review_page(analysis: &Analysis, form: Option<&Form>) -> String {
+    let relative = |path: &Path| {
+        path.strip_prefix(analysis.workspace_root())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let mut html = String::from(
+        "<!DOCTYPE html><html><head><title>cargo minify</title></head><body>\
+         <h1>cargo minify</h1>",
+    );
+    if let Some(form) = form {
+        html += &format!(
+            "<form method=\"post\" action=\"/apply\">\
+             <input type=\"hidden\" name=\"token\" value=\"{}\">",
+            escape(form.token)
+        );
+    }
+
+    for (index, change) in analysis.changes.iter().enumerate() {
+        // Semantic changes need a closer review, so they start out unselected
+        let (checked, note) = match change.category() {
+            ChangeCategory::Mechanical => (" checked", ""),
+            ChangeCategory::Semantic => ("", " <em>(semantic)</em>"),
+        };
+        let file_name = escape(&relative(change.file_name()));
+        html += &match form {
+            Some(_) => format!(
+                "<h2><label><input type=\"checkbox\" name=\"change\" value=\"{index}\"{checked}> \
+                 {file_name}{note}</label></h2><ul>"
+            ),
+            None => format!("<h2>{file_name}{note}</h2><ul>"),
+        };
+        // The same finding may come from multiple targets
+        let mut findings: Vec<_> = analysis
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| change.file_name().ends_with(&diagnostic.span.file_name))
+            .map(|diagnostic| {
+                format!(
+                    "<li>{} <code>{}</code> on line {}</li>",
+                    diagnostic.kind,
+                    escape(&diagnostic.ident),
+                    diagnostic.span.line_start
+                )
+            })
+            .collect();
+        findings.dedup();
+        html += &findings.concat();
+        html += &format!(
+            "</ul><pre>{}</pre>",
+            escape(&diff_lines(
+                change.original_content(),
+                change.proposed_content()
+            ))
+        );
+    }
+
+    if let Some(form) = form {
+        html += &format!("<button type=\"submit\">{}</button></form>", form.button);
+    }
+
+    html + "</body></html>"
+}
+
+fn report_page(report: &ApplyReport) -> String {
+    message_page(&match report {
+        ApplyReport::Applied { files, .. } => {
+            format!("Applied the changes to {} file(s).", files.len())
+        }
+        _ => "The changes were not applied, see the terminal for the reason.".to_string(),
+    })
+}
+
+fn message_page(message: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><title>cargo minify</title></head><body>\
+         <h1>cargo minify</h1><p>{}</p></body></html>",
+        escape(message)
+    )
+}
+
+/// Serves the changes on `127.0.0.1:port` until the selected ones are applied,
+/// or saved to `save_selection`
+pub fn serve(
+    mut analysis: Analysis,
+    port: u16,
+    save_selection: Option<&Path>,
+) -> Result<Option<ApplyReport>> {
+    let button = match save_selection {
+        Some(_) => "Save selection",
+        None => "Apply selected",
+    };
+    let token = new_token();
+    let form = Form {
+        button,
+        token: &token,
+    };
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let port = listener.local_addr()?.port();
+    eprintln!("reviewing the changes at http://127.0.0.1:{port}/, stop with Ctrl-C");
+
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let request = match read_request(&mut stream, READ_TIMEOUT) {
+            Ok(request) => request,
+            Err(err) => {
+                if let Some(status) = read_failure(&err) {
+                    let message = message_page("The request took too long.");
+                    let _ = respond(&mut stream, status, &message);
+                }
+                continue;
+            }
+        };
+
+        // A client going away is no reason to stop serving
+        if let Some(status) = refusal(&request, port, &token) {
+            let _ = respond(
+                &mut stream,
+                status,
+                &message_page("The request was refused."),
+            );
+            continue;
+        }
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") => {
+                let _ = respond(&mut stream, "200 OK", &review_page(&analysis, Some(&form)));
+            }
+            ("POST", "/apply") => {
+                let selected = selected_changes(&request.body);
+                analysis.changes = std::mem::take(&mut analysis.changes)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| selected.contains(index))
+                    .map(|(_, change)| change)
+                    .collect();
+
+                if let Some(path) = save_selection {
+                    let ids = analysis.changes.iter();
+                    let ids =
+                        ids.map(|change| selection::change_id(&analysis.workspace_root, change));
+                    selection::save(path, ids.collect())?;
+                    let message = format!(
+                        "Saved the selection of {} change(s) to {}.",
+                        analysis.changes.len(),
+                        path.display()
+                    );
+                    let _ = respond(&mut stream, "200 OK", &message_page(&message));
+                    return Ok(None);
+                }
+
+                // Selecting a semantic change is reviewing it
+                analysis.policy.apply_semantic = true;
+                let report = crate::apply(analysis)?;
+                let _ = respond(&mut stream, "200 OK", &report_page(&report));
+                return Ok(Some(report));
+            }
+            _ => {
+                let _ = respond(&mut stream, "404 Not Found", "not found");
+            }
+        }
+    }
+
+    unreachable!("incoming connections never run out")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn refuses_foreign_requests() {
+        let request = |method: &str, host: &str, origin: Option<&str>, body: &str| Request {
+            method: method.to_string(),
+            path: "/apply".to_string(),
+            host: Some(host.to_string()),
+            origin: origin.map(str::to_string),
+            content_length: body.len(),
+            body: body.to_string(),
+        };
+        let refusal = |request: &Request| refusal(request, 8080, "secret");
+
+        assert_eq!(refusal(&request("GET", "127.0.0.1:8080", None, "")), None);
+        let origin = Some("http://localhost:8080");
+        let posted = request("POST", "localhost:8080", origin, "token=secret&change=0");
+        assert_eq!(refusal(&posted), None);
+
+        let forbidden = Some("403 Forbidden");
+        assert_eq!(
+            refusal(&request("GET", "evil.example:8080", None, "")),
+            forbidden
+        );
+        let origin = Some("http://evil.example");
+        assert_eq!(
+            refusal(&request("POST", "127.0.0.1:8080", origin, "token=secret")),
+            forbidden
+        );
+        assert_eq!(
+            refusal(&request("POST", "127.0.0.1:8080", None, "change=0")),
+            forbidden
+        );
+        assert_eq!(
+            refusal(&request("POST", "127.0.0.1:8080", None, "token=guess")),
+            forbidden
+        );
+
+        let mut large = request("POST", "127.0.0.1:8080", None, "");
+        large.content_length = MAX_BODY + 1;
+        assert_eq!(refusal(&large), Some("413 Payload Too Large"));
+        assert_ne!(new_token(), new_token());
+    }
+
+    #[test]
+    fn times_out_stalled_requests() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .write_all(b"POST /apply HTTP/1.1\r\nContent-Length: 10\r\n\r\ntoken")
+            .unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let err = read_request(&mut stream, Duration::from_millis(50))
+            .err()
+            .unwrap();
+        assert_eq!(read_failure(&err), Some("408 Request Timeout"));
+        assert_eq!(read_failure(&io::ErrorKind::UnexpectedEof.into()), None);
+    }
+
+    #[test]
+    fn forms() {
+        assert_eq!(selected_changes("change=0&change=2"), [0, 2]);
+        assert_eq!(selected_changes("other=1&change=x"), Vec::<usize>::new());
+        assert_eq!(selected_changes(""), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(
+            escape(&diff_lines(b"fn a() {}\nfn b<T>() {}\n", b"fn a() {}\n")),
+            "-fn b&lt;T&gt;() {}\n"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Where the results of a run go. Several outputs can be written in one run,
+//! such as JSON on stdout, an HTML report and a patch file: each is a sink
+//! that is given the analysis once, before anything is applied, and then the
+//! outcome of applying, if the changes were applied.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde_json::Value;
+
+use crate::{
+    diff_format::{self, ColorMode},
+    error::Result,
+    message_format::{self, MessageFormat},
+    metrics,
+    report::{Analysis, ApplyReport},
+    serve,
+};
+
+pub trait Sink {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()>;
+
+    fn finish(&mut self, _report: Option<&ApplyReport>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The sinks of a run, which are written in the order they were registered
+#[derive(Default)]
+pub struct Sinks {
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Sinks {
+    pub fn register(&mut self, sink: impl Sink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        self.sinks
+            .iter_mut()
+            .try_for_each(|sink| sink.analysis(analysis))
+    }
+
+    pub fn finish(&mut self, report: Option<&ApplyReport>) -> Result<()> {
+        self.sinks
+            .iter_mut()
+            .try_for_each(|sink| sink.finish(report))
+    }
+}
+
+/// The diffs on stdout, followed by a summary of the changes
+pub struct Diffs {
+    pub color: ColorMode,
+    pub compact: bool,
+    pub tool: Option<String>,
+}
+
+impl Sink for Diffs {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        if analysis.changes.is_empty() {
+            return Ok(());
+        }
+        for change in &analysis.changes {
+            match &self.tool {
+                Some(tool) => diff_format::run_external(change, tool)?,
+                None if self.compact => diff_format::println_compact(change, self.color),
+                None => diff_format::println(change, self.color),
+            }
+        }
+        println!("{}", analysis.diff_stat());
+
+        Ok(())
+    }
+}
+
+/// JSON or SARIF on stdout, which holds the outcome of applying as well, so
+/// it is only printed at the end
+pub struct MachineReadable {
+    format: MessageFormat,
+    output: Option<Value>,
+}
+
+impl MachineReadable {
+    pub fn new(format: MessageFormat) -> Self {
+        MachineReadable {
+            format,
+            output: None,
+        }
+    }
+}
+
+impl Sink for MachineReadable {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        self.output = match self.format {
+            MessageFormat::Human | MessageFormat::Short => None,
+            MessageFormat::Json => Some(message_format::to_json(analysis)),
+            MessageFormat::Sarif => Some(message_format::to_sarif(analysis)),
+        };
+
+        Ok(())
+    }
+
+    fn finish(&mut self, report: Option<&ApplyReport>) -> Result<()> {
+        let Some(mut output) = self.output.take() else {
+            return Ok(());
+        };
+        if let Some(report) = report {
+            message_format::add_apply_report(self.format, &mut output, report);
+        }
+        println!("{output}");
+
+        Ok(())
+    }
+}
+
+/// The findings one per line on stdout, with `--message-format short`
+pub struct Short;
+
+impl Sink for Short {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        print!("{}", message_format::to_short(analysis));
+
+        Ok(())
+    }
+}
+
+/// A static HTML page with the changes and their findings, with
+/// `--report-html`
+pub struct Html {
+    pub path: PathBuf,
+}
+
+impl Sink for Html {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        std::fs::write(&self.path, serve::review_page(analysis, None))?;
+
+        Ok(())
+    }
+}
+
+/// The changes as a patch that `git apply` takes from the workspace root,
+/// with `--output-patch`
+pub struct Patch {
+    pub path: PathBuf,
+}
+
+impl Sink for Patch {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        let root = analysis.workspace_root();
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let relative = |file: &Path| {
+            let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+            let file = file
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+                .unwrap_or(file);
+            file.to_string_lossy().replace('\\', "/")
+        };
+
+        let patch: String = analysis
+            .changes
+            .iter()
+            .map(|change| diff_format::unified(change, &relative(change.file_name())))
+            .collect();
+        std::fs::write(&self.path, patch)?;
+
+        Ok(())
+    }
+}
+
+/// The findings as a Vim quickfix file, with `--output-quickfix`
+pub struct Quickfix {
+    pub path: PathBuf,
+}
+
+impl Sink for Quickfix {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        let root = analysis.workspace_root();
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        std::fs::write(&self.path, message_format::to_quickfix(analysis, &root))?;
+
+        Ok(())
+    }
+}
+
+/// Counts of the findings and changes in the OpenMetrics text format, with
+/// `--metrics`; the runtime is that of the analysis, from `started`
+pub struct Metrics {
+    pub path: PathBuf,
+    pub started: Instant,
+}
+
+impl Sink for Metrics {
+    fn analysis(&mut self, analysis: &Analysis) -> Result<()> {
+        let metrics = metrics::openmetrics(analysis, self.started.elapsed());
+        std::fs::write(&self.path, metrics)?;
+
+        Ok(())
+    }
+}
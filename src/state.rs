@@ -0,0 +1,277 @@
+//! Persists the pending changes of an analysis, so that an interrupted run
+//! can be resumed with `--resume` without compiling the workspace again. The
+//! [`cache`](crate::cache) stores its results the same way.
+
+use std::{
+    fmt::{Display, Formatter},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cauterize::{Change, ChangeCategory, UnparsedFile},
+    doc_links::DocLink,
+    fields::Initialization,
+    justifications::Justification,
+    unused::UnusedDiagnostic,
+    unused_deps::CrateUsage,
+};
+
+/// The directory in the target directory where cargo-minify keeps its files
+pub const TOOL_DIR: &str = "minify";
+const STATE_FILE: &str = "pending.json";
+const STATE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    version: u32,
+    /// The [`cache_key`](crate::cache_key) of the options of the analysis
+    key: u64,
+    diagnostics: Vec<UnusedDiagnostic>,
+    changes: Vec<PendingChange>,
+    kept: Kept,
+}
+
+/// What an analysis finds besides the diagnostics it removes, which is
+/// restored along with them
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Kept {
+    pub reported: Vec<UnusedDiagnostic>,
+    pub annotated: Vec<UnusedDiagnostic>,
+    pub field_initializations: Vec<Initialization>,
+    pub justified: Vec<Justification>,
+    pub expired_justifications: Vec<Justification>,
+    pub cascade_candidates: Vec<UnusedDiagnostic>,
+    pub doc_links: Vec<DocLink>,
+    pub test_only_usage: Vec<UnusedDiagnostic>,
+    pub suppressed_by_convention: usize,
+    pub suppressed_doc_only: usize,
+    #[serde(default)]
+    pub unparsed: Vec<UnparsedFile>,
+    /// With `--unused-deps`, the crates rustc's checks didn't use
+    #[serde(default)]
+    pub crate_usage: CrateUsage,
+}
+
+/// The diagnostics, changes and other findings of an analysis
+pub type Saved = (Vec<UnusedDiagnostic>, Vec<Change>, Kept);
+
+#[derive(Serialize, Deserialize)]
+struct PendingChange {
+    file_name: PathBuf,
+    original_hash: u64,
+    proposed_content: String,
+    #[serde(default)]
+    category: ChangeCategory,
+    #[serde(default)]
+    removal: bool,
+}
+
+/// Why pending changes could not be resumed
+#[derive(Debug)]
+pub enum ResumeError {
+    /// There are no pending changes
+    NotFound,
+    /// The pending changes were written by an incompatible version
+    Incompatible,
+    /// The pending changes were found with other options
+    Options,
+    /// This file was modified after the pending changes were saved
+    Stale(PathBuf),
+}
+
+impl Display for ResumeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResumeError::NotFound => write!(f, "there are no pending changes"),
+            ResumeError::Incompatible => write!(f, "the pending changes can't be read"),
+            ResumeError::Options => write!(f, "the pending changes were found with other options"),
+            ResumeError::Stale(file) => write!(
+                f,
+                "the pending changes are stale, as {} was modified",
+                file.display()
+            ),
+        }
+    }
+}
+
+/// A 64-bit FNV-1a hash, stable across platforms and compiler versions
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The directory in the target directory where cargo-minify keeps its files,
+/// which is created with a `.gitignore` so it never makes a repository dirty
+pub fn tool_dir(target_dir: &Path) -> io::Result<PathBuf> {
+    ignored_dir(&target_dir.join(TOOL_DIR))
+}
+
+/// Creates the directory along with a `.gitignore` ignoring all of it
+pub fn ignored_dir(dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(gitignore, "*\n")?;
+    }
+
+    Ok(dir.to_path_buf())
+}
+
+fn state_file(target_dir: &Path) -> PathBuf {
+    target_dir.join(TOOL_DIR).join(STATE_FILE)
+}
+
+pub fn save(
+    target_dir: &Path,
+    root: &Path,
+    key: u64,
+    diagnostics: &[UnusedDiagnostic],
+    changes: &[Change],
+    kept: &Kept,
+) -> io::Result<()> {
+    tool_dir(target_dir)?;
+    save_to(
+        &state_file(target_dir),
+        root,
+        key,
+        diagnostics,
+        changes,
+        kept,
+    )
+}
+
+/// Saves the diagnostics, changes and other findings to the file, along with
+/// the key of the options they were found with, naming the changed files
+/// relative to `root`, so that they are found again in another checkout
+pub fn save_to(
+    file: &Path,
+    root: &Path,
+    key: u64,
+    diagnostics: &[UnusedDiagnostic],
+    changes: &[Change],
+    kept: &Kept,
+) -> io::Result<()> {
+    let root = root.canonicalize()?;
+    let state = State {
+        version: STATE_VERSION,
+        key,
+        diagnostics: diagnostics.to_vec(),
+        changes: changes
+            .iter()
+            .map(|change| PendingChange {
+                file_name: change
+                    .file_name()
+                    .canonicalize()
+                    .map(|file| {
+                        file.strip_prefix(&root)
+                            .map(Path::to_path_buf)
+                            .unwrap_or(file)
+                    })
+                    .unwrap_or_else(|_| change.file_name().to_path_buf()),
+                original_hash: content_hash(change.original_content()),
+                proposed_content: String::from_utf8_lossy(change.proposed_content()).into_owned(),
+                category: change.category(),
+                removal: change.is_removal(),
+            })
+            .collect(),
+        kept: kept.clone(),
+    };
+
+    fs::write(file, serde_json::to_vec(&state).map_err(io::Error::other)?)
+}
+
+/// Loads the pending findings of an analysis with the options of `key`,
+/// checking that none of the files were modified in the meantime
+pub fn load(target_dir: &Path, root: &Path, key: u64) -> Result<Saved, ResumeError> {
+    load_from(&state_file(target_dir), root, key)
+}
+
+/// Loads the findings saved by [`save_to`] with the same key, with the
+/// changed files relative to `root`
+pub fn load_from(file: &Path, root: &Path, key: u64) -> Result<Saved, ResumeError> {
+    let contents = fs::read(file).map_err(|_| ResumeError::NotFound)?;
+    let state: State = serde_json::from_slice(&contents).map_err(|_| ResumeError::Incompatible)?;
+    if state.version != STATE_VERSION {
+        return Err(ResumeError::Incompatible);
+    }
+    if state.key != key {
+        return Err(ResumeError::Options);
+    }
+
+    let changes = state
+        .changes
+        .into_iter()
+        .map(|pending| {
+            let file_name = root.join(&pending.file_name);
+            let original_content =
+                fs::read(&file_name).map_err(|_| ResumeError::Stale(file_name.clone()))?;
+            if content_hash(&original_content) != pending.original_hash {
+                return Err(ResumeError::Stale(file_name));
+            }
+
+            let change = match pending.removal {
+                true => Change::removal(file_name, original_content),
+                false => Change::new(
+                    file_name,
+                    original_content,
+                    pending.proposed_content.into_bytes(),
+                ),
+            };
+            Ok(change.with_category(pending.category))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((state.diagnostics, changes, state.kept))
+}
+
+/// Removes the pending changes, once they are applied
+pub fn clear(target_dir: &Path) -> io::Result<()> {
+    match fs::remove_file(state_file(target_dir)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(content_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(content_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(content_hash(b"fn foo() {}"), content_hash(b"fn foa() {}"));
+    }
+
+    #[test]
+    fn loads_in_another_checkout() {
+        let dir = std::env::temp_dir().join(format!("cargo-minify-state-{}", std::process::id()));
+        let (root, other) = (dir.join("a"), dir.join("b"));
+        for root in [&root, &other] {
+            fs::create_dir_all(root).unwrap();
+            fs::write(root.join("lib.rs"), "fn f() {}\n").unwrap();
+        }
+
+        let file = dir.join("state.json");
+        let change = Change::new(root.join("lib.rs"), b"fn f() {}\n".to_vec(), Vec::new());
+        save_to(&file, &root, 1, &[], &[change], &Kept::default()).unwrap();
+        let (_, changes, _) = load_from(&file, &other, 1).unwrap();
+        assert_eq!(changes[0].file_name(), other.join("lib.rs"));
+        assert!(matches!(
+            load_from(&file, &other, 2),
+            Err(ResumeError::Options)
+        ));
+
+        fs::write(other.join("lib.rs"), "fn g() {}\n").unwrap();
+        assert!(matches!(
+            load_from(&file, &other, 1),
+            Err(ResumeError::Stale(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! rustc's own machine-applicable suggestions, such as those removing unused
+//! imports. The compiler knows best how to remove what it reports, say
+//! turning `use a::{b, c};` into `use a::b;`, so its replacements are applied
+//! verbatim, instead of the spans being worked out from the syntax tree.
+//!
+//! A suggestion may cover several findings, as when all the names of a `use`
+//! item are unused; it's only applied when all of them are removed, as the
+//! others would go along with it.
+
+use cargo_metadata::diagnostic::{Applicability, Diagnostic, DiagnosticSpan};
+use serde::{Deserialize, Serialize};
+
+use crate::{edit::TextEdit, unused::UnusedDiagnostic};
+
+/// A replacement of a byte range of the file the finding is in
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Replacement {
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub text: String,
+}
+
+impl Replacement {
+    pub fn to_edit(&self) -> TextEdit {
+        TextEdit::replace(self.byte_start..self.byte_end, self.text.clone())
+    }
+}
+
+/// The replacements of a machine-applicable suggestion, along with the
+/// number of findings it covers
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub replacements: Vec<Replacement>,
+    pub covers: usize,
+}
+
+fn replacement(span: &DiagnosticSpan) -> Option<Replacement> {
+    if span.suggestion_applicability != Some(Applicability::MachineApplicable) {
+        return None;
+    }
+
+    Some(Replacement {
+        byte_start: span.byte_start as usize,
+        byte_end: span.byte_end as usize,
+        text: span.suggested_replacement.clone()?,
+    })
+}
+
+fn covers(span: &DiagnosticSpan, finding: &UnusedDiagnostic) -> bool {
+    span.file_name == finding.span.file_name
+        && span.byte_start <= finding.span.byte_start
+        && finding.span.byte_end <= span.byte_end
+}
+
+/// Attaches the machine-applicable suggestions of the diagnostic to the
+/// findings it was turned into; each finding gets the suggestion with a
+/// replacement covering its span, if any
+pub fn attach(diagnostic: &Diagnostic, found: &mut [UnusedDiagnostic]) {
+    for child in &diagnostic.children {
+        let Some(replacements) = child
+            .spans
+            .iter()
+            .map(replacement)
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let covered =
+            |finding: &UnusedDiagnostic| child.spans.iter().any(|span| covers(span, finding));
+        let suggestion = Suggestion {
+            replacements,
+            covers: found.iter().filter(|finding| covered(finding)).count(),
+        };
+        if suggestion.replacements.is_empty() || suggestion.covers == 0 {
+            continue;
+        }
+        for finding in found.iter_mut().filter(|finding| covered(finding)) {
+            finding.suggestion.get_or_insert_with(|| suggestion.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::imports;
+
+    #[test]
+    fn attaches_suggestions() {
+        let span = |start: u32, end: u32, text: &str, primary: bool| {
+            serde_json::json!({
+                "file_name": "src/main.rs", "byte_start": start, "byte_end": end,
+                "line_start": 1, "line_end": 1, "column_start": start + 1,
+                "column_end": end + 1, "is_primary": primary,
+                "text": [{"text": text, "highlight_start": 1, "highlight_end": 1}],
+                "label": null,
+                "suggested_replacement": if primary { None } else { Some("") },
+                "suggestion_applicability": if primary { None } else { Some("MachineApplicable") },
+                "expansion": null
+            })
+        };
+        let diagnostic: Diagnostic = serde_json::from_value(serde_json::json!({
+            "message": "unused imports: `BTreeMap` and `HashSet`",
+            "code": {"code": "unused_imports", "explanation": null},
+            "level": "warning",
+            "spans": [span(32, 39, "HashSet", true), span(41, 49, "BTreeMap", true)],
+            "children": [{
+                "message": "remove the unused imports", "code": null, "level": "help",
+                "spans": [span(30, 49, "", false), span(22, 23, "", false),
+                          span(49, 50, "", false)],
+                "children": [], "rendered": null
+            }],
+            "rendered": null
+        }))
+        .unwrap();
+
+        let mut found = imports::from_diagnostic(&diagnostic).unwrap();
+        attach(&diagnostic, &mut found);
+        let suggestion = found[0].suggestion.as_ref().unwrap();
+        assert_eq!(suggestion.covers, 2);
+        assert_eq!(suggestion.replacements.len(), 3);
+        assert_eq!(found[1].suggestion.as_ref(), Some(suggestion));
+    }
+}
@@ -0,0 +1,102 @@
+//! `#[allow(dead_code)]` and similar attributes keep rustc from reporting
+//! unused code, so the ones near dead code (or covering whole modules) are
+//! reported, to audit whether they hide more code that could be removed.
+
+use std::path::Path;
+
+use crate::unused::UnusedDiagnostic;
+
+/// The lints that, when allowed, hide unused code from the analysis
+const HIDING_LINTS: &[&str] = &["dead_code", "unused", "unused_imports", "unused_macros"];
+
+/// How many lines an item-level suppression may be away from a finding to
+/// count as near it
+const NEAR_LINES: usize = 10;
+
+/// An `#[allow]` attribute of any of the lints that hide unused code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suppression {
+    pub file_name: String,
+    pub line: usize,
+    pub lints: Vec<String>,
+    /// An inner `#![allow]` attribute, which covers its whole module
+    pub module_level: bool,
+}
+
+/// The suppressions in the source, found textually
+fn find(file_name: &str, src: &str) -> Vec<Suppression> {
+    src.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.trim();
+            let (module_level, rest) = match line.strip_prefix("#![allow(") {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix("#[allow(")?),
+            };
+            let lints: Vec<_> = rest
+                .split(')')
+                .next()?
+                .split(',')
+                .map(str::trim)
+                .filter(|lint| HIDING_LINTS.contains(lint))
+                .map(str::to_string)
+                .collect();
+
+            (!lints.is_empty()).then(|| Suppression {
+                file_name: file_name.to_string(),
+                line: i + 1,
+                lints,
+                module_level,
+            })
+        })
+        .collect()
+}
+
+/// The suppressions that cover whole modules, or that are near any of the
+/// findings; `workspace_root` is used to report file names relative to it,
+/// just like rustc does
+pub fn near_dead_code(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    diagnostics: &[UnusedDiagnostic],
+) -> Vec<Suppression> {
+    files
+        .iter()
+        .filter_map(|file| {
+            let file = file.as_ref();
+            let src = std::fs::read_to_string(file).ok()?;
+            let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+            Some(find(&file_name.to_string_lossy(), &src))
+        })
+        .flatten()
+        .filter(|suppression| {
+            suppression.module_level
+                || diagnostics.iter().any(|diagnostic| {
+                    diagnostic.span.file_name == suppression.file_name
+                        && diagnostic.span.line_start.abs_diff(suppression.line) <= NEAR_LINES
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suppressions() {
+        let found = find(
+            "lib.rs",
+            "#![allow(unused)]\n\
+             #[allow(clippy::all, dead_code)]\n\
+             fn a() {}\n\
+             #[allow(non_snake_case)]\n\
+             fn B() {}\n",
+        );
+
+        assert_eq!(found.len(), 2);
+        assert!(found[0].module_level);
+        assert_eq!(found[1].line, 2);
+        assert_eq!(found[1].lints, ["dead_code"]);
+    }
+}
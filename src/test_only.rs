@@ -0,0 +1,220 @@
+//! Code only compiled for tests. `cargo check --all-targets` compiles libraries
+//! and binaries twice: on their own, and as their unit tests, with `cfg(test)`
+//! set. The first compilation reports the private helpers that only tests use
+//! as unused, so code is only unused when every compilation of its target
+//! reports it; except for the code under `#[cfg(test)]`, such as the helpers
+//! of `mod tests`, which only the test compilation sees. The cfg attributes
+//! are evaluated syntactically, like those of doc-only code.
+//!
+//! With `--test-only-usage`, the libraries and binaries are checked once more
+//! on their own, and the code that this check reports, but the full one
+//! doesn't, is only used by tests.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use quote::ToTokens;
+use syn::{spanned::Spanned, Item};
+
+use crate::{doc_only, modules, unused::UnusedDiagnostic};
+
+/// Whether the tokens of an item start with a `#[cfg(test)]`, or the like
+fn has_test_cfg(tokens: impl ToTokens) -> bool {
+    doc_only::starts_with_cfg_only(tokens.into_token_stream(), &["test"])
+}
+
+fn lines_of(span: proc_macro2::Span) -> RangeInclusive<usize> {
+    span.start().line..=span.end().line
+}
+
+/// The lines of the code of a crate that is only compiled for tests, by file
+#[derive(Debug, Default)]
+pub struct TestOnlyCode {
+    lines: HashMap<PathBuf, Vec<RangeInclusive<usize>>>,
+}
+
+/// An out-of-line module: its file, the directory of its own modules, and
+/// whether it's only compiled for tests
+type Declared = (PathBuf, PathBuf, bool);
+
+fn collect_items(
+    items: &[Item],
+    dir: &Path,
+    test_only: bool,
+    lines: &mut Vec<RangeInclusive<usize>>,
+    declared: &mut Vec<Declared>,
+) {
+    for item in items {
+        let item_test_only = test_only || has_test_cfg(item);
+        if item_test_only && !test_only {
+            lines.push(lines_of(item.span()));
+        }
+
+        match item {
+            Item::Mod(module) => match &module.content {
+                Some((_, items)) => {
+                    let dir = dir.join(module.ident.to_string());
+                    collect_items(items, &dir, item_test_only, lines, declared);
+                }
+                None => {
+                    let Some(file) = modules::declared_file(dir, &module.ident.to_string()) else {
+                        continue;
+                    };
+                    let Some(dir) = modules::module_dir(&file, false) else {
+                        continue;
+                    };
+                    declared.push((file, dir, item_test_only));
+                }
+            },
+            Item::Impl(block) if !item_test_only => {
+                let items = block.items.iter().filter(|item| has_test_cfg(item));
+                lines.extend(items.map(|item| lines_of(item.span())));
+            }
+            Item::Trait(block) if !item_test_only => {
+                let items = block.items.iter().filter(|item| has_test_cfg(item));
+                lines.extend(items.map(|item| lines_of(item.span())));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TestOnlyCode {
+    /// Finds the test-only code of the crate with the given root file, and of
+    /// the modules it declares
+    pub fn of_crate(root: &Path) -> Self {
+        let mut code = TestOnlyCode::default();
+        if let Some(dir) = modules::module_dir(root, true) {
+            code.collect_file(root, &dir, false);
+        }
+
+        code
+    }
+
+    fn collect_file(&mut self, file: &Path, dir: &Path, test_only: bool) {
+        let Ok(path) = file.canonicalize() else {
+            return;
+        };
+        if self.lines.contains_key(&path) {
+            return;
+        }
+        let Ok(src) = fs::read_to_string(file) else {
+            return;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            return;
+        };
+
+        let test_only = test_only || parsed.attrs.iter().any(has_test_cfg);
+        let mut lines = Vec::new();
+        if test_only {
+            lines.push(1..=usize::MAX);
+        }
+        let mut declared = Vec::new();
+        collect_items(&parsed.items, dir, test_only, &mut lines, &mut declared);
+        self.lines.insert(path, lines);
+
+        for (file, dir, test_only) in declared {
+            self.collect_file(&file, &dir, test_only);
+        }
+    }
+
+    /// Whether the line of the file, given relative to the workspace as in
+    /// rustc's diagnostics, is only compiled for tests
+    pub fn contains(&self, file_name: &str, line: usize) -> bool {
+        self.lines
+            .iter()
+            .filter(|(path, _)| path.ends_with(file_name))
+            .any(|(_, lines)| lines.iter().any(|lines| lines.contains(&line)))
+    }
+}
+
+/// The findings of the check without tests that the check with them doesn't
+/// have, which are used by tests alone
+pub fn used_by_tests_only(
+    without_tests: Vec<UnusedDiagnostic>,
+    with_tests: &[UnusedDiagnostic],
+) -> Vec<UnusedDiagnostic> {
+    let key = |diagnostic: &UnusedDiagnostic| {
+        (
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+            diagnostic.span.file_name.clone(),
+            diagnostic.span.line_start,
+        )
+    };
+    let unused: HashSet<_> = with_tests.iter().map(key).collect();
+
+    without_tests
+        .into_iter()
+        .filter(|diagnostic| !unused.contains(&key(diagnostic)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::unused::{synthetic_span, UnusedDiagnosticKind};
+
+    use super::*;
+
+    #[test]
+    fn finds_test_only_code() {
+        let dir = std::env::temp_dir();
+        let dir = dir.join(format!("cargo-minify-test-only-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/tests")).unwrap();
+        let lib = dir.join("src/lib.rs");
+        fs::write(
+            &lib,
+            "fn helper() {}\n\
+             #[cfg(test)]\nmod unit {\n    fn fixture() {}\n}\n\
+             #[cfg(not(test))]\nfn production() {}\n\
+             pub struct S;\nimpl S {\n    #[cfg(test)]\n    fn new() -> S { S }\n}\n\
+             #[cfg(test)]\nmod tests;\n",
+        )
+        .unwrap();
+        fs::write(dir.join("src/tests.rs"), "mod deeper;\nfn setup() {}\n").unwrap();
+        fs::write(dir.join("src/tests/deeper.rs"), "fn nested() {}\n").unwrap();
+
+        let code = TestOnlyCode::of_crate(&lib);
+        let test_only: Vec<_> = (1..=14)
+            .filter(|&line| code.contains("src/lib.rs", line))
+            .collect();
+        assert_eq!(test_only, [2, 3, 4, 5, 10, 11, 13, 14]);
+        assert!(code.contains("src/tests.rs", 2));
+        assert!(code.contains("src/tests/deeper.rs", 1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn finds_usage_by_tests_only() {
+        let src = "fn helper() {}
+fn dead() {}
+";
+        let parsed = syn::parse_file(src).unwrap();
+        let found: Vec<_> = parsed
+            .items
+            .iter()
+            .map(|item| {
+                let Item::Fn(item) = item else { unreachable!() };
+                UnusedDiagnostic {
+                    kind: UnusedDiagnosticKind::Function,
+                    ident: item.sig.ident.to_string(),
+                    span: synthetic_span("src/lib.rs", src, item.sig.ident.span()),
+                    suggestion: None,
+                    configs: Vec::new(),
+                }
+            })
+            .collect();
+
+        let used = used_by_tests_only(found.clone(), &found[1..]);
+        let idents: Vec<_> = used
+            .iter()
+            .map(|diagnostic| diagnostic.ident.as_str())
+            .collect();
+        assert_eq!(idents, ["helper"]);
+    }
+}
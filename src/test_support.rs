@@ -0,0 +1,177 @@
+//! Helpers to run cargo-minify against small throwaway crates and compare the
+//! result with golden files. Used by the integration tests, but usable by
+//! anyone who wants to test cargo-minify on their own snippets.
+
+use std::{
+    env, fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{error::Result, Analysis, ApplyReport};
+
+const MANIFEST: &str = r#"[package]
+name = "minify-test-crate"
+version = "0.1.0"
+edition = "2021"
+"#;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A crate in a temporary directory, which is removed again on drop
+pub struct TempCrate {
+    root: PathBuf,
+}
+
+impl TempCrate {
+    /// Creates a library crate with the given `src/lib.rs`
+    pub fn new(lib_rs: &str) -> io::Result<Self> {
+        Self::with_files([("src/lib.rs", lib_rs)])
+    }
+
+    /// Creates a crate with the given files, paths are relative to the crate
+    /// root; a `Cargo.toml` is added unless one is given
+    pub fn with_files<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> io::Result<Self> {
+        let root = env::temp_dir().join(format!(
+            "cargo-minify-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(&root)?;
+        let temp_crate = TempCrate { root };
+
+        fs::write(temp_crate.manifest_path(), MANIFEST)?;
+        for (path, contents) in files {
+            let path = temp_crate.root.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+
+        Ok(temp_crate)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join("Cargo.toml")
+    }
+
+    /// Reads a file, relative to the crate root
+    pub fn read(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.root.join(path))
+    }
+
+    /// Command line arguments pointing cargo-minify at this crate, followed by
+    /// `extra_args`
+    pub fn args(&self, extra_args: &[&str]) -> Vec<String> {
+        let mut args = vec![
+            "--manifest-path".to_string(),
+            self.manifest_path().to_string_lossy().into_owned(),
+        ];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+        args
+    }
+
+    /// Makes the crate a git repository with all of its files committed, and
+    /// `target` and `Cargo.lock` ignored
+    pub fn init_git(&self) -> std::result::Result<git2::Repository, git2::Error> {
+        let repo = git2::Repository::init(&self.root)?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "cargo-minify")?;
+        config.set_str("user.email", "cargo-minify@example.com")?;
+        fs::write(self.root.join(".gitignore"), "/target\n/Cargo.lock\n")
+            .map_err(|err| git2::Error::from_str(&err.to_string()))?;
+
+        let mut index = repo.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = repo.signature()?;
+        repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])?;
+        drop(tree);
+
+        Ok(repo)
+    }
+
+    /// The files of the commit at HEAD, relative to the crate root
+    pub fn committed_files(&self) -> std::result::Result<Vec<String>, git2::Error> {
+        let repo = git2::Repository::open(&self.root)?;
+        let tree = repo.head()?.peel_to_tree()?;
+        let mut files = Vec::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                files.push(format!("{dir}{}", entry.name().unwrap_or_default()));
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        files.sort();
+
+        Ok(files)
+    }
+
+    pub fn analyze(&self, extra_args: &[&str]) -> Result<Analysis> {
+        crate::analyze(&self.args(extra_args))
+    }
+
+    /// Analyzes and applies the changes, regardless of version control
+    pub fn minify(&self, extra_args: &[&str]) -> Result<ApplyReport> {
+        let mut args = vec!["--allow-no-vcs", "--allow-dirty", "--allow-staged"];
+        args.extend_from_slice(extra_args);
+        crate::apply(self.analyze(&args)?)
+    }
+}
+
+impl Drop for TempCrate {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Formats source code by piping it through `rustfmt`
+pub fn rustfmt(src: &str) -> io::Result<String> {
+    let mut child = Command::new("rustfmt")
+        .args(["--edition", "2021"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin should be piped");
+    stdin.write_all(src.as_bytes())?;
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("rustfmt failed"));
+    }
+
+    String::from_utf8(output.stdout).map_err(io::Error::other)
+}
+
+/// Minifies `input_<name>.rs` from `dir` as the `lib.rs` of a temporary crate,
+/// and asserts that the result equals `golden_<name>.rs`. The result is also
+/// written to `output_<name>.rs` for inspection. With `format`, the result is
+/// passed through rustfmt before comparing.
+pub fn assert_golden(dir: &Path, name: &str, format: bool) {
+    let input = fs::read_to_string(dir.join(format!("input_{name}.rs"))).unwrap();
+    let golden = fs::read_to_string(dir.join(format!("golden_{name}.rs"))).unwrap();
+
+    let temp_crate = TempCrate::new(&input).unwrap();
+    temp_crate.minify(&[]).unwrap();
+    let mut output = temp_crate.read("src/lib.rs").unwrap();
+    if format {
+        output = rustfmt(&output).unwrap();
+    }
+
+    fs::write(dir.join(format!("output_{name}.rs")), &output).unwrap();
+
+    assert_eq!(output, golden, "golden test `{name}` failed");
+}
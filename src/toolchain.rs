@@ -0,0 +1,142 @@
+//! The toolchain pinned by a `rust-toolchain.toml` file. rustup picks it based
+//! on the current directory, which need not be the workspace (for example with
+//! `--manifest-path`), so it is passed on to the spawned commands explicitly.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::resolver::{toml_strings, toml_value};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Toolchain {
+    /// The file the toolchain is pinned in
+    pub file: PathBuf,
+    pub channel: String,
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+}
+
+impl Toolchain {
+    /// Parses a toolchain file, either in the TOML format, or the legacy one
+    /// that only holds the channel
+    fn parse(file: PathBuf, src: &str) -> Option<Self> {
+        if !src.contains("[toolchain]") {
+            let channel = src.lines().next()?.trim();
+            return (!channel.is_empty()).then(|| Toolchain {
+                file,
+                channel: channel.to_string(),
+                components: Vec::new(),
+                targets: Vec::new(),
+            });
+        }
+
+        let strings = |key| {
+            toml_value(src, "toolchain", key)
+                .map(|value| toml_strings(&value))
+                .unwrap_or_default()
+        };
+        Some(Toolchain {
+            channel: strings("channel").into_iter().next()?,
+            components: strings("components"),
+            targets: strings("targets"),
+            file,
+        })
+    }
+
+    /// Makes the command use this toolchain
+    pub fn apply(&self, command: &mut Command) {
+        command.env("RUSTUP_TOOLCHAIN", &self.channel);
+    }
+
+    /// Whether rustup has the toolchain installed, along with the pinned
+    /// components and targets that are missing. Without rustup, there is
+    /// nothing to check (nor anything that could switch toolchains).
+    pub fn installed(&self) -> io::Result<(bool, Vec<String>)> {
+        let rustup = |args: &[&str]| match Command::new("rustup").args(args).output() {
+            Ok(output) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        };
+        // Installed names are suffixed with the host, as in `1.75-x86_64-...`
+        let matches = |name: &str, prefix: &str| {
+            name.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', '.', ' ']))
+        };
+
+        let Some(toolchains) = rustup(&["toolchain", "list"])? else {
+            return Ok((true, Vec::new()));
+        };
+        if !toolchains.lines().any(|name| matches(name, &self.channel)) {
+            return Ok((false, Vec::new()));
+        }
+
+        let installed = |kind| {
+            rustup(&[kind, "list", "--installed", "--toolchain", &self.channel])
+                .map(Option::unwrap_or_default)
+        };
+        let mut missing = Vec::new();
+        let components = installed("component")?;
+        for component in &self.components {
+            if !components.lines().any(|name| matches(name, component)) {
+                missing.push(format!("component `{component}`"));
+            }
+        }
+        let targets = installed("target")?;
+        for target in &self.targets {
+            if !targets.lines().any(|name| name.trim() == target) {
+                missing.push(format!("target `{target}`"));
+            }
+        }
+
+        Ok((true, missing))
+    }
+}
+
+/// The toolchain pinned for the directory, like rustup finds it: in the
+/// closest `rust-toolchain.toml` (or `rust-toolchain`) of the directory or the
+/// ones above it
+pub fn pinned(dir: &Path) -> Option<Toolchain> {
+    dir.ancestors().find_map(|dir| {
+        ["rust-toolchain.toml", "rust-toolchain"]
+            .iter()
+            .find_map(|name| {
+                let file = dir.join(name);
+                let src = std::fs::read_to_string(&file).ok()?;
+                Toolchain::parse(file, &src)
+            })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toolchain_files() {
+        let toolchain = Toolchain::parse(
+            PathBuf::from("rust-toolchain.toml"),
+            "[toolchain]\n\
+             channel = \"1.75\"\n\
+             components = [\"rustfmt\", \"clippy\"]\n\
+             targets = [\"thumbv7em-none-eabihf\"]\n",
+        )
+        .unwrap();
+        assert_eq!(toolchain.channel, "1.75");
+        assert_eq!(toolchain.components, ["rustfmt", "clippy"]);
+        assert_eq!(toolchain.targets, ["thumbv7em-none-eabihf"]);
+
+        let legacy = Toolchain::parse(PathBuf::from("rust-toolchain"), "nightly-2024-01-01\n");
+        assert_eq!(legacy.unwrap().channel, "nightly-2024-01-01");
+
+        assert_eq!(
+            Toolchain::parse(
+                PathBuf::from("rust-toolchain.toml"),
+                "[toolchain]\npath = \"/opt\"\n"
+            ),
+            None
+        );
+    }
+}
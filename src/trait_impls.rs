@@ -0,0 +1,453 @@
+//! An experimental syntactic analysis that finds trait impls whose methods are
+//! never used. rustc never reports trait impls as unused, but when none of the
+//! trait's methods are called anywhere in the workspace, and the trait is never
+//! mentioned (as in bounds or `dyn Trait`), nothing relies on the impl. Only
+//! traits defined in the workspace and a few std traits that are only used by
+//! calling their methods are considered; std traits invoked implicitly, such
+//! as `Drop` or `Deref`, are never removed.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{spanned::Spanned, ImplItem, Item, ItemImpl, TraitItem, Type};
+
+use crate::{
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    FileResolutionOptions,
+};
+
+/// The std traits that are considered, along with the methods that use them
+const STD_TRAITS: &[(&str, &[&str])] = &[
+    ("Display", &["to_string", "fmt"]),
+    ("From", &["from", "into"]),
+    ("Into", &["into"]),
+    ("TryFrom", &["try_from", "try_into"]),
+    ("TryInto", &["try_into"]),
+    ("FromStr", &["from_str", "parse"]),
+    ("AsRef", &["as_ref"]),
+    ("AsMut", &["as_mut"]),
+];
+
+/// A trait impl
+struct TraitImpl<'a> {
+    /// `Trait for SelfTy`, used to find the impl again when removing it
+    id: String,
+    trait_name: String,
+    /// The name of the self type, if it is a path
+    type_name: Option<String>,
+    item: &'a ItemImpl,
+}
+
+fn as_trait_impl(item: &ItemImpl) -> Option<TraitImpl<'_>> {
+    let (negative, path, _) = item.trait_.as_ref()?;
+    if negative.is_some() || item.unsafety.is_some() {
+        return None;
+    }
+    let type_name = match &*item.self_ty {
+        Type::Path(path) if path.qself.is_none() => {
+            Some(path.path.segments.last()?.ident.to_string())
+        }
+        _ => None,
+    };
+    // Blanket impls may be used by any type
+    let is_generic = |name: &String| item.generics.type_params().any(|param| param.ident == name);
+    if type_name.as_ref().is_some_and(is_generic) {
+        return None;
+    }
+
+    Some(TraitImpl {
+        id: format!(
+            "{} for {}",
+            path.to_token_stream().to_string().replace(' ', ""),
+            item.self_ty.to_token_stream().to_string().replace(' ', "")
+        ),
+        trait_name: path.segments.last()?.ident.to_string(),
+        type_name,
+        item,
+    })
+}
+
+/// What an item uses, as far as trait impls are concerned
+#[derive(Default)]
+struct Uses {
+    /// The trait defined by the item, whose own uses don't count
+    defines: Option<String>,
+    /// Method calls, as in `.name`
+    methods: HashSet<String>,
+    /// Path calls, as in `Type::name`, with an empty type if it is no ident
+    paths: HashSet<(String, String)>,
+    idents: HashSet<String>,
+    /// Whether any string literal formats a value with `Display`, as in `{}`
+    display_placeholders: bool,
+    /// The idents in the return types of functions that use `?`
+    try_types: HashSet<String>,
+    /// Whether `?` is used where the return type is not known, as in closures
+    try_anywhere: bool,
+}
+
+impl Uses {
+    fn scan(&mut self, tokens: TokenStream) {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            let punct = |j: usize, c: char| matches!(tokens.get(j), Some(TokenTree::Punct(p)) if p.as_char() == c);
+            match token {
+                TokenTree::Ident(ident) => {
+                    let name = ident.to_string();
+                    if i >= 1 && punct(i - 1, '.') {
+                        self.methods.insert(name.clone());
+                    } else if i >= 2 && punct(i - 1, ':') && punct(i - 2, ':') {
+                        let path = match i.checked_sub(3).map(|j| &tokens[j]) {
+                            Some(TokenTree::Ident(ident)) => ident.to_string(),
+                            _ => String::new(),
+                        };
+                        self.paths.insert((path, name.clone()));
+                    }
+                    self.idents.insert(name);
+                }
+                TokenTree::Group(group) => self.scan(group.stream()),
+                TokenTree::Literal(literal) => {
+                    let literal = literal.to_string();
+                    if literal.starts_with('"') || literal.starts_with('r') {
+                        self.display_placeholders |= has_display_placeholder(&literal);
+                    }
+                }
+                TokenTree::Punct(_) => {}
+            }
+        }
+    }
+
+    /// Notes the return type of a function, if its body uses `?`
+    fn scan_function(&mut self, output: &syn::ReturnType, body: TokenStream) {
+        let (uses_try, has_closures) = try_and_closures(body);
+        if !uses_try {
+            return;
+        }
+        if has_closures {
+            self.try_anywhere = true;
+        }
+        let mut output_uses = Uses::default();
+        output_uses.scan(output.to_token_stream());
+        self.try_types.extend(output_uses.idents);
+    }
+}
+
+/// Whether the tokens contain `?`, and whether they contain closures (or
+/// async blocks), in which `?` converts to an unknown type
+fn try_and_closures(tokens: TokenStream) -> (bool, bool) {
+    let (mut uses_try, mut has_closures) = (false, false);
+    for token in tokens {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '?' => uses_try = true,
+            TokenTree::Punct(punct) if punct.as_char() == '|' => has_closures = true,
+            TokenTree::Ident(ident) if ident == "async" => has_closures = true,
+            TokenTree::Group(group) => {
+                let (inner_try, inner_closures) = try_and_closures(group.stream());
+                uses_try |= inner_try;
+                has_closures |= inner_closures;
+            }
+            _ => {}
+        }
+    }
+
+    (uses_try, has_closures)
+}
+
+/// Whether the string literal has a placeholder that formats with `Display`,
+/// such as `{}`, `{name}` or `{:>8}`, rather than `{:?}` or `{:x}`
+fn has_display_placeholder(literal: &str) -> bool {
+    let mut rest = literal;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        if let Some(escaped) = rest.strip_prefix('{') {
+            rest = escaped;
+            continue;
+        }
+        let Some(end) = rest.find('}') else {
+            return false;
+        };
+        let spec = rest[..end].split_once(':').map_or("", |(_, spec)| spec);
+        if !spec.ends_with(['?', 'x', 'X', 'o', 'b', 'e', 'E', 'p']) {
+            return true;
+        }
+        rest = &rest[end + 1..];
+    }
+
+    false
+}
+
+/// Collects the trait impls, the uses of each item (along with the impl the
+/// item is, if any), and the methods and associated consts of the traits
+/// defined
+fn collect_items<'a>(
+    items: &'a [Item],
+    impls: &mut Vec<TraitImpl<'a>>,
+    uses: &mut Vec<(Option<&'a ItemImpl>, Uses)>,
+    traits: &mut HashMap<String, HashSet<String>>,
+) {
+    for item in items {
+        let mut item_uses = Uses::default();
+        match item {
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, impls, uses, traits);
+                }
+                continue;
+            }
+            // Imports only mention what they import
+            Item::Use(_) => continue,
+            Item::Impl(block) => {
+                if let Some(found) = as_trait_impl(block) {
+                    impls.push(found);
+                }
+                // Implementing a trait does not use it
+                item_uses.scan(block.generics.to_token_stream());
+                item_uses.scan(block.self_ty.to_token_stream());
+                for impl_item in &block.items {
+                    item_uses.scan(impl_item.to_token_stream());
+                    if let ImplItem::Fn(function) = impl_item {
+                        let body = function.block.to_token_stream();
+                        item_uses.scan_function(&function.sig.output, body);
+                    }
+                }
+                uses.push((Some(block), item_uses));
+                continue;
+            }
+            Item::Trait(definition) => {
+                let methods = traits.entry(definition.ident.to_string()).or_default();
+                for trait_item in &definition.items {
+                    match trait_item {
+                        TraitItem::Fn(function) => {
+                            methods.insert(function.sig.ident.to_string());
+                            if let Some(block) = &function.default {
+                                let body = block.to_token_stream();
+                                item_uses.scan_function(&function.sig.output, body);
+                            }
+                        }
+                        TraitItem::Const(constant) => {
+                            methods.insert(constant.ident.to_string());
+                        }
+                        _ => {}
+                    }
+                }
+                item_uses.defines = Some(definition.ident.to_string());
+                item_uses.scan(definition.supertraits.to_token_stream());
+                for trait_item in &definition.items {
+                    item_uses.scan(trait_item.to_token_stream());
+                }
+            }
+            Item::Fn(function) => {
+                let body = function.block.to_token_stream();
+                item_uses.scan_function(&function.sig.output, body);
+                item_uses.scan(item.to_token_stream());
+            }
+            Item::Type(alias) => {
+                // The alias may be the return type of functions that use `?`
+                let mut target = Uses::default();
+                target.scan(alias.ty.to_token_stream());
+                item_uses.try_types.extend(target.idents);
+                item_uses.scan(item.to_token_stream());
+            }
+            _ => item_uses.scan(item.to_token_stream()),
+        }
+        uses.push((None, item_uses));
+    }
+}
+
+/// Finds the span of the trait impl identified by `id`, as given out in the
+/// diagnostics of [`get_unused_trait_impls`]
+pub fn impl_spans(file: &syn::File, id: &str) -> Vec<Span> {
+    let mut impls = Vec::new();
+    collect_items(
+        &file.items,
+        &mut impls,
+        &mut Vec::new(),
+        &mut HashMap::new(),
+    );
+
+    impls
+        .iter()
+        .filter(|found| found.id == id)
+        .map(|found| found.item.span())
+        .collect()
+}
+
+/// The trait impls whose traits' methods are never used
+fn unused<'a>(
+    impls: &'a [TraitImpl<'a>],
+    uses: &[(Option<&ItemImpl>, Uses)],
+    traits: &HashMap<String, HashSet<String>>,
+) -> Vec<&'a TraitImpl<'a>> {
+    impls
+        .iter()
+        .filter(|candidate| {
+            let trait_name = &candidate.trait_name;
+            let methods: Vec<&str> = match traits.get(trait_name) {
+                Some(methods) => methods.iter().map(String::as_str).collect(),
+                None => match STD_TRAITS.iter().find(|(name, _)| name == trait_name) {
+                    Some((_, methods)) => methods.to_vec(),
+                    None => return false,
+                },
+            };
+            // Path calls on a type with its own impl of the trait only use that
+            // impl, other paths (as in `T::from`) may use any
+            let type_names: HashSet<_> = impls
+                .iter()
+                .filter(|other| &other.trait_name == trait_name)
+                .filter_map(|other| other.type_name.as_deref())
+                .collect();
+
+            let is_used = uses
+                .iter()
+                .filter(|(item, _)| !item.is_some_and(|item| std::ptr::eq(item, candidate.item)))
+                .filter(|(_, uses)| uses.defines.as_ref() != Some(trait_name))
+                .any(|(_, uses)| {
+                    let path_uses = uses.paths.iter().any(|(path, name)| {
+                        methods.contains(&name.as_str())
+                            && (path == trait_name
+                                || candidate.type_name.as_ref() == Some(path)
+                                || !type_names.contains(path.as_str()))
+                    });
+                    let implicit_uses = match trait_name.as_str() {
+                        "Display" => uses.display_placeholders,
+                        "From" => {
+                            uses.try_anywhere
+                                || candidate
+                                    .type_name
+                                    .as_ref()
+                                    .is_none_or(|name| uses.try_types.contains(name))
+                        }
+                        _ => false,
+                    };
+                    uses.idents.contains(trait_name)
+                        || methods.iter().any(|method| uses.methods.contains(*method))
+                        || path_uses
+                        || implicit_uses
+                });
+            !is_used
+        })
+        .collect()
+}
+
+/// Finds the trait impls whose methods are never used in the given source
+/// files; `workspace_root` is used to report file names relative to it, just
+/// like rustc does
+pub fn get_unused_trait_impls(
+    files: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+) -> Vec<UnusedDiagnostic> {
+    let parsed: Vec<_> = files
+        .iter()
+        .filter_map(|file| {
+            let file = file.as_ref();
+            let src = std::fs::read_to_string(file).ok()?;
+            let parsed = syn::parse_file(&src).ok()?;
+            let file_name = file
+                .strip_prefix(workspace_root)
+                .unwrap_or(file)
+                .to_string_lossy()
+                .into_owned();
+            Some((file_name, src, parsed))
+        })
+        .collect();
+
+    let mut impls = Vec::new();
+    let mut files_of_impls = Vec::new();
+    let mut uses = Vec::new();
+    let mut traits = HashMap::new();
+    for (file_name, src, parsed) in &parsed {
+        let count = impls.len();
+        collect_items(&parsed.items, &mut impls, &mut uses, &mut traits);
+        files_of_impls.extend((count..impls.len()).map(|_| (file_name, src)));
+    }
+
+    let unused = unused(&impls, &uses, &traits);
+    impls
+        .iter()
+        .zip(files_of_impls)
+        .filter(|(found, (file_name, _))| {
+            unused.iter().any(|unused| std::ptr::eq(*unused, *found))
+                && file_resolution.is_included(file_name)
+        })
+        .map(|(found, (file_name, src))| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::TraitImpl,
+            span: synthetic_span(file_name, src, found.item.impl_token.span),
+            ident: found.id.clone(),
+            suggestion: None,
+            configs: Vec::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unused_impls(src: &str) -> Vec<String> {
+        let file = syn::parse_file(src).unwrap();
+        let (mut impls, mut uses, mut traits) = (Vec::new(), Vec::new(), HashMap::new());
+        collect_items(&file.items, &mut impls, &mut uses, &mut traits);
+        unused(&impls, &uses, &traits)
+            .into_iter()
+            .map(|found| found.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn finds_unused_impls() {
+        let src = "
+            use std::fmt::Display;
+            struct A; struct B; struct C;
+            impl Display for A { fn fmt(&self, f: &mut Formatter) -> Result { Ok(()) } }
+            impl From<u32> for A { fn from(_: u32) -> Self { A } }
+            impl From<u32> for B { fn from(_: u32) -> Self { B } }
+            impl Drop for C { fn drop(&mut self) {} }
+            trait Shape { fn area(&self) -> f64; }
+            impl Shape for A { fn area(&self) -> f64 { 1.0 } }
+            fn main() { let _ = B::from(1); }
+        ";
+        assert_eq!(
+            unused_impls(src),
+            ["Display for A", "From<u32> for A", "Shape for A"]
+        );
+    }
+
+    #[test]
+    fn finds_uses() {
+        let used = |src: &str| unused_impls(&format!("struct A; {src}")).is_empty();
+        assert!(used(
+            "impl Display for A {} fn f() { println!(\"{}\", A); }"
+        ));
+        assert!(!used(
+            "impl Display for A {} fn f() { println!(\"{:?}\", A); }"
+        ));
+        assert!(used(
+            "impl Display for A {} fn f() -> String { A.to_string() }"
+        ));
+        assert!(used("impl Display for A {} fn f(x: &dyn Display) {}"));
+        assert!(used(
+            "impl From<E> for A {} fn f() -> Result<(), A> { g()?; Ok(()) }"
+        ));
+        assert!(used(
+            "impl From<E> for A {} type R = Result<(), A>; fn f() -> R { g()? }"
+        ));
+        assert!(!used(
+            "impl From<E> for A {} fn f() -> Result<(), B> { g()?; Ok(()) }"
+        ));
+        assert!(used("impl FromStr for A {} fn f() { \"a\".parse::<A>(); }"));
+        assert!(used(
+            "impl<T> From<T> for A {} fn f<T: Into<A>>(x: T) { x.into(); }"
+        ));
+    }
+
+    #[test]
+    fn placeholders() {
+        assert!(has_display_placeholder("\"{}\""));
+        assert!(has_display_placeholder("\"{x:>8}\""));
+        assert!(!has_display_placeholder("\"{{}} {:?} {x:#x}\""));
+    }
+}
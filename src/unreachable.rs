@@ -0,0 +1,152 @@
+//! Statements that can never run, as reported by the `unreachable_code` lint,
+//! such as those after a `return`, a `panic!()` or `std::process::exit()`.
+//! rustc only reports the first unreachable statement of a block, so the
+//! rest of the block goes along with it. When either the unreachable code or
+//! the expression it follows comes out of a macro, only the other one is
+//! pointed at in the source; the finding is then the code after the diverging
+//! expression, which is named `after ...`.
+
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticSpan};
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{parse::Parser, spanned::Spanned, Block, Stmt};
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The findings of an `unreachable_code` diagnostic, pointing at the
+/// unreachable code itself if it's written in the source, or else at the
+/// expression it follows
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    if code.code != "unreachable_code" || !diagnostic.message.starts_with("unreachable") {
+        return None;
+    }
+
+    let in_source = |span: &&DiagnosticSpan| span.expansion.is_none();
+    let primary = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .find(in_source);
+    let span = primary.or_else(|| diagnostic.spans.iter().find(in_source))?;
+    let text = span_text(span);
+    let text = text.lines().next().unwrap_or_default().trim();
+    let ident = match span.is_primary {
+        true => text.to_string(),
+        false => format!("after {text}"),
+    };
+
+    Some(vec![UnusedDiagnostic {
+        kind: UnusedDiagnosticKind::Unreachable,
+        ident,
+        span: span.clone(),
+        suggestion: None,
+        configs: Vec::new(),
+    }])
+}
+
+/// The key to remove the unreachable code by: its position, or that of the
+/// expression it follows, as in `at:3:5` or `after:12:5`
+pub fn removal_key(diagnostic: &UnusedDiagnostic) -> String {
+    let span = &diagnostic.span;
+    let mode = if span.is_primary { "at" } else { "after" };
+    format!("{mode}:{}:{}", span.line_start, span.column_start)
+}
+
+/// Whether the span starts at the line and (1-based) column
+fn starts_at(span: Span, line: usize, column: usize) -> bool {
+    span.start().line == line && span.start().column + 1 == column
+}
+
+/// The span from the statement to the end of the block
+fn rest_of_block(stmts: &[Stmt], index: usize) -> Option<Span> {
+    stmts[index].span().join(stmts.last()?.span())
+}
+
+/// Finds the unreachable statements in the blocks among the tokens, trying
+/// every brace-delimited group as a block; inner blocks are searched first,
+/// as the expression code follows is in the innermost block around it
+fn find_in(tokens: TokenStream, after: bool, line: usize, column: usize) -> Option<Span> {
+    for token in tokens {
+        let TokenTree::Group(group) = token else {
+            continue;
+        };
+        if let Some(span) = find_in(group.stream(), after, line, column) {
+            return Some(span);
+        }
+        if group.delimiter() != Delimiter::Brace {
+            continue;
+        }
+        let Ok(stmts) = Block::parse_within.parse2(group.stream()) else {
+            continue;
+        };
+
+        let index = if after {
+            let position = (line, column.saturating_sub(1));
+            stmts
+                .iter()
+                .position(|stmt| {
+                    let (start, end) = (stmt.span().start(), stmt.span().end());
+                    (start.line, start.column) <= position && position < (end.line, end.column)
+                })
+                .map(|index| index + 1)
+        } else {
+            stmts
+                .iter()
+                .position(|stmt| starts_at(stmt.span(), line, column))
+        };
+        match index {
+            Some(index) if index < stmts.len() => return rest_of_block(&stmts, index),
+            Some(_) => return None,
+            None => {}
+        }
+    }
+
+    None
+}
+
+/// The span of the unreachable code given by its [`removal_key`]
+pub fn unreachable_spans(file: &syn::File, key: &str) -> Vec<Span> {
+    let mut parts = key.splitn(3, ':');
+    let (Some(mode), Some(line), Some(column)) = (parts.next(), parts.next(), parts.next()) else {
+        return Vec::new();
+    };
+    let (Ok(line), Ok(column)) = (line.parse(), column.parse()) else {
+        return Vec::new();
+    };
+
+    find_in(file.to_token_stream(), mode == "after", line, column)
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::test::rust_delete;
+
+    use super::*;
+
+    fn remove(src: &str, key: &str) -> String {
+        let keys = [(UnusedDiagnosticKind::Unreachable, key.to_string())];
+        String::from_utf8(rust_delete(src.as_bytes(), keys, false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn removes_unreachable_code() {
+        let src = "fn a() -> u8 {\n    return 1;\n    let x = 2;\n    // Never\n    x\n}\n\
+                   fn c() {\n    let r = { std::process::exit(1); 6 };\n    \
+                   println!(\"{r}\");\n}\n";
+        assert_eq!(
+            remove(src, "at:3:5"),
+            "fn a() -> u8 {\n    return 1;\n}\n\
+             fn c() {\n    let r = { std::process::exit(1); 6 };\n    println!(\"{r}\");\n}\n"
+        );
+        assert_eq!(
+            remove(src, "after:8:15"),
+            "fn a() -> u8 {\n    return 1;\n    let x = 2;\n    // Never\n    x\n}\n\
+             fn c() {\n    let r = { std::process::exit(1); };\n    println!(\"{r}\");\n}\n"
+        );
+        // Code that isn't a statement of its own is left alone
+        assert_eq!(remove(src, "at:3:13"), src);
+    }
+}
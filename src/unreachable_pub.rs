@@ -0,0 +1,127 @@
+//! `pub` items that can't be reached from the crate root, as reported by the
+//! allow-by-default `unreachable_pub` lint, which is enabled when they are
+//! asked for. Such an item is only `pub` within a private module, so it is
+//! kept, and its visibility is restricted to `pub(crate)` instead, which
+//! says as much; a softer change than removing what may well be used.
+
+use cargo_metadata::diagnostic::Diagnostic;
+
+use crate::{
+    edit::TextEdit,
+    unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+const RESTRICTED: &str = "pub(crate)";
+
+/// Words that may come between `pub` and the name of the item
+const KEYWORDS: &[&str] = &[
+    "pub", "crate", "async", "const", "enum", "extern", "fn", "mod", "static", "struct", "trait",
+    "type", "union", "unsafe", "use",
+];
+
+/// The findings of an `unreachable_pub` diagnostic, named by the item
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    if code.code != "unreachable_pub" {
+        return None;
+    }
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .map(|span| {
+            // Without the ABI of `extern "C" fn`
+            let text: String = span_text(span).split('"').step_by(2).collect();
+            let ident = text
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .find(|word| !word.is_empty() && !KEYWORDS.contains(word))
+                .unwrap_or_default();
+            UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::UnreachablePub,
+                ident: ident.to_string(),
+                span: span.clone(),
+                suggestion: None,
+                configs: Vec::new(),
+            }
+        })
+        .collect();
+
+    Some(found)
+}
+
+/// The edit restricting the visibility of the finding to `pub(crate)`, which
+/// replaces the `pub` token its span starts with; `None` if the source no
+/// longer has a bare `pub` there
+pub fn restriction(src: &[u8], diagnostic: &UnusedDiagnostic) -> Option<TextEdit> {
+    let start = diagnostic.span.byte_start as usize;
+    let end = start + "pub".len();
+    if src.get(start..end)? != b"pub" {
+        return None;
+    }
+    let rest = &src[end..];
+    let bare = rest.first().is_some_and(u8::is_ascii_whitespace)
+        && rest.iter().find(|c| !c.is_ascii_whitespace()) != Some(&b'(');
+    if !bare {
+        return None;
+    }
+
+    Some(TextEdit::replace(start..end, RESTRICTED))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::rust_edit;
+
+    use super::*;
+
+    fn diagnostic(src: &str, item: &str) -> Diagnostic {
+        let start = src.find(item).unwrap();
+        let line = src[..start].matches('\n').count() + 1;
+        let text = src.lines().nth(line - 1).unwrap();
+        let column = text.find(item).unwrap() + 1;
+        let span = serde_json::json!({
+            "file_name": "src/lib.rs", "byte_start": start, "byte_end": start + item.len(),
+            "line_start": line, "line_end": line, "column_start": column,
+            "column_end": column + item.len(), "is_primary": true,
+            "text": [{"text": text, "highlight_start": column,
+                      "highlight_end": column + item.len()}],
+            "label": null, "suggested_replacement": null, "suggestion_applicability": null,
+            "expansion": null
+        });
+        serde_json::from_value(serde_json::json!({
+            "message": "unreachable `pub` item",
+            "code": {"code": "unreachable_pub", "explanation": null},
+            "level": "warning", "spans": [span], "children": [], "rendered": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn restricts_visibility() {
+        let src = "mod inner {\n    pub fn k() {}\n    pub extern \"C\" fn e() {}\n    \
+                   pub(crate) struct T;\n}\n";
+        let items = [
+            "pub fn k() {}",
+            "pub extern \"C\" fn e() {}",
+            "pub(crate) struct T;",
+        ];
+        let found: Vec<_> = items
+            .into_iter()
+            .flat_map(|item| from_diagnostic(&diagnostic(src, item)).unwrap())
+            .collect();
+        let idents: Vec<_> = found.iter().map(|finding| finding.ident.as_str()).collect();
+        assert_eq!(idents, ["k", "e", "T"]);
+
+        let edits = found
+            .iter()
+            .filter_map(|finding| restriction(src.as_bytes(), finding));
+        let restricted = rust_edit(src.as_bytes(), [], edits, false).unwrap();
+        // Visibilities that are already restricted are left alone
+        assert_eq!(
+            String::from_utf8(restricted).unwrap(),
+            "mod inner {\n    pub(crate) fn k() {}\n    pub(crate) extern \"C\" fn e() {}\n    \
+             pub(crate) struct T;\n}\n"
+        );
+    }
+}
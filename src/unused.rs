@@ -1,31 +1,111 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    env,
     fmt::{Display, Formatter},
     io::BufReader,
     path::Path,
     process::{Command, Stdio},
     str::FromStr,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
 };
 
 use cargo_metadata::{
     diagnostic::{Diagnostic, DiagnosticSpan},
-    Message,
+    Message, Target,
 };
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::{error::Result, resolver, CrateResolutionOptions, FileResolutionOptions};
+use crate::{
+    assoc_items,
+    error::Result,
+    extern_crates, fields, imports, lifetimes, modules, resolver,
+    suggestions::{self, Suggestion},
+    test_only::TestOnlyCode,
+    toolchain::Toolchain,
+    unreachable, unreachable_pub,
+    unused_deps::CrateUsage,
+    unused_mut, variables, variants, CrateResolutionOptions, FileResolutionOptions,
+};
+
+/// How the packages are checked
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    /// Leave the benchmarks out, so they can't keep anything alive
+    pub ignore_benches: bool,
+    /// Only check libraries and binaries, as the other targets need std
+    pub lib_and_bins_only: bool,
+    /// The target triple to check for, instead of the host
+    pub target: Option<String>,
+    /// The toolchain pinned for the workspace
+    pub toolchain: Option<Toolchain>,
+    /// When to stop checking, keeping the findings of the crates checked so far
+    pub deadline: Option<Instant>,
+    /// Whether the deadline was hit, so that the findings are partial
+    pub timed_out: Cell<bool>,
+    /// Also let rustc report the dependencies that are never used
+    pub unused_deps: bool,
+    /// Lints that are allowed by default, which rustc should report as well
+    pub lints: Vec<&'static str>,
+    /// Lints that rustc should report even where they are allowed, with
+    /// `--strip-allow`
+    pub force_warn: Vec<String>,
+    /// Don't access the network, like `cargo --offline`
+    pub offline: bool,
+    /// The compilations seen by the checks, and the crates they didn't use
+    pub crate_usage: RefCell<CrateUsage>,
+}
+
+#[derive(Debug, Error)]
+#[error("invalid duration `{0}`, use a number of seconds, or a number followed by s, m or h")]
+pub struct InvalidDuration(pub String);
+
+/// Parses a duration such as `90`, `90s`, `15m` or `1h`
+pub fn parse_duration(s: &str) -> Result<Duration, InvalidDuration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let number: u64 = number.parse().map_err(|_| InvalidDuration(s.to_string()))?;
+    let seconds = match unit {
+        "s" => Some(number),
+        "m" => number.checked_mul(60),
+        "h" => number.checked_mul(60 * 60),
+        _ => None,
+    };
+
+    seconds
+        .map(Duration::from_secs)
+        .ok_or_else(|| InvalidDuration(s.to_string()))
+}
 
 pub fn get_unused<'a>(
     manifest_path: Option<&Path>,
     crate_resolution: &CrateResolutionOptions,
+    targets: HashSet<Target>,
     file_resolution: &'a FileResolutionOptions,
     kinds: &'a [UnusedDiagnosticKind],
+    options: &CheckOptions,
 ) -> Result<impl Iterator<Item = UnusedDiagnostic> + 'a> {
-    let mut command = Command::new("cargo");
+    let ignore_benches = options.ignore_benches;
+    let mut command = check_command(manifest_path, options);
 
-    command.args(["check", "--all-targets", "--quiet", "--message-format", "json"]);
-    if let Some(manifest_path) = manifest_path {
-        command.args([
-            "--manifest-path", manifest_path.to_str().expect("manifest_path should be convertable to str")
-        ]);
+    if options.lib_and_bins_only {
+        if targets.iter().any(resolver::is_library) {
+            command.arg("--lib");
+        }
+        command.arg("--bins");
+    } else if ignore_benches {
+        // Benchmarks are left out of the build, so they can't keep anything alive
+        if targets.iter().any(resolver::is_library) {
+            command.arg("--lib");
+        }
+        command.args(["--bins", "--examples", "--tests"]);
+    } else {
+        command.arg("--all-targets");
     }
 
     match crate_resolution {
@@ -33,48 +113,549 @@ pub fn get_unused<'a>(
         CrateResolutionOptions::Workspace { exclude } => {
             command.arg("--workspace");
 
-            for package in *exclude {
-                command.args(["--exclude", package]);
+            let metadata = resolver::get_cargo_metadata(manifest_path)?;
+            for package in resolver::expand_package_specs(&metadata, exclude)? {
+                command.args(["--exclude", &package]);
             }
         }
         CrateResolutionOptions::Package { packages } => {
-            for package in *packages {
-                command.args(["-p", package]);
+            let metadata = resolver::get_cargo_metadata(manifest_path)?;
+            for package in resolver::expand_package_specs(&metadata, packages)? {
+                command.args(["-p", &package]);
+            }
+        }
+    }
+
+    let mut unused = check(command, &targets, options)?;
+    reported_by(&mut unused, "default");
+    require_every_compilation(&mut unused, &options.crate_usage.borrow());
+
+    // Targets with required features are skipped unless these features are
+    // enabled, so they are checked separately, with their features
+    let feature_targets: Vec<_> = targets
+        .iter()
+        .filter(|target| !target.required_features.is_empty())
+        .filter(|target| !(ignore_benches && target.kind.iter().any(|kind| kind == "bench")))
+        .filter(|target| !options.lib_and_bins_only || target.kind.iter().any(|kind| kind == "bin"))
+        .collect();
+    if !feature_targets.is_empty() {
+        let metadata = resolver::get_cargo_metadata(manifest_path)?;
+        for target in feature_targets {
+            let Some(package) = resolver::package_of(&metadata, target) else {
+                continue;
+            };
+            let Some(kind) = target.kind.first() else {
+                continue;
+            };
+
+            let mut command = check_command(manifest_path, options);
+            command.args(["-p", &package.name]);
+            command.args(["--features", &target.required_features.join(",")]);
+            command.args([&format!("--{kind}"), &target.name]);
+            let rebuilt: HashSet<_> = package
+                .targets
+                .iter()
+                .filter(|other| resolver::is_library(other) || *other == target)
+                .collect();
+            if rebuilt.len() > 1 {
+                command.arg("--lib");
             }
+
+            let mut found = check(command, &targets, options)?;
+            reported_by(
+                &mut found,
+                &format!("features={}", target.required_features.join(",")),
+            );
+            merge_feature_check(&mut unused, found, &rebuilt);
         }
     }
 
+    // Features enabled by the dev-dependencies of workspace members, like
+    // `test-util`, compile code that the regular check leaves out, so the
+    // libraries are checked once more with them
+    let metadata = resolver::get_cargo_metadata(manifest_path)?;
+    for (package, features) in resolver::dev_dependency_features(&metadata, &targets) {
+        let mut command = check_command(manifest_path, options);
+        command.args([
+            "-p",
+            &package.name,
+            "--lib",
+            "--features",
+            &features.join(","),
+        ]);
+        let rebuilt: HashSet<_> = package
+            .targets
+            .iter()
+            .filter(|target| resolver::is_library(target))
+            .collect();
+
+        let mut found = check(command, &targets, options)?;
+        reported_by(&mut found, &format!("features={}", features.join(",")));
+        merge_feature_check(&mut unused, found, &rebuilt);
+    }
+
+    // The targets that were checked at all, by either check
+    let checked: Vec<_> = targets
+        .iter()
+        .filter(|target| !(ignore_benches && target.kind.iter().any(|kind| kind == "bench")))
+        .filter(|target| {
+            !options.lib_and_bins_only
+                || resolver::is_library(target)
+                || target.kind.iter().any(|kind| kind == "bin")
+        })
+        .collect();
+    intersect_shared_files(&mut unused, &checked);
+
+    let unused = dedup(unused.into_iter().map(|(_, diagnostic)| diagnostic))
+        .into_iter()
+        // Ignore unused warnings originating from macro expansions
+        .filter(|diagnostic| diagnostic.span.expansion.is_none())
+        .filter(|diagnostic| {
+            (kinds.is_empty() && diagnostic.kind.is_default()) || kinds.contains(&diagnostic.kind)
+        })
+        .filter(|diagnostic| file_resolution.is_included(&diagnostic.span.file_name));
+
+    Ok(unused)
+}
+
+fn check_command(manifest_path: Option<&Path>, options: &CheckOptions) -> Command {
+    let mut command = Command::new("cargo");
+
+    if let Some(toolchain) = &options.toolchain {
+        toolchain.apply(&mut command);
+    }
+    command.arg("check");
+    command.args(["--quiet", "--message-format", "json"]);
+    if options.offline {
+        command.arg("--offline");
+    }
+    let mut lints = options.lints.clone();
+    if options.unused_deps {
+        lints.push("unused-crate-dependencies");
+    }
+    if !lints.is_empty() || !options.force_warn.is_empty() {
+        enable_lints(&mut command, &lints, &options.force_warn);
+    }
+    if let Some(target) = &options.target {
+        command.args(["--target", target]);
+    }
+    if let Some(manifest_path) = manifest_path {
+        command.args([
+            "--manifest-path", manifest_path.to_str().expect("manifest_path should be convertable to str")
+        ]);
+    }
+
+    command
+}
+
+/// Lets rustc report lints that are allowed by default, and the `force_warn`
+/// ones even where the code allows them: the flags are added to the
+/// `RUSTFLAGS` given by the environment, or else to the `build.rustflags` of
+/// the cargo configuration
+fn enable_lints(command: &mut Command, lints: &[&str], force_warn: &[String]) {
+    let flags: Vec<_> = lints
+        .iter()
+        .map(|lint| format!("-W{lint}"))
+        .chain(force_warn.iter().map(|lint| format!("--force-warn={lint}")))
+        .collect();
+    if let Ok(env_flags) = env::var("CARGO_ENCODED_RUSTFLAGS") {
+        command.env(
+            "CARGO_ENCODED_RUSTFLAGS",
+            format!("{env_flags}\x1f{}", flags.join("\x1f")),
+        );
+    } else if let Ok(env_flags) = env::var("RUSTFLAGS") {
+        command.env("RUSTFLAGS", format!("{env_flags} {}", flags.join(" ")));
+    } else {
+        let flags: Vec<_> = flags.iter().map(|flag| format!("\"{flag}\"")).collect();
+        command.args([
+            "--config",
+            &format!("build.rustflags=[{}]", flags.join(", ")),
+        ]);
+    }
+}
+
+/// Runs the check, returning the unused-diagnostics of the given targets. When
+/// the deadline is hit, the check is killed, and the diagnostics of the crates
+/// checked so far are returned.
+fn check(
+    mut command: Command,
+    targets: &HashSet<Target>,
+    options: &CheckOptions,
+) -> Result<Vec<(Target, UnusedDiagnostic)>> {
+    if options.timed_out.get() {
+        return Ok(Vec::new());
+    }
+    // cargo's rustc processes have to go as well, so they get a group of their
+    // own to kill
+    #[cfg(unix)]
+    if options.deadline.is_some() {
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    }
     let mut child = command.stdout(Stdio::piped()).spawn()?;
     let stdout = child.stdout.take().unwrap();
-    let reader = BufReader::new(stdout);
 
-    let targets = resolver::get_targets(manifest_path, crate_resolution)?;
+    let (done, watchdog) = match options.deadline {
+        Some(deadline) => {
+            let (done, finished) = mpsc::channel::<()>();
+            let pid = child.id();
+            let watchdog = thread::spawn(move || {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                let timed_out = finished.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout);
+                if timed_out {
+                    kill_process_group(pid);
+                }
+                timed_out
+            });
+            (Some(done), Some(watchdog))
+        }
+        None => (None, None),
+    };
+    let reader = BufReader::new(stdout);
 
     let unused = Message::parse_stream(reader)
         .flatten()
-        .filter_map(|message| {
-            if let Message::CompilerMessage(message) = message {
-                Some(message)
-            } else {
+        .filter_map(|message| match message {
+            Message::CompilerMessage(message) => Some(message),
+            Message::CompilerArtifact(artifact) => {
+                let mut usage = options.crate_usage.borrow_mut();
+                usage.compiled(&artifact.target, artifact.profile.test);
                 None
             }
+            _ => None,
         })
-        .filter(move |message| targets.contains(&message.target))
-        .map(|message| message.message)
-        .filter_map(|diagnostic| UnusedDiagnostic::try_from(diagnostic).ok())
-        // Ignore unused warnings originating from macro expansions
-        .filter(|diagnostic| diagnostic.span.expansion.is_none())
-        .filter(|diagnostic| kinds.is_empty() || kinds.contains(&diagnostic.kind))
-        .filter(|diagnostic| file_resolution.is_included(&diagnostic.span.file_name));
+        .filter(|message| targets.contains(&message.target))
+        .filter(|message| !options.crate_usage.borrow_mut().record(message))
+        .flat_map(|message| {
+            let found = imports::from_diagnostic(&message.message)
+                .or_else(|| assoc_items::from_diagnostic(&message.message))
+                .or_else(|| variants::from_diagnostic(&message.message))
+                .or_else(|| fields::from_diagnostic(&message.message))
+                .or_else(|| extern_crates::from_diagnostic(&message.message))
+                .or_else(|| lifetimes::from_diagnostic(&message.message))
+                .or_else(|| unreachable::from_diagnostic(&message.message))
+                .or_else(|| unreachable_pub::from_diagnostic(&message.message))
+                .or_else(|| variables::from_diagnostic(&message.message))
+                .or_else(|| unused_mut::from_diagnostic(&message.message));
+            let found = match found {
+                Some(mut found) => {
+                    suggestions::attach(&message.message, &mut found);
+                    found
+                }
+                None => UnusedDiagnostic::try_from(message.message)
+                    .into_iter()
+                    .collect(),
+            };
+            let target = message.target;
+            found
+                .into_iter()
+                .map(move |diagnostic| (target.clone(), diagnostic))
+        })
+        .collect();
+    if let Some(done) = done {
+        let _ = done.send(());
+    }
+    if let Some(watchdog) = watchdog {
+        options.timed_out.set(watchdog.join().unwrap_or(false));
+    }
+    child.wait()?;
 
     Ok(unused)
 }
 
-#[derive(Debug)]
+/// Kills the process and the others in its group
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    let killed = Command::new("kill")
+        .args(["-KILL", &format!("-{pid}")])
+        .status()
+        .is_ok_and(|status| status.success());
+    if !killed {
+        let _ = Command::new("kill")
+            .args(["-KILL", &pid.to_string()])
+            .status();
+    }
+}
+
+/// Kills the process and the processes it started
+#[cfg(windows)]
+fn kill_process_group(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .status();
+}
+
+/// Merges the results of checking a target with its required features into
+/// those of the regular check. Code in the `rebuilt` targets is only unused
+/// if it is unused both with and without these features, and the findings that
+/// are left list the feature sets of both checks.
+fn merge_feature_check(
+    unused: &mut Vec<(Target, UnusedDiagnostic)>,
+    found: Vec<(Target, UnusedDiagnostic)>,
+    rebuilt: &HashSet<&Target>,
+) {
+    let key = |(target, diagnostic): &(Target, UnusedDiagnostic)| {
+        (
+            target.clone(),
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+            diagnostic.span.file_name.clone(),
+            diagnostic.span.line_start,
+        )
+    };
+    let mut found_configs: HashMap<_, Vec<String>> = HashMap::new();
+    for entry in &found {
+        let configs = found_configs.entry(key(entry)).or_default();
+        for config in &entry.1.configs {
+            if !configs.contains(config) {
+                configs.push(config.clone());
+            }
+        }
+    }
+    let unused_keys: HashSet<_> = unused.iter().map(key).collect();
+
+    unused.retain(|entry| !rebuilt.contains(&entry.0) || found_configs.contains_key(&key(entry)));
+    for entry in unused.iter_mut() {
+        if let Some(configs) = found_configs.get(&key(entry)) {
+            entry.1.configs.extend(configs.iter().cloned());
+        }
+    }
+    // The target itself was skipped by the regular check
+    unused.extend(found.into_iter().filter(|entry| {
+        let is_new_target = !unused_keys.iter().any(|(target, ..)| *target == entry.0);
+        is_new_target && rebuilt.contains(&entry.0) && !resolver::is_library(&entry.0)
+    }));
+}
+
+/// Marks the findings of a check as reported by the feature set it checked
+fn reported_by(found: &mut [(Target, UnusedDiagnostic)], config: &str) {
+    for (_, diagnostic) in found {
+        diagnostic.configs = vec![config.to_string()];
+    }
+}
+
+/// Merges identical findings, which every compilation of every target that
+/// compiles their file reports, under every feature set. Findings are the
+/// same if they are of the same kind and name, at the same span; the merged
+/// finding lists the feature sets of all of them.
+pub fn dedup(diagnostics: impl IntoIterator<Item = UnusedDiagnostic>) -> Vec<UnusedDiagnostic> {
+    let mut merged: Vec<UnusedDiagnostic> = Vec::new();
+    let mut index: HashMap<_, usize> = HashMap::new();
+    for diagnostic in diagnostics {
+        let span = &diagnostic.span;
+        let key = (
+            span.file_name.clone(),
+            span.byte_start,
+            span.byte_end,
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+        );
+        match index.get(&key) {
+            Some(&at) => {
+                let configs = &mut merged[at].configs;
+                for config in diagnostic.configs {
+                    if !configs.contains(&config) {
+                        configs.push(config);
+                    }
+                }
+            }
+            None => {
+                index.insert(key, merged.len());
+                merged.push(diagnostic);
+            }
+        }
+    }
+
+    merged
+}
+
+/// Libraries and binaries are compiled both on their own and as unit tests, so
+/// their code is only unused if every compilation reports it, or if it's only
+/// compiled for tests and the test compilations report it
+fn require_every_compilation(unused: &mut Vec<(Target, UnusedDiagnostic)>, usage: &CrateUsage) {
+    let key = |(target, diagnostic): &(Target, UnusedDiagnostic)| {
+        (
+            target.clone(),
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+            diagnostic.span.file_name.clone(),
+            diagnostic.span.line_start,
+        )
+    };
+    let mut reports = HashMap::new();
+    for entry in unused.iter() {
+        *reports.entry(key(entry)).or_insert(0) += 1;
+    }
+
+    let mut test_only = HashMap::new();
+    unused.retain(|entry| {
+        let (target, diagnostic) = entry;
+        let (all, tests) = usage.compilations(target);
+        let reported = reports[&key(entry)];
+        if reported >= all {
+            return true;
+        }
+        let code = test_only
+            .entry(target.clone())
+            .or_insert_with(|| TestOnlyCode::of_crate(target.src_path.as_std_path()));
+        reported >= tests && code.contains(&diagnostic.span.file_name, diagnostic.span.line_start)
+    });
+}
+
+/// Files can be compiled into several targets, such as modules declared by
+/// both `main.rs` and `lib.rs`, or shared by integration tests. Code in these
+/// is only unused if every target compiling the file reports it as unused.
+fn intersect_shared_files(unused: &mut Vec<(Target, UnusedDiagnostic)>, checked: &[&Target]) {
+    let files: Vec<_> = checked
+        .iter()
+        .map(|target| (*target, modules::crate_files(target.src_path.as_std_path())))
+        .collect();
+    // File names are relative to the workspace, so they are resolved among the
+    // files of the reporting target
+    let compiled_by = |reporter: &Target, file_name: &str| -> Vec<&Target> {
+        let Some((_, reporter_files)) = files.iter().find(|(target, _)| *target == reporter) else {
+            return Vec::new();
+        };
+        let Some(path) = reporter_files.iter().find(|file| file.ends_with(file_name)) else {
+            return Vec::new();
+        };
+        files
+            .iter()
+            .filter(|(_, files)| files.contains(path))
+            .map(|(target, _)| *target)
+            .collect()
+    };
+    let key = |diagnostic: &UnusedDiagnostic| {
+        (
+            diagnostic.kind,
+            diagnostic.ident.clone(),
+            diagnostic.span.file_name.clone(),
+            diagnostic.span.line_start,
+        )
+    };
+
+    let reported: HashSet<_> = unused
+        .iter()
+        .map(|(target, diagnostic)| (target.clone(), key(diagnostic)))
+        .collect();
+    unused.retain(|(reporter, diagnostic)| {
+        compiled_by(reporter, &diagnostic.span.file_name)
+            .into_iter()
+            .all(|target| reported.contains(&(target.clone(), key(diagnostic))))
+    });
+}
+
+/// The source text a span of rustc points at
+pub fn span_text(span: &DiagnosticSpan) -> String {
+    span.text
+        .iter()
+        .map(|line| {
+            let len = line.highlight_end.saturating_sub(line.highlight_start);
+            let start = line.highlight_start.saturating_sub(1);
+            line.text.chars().skip(start).take(len).collect::<String>()
+        })
+        .collect()
+}
+
+/// Creates a diagnostic span for findings that don't come from rustc, pointing
+/// at `span` within `src`
+pub fn synthetic_span(file_name: &str, src: &str, span: proc_macro2::Span) -> DiagnosticSpan {
+    // Columns count chars, byte offsets don't
+    let byte_offset = |pos: proc_macro2::LineColumn| {
+        let mut lines = src.split_inclusive('\n');
+        let before: usize = lines.by_ref().take(pos.line - 1).map(str::len).sum();
+        let line = lines.next().unwrap_or_default();
+        let column = line.char_indices().nth(pos.column);
+        before + column.map_or(line.len(), |(index, _)| index)
+    };
+    let (start, end) = (span.start(), span.end());
+
+    // DiagnosticSpan is non-exhaustive, so it can only be constructed through
+    // deserialization
+    serde_json::from_value(serde_json::json!({
+        "file_name": file_name,
+        "byte_start": byte_offset(start),
+        "byte_end": byte_offset(end),
+        "line_start": start.line,
+        "line_end": end.line,
+        "column_start": start.column + 1,
+        "column_end": end.column + 1,
+        "is_primary": true,
+        "text": [],
+        "label": null,
+        "suggested_replacement": null,
+        "suggestion_applicability": null,
+        "expansion": null,
+    }))
+    .expect("diagnostic span should be deserializable")
+}
+
+/// Finds the benchmarks that mention any of the identifiers that are about to
+/// be removed, as these benchmarks will likely no longer compile afterwards
+pub fn bench_references(
+    targets: &HashSet<Target>,
+    diagnostics: &[UnusedDiagnostic],
+) -> Vec<BenchReference> {
+    let mut references = Vec::new();
+
+    for target in targets
+        .iter()
+        .filter(|t| t.kind.iter().any(|k| k == "bench"))
+    {
+        let Ok(src) = std::fs::read_to_string(&target.src_path) else {
+            continue;
+        };
+        let Ok(tokens) = src.parse::<proc_macro2::TokenStream>() else {
+            continue;
+        };
+
+        let mut idents = HashSet::new();
+        collect_idents(tokens, &mut idents);
+
+        for diagnostic in diagnostics {
+            let reference = BenchReference {
+                bench: target.name.clone(),
+                ident: diagnostic.ident.clone(),
+            };
+            if idents.contains(&reference.ident) && !references.contains(&reference) {
+                references.push(reference);
+            }
+        }
+    }
+
+    references
+}
+
+fn collect_idents(tokens: proc_macro2::TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            proc_macro2::TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            proc_macro2::TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+/// A benchmark that refers to an identifier that is about to be removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReference {
+    pub bench: String,
+    pub ident: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnusedDiagnostic {
     pub kind: UnusedDiagnosticKind,
     pub ident: String,
     pub span: DiagnosticSpan,
+    /// rustc's machine-applicable suggestion to remove it, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<Suggestion>,
+    /// The feature sets of the checks that reported it, such as `default` or
+    /// `features=test-util`; empty for findings that rustc didn't report
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub configs: Vec<String>,
 }
 
 impl TryFrom<Diagnostic> for UnusedDiagnostic {
@@ -92,7 +673,8 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
                     | UnusedDiagnosticKind::Function
                     | UnusedDiagnosticKind::Struct
                     | UnusedDiagnosticKind::Enum
-                    | UnusedDiagnosticKind::Union => message,
+                    | UnusedDiagnosticKind::Union
+                    | UnusedDiagnosticKind::Module => message,
                     UnusedDiagnosticKind::TypeAlias => {
                         let (alias, message) =
                             message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
@@ -113,7 +695,26 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
 
                         message
                     }
-                    UnusedDiagnosticKind::MacroDefinition => return Err(NotUnusedDiagnostic),
+                    UnusedDiagnosticKind::AssociatedConstant
+                    | UnusedDiagnosticKind::AssociatedType
+                    | UnusedDiagnosticKind::MacroDefinition
+                    | UnusedDiagnosticKind::Bound
+                    | UnusedDiagnosticKind::DynShim
+                    | UnusedDiagnosticKind::TraitImpl
+                    | UnusedDiagnosticKind::ConstParam
+                    | UnusedDiagnosticKind::TypeParam
+                    | UnusedDiagnosticKind::Import
+                    | UnusedDiagnosticKind::Variant
+                    | UnusedDiagnosticKind::Field
+                    | UnusedDiagnosticKind::ExternCrate
+                    | UnusedDiagnosticKind::Lifetime
+                    | UnusedDiagnosticKind::Unreachable
+                    | UnusedDiagnosticKind::UnreachablePub
+                    | UnusedDiagnosticKind::Reexport
+                    | UnusedDiagnosticKind::UnusedPub
+                    | UnusedDiagnosticKind::Allow
+                    | UnusedDiagnosticKind::Variable
+                    | UnusedDiagnosticKind::Mut => return Err(NotUnusedDiagnostic),
                 };
 
                 let (mut ident, message) = message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
@@ -128,9 +729,29 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
                     | UnusedDiagnosticKind::Enum
                     | UnusedDiagnosticKind::Union
                     | UnusedDiagnosticKind::TypeAlias
-                    | UnusedDiagnosticKind::AssociatedFunction => "is never used",
+                    | UnusedDiagnosticKind::AssociatedFunction
+                    | UnusedDiagnosticKind::Module => "is never used",
                     UnusedDiagnosticKind::Struct => "is never constructed",
-                    UnusedDiagnosticKind::MacroDefinition => return Err(NotUnusedDiagnostic),
+                    UnusedDiagnosticKind::AssociatedConstant
+                    | UnusedDiagnosticKind::AssociatedType
+                    | UnusedDiagnosticKind::MacroDefinition
+                    | UnusedDiagnosticKind::Bound
+                    | UnusedDiagnosticKind::DynShim
+                    | UnusedDiagnosticKind::TraitImpl
+                    | UnusedDiagnosticKind::ConstParam
+                    | UnusedDiagnosticKind::TypeParam
+                    | UnusedDiagnosticKind::Import
+                    | UnusedDiagnosticKind::Variant
+                    | UnusedDiagnosticKind::Field
+                    | UnusedDiagnosticKind::ExternCrate
+                    | UnusedDiagnosticKind::Lifetime
+                    | UnusedDiagnosticKind::Unreachable
+                    | UnusedDiagnosticKind::UnreachablePub
+                    | UnusedDiagnosticKind::Reexport
+                    | UnusedDiagnosticKind::UnusedPub
+                    | UnusedDiagnosticKind::Allow
+                    | UnusedDiagnosticKind::Variable
+                    | UnusedDiagnosticKind::Mut => return Err(NotUnusedDiagnostic),
                 };
 
                 if message != suffix {
@@ -139,7 +760,13 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
 
                 let span = value.spans.into_iter().next().ok_or(NotUnusedDiagnostic)?;
 
-                Ok(UnusedDiagnostic { kind, ident, span })
+                Ok(UnusedDiagnostic {
+                    kind,
+                    ident,
+                    span,
+                    suggestion: None,
+                    configs: Vec::new(),
+                })
             }
             Err(_) => {
                 if first != "unused" {
@@ -158,7 +785,27 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
                     | UnusedDiagnosticKind::Enum
                     | UnusedDiagnosticKind::Union
                     | UnusedDiagnosticKind::TypeAlias
-                    | UnusedDiagnosticKind::AssociatedFunction => return Err(NotUnusedDiagnostic),
+                    | UnusedDiagnosticKind::AssociatedFunction
+                    | UnusedDiagnosticKind::AssociatedConstant
+                    | UnusedDiagnosticKind::AssociatedType
+                    | UnusedDiagnosticKind::Bound
+                    | UnusedDiagnosticKind::DynShim
+                    | UnusedDiagnosticKind::TraitImpl
+                    | UnusedDiagnosticKind::ConstParam
+                    | UnusedDiagnosticKind::TypeParam
+                    | UnusedDiagnosticKind::Import
+                    | UnusedDiagnosticKind::Variant
+                    | UnusedDiagnosticKind::Field
+                    | UnusedDiagnosticKind::Module
+                    | UnusedDiagnosticKind::ExternCrate
+                    | UnusedDiagnosticKind::Lifetime
+                    | UnusedDiagnosticKind::Unreachable
+                    | UnusedDiagnosticKind::UnreachablePub
+                    | UnusedDiagnosticKind::Reexport
+                    | UnusedDiagnosticKind::UnusedPub
+                    | UnusedDiagnosticKind::Allow
+                    | UnusedDiagnosticKind::Variable
+                    | UnusedDiagnosticKind::Mut => return Err(NotUnusedDiagnostic),
                     UnusedDiagnosticKind::MacroDefinition => {
                         let (definition, message) =
                             message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
@@ -185,13 +832,19 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
 
                 let span = value.spans.into_iter().next().ok_or(NotUnusedDiagnostic)?;
 
-                Ok(UnusedDiagnostic { kind, ident, span })
+                Ok(UnusedDiagnostic {
+                    kind,
+                    ident,
+                    span,
+                    suggestion: None,
+                    configs: Vec::new(),
+                })
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum UnusedDiagnosticKind {
     Constant,
     Static,
@@ -201,7 +854,150 @@ pub enum UnusedDiagnosticKind {
     Union,
     TypeAlias,
     AssociatedFunction,
+    /// Associated consts of inherent impls that are never used
+    AssociatedConstant,
+    /// Associated types of inherent impls that are never used
+    AssociatedType,
     MacroDefinition,
+    /// Trait bounds on type parameters that are never required, found by a
+    /// syntactic analysis rather than by rustc
+    Bound,
+    /// Impls of a trait for trait objects of itself, while the trait is never
+    /// used as a trait object; found syntactically, and experimental
+    DynShim,
+    /// Trait impls whose methods are never used, found syntactically, and
+    /// experimental
+    TraitImpl,
+    /// Const generic parameters of functions that are never used, along with
+    /// the values passed for them; found syntactically, and experimental
+    ConstParam,
+    /// Type parameters of functions that are never used, along with the
+    /// types passed for them and the `where` predicates bounding them; found
+    /// syntactically, and experimental
+    TypeParam,
+    /// Names imported by `use` items that are never used; only removed when
+    /// asked for
+    Import,
+    /// Enum variants that are never constructed, which are only removed when
+    /// nothing else refers to them; only removed when asked for
+    Variant,
+    /// Struct fields that are never read, which are only reported, along
+    /// with the places that would need editing; only when asked for
+    Field,
+    /// Inline modules whose items are all unused, which are removed as a
+    /// whole; only when asked for
+    Module,
+    /// `extern crate` declarations that are never used, and their unused
+    /// `#[macro_use]` attributes; only when asked for
+    ExternCrate,
+    /// Lifetime parameters of functions and impl blocks that are never used;
+    /// only when asked for
+    Lifetime,
+    /// Statements after a `return`, a `panic!()` or the like, which can never
+    /// run; only when asked for
+    Unreachable,
+    /// `pub` items that can't be reached from the crate root, which are kept
+    /// and restricted to `pub(crate)`; only when asked for
+    UnreachablePub,
+    /// `pub use` re-exports whose names are never mentioned in the analyzed
+    /// packages, found syntactically; only when asked for
+    Reexport,
+    /// `pub` items whose names are never mentioned in the analyzed packages,
+    /// nor through re-exports of them, found syntactically; only when asked for
+    UnusedPub,
+    /// `#[allow]` attributes of the lints stripped by `--strip-allow` that no
+    /// longer suppress anything, found syntactically; only when asked for
+    Allow,
+    /// Variables that are never used, which are renamed to start with an
+    /// underscore, or removed along with a `let` statement without side
+    /// effects; only when asked for
+    Variable,
+    /// `mut` keywords of bindings that are never mutated, which are removed
+    /// on their own; only when asked for
+    Mut,
+}
+
+impl UnusedDiagnosticKind {
+    /// Every kind, as for listing the default ones
+    pub const ALL: &'static [UnusedDiagnosticKind] = &[
+        UnusedDiagnosticKind::Constant,
+        UnusedDiagnosticKind::Static,
+        UnusedDiagnosticKind::Function,
+        UnusedDiagnosticKind::Struct,
+        UnusedDiagnosticKind::Enum,
+        UnusedDiagnosticKind::Union,
+        UnusedDiagnosticKind::TypeAlias,
+        UnusedDiagnosticKind::AssociatedFunction,
+        UnusedDiagnosticKind::AssociatedConstant,
+        UnusedDiagnosticKind::AssociatedType,
+        UnusedDiagnosticKind::MacroDefinition,
+        UnusedDiagnosticKind::Bound,
+        UnusedDiagnosticKind::DynShim,
+        UnusedDiagnosticKind::TraitImpl,
+        UnusedDiagnosticKind::ConstParam,
+        UnusedDiagnosticKind::TypeParam,
+        UnusedDiagnosticKind::Import,
+        UnusedDiagnosticKind::Variant,
+        UnusedDiagnosticKind::Field,
+        UnusedDiagnosticKind::Module,
+        UnusedDiagnosticKind::ExternCrate,
+        UnusedDiagnosticKind::Lifetime,
+        UnusedDiagnosticKind::Unreachable,
+        UnusedDiagnosticKind::UnreachablePub,
+        UnusedDiagnosticKind::Reexport,
+        UnusedDiagnosticKind::UnusedPub,
+        UnusedDiagnosticKind::Allow,
+        UnusedDiagnosticKind::Variable,
+        UnusedDiagnosticKind::Mut,
+    ];
+
+    /// Whether the kind is removed without asking for it with `--kinds`
+    pub fn is_default(self) -> bool {
+        !matches!(
+            self,
+            UnusedDiagnosticKind::Bound
+                | UnusedDiagnosticKind::DynShim
+                | UnusedDiagnosticKind::TraitImpl
+                | UnusedDiagnosticKind::ConstParam
+                | UnusedDiagnosticKind::TypeParam
+                | UnusedDiagnosticKind::Import
+                | UnusedDiagnosticKind::Variant
+                | UnusedDiagnosticKind::Field
+                | UnusedDiagnosticKind::Module
+                | UnusedDiagnosticKind::ExternCrate
+                | UnusedDiagnosticKind::Lifetime
+                | UnusedDiagnosticKind::Unreachable
+                | UnusedDiagnosticKind::UnreachablePub
+                | UnusedDiagnosticKind::Reexport
+                | UnusedDiagnosticKind::UnusedPub
+                | UnusedDiagnosticKind::Allow
+                | UnusedDiagnosticKind::Variable
+                | UnusedDiagnosticKind::Mut
+        )
+    }
+
+    /// Whether removing the kind rewrites signatures, and possibly the calls
+    /// of the functions, instead of deleting whole items
+    pub fn is_semantic(self) -> bool {
+        matches!(
+            self,
+            UnusedDiagnosticKind::Bound
+                | UnusedDiagnosticKind::ConstParam
+                | UnusedDiagnosticKind::TypeParam
+                | UnusedDiagnosticKind::Lifetime
+        )
+    }
+
+    /// The allow-by-default lint rustc reports the kind with, which has to be
+    /// enabled for the check
+    pub fn allowed_lint(self) -> Option<&'static str> {
+        match self {
+            UnusedDiagnosticKind::ExternCrate => Some("unused-extern-crates"),
+            UnusedDiagnosticKind::Lifetime => Some("unused-lifetimes"),
+            UnusedDiagnosticKind::UnreachablePub => Some("unreachable-pub"),
+            _ => None,
+        }
+    }
 }
 
 impl FromStr for UnusedDiagnosticKind {
@@ -215,7 +1011,7 @@ impl FromStr for UnusedDiagnosticKind {
             .collect::<String>()
             .as_str()
         {
-            "constant" => Ok(UnusedDiagnosticKind::Constant),
+            "const" | "constant" => Ok(UnusedDiagnosticKind::Constant),
             "static" => Ok(UnusedDiagnosticKind::Static),
             "function" => Ok(UnusedDiagnosticKind::Function),
             "struct" => Ok(UnusedDiagnosticKind::Struct),
@@ -223,12 +1019,72 @@ impl FromStr for UnusedDiagnosticKind {
             "union" => Ok(UnusedDiagnosticKind::Union),
             "type" | "typealias" => Ok(UnusedDiagnosticKind::TypeAlias),
             "associated" | "associatedfunction" => Ok(UnusedDiagnosticKind::AssociatedFunction),
+            "associatedconst" | "associatedconstant" => {
+                Ok(UnusedDiagnosticKind::AssociatedConstant)
+            }
+            "associatedtype" => Ok(UnusedDiagnosticKind::AssociatedType),
             "macro" | "macrodefinition" => Ok(UnusedDiagnosticKind::MacroDefinition),
+            "bound" | "bounds" => Ok(UnusedDiagnosticKind::Bound),
+            "dynshim" | "dynshims" => Ok(UnusedDiagnosticKind::DynShim),
+            "traitimpl" | "traitimpls" => Ok(UnusedDiagnosticKind::TraitImpl),
+            "constparam" | "constparams" => Ok(UnusedDiagnosticKind::ConstParam),
+            "typeparam" | "typeparams" => Ok(UnusedDiagnosticKind::TypeParam),
+            "import" | "imports" => Ok(UnusedDiagnosticKind::Import),
+            "variant" | "variants" => Ok(UnusedDiagnosticKind::Variant),
+            "field" | "fields" => Ok(UnusedDiagnosticKind::Field),
+            "mod" | "module" | "modules" => Ok(UnusedDiagnosticKind::Module),
+            "externcrate" | "externcrates" => Ok(UnusedDiagnosticKind::ExternCrate),
+            "lifetime" | "lifetimes" => Ok(UnusedDiagnosticKind::Lifetime),
+            "unreachable" => Ok(UnusedDiagnosticKind::Unreachable),
+            "unreachablepub" => Ok(UnusedDiagnosticKind::UnreachablePub),
+            "reexport" | "reexports" => Ok(UnusedDiagnosticKind::Reexport),
+            "unusedpub" => Ok(UnusedDiagnosticKind::UnusedPub),
+            "allow" | "allows" => Ok(UnusedDiagnosticKind::Allow),
+            "variable" | "variables" => Ok(UnusedDiagnosticKind::Variable),
+            "mut" | "unusedmut" => Ok(UnusedDiagnosticKind::Mut),
             _ => Err(NotUnusedDiagnostic),
         }
     }
 }
 
+impl Display for UnusedDiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            UnusedDiagnosticKind::Constant => "CONST",
+            UnusedDiagnosticKind::Static => "STATIC",
+            UnusedDiagnosticKind::Function => "FUNCTION",
+            UnusedDiagnosticKind::Struct => "STRUCT",
+            UnusedDiagnosticKind::Enum => "ENUM",
+            UnusedDiagnosticKind::Union => "UNION",
+            UnusedDiagnosticKind::TypeAlias => "TYPE_ALIAS",
+            UnusedDiagnosticKind::AssociatedFunction => "ASSOCIATED_FUNCTION",
+            UnusedDiagnosticKind::AssociatedConstant => "ASSOCIATED_CONST",
+            UnusedDiagnosticKind::AssociatedType => "ASSOCIATED_TYPE",
+            UnusedDiagnosticKind::MacroDefinition => "MACRO_DEFINITION",
+            UnusedDiagnosticKind::Bound => "BOUND",
+            UnusedDiagnosticKind::DynShim => "DYN_SHIM",
+            UnusedDiagnosticKind::TraitImpl => "TRAIT_IMPL",
+            UnusedDiagnosticKind::ConstParam => "CONST_PARAM",
+            UnusedDiagnosticKind::TypeParam => "TYPE_PARAM",
+            UnusedDiagnosticKind::Import => "IMPORT",
+            UnusedDiagnosticKind::Variant => "VARIANT",
+            UnusedDiagnosticKind::Field => "FIELD",
+            UnusedDiagnosticKind::Module => "MODULE",
+            UnusedDiagnosticKind::ExternCrate => "EXTERN_CRATE",
+            UnusedDiagnosticKind::Lifetime => "LIFETIME",
+            UnusedDiagnosticKind::Unreachable => "UNREACHABLE",
+            UnusedDiagnosticKind::UnreachablePub => "UNREACHABLE_PUB",
+            UnusedDiagnosticKind::Reexport => "REEXPORT",
+            UnusedDiagnosticKind::UnusedPub => "UNUSED_PUB",
+            UnusedDiagnosticKind::Allow => "ALLOW",
+            UnusedDiagnosticKind::Variable => "VARIABLE",
+            UnusedDiagnosticKind::Mut => "MUT",
+        };
+
+        f.pad(name)
+    }
+}
+
 #[derive(Debug)]
 pub struct NotUnusedDiagnostic;
 
@@ -239,3 +1095,174 @@ impl Display for NotUnusedDiagnostic {
 }
 
 impl std::error::Error for NotUnusedDiagnostic {}
+
+#[cfg(test)]
+mod test {
+    use syn::spanned::Spanned;
+
+    use super::*;
+    use crate::test_support::TempCrate;
+
+    #[test]
+    fn kinds_round_trip() {
+        for kind in UnusedDiagnosticKind::ALL {
+            assert_eq!(
+                kind.to_string().parse::<UnusedDiagnosticKind>().unwrap(),
+                *kind
+            );
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(60 * 60));
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("99999999999999999h").is_err());
+    }
+
+    #[test]
+    fn merges_identical_findings() {
+        let src = "fn a() {}\nfn b() {}\n";
+        let file = syn::parse_file(src).unwrap();
+        let finding = |index: usize, ident: &str, config: &str| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Function,
+            ident: ident.to_string(),
+            span: synthetic_span("src/lib.rs", src, file.items[index].span()),
+            suggestion: None,
+            configs: vec![config.to_string()],
+        };
+        let merged = dedup([
+            finding(0, "a", "default"),
+            finding(1, "b", "default"),
+            finding(0, "a", "default"),
+            finding(0, "a", "features=x"),
+        ]);
+        let merged: Vec<_> = merged
+            .iter()
+            .map(|diagnostic| (diagnostic.ident.as_str(), diagnostic.configs.join(" ")))
+            .collect();
+        assert_eq!(
+            merged,
+            [
+                ("a", "default features=x".to_string()),
+                ("b", "default".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn benchmarks_keep_code_alive_unless_ignored() {
+        let temp_crate = TempCrate::with_files([
+            (
+                "Cargo.toml",
+                "[package]\nname = \"benched\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                 [[bench]]\nname = \"speed\"\nharness = false\n",
+            ),
+            ("src/main.rs", "mod util;\n\nfn main() {}\n"),
+            ("src/util.rs", "pub fn helper() {}\n"),
+            (
+                "benches/speed.rs",
+                "#[path = \"../src/util.rs\"]\nmod util;\n\nfn main() {\n    util::helper();\n}\n",
+            ),
+        ])
+        .unwrap();
+
+        let analysis = temp_crate.analyze(&[]).unwrap();
+        assert!(analysis.diagnostics.is_empty());
+
+        let report = temp_crate.minify(&["--ignore-bench-usage"]).unwrap();
+        assert!(report.is_applied());
+        assert_eq!(temp_crate.read("src/util.rs").unwrap(), "");
+        // The benchmark no longer compiles, but isn't built either
+        let analysis = temp_crate.analyze(&["--ignore-bench-usage"]).unwrap();
+        assert!(analysis.diagnostics.is_empty());
+        assert!(analysis.bench_references.is_empty());
+    }
+
+    #[test]
+    fn flags_benchmarks_referring_to_removed_code() {
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", "fn helper() {}\n"),
+            ("benches/speed.rs", "fn main() {\n    let helper = 0;\n}\n"),
+        ])
+        .unwrap();
+
+        let analysis = temp_crate.analyze(&["--ignore-bench-usage"]).unwrap();
+        let reference = BenchReference {
+            bench: "speed".to_string(),
+            ident: "helper".to_string(),
+        };
+        assert_eq!(analysis.bench_references, [reference]);
+    }
+
+    #[test]
+    fn checks_targets_with_their_required_features() {
+        let temp_crate = TempCrate::with_files([
+            (
+                "Cargo.toml",
+                "[package]\nname = \"gated\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                 [features]\nextra = []\n\n\
+                 [[bin]]\nname = \"tool\"\nrequired-features = [\"extra\"]\n",
+            ),
+            (
+                "src/lib.rs",
+                "fn helper() {}\n\n\
+                 #[cfg(feature = \"extra\")]\npub fn gated() {\n    helper();\n}\n",
+            ),
+            (
+                "src/bin/tool.rs",
+                "fn main() {\n    gated::gated();\n}\n\nfn tool_only() {}\n",
+            ),
+        ])
+        .unwrap();
+
+        // `helper` is only unused without the feature, and `tool_only` is
+        // only seen with it
+        let analysis = temp_crate.analyze(&[]).unwrap();
+        let found: Vec<_> = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.ident.as_str(), diagnostic.configs.join(" ")))
+            .collect();
+        assert_eq!(found, [("tool_only", "features=extra".to_string())]);
+    }
+
+    #[test]
+    fn keeps_code_used_behind_dev_dependency_features() {
+        let temp_crate = TempCrate::with_files([
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"kernel\", \"app\"]\n",
+            ),
+            (
+                "kernel/Cargo.toml",
+                "[package]\nname = \"kernel\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                 [features]\ntest-util = []\n",
+            ),
+            (
+                "kernel/src/lib.rs",
+                "fn shared() {}\n\nfn dead() {}\n\n\
+                 #[cfg(feature = \"test-util\")]\npub fn fixture() {\n    shared();\n}\n",
+            ),
+            (
+                "app/Cargo.toml",
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                 [dev-dependencies.kernel]\npath = \"../kernel\"\nfeatures = [\"test-util\"]\n",
+            ),
+            ("app/src/lib.rs", ""),
+        ])
+        .unwrap();
+
+        // `shared` is only used by the helper the tests of `app` enable
+        let analysis = temp_crate.analyze(&["--package", "kernel"]).unwrap();
+        let found: Vec<_> = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.ident.as_str(), diagnostic.configs.join(" ")))
+            .collect();
+        assert_eq!(found, [("dead", "default features=test-util".to_string())]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Dependencies that no target of their package uses, as reported by rustc's
+//! `unused_crate_dependencies` lint (enabled with `--unused-deps`). rustc
+//! reports a dependency for each compilation that doesn't use it, so it is
+//! only unused when every compilation of every target of the package reports
+//! it. Only the dev-dependencies are available to the test compilations of
+//! libraries and binaries, and to the tests, examples and benchmarks.
+//!
+//! The entries of the unused dependencies are removed from the manifests like
+//! unused code is removed from the sources, as changes to the manifests.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::{CompilerMessage, DependencyKind, Metadata, Target};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::cauterize::Change;
+
+const LINT: &str = "unused_crate_dependencies";
+
+/// The compilations of the targets, and the crates they didn't use; these are
+/// kept with the results of `--resume` and `--cache`, as the check isn't run
+/// again
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CrateUsage {
+    /// The number of compilations, and those that can use dev-dependencies
+    #[serde(with = "entries")]
+    compilations: HashMap<Target, (usize, usize)>,
+    /// The number of compilations that didn't use a crate
+    #[serde(with = "entries")]
+    unused: HashMap<(Target, String), usize>,
+}
+
+/// JSON objects only have strings for keys, so the maps are lists of entries
+mod entries {
+    use super::*;
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+        map: &HashMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+impl CrateUsage {
+    pub fn compiled(&mut self, target: &Target, test: bool) {
+        let with_dev_dependencies = test
+            || target
+                .kind
+                .iter()
+                .any(|kind| matches!(kind.as_str(), "test" | "example" | "bench"));
+        let (all, dev) = self.compilations.entry(target.clone()).or_default();
+        *all += 1;
+        *dev += usize::from(with_dev_dependencies);
+    }
+
+    /// The number of compilations of the target, and of those that can use
+    /// dev-dependencies, which are the ones with `cfg(test)` set for libraries
+    /// and binaries
+    pub fn compilations(&self, target: &Target) -> (usize, usize) {
+        self.compilations.get(target).copied().unwrap_or_default()
+    }
+
+    /// Records the message if it reports an unused crate, as in "extern crate
+    /// `foo` is unused in crate `bar`"; returns whether it did
+    pub fn record(&mut self, message: &CompilerMessage) -> bool {
+        let diagnostic = &message.message;
+        if diagnostic
+            .code
+            .as_ref()
+            .is_none_or(|code| code.code != LINT)
+        {
+            return false;
+        }
+        let name = diagnostic.message.split('`').nth(1);
+        if let Some(name) = name {
+            let key = (message.target.clone(), name.to_string());
+            *self.unused.entry(key).or_default() += 1;
+        }
+
+        true
+    }
+
+    /// Whether every compilation of the target that can use the crate didn't
+    fn is_unused(&self, target: &Target, name: &str, kind: &DependencyKind) -> bool {
+        let Some(&(all, dev)) = self.compilations.get(target) else {
+            return false;
+        };
+        let compilations = match kind {
+            DependencyKind::Development => dev,
+            _ => all,
+        };
+        let key = (target.clone(), name.to_string());
+        self.unused.get(&key).copied().unwrap_or_default() == compilations
+    }
+}
+
+/// A dependency of a package that none of its targets use
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnusedDependency {
+    pub package: String,
+    pub manifest_path: PathBuf,
+    /// The name of the dependency in the manifest, which is its `rename` if
+    /// it has one
+    pub name: String,
+    pub dev: bool,
+}
+
+impl UnusedDependency {
+    /// The manifest, table and name of the entry of the dependency
+    pub fn entry(&self) -> (&Path, &str, &str) {
+        let table = if self.dev {
+            "dev-dependencies"
+        } else {
+            "dependencies"
+        };
+        (&self.manifest_path, table, &self.name)
+    }
+}
+
+/// The unused dependencies of the packages of the targets. Packages with
+/// targets that weren't checked are left out, as these targets could use
+/// any dependency, and so are optional and platform-specific dependencies,
+/// as the checks only cover one set of features and one platform.
+pub fn find<'a>(
+    metadata: &Metadata,
+    targets: impl IntoIterator<Item = &'a Target>,
+    usage: &CrateUsage,
+) -> Vec<UnusedDependency> {
+    let mut packages: Vec<_> = targets
+        .into_iter()
+        .filter_map(|target| crate::resolver::package_of(metadata, target))
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    packages.dedup_by(|a, b| a.id == b.id);
+
+    let mut unused = Vec::new();
+    for package in packages {
+        // Build scripts only use the build-dependencies
+        let targets: Vec<_> = package
+            .targets
+            .iter()
+            .filter(|target| !crate::resolver::is_build_script(target))
+            .collect();
+        if !targets
+            .iter()
+            .all(|target| usage.compilations.contains_key(target))
+        {
+            continue;
+        }
+
+        for dependency in &package.dependencies {
+            let skipped = dependency.optional
+                || dependency.target.is_some()
+                || dependency.kind == DependencyKind::Build;
+            if skipped {
+                continue;
+            }
+            let name = dependency.rename.as_ref().unwrap_or(&dependency.name);
+            let crate_name = name.replace('-', "_");
+            // Dev-dependencies need at least one compilation to be unused by
+            let compiled = dependency.kind != DependencyKind::Development
+                || targets
+                    .iter()
+                    .any(|target| usage.compilations[*target].1 > 0);
+            let is_unused = targets
+                .iter()
+                .all(|target| usage.is_unused(target, &crate_name, &dependency.kind));
+            if compiled && is_unused {
+                unused.push(UnusedDependency {
+                    package: package.name.clone(),
+                    manifest_path: package.manifest_path.clone().into_std_path_buf(),
+                    name: name.clone(),
+                    dev: dependency.kind == DependencyKind::Development,
+                });
+            }
+        }
+    }
+
+    unused
+}
+
+/// The key of an entry of a table, as in `foo = "1"`, `foo.workspace = true`
+/// or `"foo" = { .. }`
+fn entry_key(line: &str) -> Option<&str> {
+    let (key, _) = line.split_once('=')?;
+    let key = key.split('.').next().unwrap_or_default();
+    Some(key.trim().trim_matches(['"', '\'']))
+}
+
+/// The change in the nesting of brackets and braces on a line
+fn nesting(line: &str) -> isize {
+    line.chars()
+        .map(|c| match c {
+            '[' | '{' => 1,
+            ']' | '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The manifest without the entry of `name` in `[table]`, be it a single
+/// line, an inline table spanning several lines, dotted keys, or a
+/// `[table.name]` table of its own; `None` if there is no such entry
+fn remove_entry(manifest: &str, table: &str, name: &str) -> Option<String> {
+    let mut out = String::with_capacity(manifest.len());
+    let (mut in_table, mut in_own_table) = (false, false);
+    let mut depth = 0;
+    let mut removed = false;
+
+    for line in manifest.split_inclusive('\n') {
+        let code = line.split('#').next().unwrap_or_default().trim();
+        if depth > 0 {
+            depth += nesting(code);
+            continue;
+        }
+        if code.starts_with('[') && !code.starts_with("[[") {
+            let header = code.trim_matches(['[', ']']).trim();
+            in_table = header == table;
+            in_own_table = header
+                .strip_prefix(table)
+                .and_then(|rest| rest.strip_prefix('.'))
+                .is_some_and(|key| key.trim().trim_matches(['"', '\'']) == name);
+        } else if code.starts_with('[') {
+            (in_table, in_own_table) = (false, false);
+        }
+        if in_own_table {
+            removed = true;
+            continue;
+        }
+        if in_table && entry_key(code) == Some(name) {
+            removed = true;
+            depth = nesting(code);
+            continue;
+        }
+        out.push_str(line);
+    }
+
+    removed.then_some(out)
+}
+
+/// The changes to the manifests that remove the entries, given by their
+/// manifest, table and name, such as those of the unused dependencies; the
+/// changes are named by `name`, like the other changes
+pub fn removals(entries: &[(&Path, &str, &str)], name: impl Fn(&Path) -> PathBuf) -> Vec<Change> {
+    let mut manifests: Vec<&Path> = entries.iter().map(|(path, _, _)| *path).collect();
+    manifests.sort();
+    manifests.dedup();
+
+    let mut changes = Vec::new();
+    for manifest_path in manifests {
+        let Ok(original) = std::fs::read_to_string(manifest_path) else {
+            continue;
+        };
+        let mut proposed = original.clone();
+        for (_, table, entry) in entries.iter().filter(|(path, _, _)| *path == manifest_path) {
+            if let Some(removed) = remove_entry(&proposed, table, entry) {
+                proposed = removed;
+            }
+        }
+        if proposed != original {
+            let file_name = name(manifest_path);
+            changes.push(Change::new(
+                file_name,
+                original.into_bytes(),
+                proposed.into_bytes(),
+            ));
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target(kind: &str) -> Target {
+        serde_json::from_value(serde_json::json!({
+            "name": "a",
+            "kind": [kind],
+            "crate_types": [kind],
+            "src_path": format!("src/{kind}.rs"),
+            "edition": "2021",
+            "doctest": false,
+            "test": true,
+            "doc": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn compilations() {
+        let (lib, test) = (target("lib"), target("test"));
+        let mut usage = CrateUsage::default();
+        usage.compiled(&lib, false);
+        usage.compiled(&lib, true);
+        usage.compiled(&test, false);
+        usage.unused.insert((lib.clone(), "foo".to_string()), 1);
+        usage.unused.insert((test.clone(), "foo".to_string()), 1);
+        usage.unused.insert((test.clone(), "bar".to_string()), 1);
+
+        // Only the regular compilation of the library didn't use `foo`
+        assert!(!usage.is_unused(&lib, "foo", &DependencyKind::Normal));
+        assert!(usage.is_unused(&test, "foo", &DependencyKind::Normal));
+        usage.unused.insert((lib.clone(), "bar".to_string()), 1);
+        assert!(usage.is_unused(&lib, "bar", &DependencyKind::Development));
+        assert!(!usage.is_unused(&lib, "bar", &DependencyKind::Normal));
+    }
+
+    #[test]
+    fn removes_entries() {
+        let manifest = "[package]\nname = \"a\"\n\n[dependencies]\nfoo = \"1\"\n\
+                        bar = { version = \"1\", features = [\n    \"x\",\n] }\n\
+                        baz.workspace = true\nqux = \"1\"\n\n[dependencies.quux]\n\
+                        version = \"1\"\n\n[dev-dependencies]\nfoo = \"1\"\n";
+        let removed = ["foo", "bar", "baz", "quux"]
+            .into_iter()
+            .try_fold(manifest.to_string(), |manifest, name| {
+                remove_entry(&manifest, "dependencies", name)
+            });
+        assert_eq!(
+            removed.unwrap(),
+            "[package]\nname = \"a\"\n\n[dependencies]\nqux = \"1\"\n\n\
+             [dev-dependencies]\nfoo = \"1\"\n"
+        );
+        assert_eq!(remove_entry(manifest, "dependencies", "fo"), None);
+    }
+}
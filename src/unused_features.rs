@@ -0,0 +1,174 @@
+//! Features that gate nothing, with `--unused-features`. A feature of a
+//! package is unused when no source of the package mentions it in a `cfg`,
+//! no target requires it, none of its other features enables it, and no other
+//! workspace member enables it, be it on their dependency or through one of
+//! their features. Features that enable dependencies, or features of them, do
+//! something without being mentioned, so they are left alone, as is
+//! `default`.
+//!
+//! The sources are searched as text rather than parsed, so a feature also
+//! counts as used when it's mentioned in a comment or a string.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::{Metadata, Package, Target};
+
+/// A feature of a package that gates no code and that nothing enables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnusedFeature {
+    pub package: String,
+    pub manifest_path: PathBuf,
+    pub name: String,
+}
+
+impl UnusedFeature {
+    /// The manifest, table and name of the entry of the feature
+    pub fn entry(&self) -> (&Path, &str, &str) {
+        (&self.manifest_path, "features", &self.name)
+    }
+}
+
+/// Whether the source mentions the feature in a `cfg`, as in
+/// `feature = "foo"`, or, in a build script, by its environment variable
+fn is_gating(src: &str, feature: &str) -> bool {
+    let quoted = format!("\"{feature}\"");
+    let in_cfg = src.match_indices(&quoted).any(|(index, _)| {
+        let before = src[..index].trim_end();
+        before
+            .strip_suffix('=')
+            .is_some_and(|before| before.trim_end().ends_with("feature"))
+    });
+    let variable = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+
+    in_cfg || src.contains(&variable)
+}
+
+/// The features of a package that enable nothing but other features, and
+/// are not mentioned by any of the sources, nor in `enabled`
+fn unused_in(
+    features: &BTreeMap<String, Vec<String>>,
+    sources: &[String],
+    enabled: &HashSet<&str>,
+) -> Vec<String> {
+    features
+        .iter()
+        .filter(|(name, _)| *name != "default" && !enabled.contains(name.as_str()))
+        .filter(|(_, enables)| {
+            enables
+                .iter()
+                .all(|feature| !feature.contains([':', '/']) && features.contains_key(feature))
+        })
+        .filter(|(name, _)| !sources.iter().any(|src| is_gating(src, name)))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// The features of the package that its own features, its targets, or the
+/// other workspace members enable
+fn enabled_features<'a>(metadata: &'a Metadata, package: &'a Package) -> HashSet<&'a str> {
+    let mut enabled = HashSet::new();
+    for enables in package.features.values() {
+        enabled.extend(enables.iter().map(String::as_str));
+    }
+    for target in &package.targets {
+        enabled.extend(target.required_features.iter().map(String::as_str));
+    }
+
+    for member in metadata.workspace_packages() {
+        if member.id == package.id {
+            continue;
+        }
+        for dependency in &member.dependencies {
+            if dependency.name != package.name {
+                continue;
+            }
+            enabled.extend(dependency.features.iter().map(String::as_str));
+            let name = dependency.rename.as_ref().unwrap_or(&dependency.name);
+            // Features of the member enabling ours, as in `dep/feature` or `dep?/feature`
+            let prefixes = [format!("{name}/"), format!("{name}?/")];
+            for enables in member.features.values() {
+                enabled.extend(enables.iter().filter_map(|feature| {
+                    prefixes
+                        .iter()
+                        .find_map(|prefix| feature.strip_prefix(prefix.as_str()))
+                }));
+            }
+        }
+    }
+
+    enabled
+}
+
+/// The unused features of the packages of the targets, searching all the
+/// sources of each package, except those in the `excluded` directories
+pub fn find<'a>(
+    metadata: &Metadata,
+    targets: impl IntoIterator<Item = &'a Target>,
+    excluded: &[PathBuf],
+) -> Vec<UnusedFeature> {
+    let mut packages: Vec<_> = targets
+        .into_iter()
+        .filter_map(|target| crate::resolver::package_of(metadata, target))
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    packages.dedup_by(|a, b| a.id == b.id);
+
+    let mut unused = Vec::new();
+    for package in packages {
+        let targets = package.targets.iter().cloned().collect();
+        let sources: Vec<_> = crate::resolver::source_files(&targets, excluded)
+            .into_iter()
+            .filter_map(|file| std::fs::read_to_string(file).ok())
+            .collect();
+        let enabled = enabled_features(metadata, package);
+        for name in unused_in(&package.features, &sources, &enabled) {
+            unused.push(UnusedFeature {
+                package: package.name.clone(),
+                manifest_path: package.manifest_path.clone().into_std_path_buf(),
+                name,
+            });
+        }
+    }
+
+    unused
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_unused_features() {
+        let features = BTreeMap::from(
+            [
+                ("default", &["std"][..]),
+                ("std", &[]),
+                ("fast", &[]),
+                ("alias", &["fast"]),
+                ("serde", &["dep:serde"]),
+                ("extra", &["serde/derive"]),
+                ("simd", &[]),
+                ("tests", &[]),
+                ("unused", &[]),
+            ]
+            .map(|(name, enables)| {
+                (
+                    name.to_string(),
+                    enables.iter().map(|s| s.to_string()).collect(),
+                )
+            }),
+        );
+        let sources = [
+            "#[cfg(feature = \"fast\")]\nfn f() {}\n".to_string(),
+            "fn main() { if env::var(\"CARGO_FEATURE_SIMD\").is_ok() {} }\n".to_string(),
+        ];
+        let enabled = HashSet::from(["std", "fast", "tests"]);
+        assert_eq!(
+            unused_in(&features, &sources, &enabled),
+            ["alias", "unused"]
+        );
+    }
+}
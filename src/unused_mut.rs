@@ -0,0 +1,104 @@
+//! Bindings declared `mut` that are never mutated, as reported by the
+//! `unused_mut` lint. Only the `mut` keyword is removed, as in `let mut x`
+//! becoming `let x`, or `|mut acc|` becoming `|acc|`; rustc's suggestions do
+//! just that, and where there is none, the keyword is found among the tokens
+//! by its position.
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The findings of an `unused_mut` diagnostic, one per binding, named by the
+/// variable
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    if code.code != "unused_mut" || !diagnostic.message.contains("does not need to be mutable") {
+        return None;
+    }
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .map(|span| {
+            let text = span_text(span);
+            UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::Mut,
+                ident: text.strip_prefix("mut").unwrap_or(&text).trim().to_string(),
+                span: span.clone(),
+                suggestion: None,
+                configs: Vec::new(),
+            }
+        })
+        .collect();
+
+    Some(found)
+}
+
+/// The key to remove the keyword by: its position, as in `3:9`
+pub fn removal_key(diagnostic: &UnusedDiagnostic) -> String {
+    format!(
+        "{}:{}",
+        diagnostic.span.line_start, diagnostic.span.column_start
+    )
+}
+
+/// Finds the `mut` keyword at the line and (1-based) column among the tokens
+fn find_mut(tokens: TokenStream, line: usize, column: usize) -> Option<Span> {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) if ident == "mut" => {
+                let start = ident.span().start();
+                if start.line == line && start.column + 1 == column {
+                    return Some(ident.span());
+                }
+            }
+            TokenTree::Group(group) => {
+                if let Some(span) = find_mut(group.stream(), line, column) {
+                    return Some(span);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The span of the `mut` keyword named by the key of [`removal_key`]
+pub fn mut_spans(file: &syn::File, key: &str) -> Vec<Span> {
+    let position = key
+        .split_once(':')
+        .and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)));
+    let Some((line, column)) = position else {
+        return Vec::new();
+    };
+
+    find_mut(file.to_token_stream(), line, column)
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::test::rust_delete;
+
+    use super::*;
+
+    #[test]
+    fn removes_mut_keywords() {
+        let src = "fn f(mut a: u8, v: &mut Vec<u8>) -> u8 {\n    let mut b = a;\n    \
+                   let (mut c, d) = (1, 2);\n    let g = |mut acc: u8| acc;\n    \
+                   v.push(b);\n    a + c + d + g(1)\n}\n";
+        let keys = ["1:6", "2:9", "3:10", "4:14", "1:22"]
+            .map(|key| (UnusedDiagnosticKind::Mut, key.to_string()));
+        assert_eq!(
+            String::from_utf8(rust_delete(src.as_bytes(), keys, false).unwrap()).unwrap(),
+            "fn f(a: u8, v: &mut Vec<u8>) -> u8 {\n    let b = a;\n    \
+             let (c, d) = (1, 2);\n    let g = |acc: u8| acc;\n    \
+             v.push(b);\n    a + c + d + g(1)\n}\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! `pub` items that no crate in the workspace uses, with `--kinds unused_pub`.
+//! rustc never reports these, as other crates may use them; but when the name
+//! of such an item is mentioned nowhere in the analyzed packages, besides its
+//! definitions, not even by the workspace members depending on the library,
+//! the item is dead weight. Names aren't resolved, so a mention of any item by
+//! that name keeps them all.
+//!
+//! Re-exports are aliases of the items they re-export: an item re-exported by
+//! the library, possibly under another name, is kept when the re-export is
+//! used, as facades are. Items that are re-exported are only removed along
+//! with their re-exports, when `--kinds reexport` is asked for too, as they
+//! can't go on their own.
+
+use std::path::Path;
+
+use quote::ToTokens;
+use syn::{Attribute, Item, Meta, Visibility};
+
+use crate::{
+    reexports::Aliases,
+    unused::{synthetic_span, UnusedDiagnostic, UnusedDiagnosticKind},
+    FileResolutionOptions,
+};
+
+/// The kinds of the items found, which are removed as any of them
+pub const ITEM_KINDS: &[UnusedDiagnosticKind] = &[
+    UnusedDiagnosticKind::Constant,
+    UnusedDiagnosticKind::Enum,
+    UnusedDiagnosticKind::Function,
+    UnusedDiagnosticKind::Static,
+    UnusedDiagnosticKind::Struct,
+    UnusedDiagnosticKind::TypeAlias,
+    UnusedDiagnosticKind::Union,
+];
+
+/// The attributes exporting items to the linker, which uses them by name
+const EXPORTING: &[&str] = &["no_mangle", "export_name"];
+
+/// Whether the item is exported to the linker, as with `#[no_mangle]` or
+/// `#[unsafe(no_mangle)]`
+fn is_exported(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let name = match &attr.meta {
+            Meta::List(list) if list.path.is_ident("unsafe") => list.tokens.to_string(),
+            meta => meta.path().to_token_stream().to_string(),
+        };
+        EXPORTING
+            .iter()
+            .any(|exporting| name.starts_with(exporting))
+    })
+}
+
+/// The `pub` items among the items and those of inline modules, which can be
+/// removed as a whole
+fn collect_items<'a>(items: &'a [Item], found: &mut Vec<&'a syn::Ident>) {
+    for item in items {
+        let (vis, attrs, ident) = match item {
+            Item::Const(item) => (&item.vis, &item.attrs, &item.ident),
+            Item::Enum(item) => (&item.vis, &item.attrs, &item.ident),
+            Item::Fn(item) => (&item.vis, &item.attrs, &item.sig.ident),
+            Item::Static(item) => (&item.vis, &item.attrs, &item.ident),
+            Item::Struct(item) => (&item.vis, &item.attrs, &item.ident),
+            Item::Type(item) => (&item.vis, &item.attrs, &item.ident),
+            Item::Union(item) => (&item.vis, &item.attrs, &item.ident),
+            Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_items(items, found);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if matches!(vis, Visibility::Public(_)) && !is_exported(attrs) {
+            found.push(ident);
+        }
+    }
+}
+
+/// The `pub` items in `files` whose names are mentioned nowhere in `sources`
+/// (which include `files`), but in definitions and the re-exports of unused
+/// names; with `with_reexports`, the re-exports are being removed as well
+pub fn get_unused_pub_items(
+    files: &[impl AsRef<Path>],
+    sources: &[impl AsRef<Path>],
+    workspace_root: &Path,
+    file_resolution: &FileResolutionOptions,
+    with_reexports: bool,
+) -> Vec<UnusedDiagnostic> {
+    let aliases = Aliases::new(sources);
+
+    let mut unused = Vec::new();
+    for file in files {
+        let file = file.as_ref();
+        let file_name = file.strip_prefix(workspace_root).unwrap_or(file);
+        let file_name = file_name.to_string_lossy();
+        if !file_resolution.is_included(&file_name) {
+            continue;
+        }
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let Ok(parsed) = syn::parse_file(&src) else {
+            continue;
+        };
+
+        let mut found = Vec::new();
+        collect_items(&parsed.items, &mut found);
+        for ident in found {
+            let name = ident.to_string();
+            if aliases.is_used(&name) || (!with_reexports && aliases.is_reexported(&name)) {
+                continue;
+            }
+            unused.push(UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::UnusedPub,
+                ident: name,
+                span: synthetic_span(&file_name, &src, ident.span()),
+                suggestion: None,
+                configs: Vec::new(),
+            });
+        }
+    }
+
+    unused
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cauterize::test::rust_delete, imports, reexports, test_support::TempCrate};
+
+    use super::*;
+
+    #[test]
+    fn keeps_items_used_through_reexports() {
+        let lib_rs = "mod internal {\n    pub fn foo() {}\n    pub fn bar() {}\n    \
+                      pub fn baz() {}\n    pub struct Kept;\n    #[no_mangle]\n    \
+                      pub extern \"C\" fn ffi() {}\n}\n\
+                      pub use internal::foo as facade;\npub use internal::{bar, Kept};\n";
+        let temp_crate = TempCrate::with_files([
+            ("src/lib.rs", lib_rs),
+            (
+                "app/src/main.rs",
+                "fn main() { lib::facade(); let _ = lib::Kept; }\n",
+            ),
+        ])
+        .unwrap();
+        let root = temp_crate.root();
+        let (lib, main) = (root.join("src/lib.rs"), root.join("app/src/main.rs"));
+        let all = FileResolutionOptions::AllBut(Vec::new());
+
+        // `bar` is only re-exported, so it stays unless its re-export goes too
+        let found = get_unused_pub_items(&[&lib], &[&lib, &main], root, &all, false);
+        let idents: Vec<_> = found.iter().map(|found| found.ident.as_str()).collect();
+        assert_eq!(idents, ["baz"]);
+
+        let mut found = get_unused_pub_items(&[&lib], &[&lib, &main], root, &all, true);
+        let idents: Vec<_> = found.iter().map(|found| found.ident.as_str()).collect();
+        assert_eq!(idents, ["bar", "baz"]);
+
+        found.extend(reexports::get_unused_reexports(
+            &[&lib],
+            &[&lib, &main],
+            root,
+            &all,
+        ));
+        let keys = found
+            .iter()
+            .map(|found| (found.kind, imports::removal_key(found)));
+        assert_eq!(
+            String::from_utf8(rust_delete(lib_rs.as_bytes(), keys, false).unwrap()).unwrap(),
+            "mod internal {\n    pub fn foo() {}\n    pub struct Kept;\n    #[no_mangle]\n    \
+             pub extern \"C\" fn ffi() {}\n}\n\
+             pub use internal::foo as facade;\npub use internal::Kept;\n"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Variables that are never used, as reported by the `unused_variables` lint.
+//! rustc suggests prefixing their names with an underscore, or ignoring the
+//! field for shorthand field patterns, as in `Point { x: _, y }`, and these
+//! suggestions are applied verbatim. A `let` statement binding nothing but the
+//! variable to an initializer without side effects, such as a literal, a path
+//! or a reference to one, is removed altogether instead. Moving a value out of
+//! another variable counts as free of side effects, though it's then dropped
+//! later.
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{parse::Parser, spanned::Spanned, Block, Expr, Local, Pat, Stmt};
+
+use crate::unused::{UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The finding of an `unused_variables` diagnostic, at the first binding of
+/// the variable, as a pattern with alternatives binds it several times
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    let ident = diagnostic
+        .message
+        .strip_prefix("unused variable: `")?
+        .strip_suffix('`')?;
+    if code.code != "unused_variables" {
+        return None;
+    }
+    let span = diagnostic.spans.iter().find(|span| span.is_primary)?;
+
+    Some(vec![UnusedDiagnostic {
+        kind: UnusedDiagnosticKind::Variable,
+        ident: ident.to_string(),
+        span: span.clone(),
+        suggestion: None,
+        configs: Vec::new(),
+    }])
+}
+
+/// The key to remove the variable by: the position of its binding, as in
+/// `3:9`
+pub fn removal_key(diagnostic: &UnusedDiagnostic) -> String {
+    format!(
+        "{}:{}",
+        diagnostic.span.line_start, diagnostic.span.column_start
+    )
+}
+
+/// Whether evaluating the expression has no effect, other than moving values
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) | Expr::Path(_) => true,
+        Expr::Reference(expr) => is_pure(&expr.expr),
+        Expr::Paren(expr) => is_pure(&expr.expr),
+        Expr::Tuple(expr) => expr.elems.iter().all(is_pure),
+        Expr::Array(expr) => expr.elems.iter().all(is_pure),
+        _ => false,
+    }
+}
+
+/// Whether the `let` statement binds a single variable to a pure initializer
+/// (or to none), without an `else` branch
+fn is_removable(local: &Local) -> bool {
+    let pat = match &local.pat {
+        Pat::Type(pat) => &*pat.pat,
+        pat => pat,
+    };
+    let Pat::Ident(pat) = pat else {
+        return false;
+    };
+    let pure = match &local.init {
+        Some(init) => init.diverge.is_none() && is_pure(&init.expr),
+        None => true,
+    };
+
+    pat.subpat.is_none() && pure
+}
+
+/// Finds the `let` statement binding a variable at the line and (1-based)
+/// column, trying every brace-delimited group among the tokens as a block
+fn find_let(tokens: TokenStream, line: usize, column: usize) -> Option<Local> {
+    for token in tokens {
+        let TokenTree::Group(group) = token else {
+            continue;
+        };
+        if let Some(local) = find_let(group.stream(), line, column) {
+            return Some(local);
+        }
+        if group.delimiter() != Delimiter::Brace {
+            continue;
+        }
+        let Ok(stmts) = Block::parse_within.parse2(group.stream()) else {
+            continue;
+        };
+        for stmt in stmts {
+            let Stmt::Local(local) = stmt else {
+                continue;
+            };
+            let pat = match &local.pat {
+                Pat::Type(pat) => &*pat.pat,
+                pat => pat,
+            };
+            let Pat::Ident(pat) = pat else {
+                continue;
+            };
+            let start = pat.ident.span().start();
+            if start.line == line && start.column + 1 == column {
+                return Some(local);
+            }
+        }
+    }
+
+    None
+}
+
+fn parse_key(key: &str) -> Option<(usize, usize)> {
+    let (line, column) = key.split_once(':')?;
+
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
+
+/// The span of the `let` statement of the variable named by the key of
+/// [`removal_key`], if the statement can be removed
+pub fn variable_spans(file: &syn::File, key: &str) -> Vec<Span> {
+    let Some((line, column)) = parse_key(key) else {
+        return Vec::new();
+    };
+
+    find_let(file.to_token_stream(), line, column)
+        .filter(is_removable)
+        .map(|local| local.span())
+        .into_iter()
+        .collect()
+}
+
+/// The keys of the unused variables among the findings whose `let`
+/// statements are removed, rather than the variables renamed
+pub fn removable_lets(src: &[u8], findings: &[&UnusedDiagnostic]) -> Vec<String> {
+    let variables: Vec<_> = findings
+        .iter()
+        .filter(|finding| finding.kind == UnusedDiagnosticKind::Variable)
+        .map(|finding| removal_key(finding))
+        .collect();
+    if variables.is_empty() {
+        return Vec::new();
+    }
+    let Ok(file) = syn::parse_str::<syn::File>(&String::from_utf8_lossy(src)) else {
+        return Vec::new();
+    };
+
+    variables
+        .into_iter()
+        .filter(|key| !variable_spans(&file, key).is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cauterize::test::rust_delete;
+
+    use super::*;
+
+    #[test]
+    fn removes_pure_lets() {
+        let src = "fn f(v: Vec<u8>) {\n    let a = 1;\n    let b: &[u8] = &v;\n    \
+                   let c = g();\n    if true {\n        let d = (1, [2, 3]);\n    }\n    \
+                   let Some(e) = Some(1) else { return };\n}\n";
+        let file = syn::parse_str(src).unwrap();
+        let removable: Vec<_> = ["2:9", "3:9", "4:9", "6:13", "8:14", "1:6"]
+            .into_iter()
+            .filter(|key| !variable_spans(&file, key).is_empty())
+            .collect();
+        assert_eq!(removable, ["2:9", "3:9", "6:13"]);
+
+        let keys = removable
+            .iter()
+            .map(|key| (UnusedDiagnosticKind::Variable, key.to_string()));
+        assert_eq!(
+            String::from_utf8(rust_delete(src.as_bytes(), keys, false).unwrap()).unwrap(),
+            "fn f(v: Vec<u8>) {\n    let c = g();\n    if true {\n    }\n    \
+             let Some(e) = Some(1) else { return };\n}\n"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Enum variants that are never constructed. Unlike the other kinds, removing
+//! them isn't always safe: patterns may still match on them, and with a
+//! `#[repr]` or explicit discriminants, the discriminants of the remaining
+//! variants matter. Such variants are only reported.
+
+use std::{collections::HashMap, path::Path};
+
+use cargo_metadata::diagnostic::Diagnostic;
+use proc_macro2::{Span, TokenStream, TokenTree};
+use syn::{spanned::Spanned, Item, ItemEnum};
+
+use crate::unused::{span_text, UnusedDiagnostic, UnusedDiagnosticKind};
+
+/// The findings of a "variants `A` and `B` are never constructed" diagnostic,
+/// one per variant, named like `Enum::A`
+pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Vec<UnusedDiagnostic>> {
+    let code = diagnostic.code.as_ref()?;
+    let message = &diagnostic.message;
+    let is_variants = message.starts_with("variant") && message.ends_with("never constructed");
+    if code.code != "dead_code" || !is_variants {
+        return None;
+    }
+    // The other span points at the name of the enum
+    let enum_name = span_text(diagnostic.spans.iter().find(|span| !span.is_primary)?);
+
+    let found = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.is_primary)
+        .map(|span| UnusedDiagnostic {
+            kind: UnusedDiagnosticKind::Variant,
+            ident: format!("{enum_name}::{}", span_text(span)),
+            span: span.clone(),
+            suggestion: None,
+            configs: Vec::new(),
+        })
+        .collect();
+
+    Some(found)
+}
+
+fn find_enum<'a>(items: &'a [Item], name: &str) -> Option<&'a ItemEnum> {
+    items.iter().find_map(|item| match item {
+        Item::Enum(item) if item.ident == name => Some(item),
+        Item::Mod(module) => find_enum(&module.content.as_ref()?.1, name),
+        _ => None,
+    })
+}
+
+/// The spans of a variant of an enum, given as `Enum::Variant`, along with
+/// its comma (or the one before it, for the last variant without one)
+pub fn variant_spans(file: &syn::File, id: &str) -> Vec<Span> {
+    let Some((enum_name, variant)) = id.split_once("::") else {
+        return Vec::new();
+    };
+    let Some(item) = find_enum(&file.items, enum_name) else {
+        return Vec::new();
+    };
+
+    let pairs: Vec<_> = item.variants.pairs().collect();
+    let Some(index) = pairs.iter().position(|pair| pair.value().ident == variant) else {
+        return Vec::new();
+    };
+    let span = pairs[index].value().span();
+    let span = match (pairs[index].punct(), index.checked_sub(1)) {
+        (Some(comma), _) => span.join(comma.span),
+        (None, Some(previous)) => pairs[previous]
+            .punct()
+            .and_then(|comma| comma.span.join(span)),
+        (None, None) => Some(span),
+    };
+
+    span.into_iter().collect()
+}
+
+pub fn count_idents(tokens: TokenStream, counts: &mut HashMap<String, usize>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => *counts.entry(ident.to_string()).or_default() += 1,
+            TokenTree::Group(group) => count_idents(group.stream(), counts),
+            _ => {}
+        }
+    }
+}
+
+/// The mentions of names across the analyzed sources, to tell whether
+/// anything (such as a pattern) refers to a variant besides its definition
+pub struct Mentions {
+    counts: HashMap<String, usize>,
+}
+
+impl Mentions {
+    pub fn new(files: &[impl AsRef<Path>]) -> Self {
+        let mut counts = HashMap::new();
+        for file in files {
+            let Ok(src) = std::fs::read_to_string(file) else {
+                continue;
+            };
+            if let Ok(tokens) = src.parse() {
+                count_idents(tokens, &mut counts);
+            }
+        }
+
+        Mentions { counts }
+    }
+
+    /// How often the name is mentioned, including its definition
+    pub fn of(&self, name: &str) -> usize {
+        self.counts.get(name).copied().unwrap_or_default()
+    }
+
+    /// Whether the variant can be removed without breaking the code: it is
+    /// not mentioned anywhere but in its definition, and the discriminants
+    /// of its enum don't matter
+    pub fn is_removable(&self, workspace_root: &Path, diagnostic: &UnusedDiagnostic) -> bool {
+        let Some((enum_name, variant)) = diagnostic.ident.split_once("::") else {
+            return false;
+        };
+        let path = workspace_root.join(&diagnostic.span.file_name);
+        let Some(parsed) = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|src| syn::parse_file(&src).ok())
+        else {
+            return false;
+        };
+        let Some(item) = find_enum(&parsed.items, enum_name) else {
+            return false;
+        };
+
+        let has_repr = item.attrs.iter().any(|attr| attr.path().is_ident("repr"));
+        let has_discriminants = item.variants.iter().any(|v| v.discriminant.is_some());
+        let mentioned = self.of(variant) > 1;
+
+        !has_repr && !has_discriminants && !mentioned
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn remove(src: &str, id: &str) -> String {
+        let spans = variant_spans(&syn::parse_file(src).unwrap(), id);
+        let ranges: Vec<_> = spans
+            .iter()
+            .map(|span| span.start().column..span.end().column)
+            .collect();
+        String::from_utf8(crate::cauterize::delete_chunks(src.as_bytes(), &ranges)).unwrap()
+    }
+
+    #[test]
+    fn removal() {
+        let src = "enum A { X, Y(u8), Z { f: u8 } }";
+        assert_eq!(remove(src, "A::X"), "enum A {  Y(u8), Z { f: u8 } }");
+        assert_eq!(remove(src, "A::Y"), "enum A { X,  Z { f: u8 } }");
+        assert_eq!(remove(src, "A::Z"), "enum A { X, Y(u8) }");
+        assert_eq!(
+            remove("mod m { enum A { X, } }", "A::X"),
+            "mod m { enum A {  } }"
+        );
+        assert_eq!(remove(src, "B::X"), src);
+    }
+}
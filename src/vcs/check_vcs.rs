@@ -15,7 +15,7 @@ pub fn existing_vcs_repo(path: &Path, cwd: &Path) -> bool {
 fn in_git_repo(path: &Path) -> bool {
     if let Ok(repo) = git2::Repository::discover(path) {
         // Don't check if the working directory itself is ignored.
-        if repo.workdir().map_or(false, |workdir| workdir == path) {
+        if repo.workdir() == Some(path) {
             true
         } else {
             !repo.is_path_ignored(path).unwrap_or(false)
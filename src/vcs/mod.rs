@@ -1,9 +1,52 @@
-use std::path::Path;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::cauterize::Change;
 
 mod check_vcs;
 
-pub fn status(path: impl AsRef<Path>) -> Status {
-    check_version_control(path.as_ref())
+/// How the state of the working directory is found out
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VcsBackend {
+    /// `git status`, falling back to libgit2 when `git` is not on the `PATH`
+    GitCli,
+    /// The libgit2 library built into `cargo minify`, which needs no binaries
+    #[default]
+    Libgit2,
+    /// No checks at all, as if there was no version control system
+    None,
+}
+
+impl FromStr for VcsBackend {
+    type Err = UnsupportedVcsBackend;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "git-cli" => Ok(VcsBackend::GitCli),
+            "libgit2" => Ok(VcsBackend::Libgit2),
+            "none" => Ok(VcsBackend::None),
+            _ => Err(UnsupportedVcsBackend),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported VCS backend, pick any of: git-cli, libgit2, none")]
+pub struct UnsupportedVcsBackend;
+
+pub fn status(path: impl AsRef<Path>, backend: VcsBackend) -> Status {
+    let path = path.as_ref();
+    match backend {
+        VcsBackend::GitCli => git_cli_status(path).unwrap_or_else(|| check_version_control(path)),
+        VcsBackend::Libgit2 => check_version_control(path),
+        VcsBackend::None => Status::NoVCS,
+    }
 }
 
 pub enum Status {
@@ -16,6 +59,216 @@ pub enum Status {
     Error(git2::Error),
 }
 
+/// Writes the proposed contents of the changes to the git index (staging area)
+/// instead of the working tree, returning the staged files
+pub fn stage(path: impl AsRef<Path>, changes: &[Change]) -> Result<Vec<PathBuf>, git2::Error> {
+    let repo = git2::Repository::discover(path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("cannot stage changes in a bare repository"))?
+        .canonicalize()
+        .map_err(|err| git2::Error::from_str(&err.to_string()))?;
+    let mut index = repo.index()?;
+
+    let mut staged = Vec::new();
+    for change in changes {
+        let file_name = change
+            .file_name()
+            .canonicalize()
+            .map_err(|err| git2::Error::from_str(&err.to_string()))?;
+        let relative = file_name.strip_prefix(&workdir).map_err(|_| {
+            git2::Error::from_str(&format!(
+                "{} is outside the repository",
+                file_name.display()
+            ))
+        })?;
+
+        if change.is_removal() {
+            index.remove_path(relative)?;
+            staged.push(change.file_name().to_path_buf());
+            continue;
+        }
+
+        let mut entry = index
+            .get_path(relative, 0)
+            .unwrap_or_else(|| git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100644,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: git2::Oid::zero(),
+                flags: 0,
+                flags_extended: 0,
+                path: Vec::new(),
+            });
+        entry.path = relative.to_string_lossy().replace('\\', "/").into_bytes();
+        entry.file_size = change.proposed_content().len() as u32;
+        index.add_frombuffer(&entry, change.proposed_content())?;
+
+        staged.push(change.file_name().to_path_buf());
+    }
+
+    index.write()?;
+
+    Ok(staged)
+}
+
+/// The id of the commit checked out at `path`, if it is in a git repository
+pub fn head_commit(path: impl AsRef<Path>) -> Option<String> {
+    let repo = git2::Repository::discover(path).ok()?;
+    let head = repo.head().ok()?.peel_to_commit().ok()?;
+
+    Some(head.id().to_string())
+}
+
+/// Creates a branch at the current HEAD and switches to it. An existing branch
+/// is only reset to HEAD with `force`.
+pub fn switch_to_new_branch(
+    path: impl AsRef<Path>,
+    name: &str,
+    force: bool,
+) -> Result<(), git2::Error> {
+    let repo = git2::Repository::discover(path)?;
+    if !force && repo.find_branch(name, git2::BranchType::Local).is_ok() {
+        return Err(git2::Error::from_str(&format!(
+            "branch `{name}` already exists; pass --force-branch to reset it"
+        )));
+    }
+
+    let head = repo.head()?.peel_to_commit()?;
+    let branch = repo.branch(name, &head, force)?;
+    let reference = branch
+        .get()
+        .name()
+        .ok_or_else(|| git2::Error::from_str("branch name is not valid UTF-8"))?;
+
+    // The branch points at HEAD, so the working tree doesn't need a checkout
+    repo.set_head(reference)
+}
+
+/// The path of the file relative to the working directory; files that were
+/// deleted are found through their directory
+fn relative_path(workdir: &Path, file: &Path) -> Result<Option<PathBuf>, git2::Error> {
+    let canonical = match (file.exists(), file.parent(), file.file_name()) {
+        (false, Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            parent.canonicalize().map(|parent| parent.join(name))
+        }
+        _ => file.canonicalize(),
+    };
+    let canonical = canonical.map_err(|err| git2::Error::from_str(&err.to_string()))?;
+
+    Ok(canonical.strip_prefix(workdir).ok().map(Path::to_path_buf))
+}
+
+/// Adds the given files to the index, removes the `removed` ones from it, and
+/// commits the index on top of HEAD, returning the id of the new commit; when
+/// the index holds nothing new, nothing is committed
+pub fn commit(
+    path: impl AsRef<Path>,
+    files: &[PathBuf],
+    removed: &[PathBuf],
+    message: &str,
+) -> Result<Option<git2::Oid>, git2::Error> {
+    let repo = git2::Repository::discover(path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("cannot commit in a bare repository"))?
+        .canonicalize()
+        .map_err(|err| git2::Error::from_str(&err.to_string()))?;
+
+    let mut index = repo.index()?;
+    for file in files {
+        if let Some(relative) = relative_path(&workdir, file)? {
+            index.add_path(&relative)?;
+        }
+    }
+    for file in removed {
+        if let Some(relative) = relative_path(&workdir, file)? {
+            index.remove_path(&relative)?;
+        }
+    }
+    index.write()?;
+
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    if tree.id() == parent.tree_id() {
+        return Ok(None);
+    }
+    let signature = repo.signature()?;
+
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &[&parent],
+    )
+    .map(Some)
+}
+
+/// The status according to `git status`, if it could tell, which it can't
+/// when `git` is not installed or the path is not in a git repository
+fn git_cli_status(path: &Path) -> Option<Status> {
+    let output = match Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["status", "--porcelain=v1", "-z", "--untracked-files=all"])
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => return Some(Status::Error(git2::Error::from_str(&err.to_string()))),
+    };
+    if !output.status.success() {
+        return None;
+    }
+
+    let (dirty, staged) = parse_porcelain(&String::from_utf8_lossy(&output.stdout));
+    if dirty.is_empty() && staged.is_empty() {
+        Some(Status::Clean)
+    } else {
+        Some(Status::Unclean { dirty, staged })
+    }
+}
+
+/// The dirty and the staged files in the output of `git status --porcelain -z`,
+/// sorted like libgit2 does: files that are only changed in the index are
+/// staged, everything else is dirty
+fn parse_porcelain(output: &str) -> (Vec<String>, Vec<String>) {
+    let mut dirty = Vec::new();
+    let mut staged = Vec::new();
+
+    let mut entries = output.split('\0');
+    while let Some(entry) = entries.next() {
+        if entry.len() <= 3 {
+            continue;
+        }
+        let (index, worktree) = (entry.as_bytes()[0], entry.as_bytes()[1]);
+        let path = entry[3..].to_string();
+        // Renames and copies are followed by the original path, however short
+        if matches!(index, b'R' | b'C') {
+            entries.next();
+        }
+        if index != b' ' && index != b'?' && worktree == b' ' {
+            staged.push(path);
+        } else {
+            dirty.push(path);
+        }
+    }
+
+    (dirty, staged)
+}
+
 // Portions of the below code are inspired by/taken from Cargo, https://github.com/rust-lang/cargo/
 // Copyright (c) 2016-2021 The Cargo Developers
 
@@ -55,3 +308,172 @@ fn check_version_control(path: &Path) -> Status {
         Status::Unclean { dirty, staged }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_support::TempCrate, ApplyReport};
+
+    #[test]
+    fn commits_module_removals() {
+        let temp_crate = TempCrate::with_files([
+            (
+                "src/lib.rs",
+                "mod dead;\npub fn used() {}\nfn unused() {}\n",
+            ),
+            ("src/dead.rs", "fn a() {}\n"),
+        ])
+        .unwrap();
+        temp_crate.init_git().unwrap();
+
+        let report = temp_crate
+            .minify(&["--kinds", "function", "--kinds", "module", "--vcs-commit"])
+            .unwrap();
+        let ApplyReport::Applied { commit, .. } = report else {
+            panic!("not applied: {report:?}");
+        };
+        assert!(commit.is_some());
+        assert_eq!(
+            temp_crate.committed_files().unwrap(),
+            [".gitignore", "Cargo.toml", "src/lib.rs"]
+        );
+        assert!(matches!(
+            status(temp_crate.root(), VcsBackend::Libgit2),
+            Status::Clean
+        ));
+    }
+
+    #[test]
+    fn reports_written_files_when_commit_fails() {
+        let temp_crate = TempCrate::new("pub fn used() {}\nfn unused() {}\n").unwrap();
+        // Without a commit at HEAD, there is nothing to commit on top of
+        git2::Repository::init(temp_crate.root()).unwrap();
+
+        let report = temp_crate.minify(&["--vcs-commit"]).unwrap();
+        assert!(report.is_partial());
+        let ApplyReport::Applied {
+            files,
+            commit,
+            commit_error,
+            ..
+        } = report
+        else {
+            panic!("not applied: {report:?}");
+        };
+        assert_eq!(files.len(), 1);
+        assert_eq!((commit, commit_error.is_some()), (None, true));
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), "pub fn used() {}\n");
+    }
+
+    #[test]
+    fn stages_changes_instead_of_writing_them() {
+        let lib_rs = "mod dead;\npub fn used() {}\nfn unused() {}\n";
+        let temp_crate =
+            TempCrate::with_files([("src/lib.rs", lib_rs), ("src/dead.rs", "fn a() {}\n")])
+                .unwrap();
+
+        // Without a repository, there is no index to stage to
+        let analysis = temp_crate
+            .analyze(&["--apply-staged", "--allow-no-vcs"])
+            .unwrap();
+        assert!(matches!(
+            crate::apply(analysis).unwrap(),
+            ApplyReport::VcsError(_)
+        ));
+
+        let repo = temp_crate.init_git().unwrap();
+        let args = ["--kinds", "function", "--kinds", "module", "--apply-staged"];
+        let report = crate::apply(temp_crate.analyze(&args).unwrap()).unwrap();
+        let ApplyReport::Applied { files, staged, .. } = report else {
+            panic!("not applied: {report:?}");
+        };
+        assert!(staged);
+        assert_eq!(files.len(), 2);
+
+        // The working tree is left alone
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+        assert_eq!(temp_crate.read("src/dead.rs").unwrap(), "fn a() {}\n");
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        let entry = index.get_path(Path::new("src/lib.rs"), 0).unwrap();
+        let blob = repo.find_blob(entry.id).unwrap();
+        assert_eq!(blob.content(), b"pub fn used() {}\n");
+        assert!(index.get_path(Path::new("src/dead.rs"), 0).is_none());
+        // What `git diff --cached` would show
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        let diff = repo
+            .diff_tree_to_index(Some(&head), Some(&index), None)
+            .unwrap();
+        let staged: Vec<_> = diff
+            .deltas()
+            .map(|delta| (delta.status(), delta.old_file().path()))
+            .collect();
+        assert_eq!(
+            staged,
+            [
+                (git2::Delta::Deleted, Some(Path::new("src/dead.rs"))),
+                (git2::Delta::Modified, Some(Path::new("src/lib.rs"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_on_a_new_branch() {
+        let lib_rs = "pub fn used() {}\nfn unused() {}\n";
+        let temp_crate = TempCrate::new(lib_rs).unwrap();
+        let repo = temp_crate.init_git().unwrap();
+        let original = repo.head().unwrap().name().unwrap().to_string();
+        let initial = repo.head().unwrap().peel_to_commit().unwrap().id();
+        let head = || repo.head().unwrap().name().unwrap().to_string();
+
+        let report = temp_crate
+            .minify(&["--vcs-branch", "minified", "--vcs-commit"])
+            .unwrap();
+        let ApplyReport::Applied { branch, commit, .. } = report else {
+            panic!("not applied: {report:?}");
+        };
+        assert_eq!(branch.as_deref(), Some("minified"));
+        assert_eq!(head(), "refs/heads/minified");
+        let commit = repo.find_commit(commit.unwrap().parse().unwrap()).unwrap();
+        assert_eq!(commit.parent_id(0).unwrap(), initial);
+        assert_eq!(repo.refname_to_id(&original).unwrap(), initial);
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), "pub fn used() {}\n");
+
+        // An existing branch is only reset when asked to
+        repo.set_head(&original).unwrap();
+        std::fs::write(temp_crate.root().join("src/lib.rs"), lib_rs).unwrap();
+        let report = temp_crate.minify(&["--vcs-branch", "minified"]).unwrap();
+        assert!(matches!(report, ApplyReport::VcsError(_)));
+        let args = [
+            "apply",
+            "--vcs-branch",
+            "minified",
+            "--allow-dirty",
+            "--quiet",
+        ];
+        let err = crate::execute(&temp_crate.args(&args)).unwrap_err();
+        assert_eq!(err.exit_code(), 8);
+        assert_eq!(head(), original);
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), lib_rs);
+
+        let report = temp_crate
+            .minify(&["--vcs-branch", "minified", "--force-branch"])
+            .unwrap();
+        assert!(report.is_applied());
+        assert_eq!(head(), "refs/heads/minified");
+        assert_eq!(repo.refname_to_id("refs/heads/minified").unwrap(), initial);
+        assert_eq!(temp_crate.read("src/lib.rs").unwrap(), "pub fn used() {}\n");
+    }
+
+    #[test]
+    fn porcelain() {
+        let (dirty, staged) =
+            parse_porcelain("M  src/a.rs\0 M src/b.rs\0MM src/c.rs\0?? new.rs\0R  d.rs\0old.rs\0");
+        assert_eq!(dirty, ["src/b.rs", "src/c.rs", "new.rs"]);
+        assert_eq!(staged, ["src/a.rs", "d.rs"]);
+
+        let (dirty, staged) = parse_porcelain("R  src/e.rs\0e\0 M src/f.rs\0");
+        assert_eq!(dirty, ["src/f.rs"]);
+        assert_eq!(staged, ["src/e.rs"]);
+    }
+}
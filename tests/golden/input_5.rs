@@ -0,0 +1,38 @@
+pub fn add(a: u8, b: u8) -> u8 {
+    helper(a) + b
+}
+
+fn helper(a: u8) -> u8 {
+    a
+}
+
+fn fixture() -> u8 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper() -> u8 {
+        add(1, 2)
+    }
+
+    fn used_helper() -> u8 {
+        add(2, 2)
+    }
+
+    #[test]
+    fn adds() {
+        assert_eq!(used_helper(), fixture() + 3);
+    }
+
+    mod nested {
+        const UNUSED: u8 = 1;
+
+        fn deeper_helper() {}
+
+        #[test]
+        fn works() {}
+    }
+}
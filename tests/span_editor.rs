@@ -0,0 +1,166 @@
+//! Property tests of the span editor: random Rust-like sources are edited with
+//! random non-overlapping spans, and the result is checked against invariants
+//! that hold for any set of edits. Set `MINIFY_FUZZ_ITERATIONS` to run more
+//! cases than the default, and `MINIFY_FUZZ_SEED` to reproduce a failure.
+
+use std::ops::Range;
+
+use cargo_minify::{EditError, SourceFileEditor, TextEdit};
+
+const DEFAULT_ITERATIONS: usize = 500;
+
+const FRAGMENTS: &[&str] = &[
+    "fn foo() {}\n",
+    "pub fn bar(x: u32) -> u32 {\n    x + 1\n}\n",
+    "\n",
+    "    ",
+    "\t",
+    "// a comment\n",
+    "/// docs\n",
+    "#[inline]\n",
+    "const FOO: i32 = 42;\n",
+    "struct S { a: u8 }\n",
+    "impl S {\n    fn f(&self) {}\n}\n",
+    "mod m {\n",
+    "}\n",
+    "\"ünïcödé\";\n",
+    "\r\n",
+];
+
+/// A xorshift generator, so that failures can be reproduced from the seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+fn source(rng: &mut Rng) -> Vec<u8> {
+    (0..rng.below(20))
+        .flat_map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())].bytes())
+        .collect()
+}
+
+/// Non-overlapping edits, in random order; they don't even touch, as the
+/// order of insertions at the same position would matter
+fn edits(rng: &mut Rng, len: usize) -> Vec<TextEdit> {
+    let mut bounds: Vec<usize> = (0..rng.below(10) * 2).map(|_| rng.below(len + 1)).collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds.truncate(bounds.len() / 2 * 2);
+
+    let mut edits: Vec<TextEdit> = bounds
+        .chunks(2)
+        .map(|range| {
+            let replacement = FRAGMENTS[rng.below(FRAGMENTS.len())];
+            match rng.below(3) {
+                0 => TextEdit::replace(range[0]..range[1], replacement),
+                _ => TextEdit::delete(range[0]..range[1]),
+            }
+        })
+        .collect();
+    for i in (1..edits.len()).rev() {
+        edits.swap(i, rng.below(i + 1));
+    }
+
+    edits
+}
+
+fn editor(src: &[u8], edits: &[TextEdit]) -> SourceFileEditor {
+    let mut editor = SourceFileEditor::new(src);
+    for edit in edits {
+        editor.edit(edit.clone());
+    }
+    editor
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[test]
+fn edits_satisfy_invariants() {
+    let iterations = env_or("MINIFY_FUZZ_ITERATIONS", DEFAULT_ITERATIONS as u64);
+    let seed = env_or("MINIFY_FUZZ_SEED", 0x5eed_cafe);
+
+    for iteration in 0..iterations {
+        let mut rng = Rng(seed.wrapping_add(iteration).max(1));
+        let src = source(&mut rng);
+        let edits = edits(&mut rng, src.len());
+        let context = format!("seed {}", seed.wrapping_add(iteration).max(1));
+
+        let editor = editor(&src, &edits);
+        assert_eq!(editor.validate(), Ok(()), "{context}");
+        let output = editor.apply().expect(&context);
+
+        // Output length math
+        let removed: usize = edits.iter().map(|edit| edit.range.len()).sum();
+        let inserted: usize = edits.iter().map(|edit| edit.replacement.len()).sum();
+        assert_eq!(output.len(), src.len() - removed + inserted, "{context}");
+
+        // Re-applying gives the same result, regardless of the order of edits
+        assert_eq!(editor.apply().unwrap(), output, "{context}");
+        let mut reversed = edits.clone();
+        reversed.reverse();
+        assert_eq!(
+            self::editor(&src, &reversed).apply().unwrap(),
+            output,
+            "{context}"
+        );
+
+        // The bytes outside of the edits are kept as they are
+        let first = edits
+            .iter()
+            .map(|edit| edit.range.start)
+            .min()
+            .unwrap_or(src.len());
+        assert_eq!(output[..first], src[..first], "{context}");
+
+        // Without replacements, deleting never makes the source longer
+        let deletions: Vec<_> = edits
+            .iter()
+            .map(|edit| TextEdit::delete(edit.range.clone()))
+            .collect();
+        assert!(self::editor(&src, &deletions).apply().unwrap().len() <= src.len());
+
+        let _ = editor.diff().expect(&context);
+    }
+}
+
+#[test]
+fn invalid_edits_are_rejected() {
+    let iterations = env_or("MINIFY_FUZZ_ITERATIONS", DEFAULT_ITERATIONS as u64);
+    let seed = env_or("MINIFY_FUZZ_SEED", 0x0dd_ba11);
+
+    for iteration in 0..iterations {
+        let mut rng = Rng(seed.wrapping_add(iteration).max(1));
+        let src = source(&mut rng);
+        let start = rng.below(src.len() + 1);
+        let end = start + 1 + rng.below(8);
+        let overlapping: Range<usize> = start.saturating_sub(rng.below(4))..end;
+
+        let mut editor = SourceFileEditor::new(src.clone());
+        editor.edit(TextEdit::delete(start..end));
+        if end > src.len() {
+            assert!(matches!(
+                editor.validate(),
+                Err(EditError::OutOfBounds { .. })
+            ));
+            continue;
+        }
+
+        editor.edit(TextEdit::delete(overlapping));
+        assert!(matches!(editor.apply(), Err(EditError::Overlapping(_, _))));
+    }
+}